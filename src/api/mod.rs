@@ -1,6 +1,8 @@
+mod pool;
 mod routes;
 
-pub use routes::AppState; 
+pub use pool::SearchPool;
+pub use routes::{AppState, GamePonder, DEFAULT_MAX_SEARCH_MS};
 use actix_web::web;
 
 pub fn config(cfg: &mut web::ServiceConfig) {
@@ -10,6 +12,12 @@ pub fn config(cfg: &mut web::ServiceConfig) {
             .service(routes::new_game)
             .service(routes::make_move)
             .service(routes::get_game)
+            .service(routes::get_eval)
             .service(routes::get_best_move)
+            .service(routes::engine_move)
+            .service(routes::stop_search)
+            .service(routes::list_backends)
+            .service(routes::probe_tablebase)
+            .service(routes::mate_search)
     );
 }
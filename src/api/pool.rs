@@ -0,0 +1,98 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tokio::sync::oneshot;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+// Fixed set of threads that run engine searches, so searches never block
+// the actix workers that handle requests
+pub struct SearchPool {
+    sender: Mutex<Sender<Job>>,
+    size: usize,
+}
+
+impl SearchPool {
+    pub fn new(size: usize) -> Self {
+        let size = size.max(1);
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        for i in 0..size {
+            let receiver = Arc::clone(&receiver);
+            thread::Builder::new()
+                .name(format!("search-{}", i))
+                .spawn(move || loop {
+                    // Hold the lock only while taking a job, not while running it
+                    let job = match receiver.lock() {
+                        Ok(receiver) => receiver.recv(),
+                        Err(_) => break,
+                    };
+                    match job {
+                        // A panicking job mustn't take the thread down with it. Its
+                        // result sender is dropped, so whoever waits gets an error.
+                        Ok(job) => {
+                            let _ = panic::catch_unwind(AssertUnwindSafe(job));
+                        },
+                        // The pool was dropped
+                        Err(_) => break,
+                    }
+                })
+                .expect("failed to spawn search thread");
+        }
+
+        Self {
+            sender: Mutex::new(sender),
+            size,
+        }
+    }
+
+    // One thread per available core
+    pub fn with_default_size() -> Self {
+        Self::new(thread::available_parallelism().map(|n| n.get()).unwrap_or(1))
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    // Queue a job whose result nobody waits for
+    pub fn execute<F>(&self, job: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        if let Ok(sender) = self.sender.lock() {
+            let _ = sender.send(Box::new(job));
+        }
+    }
+
+    // Queue a job; the receiver resolves with its result once a thread has run it
+    pub fn spawn<T, F>(&self, job: F) -> oneshot::Receiver<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.execute(move || {
+            // The requester may have gone away, in which case the result is simply dropped
+            let _ = result_sender.send(job());
+        });
+        result_receiver
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn threads_survive_panicking_jobs() {
+        let pool = SearchPool::new(1);
+        let failed = pool.spawn(|| -> usize { panic!("search failed") });
+        assert!(failed.blocking_recv().is_err());
+        let results: Vec<_> = (0..3).map(|i| pool.spawn(move || i * 2)).collect();
+        let results: Vec<usize> = results.into_iter().map(|r| r.blocking_recv().unwrap()).collect();
+        assert_eq!(results, [0, 2, 4]);
+    }
+}
@@ -1,17 +1,91 @@
 use actix_web::{get, post, web, HttpResponse, Responder};
 use serde::{Serialize, Deserialize};
 use serde_json::json;  // Added this import for the json! macro
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 use std::collections::HashMap;
+use std::future::Future;
+use std::time::Duration;
 use uuid::Uuid;
 
-use crate::chess::{Game, Engine, ChessMove};
+use crate::api::pool::SearchPool;
+use crate::chess::{Engine, Game, GameStatus, BackendRegistry, MateSearch, OpeningBook, Ponderer, SearchLimits, SearchResult, Skill, EvalParams, StopSignal, SyzygyTablebase, Wdl, MAX_SKILL_LEVEL, MIN_ELO, MAX_ELO, MAX_PHASE, PERSONALITIES, WdlChances, WdlModel};
+#[cfg(feature = "nnue")]
+use crate::chess::{Color, Network};
 use crate::error::ChessError;
 
+// Hard cap on how long a best-move request may think, in milliseconds
+pub const DEFAULT_MAX_SEARCH_MS: u64 = 30_000;
+
 // Simple in-memory game storage
 // In a real app, you'd use a database
 pub struct AppState {
     pub games: Mutex<HashMap<String, Game>>,
+    // Search backends that games can pick by name
+    pub backends: BackendRegistry,
+    // Threads that run searches off the request workers
+    pub search_pool: SearchPool,
+    // Stop handles for searches in progress, by game ID
+    pub active_searches: Mutex<HashMap<String, StopSignal>>,
+    // Searches still running after this long are stopped and return their best move so far
+    pub max_search_ms: u64,
+    // Speculative searches of the reply each engine-played game expects
+    pub ponders: Mutex<HashMap<String, GamePonder>>,
+    // Opening book consulted before searching, if the server has one
+    pub book: Option<Arc<OpeningBook>>,
+    // Syzygy endgame tablebases, if the server has any
+    pub tablebase: Option<Arc<SyzygyTablebase>>,
+    // Turns evaluations into win/draw/loss chances, as RUSTYCHESS_WDL_MODEL configures
+    pub wdl_model: WdlModel,
+    // Weights for games without their own, as RUSTYCHESS_EVAL_PARAMS configures
+    pub eval_params: EvalParams,
+    // Network the built-in engine evaluates with instead, as RUSTYCHESS_NNUE configures
+    #[cfg(feature = "nnue")]
+    pub network: Option<Arc<Network>>,
+}
+
+// A game's ponder search, with the name of the backend it runs on so the
+// backend can go back to the registry afterwards
+pub struct GamePonder {
+    backend: String,
+    ponderer: Ponderer,
+}
+
+impl GamePonder {
+    pub fn is_hit(&self, game: &Game) -> bool {
+        self.ponderer.is_hit(game)
+    }
+}
+
+// Stop a ponder search that won't be used. Its backend goes back to the
+// registry once the search has wound down, off the request worker.
+fn retire_ponder(data: &web::Data<AppState>, game_id: &str, ponder: GamePonder) {
+    ponder.ponderer.stop_signal().stop();
+    let data = data.clone();
+    let game_id = game_id.to_string();
+    actix_web::rt::task::spawn_blocking(move || {
+        if let Some(backend) = ponder.ponderer.miss() {
+            data.backends.checkin(&ponder.backend, Some(&game_id), backend);
+        }
+    });
+}
+
+// Stops a search when its request finishes or is dropped, e.g. because the
+// client disconnected, and forgets its stop handle
+struct SearchGuard {
+    data: web::Data<AppState>,
+    game_id: String,
+    stop: StopSignal,
+}
+
+impl Drop for SearchGuard {
+    fn drop(&mut self) {
+        self.stop.stop();
+        let mut active = self.data.active_searches.lock().unwrap();
+        // A newer search for the same game may have replaced ours
+        if active.get(&self.game_id).is_some_and(|stop| stop.same_as(&self.stop)) {
+            active.remove(&self.game_id);
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -25,6 +99,26 @@ struct GameResponse {
     game: Game,
 }
 
+#[derive(Deserialize)]
+struct NewGameRequest {
+    // Name of the search backend to use for this game
+    #[serde(default)]
+    engine: Option<String>,
+    // Let the engine think on the opponent's time when it plays this game
+    #[serde(default)]
+    ponder: bool,
+    // Playing strength, as a skill level from 0 to 20 or a target rating
+    #[serde(default)]
+    skill_level: Option<u8>,
+    #[serde(default)]
+    elo: Option<u32>,
+    // Evaluation, as a built-in personality or a full set of weights
+    #[serde(default)]
+    personality: Option<String>,
+    #[serde(default)]
+    eval_params: Option<EvalParams>,
+}
+
 #[derive(Deserialize)]
 struct MoveRequest {
     from: String,
@@ -36,7 +130,30 @@ struct BestMoveResponse {
     from: String,
     to: String,
     evaluation: i32,
+    // Win, draw and loss chances of the side to move, in permille
+    wdl: WdlChances,
     nodes_searched: u32,
+    // Principal variation in UCI and standard algebraic notation
+    pv: Vec<String>,
+    pv_san: Vec<String>,
+    depth: u8,
+    seldepth: u8,
+    elapsed_ms: u64,
+    nps: u64,
+    // Ranked candidate lines, best first
+    lines: Vec<LineResponse>,
+    // The move came from the opening book, so there was no search
+    book: bool,
+}
+
+#[derive(Serialize)]
+struct LineResponse {
+    // Moves in UCI notation, starting with the candidate move
+    moves: Vec<String>,
+    san: Vec<String>,
+    evaluation: i32,
+    wdl: WdlChances,
+    depth: u8,
 }
 
 #[get("/health")]
@@ -47,8 +164,76 @@ async fn health_check() -> impl Responder {
 }
 
 #[post("/games")]
-async fn new_game(data: web::Data<AppState>) -> impl Responder {
-    let game = Game::new();
+async fn new_game(
+    new_game_req: Option<web::Json<NewGameRequest>>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let mut game = Game::new();
+    
+    let new_game_req = new_game_req.map(|req| req.into_inner());
+    game.ponder = new_game_req.as_ref().is_some_and(|req| req.ponder);
+    
+    if let Some(req) = &new_game_req {
+        match (req.skill_level, req.elo) {
+            (Some(level), _) if level > MAX_SKILL_LEVEL => {
+                return HttpResponse::BadRequest().json(json!({
+                    "error": "Invalid skill level",
+                    "details": format!("Skill level must be 0-{}, got {}", MAX_SKILL_LEVEL, level)
+                }));
+            },
+            (Some(_), Some(_)) => {
+                return HttpResponse::BadRequest().json(json!({
+                    "error": "Invalid strength",
+                    "details": "Give either skill_level or elo, not both"
+                }));
+            },
+            (Some(level), None) => game.skill = Skill::new(level),
+            (None, Some(elo)) if !(MIN_ELO..=MAX_ELO).contains(&elo) => {
+                return HttpResponse::BadRequest().json(json!({
+                    "error": "Invalid Elo",
+                    "details": format!("Elo must be {}-{}, got {}", MIN_ELO, MAX_ELO, elo)
+                }));
+            },
+            (None, Some(elo)) => game.skill = Skill::from_elo(elo),
+            (None, None) => {},
+        }
+        
+        game.eval_params = match (&req.personality, req.eval_params) {
+            (Some(_), Some(_)) => {
+                return HttpResponse::BadRequest().json(json!({
+                    "error": "Invalid evaluation",
+                    "details": "Give either personality or eval_params, not both"
+                }));
+            },
+            (Some(name), None) => match EvalParams::personality(name) {
+                Some(params) => Some(params),
+                None => return HttpResponse::BadRequest().json(json!({
+                    "error": "Unknown personality",
+                    "details": format!("No personality named {}; try one of {}", name, PERSONALITIES.join(", "))
+                })),
+            },
+            (None, Some(params)) => match params.validate() {
+                Ok(params) => Some(params),
+                Err(e) => return HttpResponse::BadRequest().json(json!({
+                    "error": "Invalid evaluation parameters",
+                    "details": e.to_string()
+                })),
+            },
+            (None, None) => None,
+        };
+    }
+    
+    // Optionally pick a different search backend for this game
+    if let Some(engine) = new_game_req.and_then(|req| req.engine) {
+        if !data.backends.contains(&engine) {
+            return HttpResponse::BadRequest().json(json!({
+                "error": "Unknown engine",
+                "details": format!("No search backend named: {}", engine)
+            }));
+        }
+        game.backend = engine;
+    }
+    
     let game_id = Uuid::new_v4().to_string();
     
     // Store the game
@@ -78,7 +263,16 @@ async fn make_move(
     
     // Make move
     match game.make_move(&move_req.from, &move_req.to) {
-        Ok(_) => HttpResponse::Ok().json(game),
+        Ok(_) => {
+            // The engine guessed wrong: stop its ponder search
+            let mut ponders = data.ponders.lock().unwrap();
+            if ponders.get(&game_id_str).is_some_and(|ponder| !ponder.is_hit(game)) {
+                if let Some(ponder) = ponders.remove(&game_id_str) {
+                    retire_ponder(&data, &game_id_str, ponder);
+                }
+            }
+            HttpResponse::Ok().json(game)
+        },
         Err(e) => match e {
             ChessError::InvalidMove(msg) => HttpResponse::BadRequest().body(msg),
             _ => HttpResponse::InternalServerError().body("Internal server error"),
@@ -98,65 +292,717 @@ async fn get_game(game_id: web::Path<String>, data: web::Data<AppState>) -> impl
     }
 }
 
-#[get("/games/{id}/best-move")]
-async fn get_best_move(game_id: web::Path<String>, data: web::Data<AppState>) -> impl Responder {
+// White's lead by the network the built-in engine evaluates with, if it has one
+#[cfg(feature = "nnue")]
+fn network_score(data: &AppState, game: &Game) -> Option<i32> {
+    data.network.as_ref().map(|network| network.evaluate_board(&game.board, Color::White))
+}
+
+#[cfg(not(feature = "nnue"))]
+fn network_score(_data: &AppState, _game: &Game) -> Option<i32> {
+    None
+}
+
+// The game's evaluation term by term, with the weights the game plays with, and
+// the network's score when the engine evaluates with one
+#[get("/games/{id}/eval")]
+async fn get_eval(game_id: web::Path<String>, data: web::Data<AppState>) -> impl Responder {
     let game_id_str = game_id.into_inner();
     let games = data.games.lock().unwrap();
-    
-    // Find game with improved error message
     let game = match games.get(&game_id_str) {
         Some(game) => game,
-        None => return HttpResponse::NotFound().json(json!({
-            "error": "Game not found",
-            "details": format!("No active game with ID: {}", game_id_str)
+        None => return HttpResponse::NotFound().body("Game not found"),
+    };
+    let trace = game.eval_params.unwrap_or(data.eval_params).trace(&game.board);
+    let network = network_score(&data, game);
+    HttpResponse::Ok().json(json!({
+        "fen": game.to_fen(),
+        "terms": trace.terms,
+        "phase": trace.phase,
+        "max_phase": MAX_PHASE,
+        "total": trace.total,
+        "endgame": trace.endgame,
+        "network": network,
+        "wdl": data.wdl_model.chances(network.unwrap_or(trace.total), trace.phase)
+    }))
+}
+
+#[derive(Deserialize)]
+struct BestMoveQuery {
+    // Override the game's backend for this request
+    backend: Option<String>,
+    depth: Option<u8>,
+    // Number of candidate lines to return
+    multipv: Option<usize>,
+    // Time and node budgets; the search returns its best move so far when one runs out
+    movetime: Option<u64>,
+    nodes: Option<u64>,
+    // Set to false to search even when the position is in the opening book
+    book: Option<bool>,
+}
+
+impl BestMoveQuery {
+    // Limits for searching the game, at the game's skill level
+    fn limits(&self, game: &Game) -> SearchLimits {
+        SearchLimits {
+            depth: self.depth,
+            nodes: self.nodes,
+            movetime_ms: self.movetime,
+            multipv: self.multipv,
+            skill: Some(game.skill),
+            eval: game.eval_params,
+        }
+    }
+}
+
+impl BestMoveResponse {
+    fn from_result(result: &SearchResult) -> Self {
+        let best_move = &result.best_move;
+        Self {
+            from: best_move.from.to_string(),
+            to: best_move.to.to_string(),
+            evaluation: result.score,
+            wdl: result.pv().wdl,
+            nodes_searched: result.nodes.min(u32::MAX as u64) as u32,
+            pv: result.pv().uci(),
+            pv_san: result.pv().san.clone(),
+            depth: result.depth,
+            seldepth: result.seldepth,
+            elapsed_ms: result.elapsed_ms,
+            nps: result.nps,
+            lines: result.lines.iter().map(|line| LineResponse {
+                moves: line.uci(),
+                san: line.san.clone(),
+                evaluation: line.score,
+                wdl: line.wdl,
+                depth: line.depth,
+            }).collect(),
+            book: result.from_book,
+        }
+    }
+}
+
+// Answer from the opening book when the position is in it, unless the request opts out
+fn book_result(data: &AppState, game: &Game, query: &BestMoveQuery) -> Option<SearchResult> {
+    if query.book == Some(false) {
+        return None;
+    }
+    let book_move = data.book.as_ref()?.choose(game)?;
+    Some(SearchResult::book(game, book_move.chess_move))
+}
+
+fn game_not_found(game_id: &str) -> HttpResponse {
+    HttpResponse::NotFound().json(json!({
+        "error": "Game not found",
+        "details": format!("No active game with ID: {}", game_id)
+    }))
+}
+
+fn search_error_response(e: ChessError) -> HttpResponse {
+    log::warn!("Error finding best move: {:?}", e);
+    match e {
+        ChessError::InvalidMove(msg) => HttpResponse::BadRequest().json(json!({
+            "error": "Invalid move",
+            "details": msg
         })),
+        ChessError::Timeout(msg) => HttpResponse::GatewayTimeout().json(json!({
+            "error": "Engine timeout",
+            "details": msg
+        })),
+        _ => HttpResponse::InternalServerError().json(json!({
+            "error": "Engine error",
+            "details": format!("Failed to compute best move: {:?}", e)
+        })),
+    }
+}
+
+// Run a search on the pool so the request worker stays free. The search can be
+// cancelled through the stop endpoint, stops if the client goes away, and is
+// stopped once it reaches the server's time cap.
+async fn run_search<F>(
+    data: &web::Data<AppState>,
+    game_id: &str,
+    stop: StopSignal,
+    search: F,
+) -> Result<SearchResult, HttpResponse>
+where
+    F: FnOnce() -> Result<SearchResult, ChessError> + Send + 'static,
+{
+    let receiver = data.search_pool.spawn(search);
+    wait_for_search(data, game_id, stop, receiver).await
+}
+
+// Run a search with the instance of the named backend kept for this game, which
+// goes back to the registry once the search is over
+async fn search_with_backend(
+    data: &web::Data<AppState>,
+    game_id: &str,
+    backend_name: &str,
+    game: Game,
+    limits: SearchLimits,
+) -> Result<SearchResult, HttpResponse> {
+    let mut backend = data.backends.checkout(backend_name, Some(game_id)).map_err(unknown_backend_response)?;
+    let stop = StopSignal::new();
+    backend.set_stop_signal(stop.clone());
+    
+    let search_data = data.clone();
+    let search_backend = backend_name.to_string();
+    let search_game_id = game_id.to_string();
+    run_search(data, game_id, stop, move || {
+        let result = backend.search(&game, &limits);
+        search_data.backends.checkin(&search_backend, Some(&search_game_id), backend);
+        result
+    }).await
+}
+
+fn unknown_backend_response(e: ChessError) -> HttpResponse {
+    HttpResponse::BadRequest().json(json!({
+        "error": "Unknown backend",
+        "details": e.to_string()
+    }))
+}
+
+// Wait for a search that is already running, with the same cancellation and time cap
+async fn wait_for_search<R, E>(
+    data: &web::Data<AppState>,
+    game_id: &str,
+    stop: StopSignal,
+    receiver: R,
+) -> Result<SearchResult, HttpResponse>
+where
+    R: Future<Output = Result<Result<SearchResult, ChessError>, E>>,
+{
+    // Register a stop handle so the search can be cancelled from elsewhere
+    data.active_searches.lock().unwrap().insert(game_id.to_string(), stop.clone());
+    let _guard = SearchGuard {
+        data: data.clone(),
+        game_id: game_id.to_string(),
+        stop: stop.clone(),
     };
     
-    // Initialize engine with a search depth of 3
-    let mut engine = Engine::new(3);
-    
-    // Find the best move with improved error handling
-    match engine.find_best_move(game) {
-        Ok(best_move) => {
-            let (nodes_searched, _) = engine.get_stats();
-            let response = BestMoveResponse {
-                from: best_move.from.to_string(),
-                to: best_move.to.to_string(),
-                evaluation: best_move.score,
-                nodes_searched,
-            };
-            
-            // Print debug information
-            println!("Best move found: {} -> {} (score: {})", 
-                    best_move.from, best_move.to, best_move.score);
+    let mut receiver = std::pin::pin!(receiver);
+    let outcome = match actix_web::rt::time::timeout(Duration::from_millis(data.max_search_ms), &mut receiver).await {
+        Ok(outcome) => outcome,
+        Err(_) => {
+            // Out of time: ask the search to wrap up and take what it has
+            log::info!("Search for game {} hit the {} ms limit, stopping it", game_id, data.max_search_ms);
+            stop.stop();
+            receiver.await
+        },
+    };
+    
+    match outcome {
+        Ok(result) => result.map_err(search_error_response),
+        Err(_) => Err(HttpResponse::InternalServerError().json(json!({
+            "error": "Engine error",
+            "details": "Search thread stopped before returning a result"
+        }))),
+    }
+}
+
+#[get("/games/{id}/best-move")]
+async fn get_best_move(
+    game_id: web::Path<String>,
+    query: web::Query<BestMoveQuery>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let game_id_str = game_id.into_inner();
+    
+    // Copy the game out so the lock isn't held while the backend thinks
+    let game = match data.games.lock().unwrap().get(&game_id_str) {
+        Some(game) => game.clone(),
+        None => return game_not_found(&game_id_str),
+    };
+    
+    let backend_name = query.backend.clone().unwrap_or_else(|| game.backend.clone());
+    if !data.backends.contains(&backend_name) {
+        return unknown_backend_response(ChessError::Engine(format!("Unknown search backend: {}", backend_name)));
+    }
+    
+    if let Some(result) = book_result(&data, &game, &query) {
+        log::debug!("Book move: {} -> {}", result.best_move.from, result.best_move.to);
+        return HttpResponse::Ok().json(BestMoveResponse::from_result(&result));
+    }
+    
+    let limits = query.limits(&game);
+    match search_with_backend(&data, &game_id_str, &backend_name, game, limits).await {
+        Ok(result) => {
+            log::debug!("Best move found by {}: {} -> {} (score: {})",
+                    backend_name, result.best_move.from, result.best_move.to, result.score);
             
-            HttpResponse::Ok().json(response)
+            HttpResponse::Ok().json(BestMoveResponse::from_result(&result))
         },
-        Err(e) => {
-            // Print the error for debugging
-            println!("Error finding best move: {:?}", e);
-            match e {
-                ChessError::InvalidMove(msg) => HttpResponse::BadRequest().json(json!({
-                    "error": "Invalid move",
-                    "details": msg
-                })),
-                _ => HttpResponse::InternalServerError().json(json!({
-                    "error": "Engine error",
-                    "details": format!("Failed to compute best move: {:?}", e)
-                })),
+        Err(response) => response,
+    }
+}
+
+#[derive(Serialize)]
+struct EngineMoveResponse {
+    // The move the engine played, with its analysis
+    #[serde(rename = "move")]
+    engine_move: BestMoveResponse,
+    game: Game,
+    // Reply the engine is now pondering, in UCI notation
+    ponder: Option<String>,
+    // Whether the engine's previous ponder search was used for this move
+    ponder_hit: bool,
+}
+
+// Have the game's engine play the side to move. In pondering games the engine
+// then keeps searching the reply it expects until the opponent moves.
+#[post("/games/{id}/engine-move")]
+async fn engine_move(
+    game_id: web::Path<String>,
+    query: web::Query<BestMoveQuery>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let game_id_str = game_id.into_inner();
+    
+    let game = match data.games.lock().unwrap().get(&game_id_str) {
+        Some(game) => game.clone(),
+        None => return game_not_found(&game_id_str),
+    };
+    if matches!(game.status, GameStatus::Checkmate | GameStatus::Stalemate | GameStatus::Draw) {
+        return HttpResponse::BadRequest().json(json!({
+            "error": "Game over",
+            "details": format!("The game has ended: {:?}", game.status)
+        }));
+    }
+    
+    let backend_name = query.backend.clone().unwrap_or_else(|| game.backend.clone());
+    let limits = query.limits(&game);
+    
+    // A ponder search of this exact position becomes the real search; any other is discarded
+    let ponder = data.ponders.lock().unwrap().remove(&game_id_str);
+    let ponder = match ponder {
+        Some(ponder) if !ponder.is_hit(&game) => {
+            retire_ponder(&data, &game_id_str, ponder);
+            None
+        },
+        ponder => ponder,
+    };
+    let book = book_result(&data, &game, &query);
+    let ponder_hit = book.is_none() && ponder.is_some();
+    
+    let outcome = match (book, ponder) {
+        // Book moves need no thinking, so any ponder search is stopped
+        (Some(result), ponder) => {
+            if let Some(ponder) = ponder {
+                retire_ponder(&data, &game_id_str, ponder);
             }
+            Ok(result)
+        },
+        (None, Some(GamePonder { backend, ponderer })) => {
+            log::debug!("Ponder hit on {} in game {}", ponderer.ponder_move(), game_id_str);
+            // The ponder search already holds a pool thread, so wait for it elsewhere
+            let stop = ponderer.stop_signal();
+            let hit_data = data.clone();
+            let hit_game_id = game_id_str.clone();
+            let hit = web::block(move || ponderer.hit().map(|(searched, result)| {
+                hit_data.backends.checkin(&backend, Some(&hit_game_id), searched);
+                result
+            }));
+            wait_for_search(&data, &game_id_str, stop, hit).await
+        },
+        (None, None) => search_with_backend(&data, &game_id_str, &backend_name, game.clone(), limits).await,
+    };
+    let result = match outcome {
+        Ok(result) => result,
+        Err(response) => return response,
+    };
+    
+    // Play the move, unless the game moved on while the engine was thinking
+    let best_move = &result.best_move;
+    let updated = {
+        let mut games = data.games.lock().unwrap();
+        let stored = match games.get_mut(&game_id_str) {
+            Some(stored) => stored,
+            None => return game_not_found(&game_id_str),
+        };
+        if stored.to_fen() != game.to_fen() {
+            return HttpResponse::Conflict().json(json!({
+                "error": "Position changed",
+                "details": "A move was made while the engine was thinking"
+            }));
+        }
+        if let Err(e) = stored.make_move(&best_move.from.to_string(), &best_move.to.to_string()) {
+            return search_error_response(e);
+        }
+        stored.clone()
+    };
+    log::info!("Engine ({}) played {} -> {} in game {}", backend_name, best_move.from, best_move.to, game_id_str);
+    
+    // Think about the expected reply on the opponent's time
+    let mut ponder = None;
+    if updated.ponder && !matches!(updated.status, GameStatus::Checkmate | GameStatus::Stalemate | GameStatus::Draw) {
+        if let (Some(ponder_move), Ok(backend)) = (result.ponder_move(), data.backends.checkout(&backend_name, Some(&game_id_str))) {
+            let started = Ponderer::start(backend, &updated, ponder_move, limits, Some(data.max_search_ms), |job| data.search_pool.execute(job));
+            match started {
+                Ok(ponderer) => {
+                    ponder = Some(ponder_move.to_uci());
+                    data.ponders.lock().unwrap().insert(game_id_str.clone(), GamePonder {
+                        backend: backend_name.clone(),
+                        ponderer,
+                    });
+                },
+                Err(e) => log::warn!("Not pondering in game {}: {}", game_id_str, e),
+            }
+        }
+    }
+    
+    HttpResponse::Ok().json(EngineMoveResponse {
+        engine_move: BestMoveResponse::from_result(&result),
+        game: updated,
+        ponder,
+        ponder_hit,
+    })
+}
+
+#[derive(Deserialize)]
+struct TablebaseQuery {
+    fen: String,
+}
+
+#[derive(Serialize)]
+struct TablebaseResponse {
+    fen: String,
+    // Result for the side to move with perfect play
+    wdl: Wdl,
+    // Plies to the next capture or pawn move; negative when losing
+    dtz: i32,
+    // Every legal move, best first
+    moves: Vec<TablebaseMoveResponse>,
+}
+
+#[derive(Serialize)]
+struct TablebaseMoveResponse {
+    // UCI notation
+    #[serde(rename = "move")]
+    uci: String,
+    san: String,
+    // Where the move leads, from the point of view of the side playing it
+    wdl: Wdl,
+    dtz: i32,
+}
+
+#[get("/tablebase")]
+async fn probe_tablebase(query: web::Query<TablebaseQuery>, data: web::Data<AppState>) -> impl Responder {
+    let Some(tablebase) = data.tablebase.clone() else {
+        return HttpResponse::ServiceUnavailable().json(json!({
+            "error": "No tablebases",
+            "details": "The server has no Syzygy tablebases configured"
+        }));
+    };
+    let game = match Game::from_fen(&query.fen) {
+        Ok(game) => game,
+        Err(e) => return HttpResponse::BadRequest().json(json!({
+            "error": "Invalid FEN",
+            "details": e.to_string()
+        })),
+    };
+    
+    // Tables are read from disk on first use, so keep that off the request workers
+    let probe = web::block(move || {
+        let wdl = tablebase.probe_wdl(&game)?;
+        let dtz = tablebase.probe_dtz(&game)?;
+        let moves = tablebase.probe_root(&game)?;
+        Some((game, wdl, dtz, moves))
+    }).await;
+    
+    match probe {
+        Ok(Some((game, wdl, dtz, moves))) => {
+            let engine = Engine::new(1);
+            HttpResponse::Ok().json(TablebaseResponse {
+                fen: game.to_fen(),
+                wdl,
+                dtz,
+                moves: moves.into_iter().map(|m| TablebaseMoveResponse {
+                    uci: m.chess_move.to_uci(),
+                    san: engine.to_algebraic_notation(&m.chess_move, &game.board),
+                    wdl: m.wdl,
+                    dtz: m.dtz,
+                }).collect(),
+            })
+        },
+        Ok(None) => HttpResponse::NotFound().json(json!({
+            "error": "Position not in tablebases",
+            "details": format!("The tablebases hold positions with up to {} pieces", data.tablebase.as_ref().map_or(0, |tb| tb.max_pieces()))
+        })),
+        Err(e) => HttpResponse::InternalServerError().json(json!({
+            "error": "Tablebase probe failed",
+            "details": e.to_string()
+        })),
+    }
+}
+
+// Longest mate the endpoint searches for; deeper problems grow too quickly
+const MAX_MATE_MOVES: u32 = 8;
+const DEFAULT_MATE_MOVES: u32 = 3;
+
+#[derive(Deserialize)]
+struct MateSearchRequest {
+    fen: String,
+    // Longest mate to look for, in moves of the side to move
+    #[serde(default)]
+    max_moves: Option<u32>,
+    #[serde(default)]
+    max_nodes: Option<u64>,
+}
+
+#[post("/mate-search")]
+async fn mate_search(request: web::Json<MateSearchRequest>, data: web::Data<AppState>) -> impl Responder {
+    let game = match Game::from_fen(&request.fen) {
+        Ok(game) => game,
+        Err(e) => return HttpResponse::BadRequest().json(json!({
+            "error": "Invalid FEN",
+            "details": e.to_string()
+        })),
+    };
+    let max_moves = request.max_moves.unwrap_or(DEFAULT_MATE_MOVES);
+    if max_moves == 0 || max_moves > MAX_MATE_MOVES {
+        return HttpResponse::BadRequest().json(json!({
+            "error": "Invalid max_moves",
+            "details": format!("max_moves must be between 1 and {}", MAX_MATE_MOVES)
+        }));
+    }
+
+    // Solve on the search pool, giving up at the server's time cap
+    let stop = StopSignal::new();
+    let mut solver = MateSearch::new();
+    solver.set_node_limit(request.max_nodes);
+    solver.set_stop_signal(stop.clone());
+    let mut receiver = data.search_pool.spawn(move || {
+        let solution = solver.solve(&game, max_moves);
+        (solution, solver.nodes())
+    });
+    let outcome = match actix_web::rt::time::timeout(Duration::from_millis(data.max_search_ms), &mut receiver).await {
+        Ok(outcome) => outcome,
+        Err(_) => {
+            stop.stop();
+            receiver.await
         },
+    };
+
+    match outcome {
+        Ok((Ok(Some(solution)), _)) => HttpResponse::Ok().json(json!({
+            "found": true,
+            "solution": solution
+        })),
+        Ok((Ok(None), nodes)) => HttpResponse::Ok().json(json!({
+            "found": false,
+            "max_moves": max_moves,
+            "nodes": nodes
+        })),
+        Ok((Err(ChessError::Timeout(msg)), _)) => HttpResponse::GatewayTimeout().json(json!({
+            "error": "Mate search timeout",
+            "details": msg
+        })),
+        Ok((Err(e), _)) => HttpResponse::InternalServerError().json(json!({
+            "error": "Mate search failed",
+            "details": e.to_string()
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(json!({
+            "error": "Engine error",
+            "details": "Search thread stopped before returning a result"
+        })),
     }
 }
 
-// Configure routes
-pub fn config(cfg: &mut web::ServiceConfig) {
-    cfg.service(
-        web::scope("/api")
-            .service(health_check)
-            .service(new_game)
-            .service(make_move)
-            .service(get_game)
-            .service(get_best_move)
-    );
+#[get("/backends")]
+async fn list_backends(data: web::Data<AppState>) -> impl Responder {
+    HttpResponse::Ok().json(json!({
+        "backends": data.backends.names()
+    }))
+}
+
+#[post("/games/{id}/stop")]
+async fn stop_search(game_id: web::Path<String>, data: web::Data<AppState>) -> impl Responder {
+    let game_id_str = game_id.into_inner();
+    
+    // The pending best-move request answers with the best move found so far
+    match data.active_searches.lock().unwrap().get(&game_id_str) {
+        Some(stop) => {
+            stop.stop();
+            HttpResponse::Ok().json(json!({ "stopped": true }))
+        },
+        None => HttpResponse::NotFound().json(json!({
+            "error": "No search running",
+            "details": format!("No search in progress for game: {}", game_id_str)
+        })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, App};
+
+    fn app_state() -> AppState {
+        AppState {
+            games: Mutex::new(HashMap::new()),
+            backends: BackendRegistry::default(),
+            search_pool: SearchPool::new(1),
+            active_searches: Mutex::new(HashMap::new()),
+            max_search_ms: DEFAULT_MAX_SEARCH_MS,
+            ponders: Mutex::new(HashMap::new()),
+            book: None,
+            tablebase: None,
+            wdl_model: WdlModel::default(),
+            eval_params: EvalParams::default(),
+            #[cfg(feature = "nnue")]
+            network: None,
+        }
+    }
+
+    #[actix_rt::test]
+    async fn eval_uses_the_servers_wdl_model() {
+        let model = WdlModel { a_endgame: 100.0, a_opening: 100.0, b_endgame: 50.0, b_opening: 50.0 };
+        let data = web::Data::new(AppState { wdl_model: model, ..app_state() });
+        let app = test::init_service(App::new().app_data(data).configure(crate::api::config)).await;
+
+        let request = test::TestRequest::post().uri("/api/games").set_json(json!({})).to_request();
+        let game: serde_json::Value = test::call_and_read_body_json(&app, request).await;
+        let uri = format!("/api/games/{}/eval", game["id"].as_str().unwrap());
+        let eval: serde_json::Value = test::call_and_read_body_json(&app, test::TestRequest::get().uri(&uri).to_request()).await;
+
+        let expected = model.chances(eval["total"].as_i64().unwrap() as i32, eval["phase"].as_i64().unwrap() as i32);
+        assert_ne!(expected, WdlModel::default().chances(0, MAX_PHASE));
+        assert_eq!(eval["wdl"], serde_json::to_value(expected).unwrap());
+    }
+
+    #[actix_rt::test]
+    async fn eval_uses_the_servers_eval_params() {
+        let params = EvalParams::resolve("aggressive").unwrap();
+        let data = web::Data::new(AppState { eval_params: params, ..app_state() });
+        let app = test::init_service(App::new().app_data(data).configure(crate::api::config)).await;
+
+        // After 1.e4 the weights make a difference to White's lead
+        let request = test::TestRequest::post().uri("/api/games").set_json(json!({})).to_request();
+        let game: serde_json::Value = test::call_and_read_body_json(&app, request).await;
+        let uri = format!("/api/games/{}/moves", game["id"].as_str().unwrap());
+        let request = test::TestRequest::post().uri(&uri).set_json(json!({ "from": "e2", "to": "e4" })).to_request();
+        let played: Game = test::call_and_read_body_json(&app, request).await;
+        let uri = format!("/api/games/{}/eval", game["id"].as_str().unwrap());
+        let eval: serde_json::Value = test::call_and_read_body_json(&app, test::TestRequest::get().uri(&uri).to_request()).await;
+
+        let expected = params.trace(&played.board).total;
+        assert_ne!(expected, EvalParams::default().trace(&played.board).total);
+        assert_eq!(eval["total"], expected);
+        assert!(eval["network"].is_null());
+    }
+
+    #[actix_rt::test]
+    async fn engine_move_uses_a_ponder_hit() {
+        let data = web::Data::new(app_state());
+        let app = test::init_service(App::new().app_data(data.clone()).configure(crate::api::config)).await;
+        let request = test::TestRequest::post().uri("/api/games").set_json(json!({ "ponder": true })).to_request();
+        let game: serde_json::Value = test::call_and_read_body_json(&app, request).await;
+        let id = game["id"].as_str().unwrap().to_string();
+        let uri = format!("/api/games/{}/engine-move", id);
+        let played: serde_json::Value = test::call_and_read_body_json(&app, test::TestRequest::post().uri(&uri).to_request()).await;
+        let ponder = played["ponder"].as_str().expect("no ponder move").to_string();
+
+        let uri = format!("/api/games/{}/moves", id);
+        let request = test::TestRequest::post().uri(&uri).set_json(json!({ "from": &ponder[0..2], "to": &ponder[2..4] })).to_request();
+        assert!(test::call_service(&app, request).await.status().is_success());
+        assert!(data.ponders.lock().unwrap().contains_key(&id));
+
+        // The ponder search has the same default depth as a normal one, so the
+        // hit answers long before the server's time cap
+        let start = std::time::Instant::now();
+        let uri = format!("/api/games/{}/engine-move", id);
+        let reply: serde_json::Value = test::call_and_read_body_json(&app, test::TestRequest::post().uri(&uri).to_request()).await;
+        assert_eq!(reply["ponder_hit"], true);
+        assert_eq!(reply["move"]["depth"], 3);
+        assert!(start.elapsed() < Duration::from_millis(DEFAULT_MAX_SEARCH_MS / 2));
+    }
+
+    #[actix_rt::test]
+    async fn engine_move_after_a_ponder_miss() {
+        let data = web::Data::new(app_state());
+        let app = test::init_service(App::new().app_data(data.clone()).configure(crate::api::config)).await;
+        let request = test::TestRequest::post().uri("/api/games").set_json(json!({ "ponder": true })).to_request();
+        let game: serde_json::Value = test::call_and_read_body_json(&app, request).await;
+        let id = game["id"].as_str().unwrap().to_string();
+        let uri = format!("/api/games/{}/engine-move", id);
+        let played: serde_json::Value = test::call_and_read_body_json(&app, test::TestRequest::post().uri(&uri).to_request()).await;
+        let ponder = played["ponder"].as_str().expect("no ponder move");
+
+        let (from, to) = if ponder == "a7a6" { ("h7", "h6") } else { ("a7", "a6") };
+        let uri = format!("/api/games/{}/moves", id);
+        let request = test::TestRequest::post().uri(&uri).set_json(json!({ "from": from, "to": to })).to_request();
+        assert!(test::call_service(&app, request).await.status().is_success());
+        assert!(!data.ponders.lock().unwrap().contains_key(&id));
+
+        let uri = format!("/api/games/{}/engine-move", id);
+        let reply: serde_json::Value = test::call_and_read_body_json(&app, test::TestRequest::post().uri(&uri).to_request()).await;
+        assert_eq!(reply["ponder_hit"], false);
+        assert_eq!(reply["move"]["depth"], 3);
+        assert_eq!(reply["game"]["move_history"].as_array().unwrap().len(), 3);
+    }
+
+    async fn get_tablebase(data: web::Data<AppState>, fen: &str) -> (u16, serde_json::Value) {
+        let app = test::init_service(App::new().app_data(data).configure(crate::api::config)).await;
+        let uri = format!("/api/tablebase?fen={}", fen.replace(' ', "%20"));
+        let response = test::call_service(&app, test::TestRequest::get().uri(&uri).to_request()).await;
+        let status = response.status().as_u16();
+        (status, test::read_body_json(response).await)
+    }
+
+    #[actix_rt::test]
+    async fn tablebase_without_tables_is_unavailable() {
+        let (status, body) = get_tablebase(web::Data::new(app_state()), "7k/8/6K1/8/8/8/Q7/8 w - - 0 1").await;
+        assert_eq!(status, 503);
+        assert_eq!(body["error"], "No tablebases");
+    }
+
+    #[actix_rt::test]
+    async fn tablebase_probe() {
+        let tablebase = SyzygyTablebase::open(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/syzygy")).unwrap();
+        let data = web::Data::new(AppState { tablebase: Some(Arc::new(tablebase)), ..app_state() });
+
+        let (status, body) = get_tablebase(data.clone(), "7k/8/6K1/8/8/8/Q7/8 w - - 0 1").await;
+        assert_eq!(status, 200);
+        assert_eq!(body["wdl"], "win");
+        assert_eq!(body["dtz"], 1);
+        assert_eq!(body["moves"][0]["wdl"], "win");
+        assert_eq!(body["moves"][0]["dtz"], 1);
+
+        let (status, body) = get_tablebase(data.clone(), "8/8/8/8/8/8/6kQ/K7 b - - 0 1").await;
+        assert_eq!(status, 200);
+        assert_eq!(body["wdl"], "draw");
+        assert_eq!(body["dtz"], 0);
+        assert_eq!(body["moves"][0]["move"], "g2h2");
+
+        // Too many pieces for the tables
+        let (status, _) = get_tablebase(data.clone(), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1").await;
+        assert_eq!(status, 404);
+
+        let (status, _) = get_tablebase(data, "not a fen").await;
+        assert_eq!(status, 400);
+    }
+
+    #[actix_rt::test]
+    async fn mate_search() {
+        let app = test::init_service(App::new().app_data(web::Data::new(app_state())).configure(crate::api::config)).await;
+        let philidor = "4r2k/6pp/8/3Q2N1/8/8/5PPP/6K1 w - - 0 1";
+
+        let request = test::TestRequest::post().uri("/api/mate-search").set_json(json!({ "fen": philidor, "max_moves": 4 })).to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, request).await;
+        assert_eq!(body["found"], true);
+        assert_eq!(body["solution"]["mate_in"], 4);
+        assert_eq!(body["solution"]["keys"][0]["move"], "g5f7");
+
+        let request = test::TestRequest::post().uri("/api/mate-search").set_json(json!({ "fen": philidor, "max_moves": 2 })).to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, request).await;
+        assert_eq!(body["found"], false);
+        assert_eq!(body["max_moves"], 2);
+
+        for max_moves in [json!(0), json!(-2), json!(MAX_MATE_MOVES + 1), json!("four")] {
+            let request = test::TestRequest::post().uri("/api/mate-search").set_json(json!({ "fen": philidor, "max_moves": max_moves })).to_request();
+            assert_eq!(test::call_service(&app, request).await.status(), 400, "{}", max_moves);
+        }
+        let request = test::TestRequest::post().uri("/api/mate-search").set_json(json!({ "fen": "not a fen" })).to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), 400);
+    }
 }
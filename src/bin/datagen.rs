@@ -0,0 +1,268 @@
+use rustychess::chess::{ChessMove, Color, Engine, EvalParams, Game, OpeningBook, PieceType, MAX_SEARCH_DEPTH, TB_WIN_SCORE};
+use rustychess::chess::{insufficient_material, is_in_check, is_quiet, polyglot_key};
+#[cfg(feature = "nnue")]
+use rustychess::chess::Network;
+use rustychess::error::ChessError;
+use rand::Rng;
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Instant;
+
+const USAGE: &str = "Usage: datagen [--games N] [--threads N] [--nodes N] [--random-plies N] [--book FILE] [--params NAME|PATH] [--out FILE]";
+
+const DEFAULT_GAMES: usize = 100;
+const DEFAULT_NODES: u64 = 5000;
+const DEFAULT_RANDOM_PLIES: usize = 8;
+
+// Openings that leave one side this far ahead are played again
+const MAX_OPENING_SCORE: i32 = 400;
+// Draws of an opening before giving up on the game, for books or ply counts
+// that keep ending the game or leaving it lopsided
+const MAX_OPENING_TRIES: usize = 100;
+// A game ends as a draw after this many plies without a capture or pawn move,
+// or after this many plies in all
+const FIFTY_MOVE_PLIES: usize = 100;
+const MAX_GAME_PLIES: usize = 400;
+// A game is adjudicated as won once the side to move's score has stayed beyond
+// this for ADJUDICATION_PLIES plies in a row
+const ADJUDICATION_SCORE: i32 = 1500;
+const ADJUDICATION_PLIES: usize = 8;
+
+// Everything a worker needs to play its games
+#[derive(Clone)]
+struct Settings {
+    nodes: u64,
+    random_plies: usize,
+    book: Option<Arc<OpeningBook>>,
+    eval: EvalParams,
+    #[cfg(feature = "nnue")]
+    network: Option<Arc<Network>>,
+}
+
+// A recorded position: its FEN and the search score from White's point of view
+struct Sample {
+    fen: String,
+    score: i32,
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, ChessError> {
+    value.as_deref().and_then(|v| v.parse().ok())
+        .ok_or_else(|| ChessError::Engine(format!("{} needs a number", flag)))
+}
+
+fn play(game: &mut Game, chess_move: &ChessMove) -> Result<(), ChessError> {
+    game.make_move(&chess_move.from.to_string(), &chess_move.to.to_string())
+}
+
+// The starting position of a game: book moves while the book has them, then
+// random legal moves. Lopsided openings are thrown away and drawn again, up
+// to MAX_OPENING_TRIES times.
+fn opening(engine: &mut Engine, settings: &Settings) -> Result<Game, ChessError> {
+    let mut rng = rand::thread_rng();
+    for _ in 0..MAX_OPENING_TRIES {
+        let mut game = Game::new();
+        if let Some(book) = &settings.book {
+            while let Some(book_move) = book.choose(&game) {
+                play(&mut game, &book_move.chess_move)?;
+            }
+        }
+        let mut ended = false;
+        for _ in 0..settings.random_plies {
+            let moves = engine.get_legal_moves(&game.board, game.current_turn)?;
+            if moves.is_empty() {
+                ended = true;
+                break;
+            }
+            play(&mut game, &moves[rng.gen_range(0..moves.len())])?;
+        }
+        if ended || engine.get_legal_moves(&game.board, game.current_turn)?.is_empty() {
+            continue;
+        }
+        engine.clear_hash();
+        if engine.find_best_move(&game)?.score.abs() <= MAX_OPENING_SCORE {
+            return Ok(game);
+        }
+    }
+    Err(ChessError::Engine(format!("No balanced opening in {} tries", MAX_OPENING_TRIES)))
+}
+
+// Play one game and return its quiet positions with their scores, and the
+// result from White's point of view
+fn play_game(engine: &mut Engine, settings: &Settings) -> Result<(Vec<Sample>, f64), ChessError> {
+    let mut game = opening(engine, settings)?;
+    engine.clear_hash();
+
+    let mut samples = Vec::new();
+    let mut repetitions: HashMap<u64, usize> = HashMap::new();
+    let mut quiet_plies = 0;
+    let mut winning_streak: (Option<Color>, usize) = (None, 0);
+
+    for _ in 0..MAX_GAME_PLIES {
+        let color = game.current_turn;
+        let legal_moves = engine.get_legal_moves(&game.board, color)?;
+        if legal_moves.is_empty() {
+            // Checkmate loses for the side to move; stalemate is a draw
+            let result = if !is_in_check(&game.board, color) {
+                0.5
+            } else if color == Color::White {
+                0.0
+            } else {
+                1.0
+            };
+            return Ok((samples, result));
+        }
+        let seen = repetitions.entry(polyglot_key(&game)).or_insert(0);
+        *seen += 1;
+        if *seen >= 3 || quiet_plies >= FIFTY_MOVE_PLIES || insufficient_material(&game.board) {
+            return Ok((samples, 0.5));
+        }
+
+        let best = engine.find_best_move(&game)?;
+        let white_score = if color == Color::White { best.score } else { -best.score };
+
+        // Found mates and lasting big advantages decide the game
+        if best.score.abs() >= ADJUDICATION_SCORE {
+            let winner = if white_score > 0 { Color::White } else { Color::Black };
+            winning_streak = match winning_streak {
+                (Some(side), plies) if side == winner => (Some(side), plies + 1),
+                _ => (Some(winner), 1),
+            };
+        } else {
+            winning_streak = (None, 0);
+        }
+        if best.score.abs() > TB_WIN_SCORE || winning_streak.1 >= ADJUDICATION_PLIES {
+            let result = if white_score > 0 { 1.0 } else { 0.0 };
+            return Ok((samples, result));
+        }
+
+        // Positions in check or where the best move captures aren't settled
+        // enough for their score to describe them
+        let capture = game.board.get_piece(&best.to).is_some();
+        if !capture && is_quiet(engine, &game.board, color) {
+            samples.push(Sample { fen: game.to_fen(), score: white_score });
+        }
+
+        let pawn_move = game.board.get_piece(&best.from).is_some_and(|p| p.piece_type == PieceType::Pawn);
+        quiet_plies = if capture || pawn_move { 0 } else { quiet_plies + 1 };
+        play(&mut game, &best)?;
+    }
+    Ok((samples, 0.5))
+}
+
+fn main() -> Result<(), ChessError> {
+    // Plays engine-against-engine games with a fixed node budget per move, on
+    // several threads, and appends every quiet position to `--out` as
+    // `FEN | score | result`: the search score in centipawns and the game result
+    // (1.0, 0.5 or 0.0), both from White's point of view. Openings come from
+    // `--book` when given, followed by `--random-plies` random moves.
+    let mut games = DEFAULT_GAMES;
+    let mut threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    let mut out = PathBuf::from("datagen.txt");
+    let mut settings = Settings {
+        nodes: DEFAULT_NODES,
+        random_plies: DEFAULT_RANDOM_PLIES,
+        book: None,
+        eval: EvalParams::default(),
+        #[cfg(feature = "nnue")]
+        network: None,
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--games" => games = parse_number("--games", args.next())?,
+            "--threads" => threads = parse_number::<usize>("--threads", args.next())?.max(1),
+            "--nodes" => settings.nodes = parse_number("--nodes", args.next())?,
+            "--random-plies" => settings.random_plies = parse_number("--random-plies", args.next())?,
+            "--book" => {
+                let path = args.next().ok_or_else(|| ChessError::Book("--book needs a file".to_string()))?;
+                settings.book = Some(Arc::new(OpeningBook::open(&path)?));
+            },
+            "--params" => {
+                let name = args.next().ok_or_else(|| ChessError::EvalParams("--params needs a name or path".to_string()))?;
+                settings.eval = EvalParams::resolve(&name)?;
+            },
+            #[cfg(feature = "nnue")]
+            "--nnue" => {
+                let path = args.next().ok_or_else(|| ChessError::Nnue("--nnue needs a file".to_string()))?;
+                settings.network = Some(Arc::new(Network::load(&path)?));
+            },
+            "--out" | "-o" => {
+                out = args.next().map(PathBuf::from)
+                    .ok_or_else(|| ChessError::Engine("--out needs a file".to_string()))?;
+            },
+            _ => {
+                println!("{}", USAGE);
+                return Ok(());
+            },
+        }
+    }
+
+    let file = OpenOptions::new().create(true).append(true).open(&out)
+        .map_err(|e| ChessError::Internal(format!("Failed to open {}: {}", out.display(), e)))?;
+    let mut writer = BufWriter::new(file);
+    println!("Playing {} games on {} threads at {} nodes per move into {}", games, threads, settings.nodes, out.display());
+
+    let next_game = Arc::new(AtomicUsize::new(0));
+    let (sender, receiver) = mpsc::channel();
+    let workers: Vec<_> = (0..threads).map(|_| {
+        let settings = settings.clone();
+        let next_game = next_game.clone();
+        let sender = sender.clone();
+        thread::spawn(move || {
+            let mut engine = Engine::new(MAX_SEARCH_DEPTH);
+            engine.set_node_limit(Some(settings.nodes));
+            engine.set_eval_params(settings.eval);
+            #[cfg(feature = "nnue")]
+            engine.set_nnue(settings.network.clone());
+            while next_game.fetch_add(1, Ordering::Relaxed) < games {
+                if sender.send(play_game(&mut engine, &settings)).is_err() {
+                    break;
+                }
+            }
+        })
+    }).collect();
+    drop(sender);
+
+    let started = Instant::now();
+    let (mut finished, mut failed, mut positions) = (0, 0, 0);
+    let mut results = [0; 3];
+    for outcome in receiver {
+        // One game going wrong shouldn't cost the rest of the run
+        let (samples, result) = match outcome {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                failed += 1;
+                eprintln!("Skipping a game: {}", e);
+                continue;
+            },
+        };
+        for sample in &samples {
+            writeln!(writer, "{} | {} | {:.1}", sample.fen, sample.score, result)
+                .map_err(|e| ChessError::Internal(format!("Failed to write {}: {}", out.display(), e)))?;
+        }
+        finished += 1;
+        positions += samples.len();
+        results[(result * 2.0) as usize] += 1;
+        if finished % 10 == 0 || finished + failed == games {
+            let elapsed = started.elapsed().as_secs_f64();
+            println!(
+                "{}/{} games (+{} ={} -{}), {} positions, {:.1} positions/s",
+                finished, games, results[2], results[1], results[0], positions, positions as f64 / elapsed.max(0.001),
+            );
+        }
+    }
+    for worker in workers {
+        let _ = worker.join();
+    }
+    if failed > 0 {
+        eprintln!("{} of {} games failed and were skipped", failed, games);
+    }
+    writer.flush()
+        .map_err(|e| ChessError::Internal(format!("Failed to write {}: {}", out.display(), e)))?;
+    Ok(())
+}
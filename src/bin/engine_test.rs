@@ -1,4 +1,4 @@
-use rustychess::chess::{Board, Position, Piece, PieceType, Color, Game, ChessMove, Engine};
+use rustychess::chess::{Board, Position, Color, Game, Engine};
 use rustychess::error::ChessError;
 use std::str::FromStr;
 
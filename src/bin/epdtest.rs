@@ -0,0 +1,226 @@
+use rustychess::chess::{ChessMove, Engine, EpdPosition, EvalParams, MATE_SCORE, MAX_SEARCH_DEPTH, TB_WIN_SCORE};
+use rustychess::chess::load_epd_suite;
+#[cfg(feature = "nnue")]
+use rustychess::chess::Network;
+use rustychess::error::ChessError;
+#[cfg(feature = "nnue")]
+use std::sync::Arc;
+use std::time::Instant;
+
+const USAGE: &str = "Usage: epdtest [--movetime MS] [--depth N] [--threads N] [--hash MB] [--params NAME|PATH] [--no-extensions] SUITE.epd...";
+
+const DEFAULT_MOVETIME_MS: u64 = 1000;
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, ChessError> {
+    value.as_deref().and_then(|v| v.parse().ok())
+        .ok_or_else(|| ChessError::Engine(format!("{} needs a number", flag)))
+}
+
+// The engine's answer to one position
+struct Attempt {
+    chess_move: ChessMove,
+    san: String,
+    score: i32,
+    depth: u8,
+    // Depth and time at which the engine settled on a solution for good
+    solved_at: Option<(u8, u64)>,
+}
+
+// "+1.25", "-0.40", "M3" or "-M2", from the side to move's point of view
+fn format_score(score: i32) -> String {
+    if score.abs() > TB_WIN_SCORE {
+        let moves = (MATE_SCORE - score.abs() + 1) / 2;
+        format!("{}M{}", if score < 0 { "-" } else { "" }, moves)
+    } else {
+        format!("{:+.2}", score as f64 / 100.0)
+    }
+}
+
+// Search one depth at a time so that the moment the right move appears can be
+// timed. The transposition table carries over between depths, so each step
+// mostly repeats work it already has.
+fn solve(engine: &mut Engine, position: &EpdPosition, max_depth: u8, movetime_ms: Option<u64>) -> Result<Attempt, ChessError> {
+    engine.clear_hash();
+    let start = Instant::now();
+    let mut attempt: Option<Attempt> = None;
+    for depth in 1..=max_depth {
+        let elapsed = start.elapsed().as_millis() as u64;
+        let remaining = movetime_ms.map(|ms| ms.saturating_sub(elapsed));
+        if remaining == Some(0) {
+            break;
+        }
+        engine.set_depth(depth);
+        engine.set_movetime(remaining);
+        let lines = engine.analyze(&position.game)?;
+        let line = &lines[0];
+        // Out of time before this depth finished
+        if line.depth < depth && attempt.is_some() {
+            break;
+        }
+
+        let chess_move = line.moves[0].clone();
+        let solved = position.is_solved_by(engine, &chess_move, line.score)?;
+        let solved_at = match attempt.as_ref().and_then(|a| a.solved_at) {
+            Some(at) if solved => Some(at),
+            _ if solved => Some((line.depth, start.elapsed().as_millis() as u64)),
+            _ => None,
+        };
+        let mate_found = line.score.abs() > TB_WIN_SCORE && (MATE_SCORE - line.score.abs()) as u8 <= line.depth;
+        attempt = Some(Attempt {
+            chess_move,
+            san: line.san[0].clone(),
+            score: line.score,
+            depth: line.depth,
+            solved_at,
+        });
+        // A mate within the search horizon can't change with more depth
+        if mate_found || line.depth < depth {
+            break;
+        }
+    }
+    attempt.ok_or_else(|| ChessError::Engine("No move found".to_string()))
+}
+
+// "STS(v1.0) Undermine.001" -> "STS(v1.0) Undermine"
+fn suite_group(id: &str) -> &str {
+    match id.rsplit_once('.') {
+        Some((group, number)) if number.bytes().all(|b| b.is_ascii_digit()) => group,
+        _ => id,
+    }
+}
+
+fn main() -> Result<(), ChessError> {
+    // Runs the engine on every position of each EPD suite and checks its move
+    // against the bm, am and dm operations. Each position gets `--movetime`
+    // milliseconds (one second by default) and at most `--depth` plies. Prints a
+    // line per position with the time the engine first settled on the solution,
+    // then the number solved and, for suites that score moves (STS), the points.
+    let mut engine = Engine::new(MAX_SEARCH_DEPTH);
+    let mut max_depth = MAX_SEARCH_DEPTH;
+    let mut movetime_ms = None;
+    let mut suites = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--movetime" => movetime_ms = Some(parse_number("--movetime", args.next())?),
+            "--depth" => max_depth = parse_number::<u8>("--depth", args.next())?.clamp(1, MAX_SEARCH_DEPTH),
+            "--threads" => engine.set_threads(parse_number::<usize>("--threads", args.next())?.max(1)),
+            "--hash" => engine.set_hash_size(parse_number("--hash", args.next())?),
+            "--no-extensions" => engine.set_extensions(false),
+            "--params" => {
+                let name = args.next().ok_or_else(|| ChessError::EvalParams("--params needs a name or path".to_string()))?;
+                engine.set_eval_params(EvalParams::resolve(&name)?);
+            },
+            #[cfg(feature = "nnue")]
+            "--nnue" => {
+                let path = args.next().ok_or_else(|| ChessError::Nnue("--nnue needs a file".to_string()))?;
+                engine.set_nnue(Some(Arc::new(Network::load(&path)?)));
+            },
+            _ if arg.starts_with("--") => {
+                println!("{}", USAGE);
+                return Ok(());
+            },
+            _ => suites.push(arg),
+        }
+    }
+    if suites.is_empty() {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+    // A depth alone means no time limit
+    if movetime_ms.is_none() && max_depth == MAX_SEARCH_DEPTH {
+        movetime_ms = Some(DEFAULT_MOVETIME_MS);
+    }
+
+    for suite in &suites {
+        let positions = load_epd_suite(suite)?;
+        println!("{}: {} positions", suite, positions.len());
+        let (mut solved, mut skipped) = (0, 0);
+        let mut solve_time_ms = 0;
+        let (mut points, mut max_points) = (0, 0);
+        // Points and maximum per group of ids, in order of appearance
+        let mut groups: Vec<(String, u32, u32)> = Vec::new();
+        let started = Instant::now();
+
+        for (number, position) in positions.iter().enumerate() {
+            let id = position.id().map(str::to_string).unwrap_or_else(|| format!("#{}", number + 1));
+            let expected: Vec<String> = [("bm", ""), ("am", "not "), ("dm", "mate in ")].iter()
+                .filter_map(|(opcode, prefix)| position.operation(opcode).map(|moves| format!("{}{}", prefix, moves.join(" "))))
+                .collect();
+            // Positions whose moves this board can't play, such as castling
+            let checked = position.best_moves(&engine)
+                .and_then(|_| position.avoid_moves(&engine))
+                .and_then(|_| position.move_points(&engine));
+            let scored = match checked {
+                Ok(scored) => scored,
+                Err(e) => {
+                    println!("{:>4} {:<24} skip  {}", number + 1, id, e);
+                    skipped += 1;
+                    continue;
+                },
+            };
+            let attempt = match solve(&mut engine, position, max_depth, movetime_ms) {
+                Ok(attempt) => attempt,
+                Err(e) => {
+                    println!("{:>4} {:<24} skip  {}", number + 1, id, e);
+                    skipped += 1;
+                    continue;
+                },
+            };
+
+            let mut line = format!(
+                "{:>4} {:<24} {}  {:<7} {:>6}  depth {:>2}",
+                number + 1, id, if attempt.solved_at.is_some() { "pass" } else { "FAIL" },
+                attempt.san, format_score(attempt.score), attempt.depth,
+            );
+            if let Some((depth, ms)) = attempt.solved_at {
+                solved += 1;
+                solve_time_ms += ms;
+                line.push_str(&format!("  solved at depth {} in {:.2}s", depth, ms as f64 / 1000.0));
+            }
+            if !scored.is_empty() {
+                let earned = scored.iter()
+                    .find(|(m, _)| m.from == attempt.chess_move.from && m.to == attempt.chess_move.to)
+                    .map_or(0, |(_, p)| *p);
+                let best = scored.iter().map(|(_, p)| *p).max().unwrap_or(0);
+                points += earned;
+                max_points += best;
+                let group = suite_group(&id).to_string();
+                match groups.iter_mut().find(|(name, _, _)| *name == group) {
+                    Some(entry) => {
+                        entry.1 += earned;
+                        entry.2 += best;
+                    },
+                    None => groups.push((group, earned, best)),
+                }
+                line.push_str(&format!("  {}/{} points", earned, best));
+            }
+            if !expected.is_empty() {
+                line.push_str(&format!("  ({})", expected.join(", ")));
+            }
+            println!("{}", line);
+        }
+
+        let tried = positions.len() - skipped;
+        println!();
+        println!(
+            "{}: solved {}/{} ({:.1}%){} in {:.1}s",
+            suite, solved, tried, solved as f64 * 100.0 / tried.max(1) as f64,
+            if skipped > 0 { format!(", {} skipped", skipped) } else { String::new() },
+            started.elapsed().as_secs_f64(),
+        );
+        if solved > 0 {
+            println!("Average time to solution: {:.2}s", solve_time_ms as f64 / solved as f64 / 1000.0);
+        }
+        if max_points > 0 {
+            if groups.len() > 1 {
+                for (group, earned, best) in groups.iter().filter(|(_, _, best)| *best > 0) {
+                    println!("  {:<40} {:>5}/{:<5} ({:.1}%)", group, earned, best, *earned as f64 * 100.0 / *best as f64);
+                }
+            }
+            println!("Points: {}/{} ({:.1}%)", points, max_points, points as f64 * 100.0 / max_points as f64);
+        }
+        println!();
+    }
+    Ok(())
+}
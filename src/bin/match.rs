@@ -0,0 +1,199 @@
+use rustychess::chess::{Adjudication, EngineSpec, Game, GameRecord, MatchScore, Sprt, SprtVerdict, TimeControl};
+use rustychess::chess::{load_openings, play_game, random_opening, Color};
+use rustychess::error::ChessError;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+const USAGE: &str = "Usage: match --engine1 SPEC --engine2 SPEC [--games N] [--concurrency N] [--tc 10+0.1|movetime=MS|nodes=N|depth=N] \
+[--openings FILE] [--random-plies N] [--sprt ELO0 ELO1 [ALPHA BETA]] [--draw SCORE MOVES AFTER] [--no-draw] \
+[--resign SCORE MOVES] [--no-resign] [--pgn FILE]
+
+An engine SPEC is a comma-separated list of key=value settings:
+  built-in engine: personality=NAME|PATH, skill=0-20, elo=N, threads=N, hash=MB, extensions=true|false, name=NAME
+  UCI engine:      cmd=PATH, arg=ARG, option.NAME=VALUE, name=NAME";
+
+const DEFAULT_GAMES: usize = 100;
+const DEFAULT_TIME_CONTROL: &str = "10+0.1";
+const DEFAULT_RANDOM_PLIES: usize = 8;
+
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, ChessError> {
+    value.as_deref().and_then(|v| v.parse().ok())
+        .ok_or_else(|| ChessError::Engine(format!("{} needs a number", flag)))
+}
+
+fn next_arg(flag: &str, args: &mut impl Iterator<Item = String>) -> Result<String, ChessError> {
+    args.next().ok_or_else(|| ChessError::Engine(format!("{} needs a value", flag)))
+}
+
+// One finished game of a pair, with the points engine 1 scored in it
+struct Finished {
+    pair: usize,
+    record: GameRecord,
+    points: f64,
+}
+
+// The running score line, e.g. "+12 =30 -8 [0.540] Elo +27.9 +/- 61.2, LOS 81.6%, LLR 0.53 [-2.94, 2.94]"
+fn report(score: &MatchScore, sprt: Option<&Sprt>) -> String {
+    let mut line = format!("{} [{:.3}]", score, score.score());
+    match score.elo() {
+        Some((elo, margin)) => line.push_str(&format!(" Elo {:+.1} +/- {:.1}", elo, margin)),
+        None => line.push_str(" Elo -"),
+    }
+    line.push_str(&format!(", LOS {:.1}%", score.los() * 100.0));
+    if let Some(sprt) = sprt {
+        let (lower, upper) = sprt.bounds();
+        line.push_str(&format!(", LLR {:.2} [{:.2}, {:.2}]", sprt.llr(score), lower, upper));
+    }
+    line
+}
+
+fn main() -> Result<(), ChessError> {
+    // Plays engine 1 against engine 2 over pairs of games from the same opening,
+    // once with each color, several games at a time. Each opening comes from
+    // `--openings` (an EPD or FEN file, used in order) or from random moves.
+    // With `--sprt` the match stops as soon as the test reaches a verdict.
+    let mut engines: [Option<EngineSpec>; 2] = [None, None];
+    let mut games = DEFAULT_GAMES;
+    let mut concurrency = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    let mut time_control: TimeControl = DEFAULT_TIME_CONTROL.parse()?;
+    let mut openings: Option<Vec<Game>> = None;
+    let mut random_plies = DEFAULT_RANDOM_PLIES;
+    let mut sprt = None;
+    let mut adjudication = Adjudication::default();
+    let mut pgn_path: Option<PathBuf> = None;
+
+    let mut args = std::env::args().skip(1).peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--engine1" => engines[0] = Some(next_arg(&arg, &mut args)?.parse()?),
+            "--engine2" => engines[1] = Some(next_arg(&arg, &mut args)?.parse()?),
+            "--games" => games = parse_value(&arg, args.next())?,
+            "--concurrency" => concurrency = parse_value::<usize>(&arg, args.next())?.max(1),
+            "--tc" => time_control = next_arg(&arg, &mut args)?.parse()?,
+            "--openings" => openings = Some(load_openings(next_arg(&arg, &mut args)?)?),
+            "--random-plies" => random_plies = parse_value(&arg, args.next())?,
+            "--sprt" => {
+                let mut test = Sprt::new(parse_value(&arg, args.next())?, parse_value(&arg, args.next())?);
+                // Alpha and beta are optional
+                if args.peek().is_some_and(|a| !a.starts_with("--")) {
+                    test.alpha = parse_value(&arg, args.next())?;
+                    test.beta = parse_value(&arg, args.next())?;
+                }
+                sprt = Some(test);
+            },
+            "--draw" => {
+                adjudication.draw_score = Some(parse_value(&arg, args.next())?);
+                adjudication.draw_moves = parse_value(&arg, args.next())?;
+                adjudication.draw_after = parse_value(&arg, args.next())?;
+            },
+            "--no-draw" => adjudication.draw_score = None,
+            "--resign" => {
+                adjudication.resign_score = Some(parse_value(&arg, args.next())?);
+                adjudication.resign_moves = parse_value(&arg, args.next())?;
+            },
+            "--no-resign" => adjudication.resign_score = None,
+            "--pgn" => pgn_path = Some(PathBuf::from(next_arg(&arg, &mut args)?)),
+            _ => {
+                println!("{}", USAGE);
+                return Ok(());
+            },
+        }
+    }
+    let [Some(engine1), Some(engine2)] = engines else {
+        println!("{}", USAGE);
+        return Ok(());
+    };
+    let pairs = games.div_ceil(2);
+    let mut pgn = match &pgn_path {
+        Some(path) => Some(BufWriter::new(File::create(path)
+            .map_err(|e| ChessError::Internal(format!("Failed to create {}: {}", path.display(), e)))?)),
+        None => None,
+    };
+    println!(
+        "{} vs {}: {} games at {}, {} at a time",
+        engine1.name, engine2.name, pairs * 2, time_control, concurrency,
+    );
+    if let Some(sprt) = &sprt {
+        println!("SPRT: elo0 {} elo1 {} alpha {} beta {}", sprt.elo0, sprt.elo1, sprt.alpha, sprt.beta);
+    }
+
+    let engines = Arc::new((engine1, engine2));
+    let openings = Arc::new(openings);
+    let next_pair = Arc::new(AtomicUsize::new(0));
+    let stop = Arc::new(AtomicBool::new(false));
+    let (sender, receiver) = mpsc::channel();
+    let workers: Vec<_> = (0..concurrency.min(pairs)).map(|_| {
+        let (engines, openings, next_pair, stop, sender) = (engines.clone(), openings.clone(), next_pair.clone(), stop.clone(), sender.clone());
+        thread::spawn(move || {
+            let (spec1, spec2) = &*engines;
+            let (mut first, mut second) = (spec1.create(), spec2.create());
+            loop {
+                let pair = next_pair.fetch_add(1, Ordering::Relaxed);
+                if pair >= pairs || stop.load(Ordering::Relaxed) {
+                    break;
+                }
+                let opening = match &*openings {
+                    Some(openings) => openings[pair % openings.len()].clone(),
+                    None => random_opening(random_plies),
+                };
+                // The same opening with engine 1 as White, then as Black
+                for engine1_color in [Color::White, Color::Black] {
+                    let record = if engine1_color == Color::White {
+                        play_game((&spec1.name, first.as_mut()), (&spec2.name, second.as_mut()), &opening, time_control, &adjudication)
+                    } else {
+                        play_game((&spec2.name, second.as_mut()), (&spec1.name, first.as_mut()), &opening, time_control, &adjudication)
+                    };
+                    let points = record.result.points(engine1_color);
+                    if sender.send(Finished { pair, record, points }).is_err() {
+                        return;
+                    }
+                }
+            }
+        })
+    }).collect();
+    drop(sender);
+
+    let mut score = MatchScore::default();
+    let mut verdict = SprtVerdict::Continue;
+    for finished in receiver {
+        score.add(finished.points);
+        let record = &finished.record;
+        println!(
+            "Game {} ({} vs {}): {} {{{}}}",
+            score.games(), record.white, record.black, record.result, record.termination,
+        );
+        if let Some(pgn) = pgn.as_mut() {
+            let round = format!("{}.{}", finished.pair + 1, if record.white == engines.0.name { 1 } else { 2 });
+            pgn.write_all(record.to_pgn("rustychess match", &round).as_bytes())
+                .map_err(|e| ChessError::Internal(format!("Failed to write PGN: {}", e)))?;
+        }
+        println!("Score of {} vs {}: {}", engines.0.name, engines.1.name, report(&score, sprt.as_ref()));
+
+        if let Some(sprt) = &sprt {
+            if verdict == SprtVerdict::Continue {
+                verdict = sprt.verdict(&score);
+                if verdict != SprtVerdict::Continue {
+                    println!("SPRT: {}; finishing the games in progress", verdict);
+                    stop.store(true, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+    for worker in workers {
+        let _ = worker.join();
+    }
+    if let Some(mut pgn) = pgn {
+        pgn.flush().map_err(|e| ChessError::Internal(format!("Failed to write PGN: {}", e)))?;
+    }
+
+    println!();
+    println!("Final score of {} vs {}: {}", engines.0.name, engines.1.name, report(&score, sprt.as_ref()));
+    if let Some(sprt) = &sprt {
+        println!("SPRT ({:+} to {:+} Elo): {}", sprt.elo0, sprt.elo1, sprt.verdict(&score));
+    }
+    Ok(())
+}
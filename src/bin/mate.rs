@@ -0,0 +1,126 @@
+use rustychess::chess::{Game, MateMove, MateSearch, Stipulation};
+use rustychess::error::ChessError;
+use std::time::Instant;
+
+const USAGE: &str = "Usage: mate [--moves N | --stipulation #N|s#N|h#N|h#N.5] [--nodes N] [--json] FEN";
+
+const DEFAULT_MOVES: u32 = 3;
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, ChessError> {
+    value.as_deref().and_then(|v| v.parse().ok())
+        .ok_or_else(|| ChessError::Engine(format!("{} needs a number", flag)))
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> Result<String, ChessError> {
+    serde_json::to_string_pretty(value)
+        .map_err(|e| ChessError::Internal(format!("Failed to serialize solution: {}", e)))
+}
+
+// The defences against `chess_move` and their answers, one per line, in the
+// problemists' notation where the attacker's moves are numbered from 1
+// whichever side it is. A selfmating defence ends its line.
+fn print_defences(chess_move: &MateMove, number: u32, indent: usize) {
+    for defence in &chess_move.defences {
+        let Some(answer) = &defence.continuation else {
+            println!("{:indent$}{}... {}", "", number, defence.san);
+            continue;
+        };
+        let mut line = format!("{:indent$}{}... {} {}. {}", "", number, defence.san, number + 1, answer.san);
+        if !answer.duals.is_empty() {
+            line.push_str(&format!("  (dual: {})", answer.duals.join(", ")));
+        }
+        println!("{}", line);
+        print_defences(answer, number + 1, indent + 3);
+    }
+}
+
+fn main() -> Result<(), ChessError> {
+    // Solves chess problems. Direct mates (#N) and selfmates (s#N) get the
+    // shortest forced solution within N moves, whether the key is unique or the
+    // problem is cooked, and every defence with its answer. Helpmates (h#N)
+    // list every solution, flag duplicates reaching the same mate in another
+    // order, and look for shorter solutions that cook the problem.
+    let mut stipulation = Stipulation::Direct(DEFAULT_MOVES);
+    let mut solver = MateSearch::new();
+    let mut json = false;
+    let mut fen = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--moves" | "-n" => stipulation = Stipulation::Direct(parse_number("--moves", args.next())?),
+            "--stipulation" | "-s" => {
+                let text = args.next().ok_or_else(|| ChessError::Engine("--stipulation needs a value".to_string()))?;
+                stipulation = text.parse()?;
+            },
+            "--nodes" => solver.set_node_limit(Some(parse_number("--nodes", args.next())?)),
+            "--json" => json = true,
+            _ if arg.starts_with("--") => {
+                println!("{}", USAGE);
+                return Ok(());
+            },
+            _ => fen.push(arg),
+        }
+    }
+    if fen.is_empty() {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+    let game = Game::from_fen(&fen.join(" "))?;
+
+    let started = Instant::now();
+    let (max_moves, selfmate) = match stipulation {
+        Stipulation::Direct(moves) => (moves, false),
+        Stipulation::Selfmate(moves) => (moves, true),
+        Stipulation::Helpmate(plies) => {
+            let solution = solver.solve_helpmate(&game, plies)?;
+            if json {
+                println!("{}", to_json(&solution)?);
+                return Ok(());
+            }
+            let verdict = match solution.solutions.len() {
+                0 => "no solution".to_string(),
+                1 => "1 solution".to_string(),
+                n => format!("{} solutions", n),
+            };
+            println!("{} ({} nodes, {:.2}s): {}", stipulation, solution.nodes, started.elapsed().as_secs_f64(), verdict);
+            for (i, line) in solution.solutions.iter().enumerate() {
+                let twin = solution.duplicates.iter()
+                    .find(|group| group.contains(&i) && group[0] != i)
+                    .map(|group| format!("  (duplicate of {})", group[0] + 1))
+                    .unwrap_or_default();
+                println!("{:>3}) {}{}", i + 1, line.notation, twin);
+            }
+            if !solution.short_solutions.is_empty() {
+                println!();
+                println!("Cooked by shorter solutions:");
+                for line in &solution.short_solutions {
+                    println!("     {}", line.notation);
+                }
+            }
+            return Ok(());
+        },
+    };
+
+    let solution = if selfmate { solver.solve_selfmate(&game, max_moves)? } else { solver.solve(&game, max_moves)? };
+    if json {
+        println!("{}", to_json(&solution)?);
+        return Ok(());
+    }
+    let goal = if selfmate { "Selfmate" } else { "Mate" };
+    let Some(solution) = solution else {
+        println!("No {} in {} ({} nodes, {:.2}s)", goal.to_lowercase(), max_moves, solver.nodes(), started.elapsed().as_secs_f64());
+        return Ok(());
+    };
+
+    println!(
+        "{} in {} ({} nodes, {:.2}s): {}",
+        goal, solution.mate_in, solution.nodes, started.elapsed().as_secs_f64(),
+        if solution.unique { "unique key".to_string() } else { format!("cooked, {} keys", solution.keys.len()) },
+    );
+    for key in &solution.keys {
+        println!();
+        println!("1. {}!", key.san);
+        print_defences(key, 1, 3);
+    }
+    Ok(())
+}
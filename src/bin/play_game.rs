@@ -1,14 +1,54 @@
-use rustychess::chess::{Board, Position, Color, Game, Engine};
+use rustychess::api::SearchPool;
+use rustychess::chess::{Color, Game, BackendRegistry, EvalParams, Ponderer, SearchLimits, Skill, DEFAULT_BACKEND};
 use rustychess::error::ChessError;
-use std::str::FromStr;
 use std::io::{self, Write};
 
 fn main() -> Result<(), ChessError> {
     println!("RustyChess Engine Interactive Test");
     println!("==================================");
     
+    // The engine's backend can be picked by name, e.g. `play_game greedy`.
+    // With `--ponder` the engine keeps thinking while you do, and `--skill=N`
+    // (0-20) or `--elo=N` make it play weaker. `--personality=NAME` picks a built-in
+    // personality or an evaluation parameter file.
+    let registry = BackendRegistry::default();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let ponder = args.iter().any(|a| a == "--ponder");
+    let mut skill = Skill::full();
+    let mut eval_params = None;
+    for arg in &args {
+        if let Some(personality) = arg.strip_prefix("--personality=") {
+            eval_params = Some(EvalParams::resolve(personality)?);
+        } else if let Some(level) = arg.strip_prefix("--skill=") {
+            skill = level.parse()?;
+        } else if let Some(elo) = arg.strip_prefix("--elo=") {
+            let elo = elo.parse::<u32>()
+                .map_err(|_| ChessError::Engine(format!("Invalid Elo: {}", elo)))?;
+            skill = Skill::from_elo(elo);
+        }
+    }
+    let backend_name = args.iter().find(|a| !a.starts_with("--")).cloned()
+        .unwrap_or_else(|| DEFAULT_BACKEND.to_string());
+    let mut backend = registry.create(&backend_name)?;
+    println!("Playing against the {} backend{}", backend.name(), if ponder { " (pondering)" } else { "" });
+    if skill.is_limited() {
+        println!("Engine strength: skill {}", skill);
+    }
+    
     // Create a new game
     let mut game = Game::new();
+    game.backend = backend_name;
+    game.ponder = ponder;
+    game.skill = skill;
+    game.eval_params = eval_params;
+    let limits = SearchLimits {
+        eval: eval_params,
+        ..SearchLimits::depth(3).with_skill(skill)
+    };
+    
+    // Speculative search of your expected reply, started after each engine move
+    let mut ponderer: Option<Ponderer> = None;
+    let ponder_pool = SearchPool::new(1);
     
     // Print the initial board
     println!("Initial board:");
@@ -60,19 +100,44 @@ fn main() -> Result<(), ChessError> {
             // Engine's turn (Black)
             println!("Engine is thinking...");
             
-            // Create an engine with depth 3
-            let mut engine = Engine::new(3);
+            // If you played the move the engine expected, its ponder search is the real one
+            let outcome = match ponderer.take() {
+                Some(ponder) if ponder.is_hit(&game) => {
+                    println!("Ponder hit on {}", ponder.ponder_move());
+                    ponder.hit().map(|(ponder_backend, result)| {
+                        backend = ponder_backend;
+                        result
+                    })
+                },
+                Some(ponder) => {
+                    backend = match ponder.miss() {
+                        Some(ponder_backend) => ponder_backend,
+                        None => registry.create(&game.backend)?,
+                    };
+                    backend.search(&game, &limits)
+                },
+                None => backend.search(&game, &limits),
+            };
             
-            // Find the best move
-            match engine.find_best_move(&game) {
-                Ok(best_move) => {
-                    let (nodes, depth) = engine.get_stats();
+            match outcome {
+                Ok(result) => {
+                    let best_move = &result.best_move;
                     println!("Engine's move: {} -> {} (score: {}, nodes: {}, depth: {})", 
-                             best_move.from, best_move.to, best_move.score, nodes, depth);
+                             best_move.from, best_move.to, result.score, result.nodes, result.depth);
                     
                     // Apply the move to the game
                     game.make_move(&best_move.from.to_string(), &best_move.to.to_string())?;
                     println!("{}", game.board.debug_print());
+                    
+                    // Think about the expected reply on your time. The backend
+                    // moves into the ponder search and comes back when it ends.
+                    if game.ponder {
+                        if let Some(ponder_move) = result.ponder_move() {
+                            println!("Pondering on {}", ponder_move);
+                            let idle = std::mem::replace(&mut backend, registry.create(&game.backend)?);
+                            ponderer = Some(Ponderer::start(idle, &game, ponder_move, limits, None, |job| ponder_pool.execute(job))?);
+                        }
+                    }
                 },
                 Err(e) => {
                     println!("Engine error: {:?}", e);
@@ -0,0 +1,140 @@
+use rustychess::chess::{Color, DtmTablebase, dtm_material, encode_syzygy};
+use rustychess::error::ChessError;
+use std::path::PathBuf;
+use std::time::Instant;
+
+// Longest mates in moves with the stronger side to move, as published for
+// complete tablebases, to cross-check what the generator finds
+const KNOWN_LONGEST_MATES: [(&str, u16); 10] = [
+    ("KQvK", 10),
+    ("KRvK", 16),
+    ("KBBvK", 19),
+    ("KBNvK", 33),
+    ("KPvK", 28),
+    ("KQvKN", 21),
+    ("KQvKB", 17),
+    ("KQvKR", 35),
+    ("KRvKB", 29),
+    ("KRvKN", 40),
+];
+
+// Every material with three or four pieces
+fn all_materials(max_pieces: usize) -> Vec<String> {
+    let extras = ["Q", "R", "B", "N", "P"];
+    let mut materials = Vec::new();
+    for a in extras {
+        materials.push(format!("K{}vK", a));
+    }
+    if max_pieces >= 4 {
+        for (i, a) in extras.iter().enumerate() {
+            for b in &extras[i..] {
+                materials.push(format!("K{}{}vK", a, b));
+            }
+            for b in extras {
+                materials.push(format!("K{}vK{}", a, b));
+            }
+        }
+    }
+    let mut names: Vec<String> = materials.iter().filter_map(|m| dtm_material(m).ok()).collect();
+    names.sort_by_key(|name| name.len());
+    names.dedup();
+    names
+}
+
+fn main() -> Result<(), ChessError> {
+    // Usage: tbgen [--out DIR] [--syzygy] MATERIAL... where a material is written
+    // like KQK or KRvKN, or `all3` / `all4` for every ending with up to three or
+    // four pieces. Tables the requested ones depend on are generated and written
+    // too. `--syzygy` also writes the requested three-piece endings without pawns
+    // as Syzygy files, which is how the prober's test tables are made.
+    let mut out = PathBuf::from("tablebases");
+    let mut syzygy = false;
+    let mut materials = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--out" | "-o" => {
+                out = args.next().map(PathBuf::from)
+                    .ok_or_else(|| ChessError::Tablebase("--out needs a directory".to_string()))?;
+            },
+            "--syzygy" => syzygy = true,
+            "all3" => materials.extend(all_materials(3)),
+            "all4" => materials.extend(all_materials(4)),
+            material => materials.push(material.to_string()),
+        }
+    }
+    if materials.is_empty() {
+        println!("Usage: tbgen [--out DIR] [--syzygy] MATERIAL... (e.g. KQK KRK KBNK KPK, all3, all4)");
+        return Ok(());
+    }
+
+    std::fs::create_dir_all(&out)
+        .map_err(|e| ChessError::Tablebase(format!("Failed to create {}: {}", out.display(), e)))?;
+    // Tables already on disk don't need generating again
+    let mut tablebase = DtmTablebase::open(&out).unwrap_or_default();
+    if !tablebase.is_empty() {
+        println!("Loaded {} existing tables from {}", tablebase.len(), out.display());
+    }
+
+    let mut mismatches = 0;
+    for material in &materials {
+        let started = Instant::now();
+        let generated = tablebase.generate(material)?;
+        if generated.is_empty() {
+            println!("{} is already in {}", material, out.display());
+        } else {
+            println!("Generated {} in {:.1}s", generated.join(", "), started.elapsed().as_secs_f64());
+        }
+        for name in generated {
+            let table = tablebase.get(&name).expect("generated table is loaded");
+            let path = out.join(format!("{}.dtm", name));
+            table.write(&path)?;
+
+            let white = table.stats(Color::White);
+            let black = table.stats(Color::Black);
+            println!("{} -> {}", name, path.display());
+            for (side, stats) in [("white", white), ("black", black)] {
+                println!(
+                    "  {} to move: {} positions, {} wins, {} draws, {} losses, longest mate {} plies",
+                    side, stats.positions, stats.wins, stats.draws, stats.losses,
+                    stats.longest_win.max(stats.longest_loss),
+                );
+            }
+
+            let longest = white.longest_win.div_ceil(2);
+            if let Some(fen) = table.longest_win_fen(Color::White) {
+                println!("  longest win for white: mate in {} ({})", longest, fen);
+            }
+            if let Some(&(_, known)) = KNOWN_LONGEST_MATES.iter().find(|(known, _)| *known == name) {
+                if known == longest {
+                    println!("  matches the known longest mate in {}", known);
+                } else {
+                    println!("  MISMATCH: the known longest mate is in {}", known);
+                    mismatches += 1;
+                }
+            }
+        }
+    }
+
+    if syzygy {
+        for material in &materials {
+            let name = dtm_material(material)?;
+            match encode_syzygy(&tablebase, &name) {
+                Ok((wdl, dtz)) => {
+                    for (extension, bytes) in [("rtbw", wdl), ("rtbz", dtz)] {
+                        let path = out.join(format!("{}.{}", name, extension));
+                        std::fs::write(&path, bytes)
+                            .map_err(|e| ChessError::Tablebase(format!("Failed to write {}: {}", path.display(), e)))?;
+                        println!("{} -> {}", name, path.display());
+                    }
+                },
+                Err(e) => println!("{}: {}", name, e),
+            }
+        }
+    }
+
+    if mismatches > 0 {
+        return Err(ChessError::Tablebase(format!("{} tables disagree with known mate lengths", mismatches)));
+    }
+    Ok(())
+}
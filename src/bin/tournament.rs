@@ -0,0 +1,178 @@
+use rustychess::chess::{Adjudication, EngineSpec, Entrant, Game, GameRecord, SearchBackend, TimeControl, Tournament, TournamentFormat};
+use rustychess::chess::{load_openings, play_game, random_opening};
+use rustychess::error::ChessError;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+const USAGE: &str = "Usage: tournament --engine SPEC --engine SPEC [--engine SPEC ...] [--format round-robin|double|gauntlet] \
+[--rounds N] [--concurrency N] [--tc 10+0.1|movetime=MS|nodes=N|depth=N] [--openings FILE] [--random-plies N] \
+[--draw SCORE MOVES AFTER] [--no-draw] [--resign SCORE MOVES] [--no-resign] [--state FILE] [--pgn FILE]
+       tournament --resume FILE [--concurrency N] [--pgn FILE]
+
+An engine SPEC is a comma-separated list of key=value settings:
+  built-in engine: personality=NAME|PATH, skill=0-20, elo=N, threads=N, hash=MB, extensions=true|false, name=NAME
+  UCI engine:      cmd=PATH, arg=ARG, option.NAME=VALUE, name=NAME
+In a gauntlet the first engine plays all the others.";
+
+const DEFAULT_TIME_CONTROL: &str = "10+0.1";
+const DEFAULT_RANDOM_PLIES: usize = 8;
+const DEFAULT_STATE: &str = "tournament.json";
+const EVENT: &str = "rustychess tournament";
+
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, ChessError> {
+    value.as_deref().and_then(|v| v.parse().ok())
+        .ok_or_else(|| ChessError::Engine(format!("{} needs a number", flag)))
+}
+
+fn next_arg(flag: &str, args: &mut impl Iterator<Item = String>) -> Result<String, ChessError> {
+    args.next().ok_or_else(|| ChessError::Engine(format!("{} needs a value", flag)))
+}
+
+fn write_pgn(tournament: &Tournament, path: &PathBuf) -> Result<(), ChessError> {
+    fs::write(path, tournament.to_pgn(EVENT))
+        .map_err(|e| ChessError::Internal(format!("Failed to write {}: {}", path.display(), e)))
+}
+
+fn main() -> Result<(), ChessError> {
+    // Plays every pairing of the schedule, several games at a time, and saves
+    // the tournament to `--state` after each game. `--resume` picks a saved
+    // tournament up where it stopped; the games that were in progress are played
+    // again. The crosstable and rating list are printed at the end, and the
+    // combined PGN of all games is rewritten after each game.
+    let mut specs: Vec<String> = Vec::new();
+    let mut format = TournamentFormat::RoundRobin;
+    let mut rounds = 1;
+    let mut concurrency = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    let mut time_control: TimeControl = DEFAULT_TIME_CONTROL.parse()?;
+    let mut openings: Option<Vec<Game>> = None;
+    let mut random_plies = DEFAULT_RANDOM_PLIES;
+    let mut adjudication = Adjudication::default();
+    let mut state_path: Option<PathBuf> = None;
+    let mut resume: Option<PathBuf> = None;
+    let mut pgn_path: Option<PathBuf> = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--engine" => specs.push(next_arg(&arg, &mut args)?),
+            "--format" => format = next_arg(&arg, &mut args)?.parse()?,
+            "--rounds" => rounds = parse_value::<usize>(&arg, args.next())?.max(1),
+            "--concurrency" => concurrency = parse_value::<usize>(&arg, args.next())?.max(1),
+            "--tc" => time_control = next_arg(&arg, &mut args)?.parse()?,
+            "--openings" => openings = Some(load_openings(next_arg(&arg, &mut args)?)?),
+            "--random-plies" => random_plies = parse_value(&arg, args.next())?,
+            "--draw" => {
+                adjudication.draw_score = Some(parse_value(&arg, args.next())?);
+                adjudication.draw_moves = parse_value(&arg, args.next())?;
+                adjudication.draw_after = parse_value(&arg, args.next())?;
+            },
+            "--no-draw" => adjudication.draw_score = None,
+            "--resign" => {
+                adjudication.resign_score = Some(parse_value(&arg, args.next())?);
+                adjudication.resign_moves = parse_value(&arg, args.next())?;
+            },
+            "--no-resign" => adjudication.resign_score = None,
+            "--state" => state_path = Some(PathBuf::from(next_arg(&arg, &mut args)?)),
+            "--resume" => resume = Some(PathBuf::from(next_arg(&arg, &mut args)?)),
+            "--pgn" => pgn_path = Some(PathBuf::from(next_arg(&arg, &mut args)?)),
+            _ => {
+                println!("{}", USAGE);
+                return Ok(());
+            },
+        }
+    }
+
+    let mut tournament = match &resume {
+        Some(path) => {
+            let tournament = Tournament::load(path)?;
+            println!("Resuming {}: {} of {} games played", path.display(), tournament.finished_games(), tournament.pairings.len());
+            tournament
+        },
+        None => {
+            if specs.len() < 2 {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            let mut players = Vec::new();
+            for spec in &specs {
+                let parsed: EngineSpec = spec.parse()?;
+                players.push(Entrant { spec: spec.clone(), name: parsed.name });
+            }
+            Tournament::new(players, format, rounds, time_control, adjudication, |i| match &openings {
+                Some(openings) => openings[i % openings.len()].clone(),
+                None => random_opening(random_plies),
+            })?
+        },
+    };
+    let state_path = state_path.or(resume).unwrap_or_else(|| PathBuf::from(DEFAULT_STATE));
+    let engines = tournament.players.iter()
+        .map(|player| player.spec.parse::<EngineSpec>().map(|mut spec| {
+            spec.name = player.name.clone();
+            spec
+        }))
+        .collect::<Result<Vec<_>, _>>()?;
+    tournament.save(&state_path)?;
+
+    let pending = tournament.pending();
+    println!(
+        "{}, {} cycle{}, {} engines: {} games at {}, {} at a time, saved to {}",
+        tournament.format, tournament.cycles, if tournament.cycles == 1 { "" } else { "s" }, engines.len(), pending.len(), tournament.time_control, concurrency, state_path.display(),
+    );
+
+    // Each worker keeps its own instance of every engine it has needed so far
+    let jobs: Arc<Vec<(usize, usize, usize, Game)>> = Arc::new(pending.iter()
+        .map(|&i| tournament.opening(i).map(|opening| (i, tournament.pairings[i].white, tournament.pairings[i].black, opening)))
+        .collect::<Result<_, _>>()?);
+    let engines = Arc::new(engines);
+    let next_job = Arc::new(AtomicUsize::new(0));
+    let (sender, receiver) = mpsc::channel::<(usize, GameRecord)>();
+    let (time_control, adjudication) = (tournament.time_control, tournament.adjudication);
+    let workers: Vec<_> = (0..concurrency.min(jobs.len())).map(|_| {
+        let (jobs, engines, next_job, sender) = (jobs.clone(), engines.clone(), next_job.clone(), sender.clone());
+        thread::spawn(move || {
+            let mut instances: Vec<Option<Box<dyn SearchBackend>>> = (0..engines.len()).map(|_| None).collect();
+            while let Some((pairing, white, black, opening)) = jobs.get(next_job.fetch_add(1, Ordering::Relaxed)) {
+                let mut white_engine = instances[*white].take().unwrap_or_else(|| engines[*white].create());
+                let mut black_engine = instances[*black].take().unwrap_or_else(|| engines[*black].create());
+                let record = play_game(
+                    (&engines[*white].name, white_engine.as_mut()),
+                    (&engines[*black].name, black_engine.as_mut()),
+                    opening, time_control, &adjudication,
+                );
+                instances[*white] = Some(white_engine);
+                instances[*black] = Some(black_engine);
+                if sender.send((*pairing, record)).is_err() {
+                    return;
+                }
+            }
+        })
+    }).collect();
+    drop(sender);
+
+    for (pairing, record) in receiver {
+        println!(
+            "Game {}/{} round {} ({} vs {}): {} {{{}}}",
+            tournament.finished_games() + 1, tournament.pairings.len(), tournament.round_label(pairing),
+            record.white, record.black, record.result, record.termination,
+        );
+        tournament.record(pairing, record);
+        tournament.save(&state_path)?;
+        if let Some(path) = &pgn_path {
+            write_pgn(&tournament, path)?;
+        }
+    }
+    for worker in workers {
+        let _ = worker.join();
+    }
+    if let Some(path) = &pgn_path {
+        write_pgn(&tournament, path)?;
+    }
+
+    println!();
+    println!("{}", tournament.crosstable());
+    println!("{}", tournament.rating_table());
+    Ok(())
+}
@@ -0,0 +1,123 @@
+use rustychess::chess::{Engine, EvalParams, TuningPosition, WdlModel, load_epd, load_pgn, is_quiet, fit_k, tune, tuning_error};
+use rustychess::error::ChessError;
+use std::path::PathBuf;
+use std::time::Instant;
+
+const USAGE: &str = "Usage: tune [--params NAME|PATH] [--out FILE] [--passes N] [--skip-plies N] [--k K] [--all] [--wdl] DATASET...";
+
+// Plies at the start of each PGN game that are left out as opening theory
+const DEFAULT_SKIP_PLIES: usize = 8;
+const DEFAULT_PASSES: usize = 100;
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, ChessError> {
+    value.as_deref().and_then(|v| v.parse().ok())
+        .ok_or_else(|| ChessError::EvalParams(format!("{} needs a number", flag)))
+}
+
+fn main() -> Result<(), ChessError> {
+    // Fits the evaluation weights to a set of positions labelled with their game
+    // results. Datasets are `.pgn` files of finished games or EPD files whose lines
+    // carry a result (`c9 "1-0";` or `[1.0]`). Tuning starts from `--params` (the
+    // default weights unless given) and writes the result to `--out` as TOML or JSON.
+    // Only quiet positions are used unless `--all` is given. With `--wdl` it fits
+    // the win/draw/loss model to the evaluation `--params` instead, and writes
+    // that to `--out` (wdl.toml by default).
+    let mut start = EvalParams::default();
+    let mut out = None;
+    let mut passes = DEFAULT_PASSES;
+    let mut skip_plies = DEFAULT_SKIP_PLIES;
+    let mut k = None;
+    let mut keep_all = false;
+    let mut fit_wdl = false;
+    let mut datasets = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--params" => {
+                let name = args.next().ok_or_else(|| ChessError::EvalParams("--params needs a name or path".to_string()))?;
+                start = EvalParams::resolve(&name)?;
+            },
+            "--out" | "-o" => {
+                out = Some(args.next().map(PathBuf::from)
+                    .ok_or_else(|| ChessError::EvalParams("--out needs a file".to_string()))?);
+            },
+            "--passes" => passes = parse_number("--passes", args.next())?,
+            "--skip-plies" => skip_plies = parse_number("--skip-plies", args.next())?,
+            "--k" => k = Some(parse_number::<f64>("--k", args.next())?),
+            "--all" => keep_all = true,
+            "--wdl" => fit_wdl = true,
+            dataset => datasets.push(PathBuf::from(dataset)),
+        }
+    }
+    if datasets.is_empty() {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+
+    // Load and filter the positions
+    let engine = Engine::new(1);
+    let mut positions: Vec<TuningPosition> = Vec::new();
+    for path in &datasets {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| ChessError::EvalParams(format!("Failed to read {}: {}", path.display(), e)))?;
+        let loaded = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("pgn") => load_pgn(&text, &engine, skip_plies),
+            _ => load_epd(&text)?,
+        };
+        println!("{}: {} positions", path.display(), loaded.len());
+        positions.extend(loaded);
+    }
+    if !keep_all {
+        let total = positions.len();
+        positions.retain(|p| is_quiet(&engine, &p.board, p.side_to_move));
+        println!("Kept {} of {} positions as quiet", positions.len(), total);
+    }
+    if positions.is_empty() {
+        return Err(ChessError::EvalParams("No positions to tune on".to_string()));
+    }
+
+    if fit_wdl {
+        let out = out.unwrap_or_else(|| PathBuf::from("wdl.toml"));
+        let started = Instant::now();
+        let (model, loss) = WdlModel::fit(&positions, &start);
+        println!("Fitted WDL model in {:.1}s, log loss {:.6}", started.elapsed().as_secs_f64(), loss);
+        println!("  a: {:.1} (endgame) to {:.1} (opening)", model.a_endgame, model.a_opening);
+        println!("  b: {:.1} (endgame) to {:.1} (opening)", model.b_endgame, model.b_opening);
+        model.save(&out)?;
+        println!("Wrote {}", out.display());
+        return Ok(());
+    }
+    let out = out.unwrap_or_else(|| PathBuf::from("tuned.toml"));
+
+    let k = match k {
+        Some(k) => k,
+        None => {
+            let k = fit_k(&start, &positions);
+            println!("Fitted K = {:.3}", k);
+            k
+        },
+    };
+    let error_before = tuning_error(&start, &positions, k);
+    println!("Error before tuning: {:.6}", error_before);
+
+    let started = Instant::now();
+    let (tuned, error_after) = tune(start, &positions, k, passes, |pass, params, error| {
+        println!(
+            "pass {:3}: error {:.6} (P {} N {} B {} R {} Q {}, center {}, developed {}) {:.1}s",
+            pass, error, params.pawn_value, params.knight_value, params.bishop_value, params.rook_value,
+            params.queen_value, params.center_control_bonus, params.developed_piece_bonus,
+            started.elapsed().as_secs_f64(),
+        );
+    });
+
+    println!("Error before tuning: {:.6}", error_before);
+    println!("Error after tuning:  {:.6}", error_after);
+    for ((name, before), (_, after)) in start.tunable().into_iter().zip(tuned.tunable()) {
+        if before != after {
+            println!("  {}: {} -> {}", name, before, after);
+        }
+    }
+    tuned.save(&out)?;
+    println!("Wrote {}", out.display());
+    Ok(())
+}
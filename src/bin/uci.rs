@@ -0,0 +1,412 @@
+use rustychess::chess::{Color, DtmTablebase, Engine, EvalParams, Game, PvLine, Skill, StopSignal, SyzygyTablebase, MATE_SCORE, MAX_SEARCH_DEPTH};
+use rustychess::chess::{WdlModel, MAX_ELO, MAX_SKILL_LEVEL, MIN_ELO};
+#[cfg(feature = "nnue")]
+use rustychess::chess::Network;
+use rustychess::error::ChessError;
+use std::io::{self, BufRead, Write};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+// Depth used when `go` doesn't specify one
+const DEFAULT_DEPTH: u8 = 4;
+
+// Moves left to plan for when the GUI doesn't send movestogo
+const DEFAULT_MOVES_TO_GO: u64 = 30;
+
+// Time kept in reserve for communication lag, in milliseconds
+const MOVE_OVERHEAD_MS: u64 = 50;
+
+// Format a score the way UCI expects: centipawns, or moves to mate
+fn uci_score(score: i32) -> String {
+    if score.abs() >= MATE_SCORE - 1000 {
+        let plies = MATE_SCORE - score.abs();
+        let moves = (plies + 1) / 2;
+        format!("mate {}", if score > 0 { moves } else { -moves })
+    } else {
+        format!("cp {}", score)
+    }
+}
+
+// Handle `position [startpos | fen <fen>] [moves ...]`
+fn parse_position(args: &[&str]) -> Result<Game, ChessError> {
+    let moves_at = args.iter().position(|&t| t == "moves").unwrap_or(args.len());
+    
+    let mut game = match args.first() {
+        Some(&"startpos") => Game::new(),
+        Some(&"fen") => Game::from_fen(&args[1..moves_at].join(" "))?,
+        _ => return Err(ChessError::InvalidPosition("Expected startpos or fen".to_string())),
+    };
+    
+    for mv in args.iter().skip(moves_at + 1) {
+        // Promotion suffixes aren't supported by the board
+        match (mv.get(0..2), mv.get(2..4)) {
+            (Some(from), Some(to)) => game.make_move(from, to)?,
+            _ => return Err(ChessError::InvalidMove(format!("Invalid move: {}", mv))),
+        }
+    }
+    
+    Ok(game)
+}
+
+// The strength options: `Skill Level`, or `UCI_Elo` while `UCI_LimitStrength` is on
+struct Strength {
+    level: u8,
+    limit: bool,
+    elo: u32,
+}
+
+impl Strength {
+    fn skill(&self) -> Skill {
+        if self.limit { Skill::from_elo(self.elo) } else { Skill::new(self.level) }
+    }
+}
+
+// Handle `setoption name <name> value <value>`
+fn set_option(engine: &mut Engine, strength: &mut Strength, show_wdl: &mut bool, args: &[&str]) {
+    let value_at = args.iter().position(|&t| t == "value").unwrap_or(args.len());
+    let name = args.get(1..value_at).map(|n| n.join(" ")).unwrap_or_default();
+    let value = args.get(value_at + 1..).map(|v| v.join(" ")).unwrap_or_default();
+    
+    // Paths are the only options that aren't numbers
+    if name.eq_ignore_ascii_case("syzygypath") {
+        if value.is_empty() || value == "<empty>" {
+            engine.set_tablebase(None);
+            return;
+        }
+        match SyzygyTablebase::open(&value) {
+            Ok(tablebase) => {
+                println!("info string found {} tablebases with up to {} pieces", tablebase.len(), tablebase.max_pieces());
+                engine.set_tablebase(Some(Arc::new(tablebase)));
+            },
+            Err(e) => println!("info string {}", e),
+        }
+        return;
+    }
+    if name.eq_ignore_ascii_case("dtmpath") {
+        if value.is_empty() || value == "<empty>" {
+            engine.set_dtm_tablebase(None);
+            return;
+        }
+        match DtmTablebase::open(&value) {
+            Ok(tablebase) => {
+                println!("info string found {} DTM tables with up to {} pieces", tablebase.len(), tablebase.max_pieces());
+                engine.set_dtm_tablebase(Some(Arc::new(tablebase)));
+            },
+            Err(e) => println!("info string {}", e),
+        }
+        return;
+    }
+    #[cfg(feature = "nnue")]
+    if name.eq_ignore_ascii_case("evalfile") {
+        if value.is_empty() || value == "<empty>" {
+            engine.set_nnue(None);
+        } else {
+            match Network::load(&value) {
+                Ok(network) => {
+                    println!("info string loaded network {} with {} hidden neurons", value, network.hidden_size());
+                    engine.set_nnue(Some(Arc::new(network)));
+                },
+                Err(e) => println!("info string {}", e),
+            }
+        }
+        engine.clear_hash();
+        return;
+    }
+    
+    if name.eq_ignore_ascii_case("personality") {
+        match EvalParams::resolve(&value) {
+            Ok(params) => {
+                engine.set_eval_params(params);
+                engine.clear_hash();
+            },
+            Err(e) => println!("info string {}", e),
+        }
+        return;
+    }
+    if name.eq_ignore_ascii_case("wdlfile") {
+        if value.is_empty() || value == "<empty>" {
+            engine.set_wdl_model(WdlModel::default());
+            return;
+        }
+        match WdlModel::load(&value) {
+            Ok(model) => engine.set_wdl_model(model),
+            Err(e) => println!("info string {}", e),
+        }
+        return;
+    }
+    if name.eq_ignore_ascii_case("uci_showwdl") {
+        *show_wdl = value.eq_ignore_ascii_case("true");
+        return;
+    }
+    if name.eq_ignore_ascii_case("uci_limitstrength") {
+        strength.limit = value.eq_ignore_ascii_case("true");
+        engine.set_skill(strength.skill());
+        return;
+    }
+    
+    match (name.to_lowercase().as_str(), value.parse::<usize>()) {
+        ("skill level", Ok(level)) => {
+            strength.level = level.min(MAX_SKILL_LEVEL as usize) as u8;
+            engine.set_skill(strength.skill());
+        },
+        ("uci_elo", Ok(elo)) => {
+            strength.elo = (elo as u32).clamp(MIN_ELO, MAX_ELO);
+            engine.set_skill(strength.skill());
+        },
+        ("hash", Ok(mb)) => engine.set_hash_size(mb),
+        ("threads", Ok(threads)) => engine.set_threads(threads),
+        ("multipv", Ok(multipv)) => engine.set_multipv(multipv),
+        // Only tells us the GUI may send `go ponder`; nothing to configure
+        ("ponder", _) => {},
+        _ => println!("info string unknown option {}", name),
+    }
+}
+
+// Value following a `go` parameter, e.g. the 5000 in `movetime 5000`
+fn go_param(args: &[&str], name: &str) -> Option<u64> {
+    args.iter().position(|&t| t == name)
+        .and_then(|i| args.get(i + 1))
+        .and_then(|v| v.parse().ok())
+}
+
+// Apply the limits of `go [depth N] [nodes N] [movetime N] [wtime N btime N winc N binc N movestogo N] [infinite] [ponder]`.
+// Returns the time budget, which a ponder search only starts spending on `ponderhit`.
+fn apply_go_limits(engine: &mut Engine, game: &Game, args: &[&str]) -> Option<u64> {
+    let (time, inc) = match game.current_turn {
+        Color::White => (go_param(args, "wtime"), go_param(args, "winc")),
+        Color::Black => (go_param(args, "btime"), go_param(args, "binc")),
+    };
+    
+    // Spend an even share of the remaining time plus most of the increment
+    let clock_budget = time.map(|time| {
+        let moves_to_go = go_param(args, "movestogo").unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+        let budget = time / moves_to_go + inc.unwrap_or(0) * 3 / 4;
+        budget.min(time.saturating_sub(MOVE_OVERHEAD_MS)).max(1)
+    });
+    let movetime = go_param(args, "movetime").or(clock_budget);
+    let nodes = go_param(args, "nodes");
+    
+    // Without a depth, search as deep as the time or node budget allows
+    let pondering = args.contains(&"ponder");
+    let unbounded = args.contains(&"infinite") || pondering || movetime.is_some() || nodes.is_some();
+    let depth = go_param(args, "depth")
+        .map(|d| d.min(MAX_SEARCH_DEPTH as u64) as u8)
+        .unwrap_or(if unbounded { MAX_SEARCH_DEPTH } else { DEFAULT_DEPTH });
+    
+    engine.set_depth(depth);
+    engine.set_movetime(if pondering { None } else { movetime });
+    engine.set_node_limit(nodes);
+    movetime
+}
+
+// Keeps a ponder search from reporting its move until the GUI sends `ponderhit` or `stop`
+struct PonderHold {
+    pondering: Mutex<bool>,
+    released: Condvar,
+}
+
+impl PonderHold {
+    fn new(pondering: bool) -> Arc<Self> {
+        Arc::new(Self {
+            pondering: Mutex::new(pondering),
+            released: Condvar::new(),
+        })
+    }
+    
+    fn release(&self) {
+        if let Ok(mut pondering) = self.pondering.lock() {
+            *pondering = false;
+            self.released.notify_all();
+        }
+    }
+    
+    fn wait(&self) {
+        if let Ok(mut pondering) = self.pondering.lock() {
+            while *pondering {
+                pondering = match self.released.wait(pondering) {
+                    Ok(pondering) => pondering,
+                    Err(_) => return,
+                };
+            }
+        }
+    }
+}
+
+// A search running in the background
+struct RunningSearch {
+    // Hands the engine back when the search is done
+    handle: JoinHandle<Engine>,
+    stop: StopSignal,
+    hold: Arc<PonderHold>,
+    // Time budget that starts on `ponderhit`
+    movetime: Option<u64>,
+}
+
+impl RunningSearch {
+    // The GUI's opponent played the expected move: the ponder search is now
+    // the real one and its clock starts running
+    fn ponderhit(&self) {
+        self.hold.release();
+        if let Some(ms) = self.movetime {
+            let stop = self.stop.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(ms));
+                stop.stop();
+            });
+        }
+    }
+}
+
+// Search on a background thread so `stop`, `ponderhit` and `isready` are answered meanwhile
+fn start_search(mut engine: Engine, game: Game, hold: Arc<PonderHold>, show_wdl: bool) -> JoinHandle<Engine> {
+    thread::spawn(move || {
+        let outcome = engine.analyze(&game);
+        // UCI doesn't allow a best move while pondering, even if the search is done
+        hold.wait();
+        match outcome {
+            Ok(lines) => {
+                print_lines(&lines, &engine, show_wdl);
+                match lines[0].moves.get(1) {
+                    Some(ponder) => println!("bestmove {} ponder {}", lines[0].moves[0].to_uci(), ponder.to_uci()),
+                    None => println!("bestmove {}", lines[0].moves[0].to_uci()),
+                }
+            },
+            Err(e) => {
+                println!("info string {}", e);
+                println!("bestmove 0000");
+            },
+        }
+        let _ = io::stdout().flush();
+        engine
+    })
+}
+
+// Wait for the running search to hand the engine back, stopping it first if asked
+fn finish_search(search: &mut Option<RunningSearch>, engine: &mut Option<Engine>, stop: bool) {
+    if let Some(running) = search.take() {
+        if stop {
+            running.stop.stop();
+            running.hold.release();
+        }
+        *engine = Some(running.handle.join().unwrap_or_else(|_| Engine::new(DEFAULT_DEPTH)));
+    }
+}
+
+// With UCI_ShowWDL on, each line also carries `wdl W D L` after its score
+fn print_lines(lines: &[PvLine], engine: &Engine, show_wdl: bool) {
+    let (nodes, _) = engine.get_stats();
+    for (i, line) in lines.iter().enumerate() {
+        let wdl = if show_wdl { format!(" wdl {} {} {}", line.wdl.win, line.wdl.draw, line.wdl.loss) } else { String::new() };
+        println!("info depth {} seldepth {} multipv {} score {}{} nodes {} nps {} tbhits {} time {} pv {}",
+                 line.depth, engine.seldepth(), i + 1, uci_score(line.score), wdl, nodes,
+                 engine.nps(), engine.tb_hits(), engine.elapsed_ms(), line.uci().join(" "));
+    }
+}
+
+fn main() {
+    let mut engine = Some(Engine::new(DEFAULT_DEPTH));
+    let mut game = Game::new();
+    let mut search: Option<RunningSearch> = None;
+    let mut strength = Strength {
+        level: MAX_SKILL_LEVEL,
+        limit: false,
+        elo: MAX_ELO,
+    };
+    let mut show_wdl = false;
+    
+    for line in io::stdin().lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        
+        // Anything that touches the engine or position waits for the search to end
+        if !matches!(tokens.first().copied(), Some("isready") | Some("stop") | Some("ponderhit") | Some("quit") | None) {
+            finish_search(&mut search, &mut engine, false);
+        }
+        
+        match tokens.first().copied() {
+            Some("uci") => {
+                println!("id name RustyChess {}", env!("CARGO_PKG_VERSION"));
+                println!("id author {}", env!("CARGO_PKG_AUTHORS"));
+                println!("option name Hash type spin default 16 min 1 max 4096");
+                println!("option name Threads type spin default 1 min 1 max 256");
+                println!("option name MultiPV type spin default 1 min 1 max 256");
+                println!("option name Ponder type check default false");
+                println!("option name SyzygyPath type string default <empty>");
+                println!("option name DtmPath type string default <empty>");
+                println!("option name Personality type string default default");
+                #[cfg(feature = "nnue")]
+                println!("option name EvalFile type string default <empty>");
+                println!("option name Skill Level type spin default {} min 0 max {}", MAX_SKILL_LEVEL, MAX_SKILL_LEVEL);
+                println!("option name UCI_LimitStrength type check default false");
+                println!("option name UCI_Elo type spin default {} min {} max {}", MAX_ELO, MIN_ELO, MAX_ELO);
+                println!("option name UCI_ShowWDL type check default false");
+                println!("option name WdlFile type string default <empty>");
+                println!("uciok");
+            },
+            Some("isready") => println!("readyok"),
+            Some("ucinewgame") => {
+                game = Game::new();
+                if let Some(engine) = engine.as_mut() {
+                    engine.clear_hash();
+                }
+            },
+            Some("setoption") => {
+                if let Some(engine) = engine.as_mut() {
+                    set_option(engine, &mut strength, &mut show_wdl, &tokens[1..]);
+                }
+            },
+            Some("position") => match parse_position(&tokens[1..]) {
+                Ok(new_game) => game = new_game,
+                Err(e) => println!("info string {}", e),
+            },
+            Some("go") => {
+                if let Some(mut idle) = engine.take() {
+                    let movetime = apply_go_limits(&mut idle, &game, &tokens[1..]);
+                    let stop = StopSignal::new();
+                    idle.set_stop_signal(stop.clone());
+                    let hold = PonderHold::new(tokens.contains(&"ponder"));
+                    search = Some(RunningSearch {
+                        handle: start_search(idle, game.clone(), Arc::clone(&hold), show_wdl),
+                        stop,
+                        hold,
+                        movetime,
+                    });
+                }
+            },
+            Some("ponderhit") => {
+                if let Some(running) = search.as_ref() {
+                    running.ponderhit();
+                }
+            },
+            // The search prints its best move so far as it returns
+            Some("stop") => finish_search(&mut search, &mut engine, true),
+            // Non-standard: show the current position
+            Some("d") => {
+                println!("{}", game.board);
+                println!("Fen: {}", game.to_fen());
+            },
+            // Non-standard: the evaluation of the current position, term by term
+            Some("eval") => {
+                if let Some(engine) = engine.as_ref() {
+                    println!("{}", engine.eval_params().trace(&game.board));
+                    #[cfg(feature = "nnue")]
+                    if let Some(network) = engine.nnue() {
+                        let score = network.evaluate_board(&game.board, Color::White);
+                        println!("NNUE evaluation: {:+.2} (white side)", score as f64 / 100.0);
+                    }
+                }
+            },
+            Some("quit") => break,
+            Some(other) => println!("info string unknown command {}", other),
+            None => {}
+        }
+        
+        let _ = io::stdout().flush();
+    }
+    
+    finish_search(&mut search, &mut engine, true);
+}
@@ -0,0 +1,559 @@
+use crate::chess::{Board, ChessMove, Color, Engine, EvalParams, Game, PieceType, Position, SearchBackend, SearchLimits, Skill};
+use crate::chess::{UciBackend, UciEngineConfig, is_in_check, polyglot_key};
+#[cfg(feature = "nnue")]
+use crate::chess::Network;
+use crate::error::ChessError;
+use rand::Rng;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+#[cfg(feature = "nnue")]
+use std::sync::Arc;
+use std::time::Instant;
+
+// Engine-against-engine games, as played by the match and tournament tools:
+// who plays, under what time control, when a game is adjudicated, and the
+// record of each game.
+
+// Moves a clock is expected to last for when planning how long to think
+const MOVES_TO_GO: u64 = 30;
+// Kept back from each move's allotment for the engine's own overhead
+const MOVE_OVERHEAD_MS: u64 = 20;
+const MIN_MOVE_TIME_MS: u64 = 10;
+// Plies without a capture or pawn move that draw a game (the fifty-move rule)
+const FIFTY_MOVE_PLIES: usize = 100;
+
+// An engine that can take part in a game: the built-in engine with its own
+// settings, or an external UCI engine. Written as comma-separated `key=value`
+// pairs, e.g. `personality=aggressive,skill=15` or `cmd=/usr/bin/stockfish,option.Hash=64`.
+#[derive(Debug, Clone)]
+pub struct EngineSpec {
+    pub name: String,
+    kind: EngineKind,
+}
+
+#[derive(Debug, Clone)]
+enum EngineKind {
+    Builtin {
+        skill: Skill,
+        eval: EvalParams,
+        threads: usize,
+        hash_mb: Option<usize>,
+        extensions: bool,
+        #[cfg(feature = "nnue")]
+        network: Option<Arc<Network>>,
+    },
+    Uci(UciEngineConfig),
+}
+
+impl EngineSpec {
+    // Whether this is an external UCI engine
+    pub fn is_uci(&self) -> bool {
+        matches!(self.kind, EngineKind::Uci(_))
+    }
+
+    // A fresh instance to play with
+    pub fn create(&self) -> Box<dyn SearchBackend> {
+        match &self.kind {
+            EngineKind::Builtin { skill, eval, threads, hash_mb, extensions, #[cfg(feature = "nnue")] network } => {
+                let mut engine = Engine::new(3);
+                engine.set_skill(*skill);
+                engine.set_eval_params(*eval);
+                engine.set_threads(*threads);
+                if let Some(mb) = hash_mb {
+                    engine.set_hash_size(*mb);
+                }
+                engine.set_extensions(*extensions);
+                #[cfg(feature = "nnue")]
+                engine.set_nnue(network.clone());
+                Box::new(engine)
+            },
+            EngineKind::Uci(config) => Box::new(UciBackend::new(config.clone())),
+        }
+    }
+}
+
+impl FromStr for EngineSpec {
+    type Err = ChessError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut name = None;
+        let mut command = None;
+        let mut args = Vec::new();
+        let mut options = HashMap::new();
+        let mut skill = Skill::full();
+        let mut eval = EvalParams::default();
+        let mut threads = 1;
+        let mut hash_mb = None;
+        let mut extensions = true;
+        #[cfg(feature = "nnue")]
+        let mut network = None;
+
+        let number = |key: &str, value: &str| -> Result<usize, ChessError> {
+            value.parse().map_err(|_| ChessError::Engine(format!("{} must be a number, got {}", key, value)))
+        };
+        for pair in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            // A bare word is shorthand for the built-in engine
+            if pair == "builtin" {
+                continue;
+            }
+            let (key, value) = pair.split_once('=')
+                .ok_or_else(|| ChessError::Engine(format!("Expected key=value in engine spec, got {}", pair)))?;
+            match key {
+                "name" => name = Some(value.to_string()),
+                "cmd" => command = Some(value.to_string()),
+                "arg" => args.push(value.to_string()),
+                "personality" | "params" => eval = EvalParams::resolve(value)?,
+                "skill" => skill = value.parse()?,
+                "elo" => skill = Skill::from_elo(number(key, value)? as u32),
+                "threads" => threads = number(key, value)?.max(1),
+                "hash" => hash_mb = Some(number(key, value)?),
+                "extensions" => extensions = value.parse()
+                    .map_err(|_| ChessError::Engine(format!("extensions must be true or false, got {}", value)))?,
+                #[cfg(feature = "nnue")]
+                "nnue" => network = Some(Arc::new(Network::load(value)?)),
+                _ => match key.strip_prefix("option.") {
+                    Some(option) => {
+                        options.insert(option.to_string(), value.to_string());
+                    },
+                    None => return Err(ChessError::Engine(format!("Unknown engine spec key: {}", key))),
+                },
+            }
+        }
+
+        let kind = match command {
+            Some(path) => {
+                let default_name = Path::new(&path).file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| path.clone());
+                let mut config = UciEngineConfig::new(name.as_deref().unwrap_or(&default_name), &path);
+                config.args = args;
+                config.options = options;
+                name = Some(config.name.clone());
+                EngineKind::Uci(config)
+            },
+            None => EngineKind::Builtin {
+                skill,
+                eval,
+                threads,
+                hash_mb,
+                extensions,
+                #[cfg(feature = "nnue")]
+                network,
+            },
+        };
+        // Built-in engines are named after their settings unless named explicitly
+        let name = name.unwrap_or_else(|| if s.trim().is_empty() { "rustychess".to_string() } else { s.trim().to_string() });
+        Ok(Self { name, kind })
+    }
+}
+
+// How long each side may think
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeControl {
+    // A clock: base time plus an increment after every move, in milliseconds
+    Clock { base_ms: u64, increment_ms: u64 },
+    MoveTime(u64),
+    Nodes(u64),
+    Depth(u8),
+}
+
+impl FromStr for TimeControl {
+    type Err = ChessError;
+
+    // `40+0.4` (seconds plus increment), `movetime=100`, `nodes=5000` or `depth=4`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ChessError::Engine(format!("Invalid time control: {} (e.g. 10+0.1, movetime=100, nodes=5000, depth=4)", s));
+        if let Some((kind, value)) = s.split_once('=') {
+            return match kind {
+                "movetime" => value.parse().map(TimeControl::MoveTime).map_err(|_| invalid()),
+                "nodes" => value.parse().map(TimeControl::Nodes).map_err(|_| invalid()),
+                "depth" => value.parse().map(TimeControl::Depth).map_err(|_| invalid()),
+                _ => Err(invalid()),
+            };
+        }
+        let (base, increment) = s.split_once('+').unwrap_or((s, "0"));
+        let seconds = |v: &str| v.parse::<f64>().ok().filter(|v| *v >= 0.0).map(|v| (v * 1000.0) as u64);
+        match (seconds(base), seconds(increment)) {
+            (Some(base_ms), Some(increment_ms)) if base_ms > 0 => Ok(TimeControl::Clock { base_ms, increment_ms }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for TimeControl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimeControl::Clock { base_ms, increment_ms } => write!(f, "{}+{}", *base_ms as f64 / 1000.0, *increment_ms as f64 / 1000.0),
+            TimeControl::MoveTime(ms) => write!(f, "movetime={}", ms),
+            TimeControl::Nodes(nodes) => write!(f, "nodes={}", nodes),
+            TimeControl::Depth(depth) => write!(f, "depth={}", depth),
+        }
+    }
+}
+
+// When a game is stopped early and scored by the engines' evaluations
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Adjudication {
+    // Draw once both engines have scored within draw_score centipawns for
+    // draw_moves moves each, from move draw_after on
+    pub draw_score: Option<i32>,
+    pub draw_moves: usize,
+    pub draw_after: usize,
+    // An engine resigns once its own score has been at or below -resign_score
+    // for resign_moves of its moves in a row
+    pub resign_score: Option<i32>,
+    pub resign_moves: usize,
+    // Games still going after this many plies are drawn
+    pub max_plies: usize,
+}
+
+impl Default for Adjudication {
+    fn default() -> Self {
+        Self {
+            draw_score: Some(10),
+            draw_moves: 8,
+            draw_after: 40,
+            resign_score: Some(1000),
+            resign_moves: 3,
+            max_plies: 400,
+        }
+    }
+}
+
+// The result of a finished game
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+}
+
+impl GameResult {
+    // A win for the given side
+    pub fn win_for(color: Color) -> Self {
+        match color {
+            Color::White => GameResult::WhiteWins,
+            Color::Black => GameResult::BlackWins,
+        }
+    }
+
+    // Points scored by the given side
+    pub fn points(&self, color: Color) -> f64 {
+        match (self, color) {
+            (GameResult::Draw, _) => 0.5,
+            (GameResult::WhiteWins, Color::White) | (GameResult::BlackWins, Color::Black) => 1.0,
+            _ => 0.0,
+        }
+    }
+}
+
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GameResult::WhiteWins => write!(f, "1-0"),
+            GameResult::BlackWins => write!(f, "0-1"),
+            GameResult::Draw => write!(f, "1/2-1/2"),
+        }
+    }
+}
+
+// A finished game, with enough to write it out as PGN
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameRecord {
+    pub white: String,
+    pub black: String,
+    // Position the game started from
+    pub start_fen: String,
+    // Moves in standard algebraic notation
+    pub moves: Vec<String>,
+    pub result: GameResult,
+    // Why the game ended, e.g. "checkmate" or "adjudication"
+    pub termination: String,
+}
+
+impl GameRecord {
+    // The game as PGN, with the given event and round tags
+    pub fn to_pgn(&self, event: &str, round: &str) -> String {
+        let mut pgn = format!(
+            "[Event \"{}\"]\n[Site \"?\"]\n[Round \"{}\"]\n[White \"{}\"]\n[Black \"{}\"]\n[Result \"{}\"]\n",
+            event, round, self.white, self.black, self.result,
+        );
+        if self.start_fen != Game::new().to_fen() {
+            pgn.push_str(&format!("[SetUp \"1\"]\n[FEN \"{}\"]\n", self.start_fen));
+        }
+        pgn.push_str(&format!("[PlyCount \"{}\"]\n[Termination \"{}\"]\n\n", self.moves.len(), self.termination));
+
+        // Number the moves from the starting position's move number and side to move
+        let fields: Vec<&str> = self.start_fen.split_whitespace().collect();
+        let black_first = fields.get(1) == Some(&"b");
+        let first_move: usize = fields.get(5).and_then(|n| n.parse().ok()).unwrap_or(1);
+        let mut movetext = String::new();
+        for (i, san) in self.moves.iter().enumerate() {
+            let ply = i + black_first as usize;
+            if ply.is_multiple_of(2) {
+                movetext.push_str(&format!("{}. ", first_move + ply / 2));
+            } else if i == 0 {
+                movetext.push_str(&format!("{}... ", first_move));
+            }
+            movetext.push_str(san);
+            movetext.push(' ');
+        }
+        movetext.push_str(&self.result.to_string());
+
+        // Wrap the movetext at 80 columns
+        let mut line_length = 0;
+        for word in movetext.split(' ') {
+            if line_length > 0 && line_length + word.len() + 1 > 80 {
+                pgn.push('\n');
+                line_length = 0;
+            } else if line_length > 0 {
+                pgn.push(' ');
+                line_length += 1;
+            }
+            pgn.push_str(word);
+            line_length += word.len();
+        }
+        pgn.push_str("\n\n");
+        pgn
+    }
+}
+
+// Neither side can mate with kings alone, or with a single minor piece
+pub fn insufficient_material(board: &Board) -> bool {
+    let mut minors = 0;
+    for rank in 0..8 {
+        for file in 0..8 {
+            match board.get_piece(&Position::new(file, rank)).map(|p| p.piece_type) {
+                None | Some(PieceType::King) => {},
+                Some(PieceType::Knight) | Some(PieceType::Bishop) => minors += 1,
+                Some(_) => return false,
+            }
+        }
+    }
+    minors <= 1
+}
+
+// Starting positions from an EPD or FEN file, one per line
+pub fn load_openings(path: impl AsRef<Path>) -> Result<Vec<Game>, ChessError> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path)
+        .map_err(|e| ChessError::Engine(format!("Failed to read {}: {}", path.display(), e)))?;
+    let mut openings = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let fields: Vec<&str> = line.split_whitespace().take(2).collect();
+        if fields.is_empty() || fields[0].starts_with('#') {
+            continue;
+        }
+        let game = Game::from_fen(&fields.join(" "))
+            .map_err(|e| ChessError::InvalidPosition(format!("{} line {}: {}", path.display(), number + 1, e)))?;
+        openings.push(game);
+    }
+    if openings.is_empty() {
+        return Err(ChessError::InvalidPosition(format!("No positions in {}", path.display())));
+    }
+    Ok(openings)
+}
+
+// A starting position reached by `plies` random legal moves from the initial
+// position, drawn again if the game would already be over
+pub fn random_opening(plies: usize) -> Game {
+    let engine = Engine::new(1);
+    let mut rng = rand::thread_rng();
+    'retry: loop {
+        let mut game = Game::new();
+        for _ in 0..plies {
+            let moves = engine.get_legal_moves(&game.board, game.current_turn).unwrap_or_default();
+            if moves.is_empty() {
+                continue 'retry;
+            }
+            let chess_move = &moves[rng.gen_range(0..moves.len())];
+            if game.make_move(&chess_move.from.to_string(), &chess_move.to.to_string()).is_err() {
+                continue 'retry;
+            }
+        }
+        if !engine.get_legal_moves(&game.board, game.current_turn).unwrap_or_default().is_empty() {
+            return game;
+        }
+    }
+}
+
+// Search limits for one move, and the time left on the mover's clock if any
+fn move_limits(time_control: TimeControl, clock_ms: i64) -> SearchLimits {
+    match time_control {
+        TimeControl::Clock { increment_ms, .. } => {
+            let remaining = clock_ms.max(0) as u64;
+            let allotted = (remaining / MOVES_TO_GO + increment_ms * 3 / 4)
+                .min(remaining.saturating_sub(MOVE_OVERHEAD_MS))
+                .max(MIN_MOVE_TIME_MS);
+            SearchLimits { movetime_ms: Some(allotted), ..SearchLimits::default() }
+        },
+        TimeControl::MoveTime(ms) => SearchLimits { movetime_ms: Some(ms), ..SearchLimits::default() },
+        TimeControl::Nodes(nodes) => SearchLimits { nodes: Some(nodes), ..SearchLimits::default() },
+        TimeControl::Depth(depth) => SearchLimits::depth(depth),
+    }
+}
+
+// Play a game from `opening` between two engines and return its record. Each
+// engine starts a new game first. An engine that fails to move loses, as does
+// one that overruns its clock.
+pub fn play_game(
+    white: (&str, &mut dyn SearchBackend),
+    black: (&str, &mut dyn SearchBackend),
+    opening: &Game,
+    time_control: TimeControl,
+    adjudication: &Adjudication,
+) -> GameRecord {
+    let names = (white.0.to_string(), black.0.to_string());
+    let (white, black) = (white.1, black.1);
+    let engine = Engine::new(1);
+    let mut game = opening.clone();
+    game.move_history.clear();
+    let start_fen = game.to_fen();
+
+    let mut moves = Vec::new();
+    let mut repetitions: HashMap<u64, usize> = HashMap::new();
+    let mut quiet_plies = 0;
+    let mut clocks = match time_control {
+        TimeControl::Clock { base_ms, .. } => [base_ms as i64; 2],
+        _ => [0; 2],
+    };
+    // Consecutive moves each side has scored as drawn or as lost
+    let mut drawish = [0usize; 2];
+    let mut losing = [0usize; 2];
+
+    let finish = |moves: Vec<String>, result: GameResult, termination: &str| GameRecord {
+        white: names.0.clone(),
+        black: names.1.clone(),
+        start_fen: start_fen.clone(),
+        moves,
+        result,
+        termination: termination.to_string(),
+    };
+
+    // Engines reused from earlier games forget them first, hash table included
+    if white.new_game().is_err() {
+        return finish(moves, GameResult::win_for(Color::Black), "engine error");
+    }
+    if black.new_game().is_err() {
+        return finish(moves, GameResult::win_for(Color::White), "engine error");
+    }
+
+    loop {
+        let color = game.current_turn;
+        let side = color as usize;
+        let legal_moves = engine.get_legal_moves(&game.board, color).unwrap_or_default();
+        if legal_moves.is_empty() {
+            return if is_in_check(&game.board, color) {
+                finish(moves, GameResult::win_for(color.opposite()), "checkmate")
+            } else {
+                finish(moves, GameResult::Draw, "stalemate")
+            };
+        }
+        let seen = repetitions.entry(polyglot_key(&game)).or_insert(0);
+        *seen += 1;
+        if *seen >= 3 {
+            return finish(moves, GameResult::Draw, "threefold repetition");
+        }
+        if quiet_plies >= FIFTY_MOVE_PLIES {
+            return finish(moves, GameResult::Draw, "fifty-move rule");
+        }
+        if insufficient_material(&game.board) {
+            return finish(moves, GameResult::Draw, "insufficient material");
+        }
+        if moves.len() >= adjudication.max_plies {
+            return finish(moves, GameResult::Draw, "move limit");
+        }
+
+        let backend: &mut dyn SearchBackend = if color == Color::White { &mut *white } else { &mut *black };
+        let started = Instant::now();
+        let result = backend.search(&game, &move_limits(time_control, clocks[side]));
+        let elapsed = started.elapsed().as_millis() as i64;
+        let search = match result {
+            Ok(search) if legal_moves.iter().any(|m| m.from == search.best_move.from && m.to == search.best_move.to) => search,
+            Ok(_) => return finish(moves, GameResult::win_for(color.opposite()), "illegal move"),
+            Err(_) => return finish(moves, GameResult::win_for(color.opposite()), "engine error"),
+        };
+        if let TimeControl::Clock { increment_ms, .. } = time_control {
+            clocks[side] -= elapsed;
+            if clocks[side] < 0 {
+                return finish(moves, GameResult::win_for(color.opposite()), "time forfeit");
+            }
+            clocks[side] += increment_ms as i64;
+        }
+
+        // Scores the engines agree on end the game early
+        let score = search.score;
+        let in_draw_range = adjudication.draw_score.is_some_and(|limit| score.abs() <= limit);
+        drawish[side] = if in_draw_range && moves.len() / 2 + 1 >= adjudication.draw_after { drawish[side] + 1 } else { 0 };
+        let lost = adjudication.resign_score.is_some_and(|limit| score <= -limit);
+        losing[side] = if lost { losing[side] + 1 } else { 0 };
+
+        let chess_move: ChessMove = search.best_move;
+        moves.push(engine.to_algebraic_notation(&chess_move, &game.board));
+        let capture = game.board.get_piece(&chess_move.to).is_some();
+        let pawn_move = game.board.get_piece(&chess_move.from).is_some_and(|p| p.piece_type == PieceType::Pawn);
+        quiet_plies = if capture || pawn_move { 0 } else { quiet_plies + 1 };
+        if game.make_move(&chess_move.from.to_string(), &chess_move.to.to_string()).is_err() {
+            return finish(moves, GameResult::win_for(color.opposite()), "illegal move");
+        }
+
+        if losing[side] >= adjudication.resign_moves.max(1) {
+            return finish(moves, GameResult::win_for(color.opposite()), "adjudication");
+        }
+        if drawish[0] >= adjudication.draw_moves.max(1) && drawish[1] >= adjudication.draw_moves.max(1) {
+            return finish(moves, GameResult::Draw, "adjudication");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::{RandomMover, SearchResult};
+
+    // Plays random moves and counts the new games it is told about
+    #[derive(Default)]
+    struct Counting {
+        new_games: usize,
+        broken: bool,
+    }
+
+    impl SearchBackend for Counting {
+        fn name(&self) -> &str {
+            "counting"
+        }
+
+        fn search(&mut self, game: &Game, limits: &SearchLimits) -> Result<SearchResult, ChessError> {
+            RandomMover.search(game, limits)
+        }
+
+        fn new_game(&mut self) -> Result<(), ChessError> {
+            if self.broken {
+                return Err(ChessError::Engine("engine died".to_string()));
+            }
+            self.new_games += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn engines_start_every_game_fresh() {
+        let (mut first, mut second) = (Counting::default(), Counting::default());
+        let adjudication = Adjudication { max_plies: 6, ..Adjudication::default() };
+        for _ in 0..3 {
+            let record = play_game(("first", &mut first), ("second", &mut second), &Game::new(), TimeControl::Depth(1), &adjudication);
+            assert_ne!(record.termination, "engine error");
+        }
+        assert_eq!((first.new_games, second.new_games), (3, 3));
+
+        second.broken = true;
+        let record = play_game(("first", &mut first), ("second", &mut second), &Game::new(), TimeControl::Depth(1), &adjudication);
+        assert_eq!((record.result, record.termination.as_str()), (GameResult::WhiteWins, "engine error"));
+        assert!(record.moves.is_empty());
+    }
+}
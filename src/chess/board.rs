@@ -84,6 +84,78 @@ impl Board {
         Ok(())
     }
     
+    // An empty board, for setting up arbitrary positions
+    pub fn empty() -> Self {
+        Self {
+            squares: [[None; 8]; 8],
+        }
+    }
+    
+    // Build a board from the piece placement field of a FEN string
+    pub fn from_fen(placement: &str) -> Result<Self, ChessError> {
+        let mut board = Self::empty();
+        let ranks: Vec<&str> = placement.split('/').collect();
+        if ranks.len() != 8 {
+            return Err(ChessError::InvalidPosition(format!("FEN needs 8 ranks, got {}", ranks.len())));
+        }
+        
+        for (i, rank_str) in ranks.iter().enumerate() {
+            let rank = 7 - i;
+            let mut file = 0;
+            for c in rank_str.chars() {
+                if let Some(empty) = c.to_digit(10) {
+                    if empty == 0 {
+                        return Err(ChessError::InvalidPosition(format!("FEN rank {} has an empty run of 0", rank + 1)));
+                    }
+                    file += empty as usize;
+                } else {
+                    let piece = Piece::from_char(c).ok_or_else(|| {
+                        ChessError::InvalidPosition(format!("Invalid piece in FEN: {}", c))
+                    })?;
+                    if file > 7 {
+                        return Err(ChessError::InvalidPosition(format!("FEN rank {} has more than 8 files", rank + 1)));
+                    }
+                    board.squares[rank][file] = Some(piece);
+                    file += 1;
+                }
+            }
+            if file != 8 {
+                return Err(ChessError::InvalidPosition(format!("FEN rank {} doesn't have 8 files", rank + 1)));
+            }
+        }
+        
+        Ok(board)
+    }
+    
+    // Piece placement field of a FEN string (ranks 8 to 1)
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+        
+        for rank in (0..8).rev() {
+            let mut empty = 0;
+            for file in 0..8 {
+                match self.squares[rank][file] {
+                    Some(p) => {
+                        if empty > 0 {
+                            fen.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        fen.push(p.to_char());
+                    },
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if rank > 0 {
+                fen.push('/');
+            }
+        }
+        
+        fen
+    }
+    
     // Print the pieces on the board - useful for debugging
     pub fn debug_print(&self) -> String {
         let mut output = String::new();
@@ -107,6 +179,12 @@ impl Board {
     }
 }
 
+impl Default for Board {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl fmt::Display for Board {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         for rank in (0..8).rev() {
@@ -125,3 +203,38 @@ impl fmt::Display for Board {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fen_round_trip() {
+        let placement = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R";
+        assert_eq!(Board::from_fen(placement).unwrap().to_fen(), placement);
+        assert_eq!(Board::from_fen("8/8/8/8/8/8/8/8").unwrap().to_fen(), "8/8/8/8/8/8/8/8");
+    }
+
+    #[test]
+    fn ranks_must_have_8_files() {
+        for placement in [
+            // Too many files
+            "8p/8/8/8/8/8/8/8",
+            "ppppppppp/8/8/8/8/8/8/8",
+            "44p/8/8/8/8/8/8/8",
+            "9/8/8/8/8/8/8/8",
+            // Too few
+            "7/8/8/8/8/8/8/8",
+            "ppppppp/8/8/8/8/8/8/8",
+            "/8/8/8/8/8/8/8",
+            // Empty runs are at least one square
+            "08/8/8/8/8/8/8/8",
+            "p0ppppppp/8/8/8/8/8/8/8",
+        ] {
+            assert!(
+                matches!(Board::from_fen(placement), Err(ChessError::InvalidPosition(_))),
+                "{} was accepted", placement
+            );
+        }
+    }
+}
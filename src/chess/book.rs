@@ -0,0 +1,293 @@
+use crate::chess::{ChessMove, Engine, Game, PieceType, Position};
+use crate::chess::polyglot_key;
+use crate::error::ChessError;
+use rand::Rng;
+use serde::{Serialize, Deserialize};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+// Size of one Polyglot book entry on disk
+const ENTRY_BYTES: usize = 16;
+
+// How a move is picked when the book knows several for a position
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BookSelection {
+    // Random, in proportion to the moves' weights
+    #[default]
+    Weighted,
+    // Always the highest-weighted move
+    Best,
+    // Any book move with equal probability
+    Uniform,
+}
+
+impl FromStr for BookSelection {
+    type Err = ChessError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "weighted" | "random" => Ok(Self::Weighted),
+            "best" => Ok(Self::Best),
+            "uniform" => Ok(Self::Uniform),
+            _ => Err(ChessError::Book(format!("Unknown book selection: {}", s))),
+        }
+    }
+}
+
+// One raw entry of a Polyglot book
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BookEntry {
+    pub key: u64,
+    pub raw_move: u16,
+    pub weight: u16,
+    pub learn: u32,
+}
+
+// A book move that can be played in the position it was looked up for
+#[derive(Debug, Clone, Serialize)]
+pub struct BookMove {
+    pub chess_move: ChessMove,
+    pub weight: u16,
+}
+
+// A Polyglot `.bin` opening book
+#[derive(Clone, Default)]
+pub struct OpeningBook {
+    // Sorted by key, as in the file
+    entries: Vec<BookEntry>,
+    selection: BookSelection,
+    // Leave the book once the game is this many plies long
+    max_depth: Option<usize>,
+}
+
+impl OpeningBook {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, ChessError> {
+        let path = path.as_ref();
+        let bytes = fs::read(path)
+            .map_err(|e| ChessError::Book(format!("Failed to read {}: {}", path.display(), e)))?;
+        Self::from_bytes(&bytes)
+    }
+    
+    // Parse the big-endian 16-byte entries of a Polyglot book
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ChessError> {
+        if !bytes.len().is_multiple_of(ENTRY_BYTES) {
+            return Err(ChessError::Book(format!(
+                "Book size {} is not a multiple of {} bytes", bytes.len(), ENTRY_BYTES
+            )));
+        }
+        
+        let mut entries: Vec<BookEntry> = bytes.chunks_exact(ENTRY_BYTES).map(|chunk| BookEntry {
+            key: u64::from_be_bytes(chunk[0..8].try_into().unwrap()),
+            raw_move: u16::from_be_bytes(chunk[8..10].try_into().unwrap()),
+            weight: u16::from_be_bytes(chunk[10..12].try_into().unwrap()),
+            learn: u32::from_be_bytes(chunk[12..16].try_into().unwrap()),
+        }).collect();
+        // Books are written sorted, but lookups depend on it so don't trust that
+        entries.sort_by_key(|entry| entry.key);
+        
+        Ok(Self {
+            entries,
+            ..Self::default()
+        })
+    }
+    
+    pub fn with_selection(mut self, selection: BookSelection) -> Self {
+        self.selection = selection;
+        self
+    }
+    
+    // Only use the book for the first `plies` half-moves of a game; None for no limit
+    pub fn with_max_depth(mut self, plies: Option<usize>) -> Self {
+        self.max_depth = plies;
+        self
+    }
+    
+    pub fn selection(&self) -> BookSelection {
+        self.selection
+    }
+    
+    pub fn max_depth(&self) -> Option<usize> {
+        self.max_depth
+    }
+    
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    
+    // Raw entries stored for a Polyglot key
+    pub fn entries(&self, key: u64) -> &[BookEntry] {
+        let start = self.entries.partition_point(|entry| entry.key < key);
+        let end = self.entries.partition_point(|entry| entry.key <= key);
+        &self.entries[start..end]
+    }
+    
+    // Book moves for the game's position that the engine can play, highest weight first
+    pub fn moves(&self, game: &Game) -> Vec<BookMove> {
+        let entries = self.entries(polyglot_key(game));
+        if entries.is_empty() {
+            return Vec::new();
+        }
+        
+        let legal_moves = Engine::new(1).get_legal_moves(&game.board, game.current_turn).unwrap_or_default();
+        let mut moves: Vec<BookMove> = entries.iter()
+            .filter_map(|entry| {
+                let chess_move = decode_move(game, entry.raw_move)?;
+                legal_moves.iter()
+                    .any(|m| m.from == chess_move.from && m.to == chess_move.to)
+                    .then_some(BookMove { chess_move, weight: entry.weight })
+            })
+            .collect();
+        moves.sort_by_key(|m| std::cmp::Reverse(m.weight));
+        moves
+    }
+    
+    // Pick a move for the game, if its position is in the book and within the depth limit
+    pub fn choose(&self, game: &Game) -> Option<BookMove> {
+        if self.max_depth.is_some_and(|plies| game.move_history.len() >= plies) {
+            return None;
+        }
+        
+        let mut moves = self.moves(game);
+        if moves.is_empty() {
+            return None;
+        }
+        
+        let mut rng = rand::thread_rng();
+        let total: u64 = moves.iter().map(|m| m.weight as u64).sum();
+        let index = match self.selection {
+            BookSelection::Best => 0,
+            // All-zero weights leave nothing to be proportional to
+            BookSelection::Uniform => rng.gen_range(0..moves.len()),
+            BookSelection::Weighted if total == 0 => rng.gen_range(0..moves.len()),
+            BookSelection::Weighted => {
+                let mut pick = rng.gen_range(0..total);
+                moves.iter().position(|m| {
+                    let hit = pick < m.weight as u64;
+                    pick = pick.saturating_sub(m.weight as u64);
+                    hit
+                }).unwrap_or(0)
+            },
+        };
+        Some(moves.swap_remove(index))
+    }
+}
+
+impl fmt::Debug for OpeningBook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "OpeningBook({} entries, {:?}, max depth {:?})", self.entries.len(), self.selection, self.max_depth)
+    }
+}
+
+// Decode a Polyglot move: to file/rank in bits 0-5, from file/rank in bits 6-11 and
+// the promotion piece in bits 12-14. Castling is stored as the king taking its own rook.
+fn decode_move(game: &Game, raw: u16) -> Option<ChessMove> {
+    let square = |bits: u16| Position::new((bits & 7) as u8, ((bits >> 3) & 7) as u8);
+    let from = square(raw >> 6);
+    let mut to = square(raw);
+    
+    // The board can't promote, so promotions are never playable
+    if (raw >> 12) & 7 != 0 {
+        return None;
+    }
+    
+    let castles = game.board.get_piece(&from).is_some_and(|piece| piece.piece_type == PieceType::King)
+        && from.file == 4 && from.rank == to.rank && (to.file == 0 || to.file == 7);
+    if castles {
+        to = Position::new(if to.file == 7 { 6 } else { 2 }, to.rank);
+    }
+    
+    Some(ChessMove::new(from, to))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A Polyglot entry for a move between squares given as (file, rank)
+    fn entry(game: &Game, from: (u16, u16), to: (u16, u16), weight: u16) -> Vec<u8> {
+        let raw_move = (from.1 * 8 + from.0) << 6 | (to.1 * 8 + to.0);
+        let mut bytes = polyglot_key(game).to_be_bytes().to_vec();
+        bytes.extend_from_slice(&raw_move.to_be_bytes());
+        bytes.extend_from_slice(&weight.to_be_bytes());
+        bytes.extend_from_slice(&0u32.to_be_bytes());
+        bytes
+    }
+
+    // 1.e4 (weight 10), 1.d4 (5) and 1.Nf3 (0), then 1...e5 after 1.e4
+    fn book() -> OpeningBook {
+        let start = Game::new();
+        let mut after_e4 = Game::new();
+        after_e4.make_move("e2", "e4").unwrap();
+        // Deliberately out of key order, with an illegal move thrown in
+        let bytes = [
+            entry(&start, (3, 1), (3, 3), 5),
+            entry(&after_e4, (4, 6), (4, 4), 7),
+            entry(&start, (6, 0), (5, 2), 0),
+            entry(&start, (4, 1), (4, 4), 3),
+            entry(&start, (4, 1), (4, 3), 10),
+        ].concat();
+        OpeningBook::from_bytes(&bytes).unwrap()
+    }
+
+    fn uci(book_move: Option<BookMove>) -> Option<String> {
+        book_move.map(|m| m.chess_move.to_uci())
+    }
+
+    #[test]
+    fn moves_are_legal_and_by_weight() {
+        let book = book();
+        assert_eq!(book.len(), 5);
+        let moves: Vec<(String, u16)> = book.moves(&Game::new()).iter()
+            .map(|m| (m.chess_move.to_uci(), m.weight))
+            .collect();
+        assert_eq!(moves, [("e2e4".to_string(), 10), ("d2d4".to_string(), 5), ("g1f3".to_string(), 0)]);
+        assert!(OpeningBook::from_bytes(&[0; 17]).is_err());
+    }
+
+    #[test]
+    fn best_selection() {
+        let book = book().with_selection(BookSelection::Best);
+        for _ in 0..20 {
+            assert_eq!(uci(book.choose(&Game::new())).as_deref(), Some("e2e4"));
+        }
+    }
+
+    #[test]
+    fn weighted_selection() {
+        let book = book();
+        assert_eq!(book.selection(), BookSelection::Weighted);
+        let (mut e4, mut d4) = (0, 0);
+        for _ in 0..1500 {
+            match uci(book.choose(&Game::new())).as_deref() {
+                Some("e2e4") => e4 += 1,
+                Some("d2d4") => d4 += 1,
+                other => panic!("picked {:?}, which has no weight", other),
+            }
+        }
+        // Two to one on average; far enough from even to never fail by chance
+        assert!(d4 > 300 && e4 > 800, "e4 {} d4 {}", e4, d4);
+    }
+
+    #[test]
+    fn max_depth() {
+        let mut game = Game::new();
+        game.make_move("e2", "e4").unwrap();
+        let book = book().with_selection(BookSelection::Best);
+        assert_eq!(uci(book.choose(&game)).as_deref(), Some("e7e5"));
+
+        let book = book.with_max_depth(Some(1));
+        assert_eq!(uci(book.choose(&Game::new())).as_deref(), Some("e2e4"));
+        assert!(book.choose(&game).is_none());
+
+        // Out of book
+        game.make_move("e7", "e5").unwrap();
+        assert!(book.with_max_depth(None).choose(&game).is_none());
+    }
+}
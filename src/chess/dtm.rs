@@ -0,0 +1,1081 @@
+use crate::chess::{Board, ChessMove, Color, Engine, Game, Piece, PieceType, Position};
+use crate::error::ChessError;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+// Distance-to-mate tablebases that rustychess generates itself by retrograde
+// analysis. A table covers one material balance such as KQvK or KRvKN and stores
+// one byte per position and side to move: draw, win or loss, and the number of
+// plies to mate with perfect play (the winner mating as fast as possible and the
+// loser holding out as long as possible). Tables are folded by the board's
+// symmetries through the white king's square, so KQvKR takes about 5MB on disk and KQvKP 13MB.
+// En passant and castling rights are never part of a table position.
+
+// Most pieces, kings included, a table can have
+pub const DTM_PIECES: usize = 4;
+
+const MAGIC: [u8; 4] = *b"RCTB";
+const VERSION: u8 = 1;
+
+// Byte values in a table. Wins in 1..=127 are mates in 2n-1 plies, losses in
+// 128..=253 are mated in 2(n-128) plies, 128 itself being checkmate.
+const DRAW: u8 = 0;
+const LOSS_BASE: u8 = 128;
+const ILLEGAL: u8 = 255;
+
+// Longest mate the byte encoding can hold
+const MAX_PLIES: usize = 250;
+
+// Piece codes, as in the Syzygy prober: white 1-6, black 9-14
+const PAWN: u8 = 1;
+const KNIGHT: u8 = 2;
+const BISHOP: u8 = 3;
+const ROOK: u8 = 4;
+const QUEEN: u8 = 5;
+const KING: u8 = 6;
+const BLACK: u8 = 8;
+
+// Squares the white king is folded onto in tables without pawns (a1-d1-d4)
+const TRIANGLE: [usize; 10] = [0, 1, 2, 3, 9, 10, 11, 18, 19, 27];
+
+const KING_STEPS: [(i32, i32); 8] = [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)];
+const KNIGHT_STEPS: [(i32, i32); 8] = [(-2, -1), (-2, 1), (-1, -2), (-1, 2), (1, -2), (1, 2), (2, -1), (2, 1)];
+const ROOK_STEPS: [(i32, i32); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+const BISHOP_STEPS: [(i32, i32); 4] = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
+
+// Result of a position with perfect play, from the side to move's point of view
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(tag = "result", content = "plies", rename_all = "snake_case")]
+pub enum Dtm {
+    // Mates in this many plies
+    Win(u16),
+    // Gets mated in this many plies; 0 is checkmate on the board
+    Loss(u16),
+    Draw,
+}
+
+impl Dtm {
+    // Full moves until mate, negative when getting mated, as UCI reports it
+    pub fn mate_in(&self) -> Option<i32> {
+        match *self {
+            Self::Win(plies) => Some((plies as i32 + 1) / 2),
+            Self::Loss(plies) => Some(-(plies as i32) / 2),
+            Self::Draw => None,
+        }
+    }
+
+    // The same position seen from the side that just moved into it
+    fn before_move(self) -> Self {
+        match self {
+            Self::Win(plies) => Self::Loss(plies + 1),
+            Self::Loss(plies) => Self::Win(plies + 1),
+            Self::Draw => Self::Draw,
+        }
+    }
+
+    // Sort key, best result first
+    fn rank(&self) -> (u8, i32) {
+        match *self {
+            Self::Win(plies) => (0, plies as i32),
+            Self::Draw => (1, 0),
+            Self::Loss(plies) => (2, -(plies as i32)),
+        }
+    }
+
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            DRAW => Some(Self::Draw),
+            ILLEGAL => None,
+            b if b < LOSS_BASE => Some(Self::Win(2 * b as u16 - 1)),
+            b => Some(Self::Loss(2 * (b - LOSS_BASE) as u16)),
+        }
+    }
+}
+
+impl fmt::Display for Dtm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Win(plies) => write!(f, "win, mate in {} plies", plies),
+            Self::Loss(0) => write!(f, "checkmated"),
+            Self::Loss(plies) => write!(f, "loss, mated in {} plies", plies),
+            Self::Draw => write!(f, "draw"),
+        }
+    }
+}
+
+// Byte for a result found `plies` from mate; odd for wins, even for losses
+fn plies_byte(plies: usize) -> u8 {
+    if plies % 2 == 1 {
+        plies.div_ceil(2) as u8
+    } else {
+        LOSS_BASE + (plies / 2) as u8
+    }
+}
+
+// A legal move ranked by the table
+#[derive(Debug, Clone, Serialize)]
+pub struct DtmMove {
+    pub chess_move: ChessMove,
+    // Result for the side making the move
+    pub dtm: Dtm,
+}
+
+// How many positions of one side to move have which result
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct DtmStats {
+    // Legal positions, counted once per symmetry class
+    pub positions: u64,
+    pub wins: u64,
+    pub draws: u64,
+    pub losses: u64,
+    // Longest mates in plies, for the winning and the losing side to move
+    pub longest_win: u16,
+    pub longest_loss: u16,
+}
+
+fn file_of(sq: usize) -> i32 {
+    (sq % 8) as i32
+}
+
+fn rank_of(sq: usize) -> i32 {
+    (sq / 8) as i32
+}
+
+fn offset(sq: usize, (df, dr): (i32, i32)) -> Option<usize> {
+    let (file, rank) = (file_of(sq) + df, rank_of(sq) + dr);
+    ((0..8).contains(&file) && (0..8).contains(&rank)).then(|| (rank * 8 + file) as usize)
+}
+
+fn is_white(code: u8) -> bool {
+    code & BLACK == 0
+}
+
+fn piece_code(piece_type: PieceType, color: Color) -> u8 {
+    let kind = match piece_type {
+        PieceType::Pawn => PAWN,
+        PieceType::Knight => KNIGHT,
+        PieceType::Bishop => BISHOP,
+        PieceType::Rook => ROOK,
+        PieceType::Queen => QUEEN,
+        PieceType::King => KING,
+    };
+    if color == Color::White { kind } else { kind | BLACK }
+}
+
+fn letter(kind: u8) -> char {
+    ['?', 'P', 'N', 'B', 'R', 'Q', 'K'][kind as usize]
+}
+
+// Pieces in the order material names list them
+fn kind_order(kind: u8) -> usize {
+    [KING, QUEEN, ROOK, BISHOP, KNIGHT, PAWN].iter().position(|&k| k == kind).unwrap_or(6)
+}
+
+// A few pieces on an otherwise empty board, as (square, code) pairs
+#[derive(Debug, Clone, Copy)]
+struct Placement {
+    pieces: [(usize, u8); DTM_PIECES],
+    len: usize,
+}
+
+impl Placement {
+    fn new(pieces: &[(usize, u8)]) -> Self {
+        let mut placement = Self { pieces: [(0, 0); DTM_PIECES], len: pieces.len() };
+        placement.pieces[..pieces.len()].copy_from_slice(pieces);
+        placement
+    }
+
+    fn pieces(&self) -> &[(usize, u8)] {
+        &self.pieces[..self.len]
+    }
+
+    fn board(&self) -> [u8; 64] {
+        let mut board = [0; 64];
+        for &(sq, code) in self.pieces() {
+            board[sq] = code;
+        }
+        board
+    }
+
+    fn remove(&mut self, index: usize) {
+        self.pieces.copy_within(index + 1..self.len, index);
+        self.len -= 1;
+    }
+
+    fn king(&self, white: bool) -> usize {
+        let king = if white { KING } else { KING | BLACK };
+        self.pieces().iter().find(|(_, code)| *code == king).map(|(sq, _)| *sq).unwrap_or(0)
+    }
+
+    // Whether the side's king is attacked
+    fn in_check(&self, board: &[u8; 64], white: bool) -> bool {
+        let king = self.king(white);
+        self.pieces().iter().any(|&(sq, code)| is_white(code) != white && attacks(board, sq, code, king))
+    }
+}
+
+fn attacks(board: &[u8; 64], from: usize, code: u8, to: usize) -> bool {
+    let (df, dr) = (file_of(to) - file_of(from), rank_of(to) - rank_of(from));
+    let clear = || {
+        let step = (df.signum(), dr.signum());
+        let mut sq = offset(from, step);
+        while let Some(s) = sq {
+            if s == to {
+                return true;
+            }
+            if board[s] != 0 {
+                return false;
+            }
+            sq = offset(s, step);
+        }
+        false
+    };
+    let straight = (df == 0) != (dr == 0);
+    let diagonal = df != 0 && df.abs() == dr.abs();
+    match code & 7 {
+        PAWN => df.abs() == 1 && dr == if is_white(code) { 1 } else { -1 },
+        KNIGHT => df.abs() * dr.abs() == 2,
+        BISHOP => diagonal && clear(),
+        ROOK => straight && clear(),
+        QUEEN => (straight || diagonal) && clear(),
+        _ => df.abs().max(dr.abs()) == 1,
+    }
+}
+
+// Squares a piece moves to from `from`, stopping sliders at the first piece
+fn targets(board: &[u8; 64], from: usize, code: u8, out: &mut Vec<usize>) {
+    out.clear();
+    let (steps, slides): (&[(i32, i32)], bool) = match code & 7 {
+        KNIGHT => (&KNIGHT_STEPS, false),
+        BISHOP => (&BISHOP_STEPS, true),
+        ROOK => (&ROOK_STEPS, true),
+        QUEEN => (&KING_STEPS, true),
+        KING => (&KING_STEPS, false),
+        _ => {
+            let forward = if is_white(code) { 1 } else { -1 };
+            if let Some(sq) = offset(from, (0, forward)).filter(|&sq| board[sq] == 0) {
+                out.push(sq);
+                let start = if is_white(code) { 1 } else { 6 };
+                if let Some(sq) = offset(sq, (0, forward)).filter(|&sq| rank_of(from) == start && board[sq] == 0) {
+                    out.push(sq);
+                }
+            }
+            for df in [-1, 1] {
+                if let Some(sq) = offset(from, (df, forward)).filter(|&sq| board[sq] != 0) {
+                    out.push(sq);
+                }
+            }
+            return;
+        },
+    };
+    for &step in steps {
+        let mut sq = offset(from, step);
+        while let Some(s) = sq {
+            out.push(s);
+            if board[s] != 0 || !slides {
+                break;
+            }
+            sq = offset(s, step);
+        }
+    }
+}
+
+// Squares a piece of the side that just moved could have come from to reach
+// `to`. Captures and promotions lead here from bigger tables, so only quiet
+// moves are undone.
+fn sources(board: &[u8; 64], to: usize, code: u8, out: &mut Vec<usize>) {
+    if code & 7 != PAWN {
+        targets(board, to, code, out);
+        out.retain(|&sq| board[sq] == 0);
+        return;
+    }
+
+    out.clear();
+    let back = if is_white(code) { -1 } else { 1 };
+    let Some(one) = offset(to, (0, back)).filter(|&sq| board[sq] == 0) else {
+        return;
+    };
+    // Pawns never stand on their first rank
+    if (8..56).contains(&one) {
+        out.push(one);
+    }
+    let double = if is_white(code) { 3 } else { 4 };
+    if rank_of(to) == double {
+        if let Some(two) = offset(one, (0, back)).filter(|&sq| board[sq] == 0) {
+            out.push(two);
+        }
+    }
+}
+
+// A position a move leads to. Captures and promotions leave the table, so the
+// result of those comes from a smaller or different table.
+struct Successor {
+    placement: Placement,
+    leaves_table: bool,
+}
+
+// Every legal move of the side to move. Pieces keep their order, so positions
+// that stay in the table can be indexed directly.
+fn successors(placement: &Placement, white: bool, scratch: &mut Vec<usize>, out: &mut Vec<Successor>) {
+    out.clear();
+    let board = placement.board();
+    for (i, &(from, code)) in placement.pieces().iter().enumerate() {
+        if is_white(code) != white {
+            continue;
+        }
+        targets(&board, from, code, scratch);
+        for &to in scratch.iter() {
+            let captured = board[to];
+            if captured != 0 && (is_white(captured) == white || captured & 7 == KING) {
+                continue;
+            }
+
+            let mut after = *placement;
+            after.pieces[i].0 = to;
+            if captured != 0 {
+                let index = after.pieces().iter().position(|&(sq, c)| sq == to && c == captured).unwrap();
+                after.remove(index);
+            }
+            let mut after_board = board;
+            after_board[from] = 0;
+            after_board[to] = code;
+            if after.in_check(&after_board, white) {
+                continue;
+            }
+
+            let promotes = code & 7 == PAWN && !(8..56).contains(&to);
+            if promotes {
+                let moved = after.pieces().iter().position(|&(sq, _)| sq == to).unwrap();
+                for kind in [QUEEN, ROOK, BISHOP, KNIGHT] {
+                    let mut promoted = after;
+                    promoted.pieces[moved].1 = (code & BLACK) | kind;
+                    out.push(Successor { placement: promoted, leaves_table: true });
+                }
+            } else {
+                out.push(Successor { placement: after, leaves_table: captured != 0 });
+            }
+        }
+    }
+}
+
+// Material of one side as in table names, e.g. "KBN"
+fn side_material(codes: impl Iterator<Item = u8>) -> String {
+    let mut kinds: Vec<u8> = codes.map(|code| code & 7).collect();
+    kinds.sort_by_key(|&kind| kind_order(kind));
+    kinds.into_iter().map(letter).collect()
+}
+
+// Whether one side's material outranks the other's; the stronger side is white in a table
+fn stronger(a: &str, b: &str) -> bool {
+    let ranks = |s: &str| s.chars().map(|c| 6 - "KQRBNP".find(c).unwrap_or(6)).collect::<Vec<_>>();
+    ranks(a) > ranks(b)
+}
+
+fn table_name(white: &str, black: &str) -> String {
+    if stronger(black, white) {
+        format!("{}v{}", black, white)
+    } else {
+        format!("{}v{}", white, black)
+    }
+}
+
+// Table name for a material written as "KQvKR", "KQKR" or "kqkr"
+pub fn dtm_material(material: &str) -> Result<String, ChessError> {
+    let upper = material.trim().to_uppercase();
+    let (white, black) = match upper.split_once('V') {
+        Some(sides) => sides,
+        None => match upper.get(1..).and_then(|rest| rest.find('K')) {
+            Some(i) => upper.split_at(i + 1),
+            None => ("", ""),
+        },
+    };
+    let valid = |side: &str| side.starts_with('K')
+        && side[1..].chars().all(|c| "QRBNP".contains(c));
+    if !valid(white) || !valid(black) {
+        return Err(ChessError::Tablebase(format!("Invalid material: {}", material)));
+    }
+    if white.len() + black.len() > DTM_PIECES {
+        return Err(ChessError::Tablebase(format!("{} has more than {} pieces", material, DTM_PIECES)));
+    }
+
+    let sort = |side: &str| side_material(side.chars().map(|c| match c {
+        'Q' => QUEEN,
+        'R' => ROOK,
+        'B' => BISHOP,
+        'N' => KNIGHT,
+        'P' => PAWN,
+        _ => KING,
+    }));
+    Ok(table_name(&sort(white), &sort(black)))
+}
+
+// Piece codes of a table in index order: white king, black king, then the
+// other white and black pieces as the name lists them
+fn table_pieces(name: &str) -> Vec<u8> {
+    let (white, black) = name.split_once('v').unwrap_or((name, ""));
+    let code = |c: char| match c {
+        'Q' => QUEEN,
+        'R' => ROOK,
+        'B' => BISHOP,
+        'N' => KNIGHT,
+        'P' => PAWN,
+        _ => KING,
+    };
+    let mut pieces = vec![KING, KING | BLACK];
+    pieces.extend(white.chars().skip(1).map(code));
+    pieces.extend(black.chars().skip(1).map(|c| code(c) | BLACK));
+    pieces
+}
+
+// Tables a position of this material can move into by a capture or promotion
+fn dependencies(name: &str) -> Vec<String> {
+    let pieces = table_pieces(name);
+    let mut names = Vec::new();
+    let mut add = |codes: &[u8]| {
+        let white = side_material(codes.iter().copied().filter(|&c| is_white(c)));
+        let black = side_material(codes.iter().copied().filter(|&c| !is_white(c)));
+        let name = table_name(&white, &black);
+        if codes.len() > 2 && !names.contains(&name) {
+            names.push(name);
+        }
+    };
+
+    for captured in 2..pieces.len() {
+        let mut codes = pieces.clone();
+        codes.remove(captured);
+        add(&codes);
+    }
+    for pawn in (2..pieces.len()).filter(|&i| pieces[i] & 7 == PAWN) {
+        for kind in [QUEEN, ROOK, BISHOP, KNIGHT] {
+            let mut codes = pieces.clone();
+            codes[pawn] = (pieces[pawn] & BLACK) | kind;
+            add(&codes);
+            // Promoting with a capture
+            for captured in (2..pieces.len()).filter(|&i| is_white(pieces[i]) != is_white(pieces[pawn])) {
+                let mut codes = codes.clone();
+                codes.remove(captured);
+                add(&codes);
+            }
+        }
+    }
+    names
+}
+
+// The table of one material balance
+#[derive(Clone)]
+pub struct DtmTable {
+    name: String,
+    // Piece codes in index order
+    pieces: Vec<u8>,
+    has_pawns: bool,
+    // Positions per side to move
+    size: usize,
+    // Results by side to move, white first
+    values: [Vec<u8>; 2],
+}
+
+impl DtmTable {
+    fn new(name: &str) -> Self {
+        let pieces = table_pieces(name);
+        let has_pawns = pieces.iter().any(|&code| code & 7 == PAWN);
+        let mut table = Self {
+            name: name.to_string(),
+            pieces,
+            has_pawns,
+            size: 0,
+            values: [Vec::new(), Vec::new()],
+        };
+        table.size = (0..table.pieces.len()).map(|i| table.radix(i)).product();
+        table.values = [vec![DRAW; table.size], vec![DRAW; table.size]];
+        table
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn piece_count(&self) -> usize {
+        self.pieces.len()
+    }
+
+    // Squares the piece at `i` in index order can be on
+    fn radix(&self, i: usize) -> usize {
+        match (i, self.pieces[i] & 7) {
+            (0, _) if self.has_pawns => 32,
+            (0, _) => TRIANGLE.len(),
+            (_, PAWN) => 48,
+            _ => 64,
+        }
+    }
+
+    fn decode(&self, mut index: usize) -> [usize; DTM_PIECES] {
+        let mut squares = [0; DTM_PIECES];
+        for i in (0..self.pieces.len()).rev() {
+            let radix = self.radix(i);
+            let code = index % radix;
+            index /= radix;
+            squares[i] = match (i, self.pieces[i] & 7) {
+                (0, _) if self.has_pawns => code / 4 * 8 + code % 4,
+                (0, _) => TRIANGLE[code],
+                (_, PAWN) => code + 8,
+                _ => code,
+            };
+        }
+        squares
+    }
+
+    // Index of a position given the squares of the table's pieces in index order.
+    // The white king is brought to the a-d files, and without pawns to a1-d1-d4;
+    // with it on the long diagonal the first piece off it decides the reflection.
+    fn index(&self, squares: &[usize]) -> usize {
+        let mut squares: [usize; DTM_PIECES] = {
+            let mut s = [0; DTM_PIECES];
+            s[..squares.len()].copy_from_slice(squares);
+            s
+        };
+        let squares = &mut squares[..self.pieces.len()];
+        if file_of(squares[0]) > 3 {
+            squares.iter_mut().for_each(|sq| *sq ^= 7);
+        }
+        if !self.has_pawns {
+            if rank_of(squares[0]) > 3 {
+                squares.iter_mut().for_each(|sq| *sq ^= 56);
+            }
+            let above = squares.iter()
+                .find(|&&sq| file_of(sq) != rank_of(sq))
+                .is_some_and(|&sq| rank_of(sq) > file_of(sq));
+            if above {
+                squares.iter_mut().for_each(|sq| *sq = (*sq % 8) * 8 + *sq / 8);
+            }
+        }
+
+        let mut index = 0;
+        for (i, &sq) in squares.iter().enumerate() {
+            let code = match (i, self.pieces[i] & 7) {
+                (0, _) if self.has_pawns => rank_of(sq) as usize * 4 + file_of(sq) as usize,
+                (0, _) => TRIANGLE.iter().position(|&t| t == sq).unwrap_or(0),
+                (_, PAWN) => sq.saturating_sub(8).min(47),
+                _ => sq,
+            };
+            index = index * self.radix(i) + code;
+        }
+        index
+    }
+
+    fn side(white: bool) -> usize {
+        if white { 0 } else { 1 }
+    }
+
+    // Result of a position given in index order, None for positions that can't occur
+    fn lookup(&self, squares: &[usize], white: bool) -> Option<Dtm> {
+        Dtm::from_byte(self.values[Self::side(white)][self.index(squares)])
+    }
+
+    fn placement(&self, squares: &[usize]) -> Placement {
+        let pieces: Vec<(usize, u8)> = squares.iter().copied().zip(self.pieces.iter().copied()).collect();
+        Placement::new(&pieces)
+    }
+
+    // Results of the side to move, counted over legal positions
+    pub fn stats(&self, color: Color) -> DtmStats {
+        let mut stats = DtmStats::default();
+        for &byte in &self.values[Self::side(color == Color::White)] {
+            match Dtm::from_byte(byte) {
+                Some(Dtm::Win(plies)) => {
+                    stats.wins += 1;
+                    stats.longest_win = stats.longest_win.max(plies);
+                },
+                Some(Dtm::Loss(plies)) => {
+                    stats.losses += 1;
+                    stats.longest_loss = stats.longest_loss.max(plies);
+                },
+                Some(Dtm::Draw) => stats.draws += 1,
+                None => continue,
+            }
+            stats.positions += 1;
+        }
+        stats
+    }
+
+    // Position of the longest win for the side to move, as a FEN string
+    pub fn longest_win_fen(&self, color: Color) -> Option<String> {
+        let white = color == Color::White;
+        let values = &self.values[Self::side(white)];
+        let (index, _) = values.iter().enumerate()
+            .filter(|(_, &byte)| byte != DRAW && byte < LOSS_BASE)
+            .max_by_key(|(_, &byte)| byte)?;
+        let squares = self.decode(index);
+        let mut board = Board::empty();
+        for (&sq, &code) in squares.iter().zip(&self.pieces) {
+            let piece_type = match code & 7 {
+                PAWN => PieceType::Pawn,
+                KNIGHT => PieceType::Knight,
+                BISHOP => PieceType::Bishop,
+                ROOK => PieceType::Rook,
+                QUEEN => PieceType::Queen,
+                _ => PieceType::King,
+            };
+            let piece_color = if is_white(code) { Color::White } else { Color::Black };
+            board.set_piece(&Position::new(file_of(sq) as u8, rank_of(sq) as u8), Some(Piece::new(piece_type, piece_color))).ok()?;
+        }
+        Some(format!("{} {} - - 0 1", board.to_fen(), if white { "w" } else { "b" }))
+    }
+
+    // Work out every position by retrograde analysis. Mates are found first, then
+    // level by level each result is passed back through the moves that lead to
+    // it: positions one ply before a loss are wins, and positions whose moves all
+    // lead to wins for the opponent are losses. Captures and promotions take
+    // their results from the smaller tables, which must already be in `tables`.
+    fn generate(name: &str, tables: &DtmTablebase) -> Self {
+        let mut table = Self::new(name);
+        let mut scratch = Vec::new();
+        let mut moves = Vec::new();
+        let mut buckets: Vec<Vec<(bool, usize)>> = vec![Vec::new(); MAX_PLIES + 2];
+        let mut resolved = Vec::new();
+        let mut leaving = Vec::new();
+
+        for white in [true, false] {
+            let side = Self::side(white);
+            for index in 0..table.size {
+                let squares = table.decode(index);
+                let squares = &squares[..table.pieces.len()];
+                let placement = table.placement(squares);
+                let board = placement.board();
+                let overlapping = board.iter().filter(|&&code| code != 0).count() < placement.len;
+                let kings_touch = attacks(&board, squares[0], KING, squares[1]);
+                // Positions the index folds onto another one are never looked up
+                if overlapping || kings_touch || table.index(squares) != index || placement.in_check(&board, !white) {
+                    table.values[side][index] = ILLEGAL;
+                    continue;
+                }
+
+                successors(&placement, white, &mut scratch, &mut moves);
+                if moves.is_empty() {
+                    if placement.in_check(&board, white) {
+                        table.values[side][index] = plies_byte(0);
+                        resolved.push((white, index));
+                    }
+                } else if moves.iter().any(|m| m.leaves_table) {
+                    leaving.push((white, index));
+                }
+            }
+        }
+
+        // Results that come from the smaller tables are known from the start
+        for (white, index) in leaving {
+            if let Some(plies) = table.evaluate(white, index, tables, &mut scratch, &mut moves) {
+                buckets[plies.min(MAX_PLIES + 1)].push((white, index));
+            }
+        }
+
+        let mut sources_scratch = Vec::new();
+        for level in 1..=MAX_PLIES {
+            let mut candidates = std::mem::take(&mut buckets[level]);
+            for &(white, index) in &resolved {
+                table.predecessors(white, index, &mut sources_scratch, &mut candidates);
+            }
+
+            resolved.clear();
+            for (white, index) in candidates {
+                let side = Self::side(white);
+                if table.values[side][index] != DRAW {
+                    continue;
+                }
+                match table.evaluate(white, index, tables, &mut scratch, &mut moves) {
+                    Some(plies) if plies == level => {
+                        table.values[side][index] = plies_byte(plies);
+                        resolved.push((white, index));
+                    },
+                    Some(plies) if plies > level => buckets[plies.min(MAX_PLIES + 1)].push((white, index)),
+                    _ => {},
+                }
+            }
+
+            if resolved.is_empty() && buckets[level + 1..=MAX_PLIES].iter().all(Vec::is_empty) {
+                break;
+            }
+        }
+
+        table
+    }
+
+    // Plies to mate of a position from what its moves lead to, if that's already known
+    fn evaluate(&self, white: bool, index: usize, tables: &DtmTablebase, scratch: &mut Vec<usize>, moves: &mut Vec<Successor>) -> Option<usize> {
+        let squares = self.decode(index);
+        let placement = self.placement(&squares[..self.pieces.len()]);
+        successors(&placement, white, scratch, moves);
+        if moves.is_empty() {
+            return None;
+        }
+
+        let mut fastest_win = None;
+        let mut all_lose = true;
+        let mut longest_loss = 0;
+        for successor in moves.iter() {
+            let result = if successor.leaves_table {
+                tables.probe_pieces(successor.placement.pieces(), !white)
+            } else {
+                let mut squares = [0; DTM_PIECES];
+                for (sq, &(s, _)) in squares.iter_mut().zip(successor.placement.pieces()) {
+                    *sq = s;
+                }
+                self.lookup(&squares[..successor.placement.len], !white)
+            };
+            match result {
+                Some(Dtm::Loss(plies)) => {
+                    let plies = plies as usize + 1;
+                    fastest_win = Some(fastest_win.map_or(plies, |best: usize| best.min(plies)));
+                },
+                Some(Dtm::Win(plies)) => longest_loss = longest_loss.max(plies as usize + 1),
+                _ => all_lose = false,
+            }
+        }
+
+        fastest_win.or(all_lose.then_some(longest_loss))
+    }
+
+    // Positions with the other side to move that reach this one by a quiet move
+    fn predecessors(&self, white: bool, index: usize, scratch: &mut Vec<usize>, out: &mut Vec<(bool, usize)>) {
+        let squares = self.decode(index);
+        let squares = &squares[..self.pieces.len()];
+        let board = self.placement(squares).board();
+        for (i, &code) in self.pieces.iter().enumerate() {
+            if is_white(code) == white {
+                continue;
+            }
+            sources(&board, squares[i], code, scratch);
+            for &from in scratch.iter() {
+                let mut before = [0; DTM_PIECES];
+                before[..squares.len()].copy_from_slice(squares);
+                before[i] = from;
+                let before_index = self.index(&before[..squares.len()]);
+                if self.values[Self::side(!white)][before_index] == DRAW {
+                    out.push((!white, before_index));
+                }
+            }
+        }
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<(), ChessError> {
+        let path = path.as_ref();
+        let mut bytes = Vec::with_capacity(2 * self.size + 16);
+        bytes.extend_from_slice(&MAGIC);
+        bytes.push(VERSION);
+        bytes.push(self.name.len() as u8);
+        bytes.extend_from_slice(self.name.as_bytes());
+        bytes.extend_from_slice(&self.values[0]);
+        bytes.extend_from_slice(&self.values[1]);
+        fs::write(path, bytes)
+            .map_err(|e| ChessError::Tablebase(format!("Failed to write {}: {}", path.display(), e)))
+    }
+
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, ChessError> {
+        let path = path.as_ref();
+        let bytes = fs::read(path)
+            .map_err(|e| ChessError::Tablebase(format!("Failed to read {}: {}", path.display(), e)))?;
+        let invalid = |reason: &str| ChessError::Tablebase(format!("{}: {}", path.display(), reason));
+
+        if bytes.len() < 6 || bytes[..4] != MAGIC {
+            return Err(invalid("not a rustychess DTM table"));
+        }
+        if bytes[4] != VERSION {
+            return Err(invalid(&format!("unsupported version {}", bytes[4])));
+        }
+        let name_end = 6 + bytes[5] as usize;
+        let name = bytes.get(6..name_end)
+            .and_then(|name| std::str::from_utf8(name).ok())
+            .ok_or_else(|| invalid("truncated header"))?;
+        if dtm_material(name).ok().as_deref() != Some(name) {
+            return Err(invalid(&format!("invalid material {}", name)));
+        }
+
+        let mut table = Self::new(name);
+        let data = &bytes[name_end..];
+        if data.len() != 2 * table.size {
+            return Err(invalid(&format!("expected {} bytes of results, found {}", 2 * table.size, data.len())));
+        }
+        table.values = [data[..table.size].to_vec(), data[table.size..].to_vec()];
+        Ok(table)
+    }
+}
+
+impl fmt::Debug for DtmTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "DtmTable({}, {} positions)", self.name, 2 * self.size)
+    }
+}
+
+// Generated distance-to-mate tables, by material
+#[derive(Default, Clone)]
+pub struct DtmTablebase {
+    tables: HashMap<String, DtmTable>,
+}
+
+impl DtmTablebase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Load every `.dtm` table in a directory
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self, ChessError> {
+        let dir = dir.as_ref();
+        let entries = fs::read_dir(dir)
+            .map_err(|e| ChessError::Tablebase(format!("Failed to read {}: {}", dir.display(), e)))?;
+
+        let mut tablebase = Self::new();
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == "dtm") {
+                tablebase.insert(DtmTable::read(&path)?);
+            }
+        }
+        if tablebase.is_empty() {
+            return Err(ChessError::Tablebase(format!("No DTM tables in {}", dir.display())));
+        }
+        Ok(tablebase)
+    }
+
+    pub fn insert(&mut self, table: DtmTable) {
+        self.tables.insert(table.name.clone(), table);
+    }
+
+    pub fn get(&self, material: &str) -> Option<&DtmTable> {
+        self.tables.get(&dtm_material(material).ok()?)
+    }
+
+    pub fn len(&self) -> usize {
+        self.tables.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    // Most pieces of any loaded table
+    pub fn max_pieces(&self) -> usize {
+        self.tables.values().map(DtmTable::piece_count).max().unwrap_or(0)
+    }
+
+    // Generate the table for a material along with any smaller tables it depends
+    // on that aren't loaded yet. Returns the names of the new tables, smallest first.
+    pub fn generate(&mut self, material: &str) -> Result<Vec<String>, ChessError> {
+        let name = dtm_material(material)?;
+        let mut generated = Vec::new();
+        self.generate_table(&name, &mut generated);
+        Ok(generated)
+    }
+
+    fn generate_table(&mut self, name: &str, generated: &mut Vec<String>) {
+        if self.tables.contains_key(name) || table_pieces(name).len() <= 2 {
+            return;
+        }
+        for dependency in dependencies(name) {
+            self.generate_table(&dependency, generated);
+        }
+        let table = DtmTable::generate(name, self);
+        self.insert(table);
+        generated.push(name.to_string());
+    }
+
+    // Result of the game's position, if a table covers it
+    pub fn probe(&self, game: &Game) -> Option<Dtm> {
+        self.probe_board(&game.board, game.current_turn)
+    }
+
+    // Every legal move with the result it leads to, best first. Moves into a
+    // position no table covers are left out, such as a pawn reaching the last
+    // rank, which the board doesn't promote.
+    pub fn probe_root(&self, game: &Game) -> Option<Vec<DtmMove>> {
+        let board = &game.board;
+        let color = game.current_turn;
+        self.probe_board(board, color)?;
+
+        let mut moves = Vec::new();
+        for chess_move in Engine::new(1).get_legal_moves(board, color).ok()? {
+            let mut after = board.clone();
+            if after.make_move(&chess_move.from, &chess_move.to).is_err() {
+                continue;
+            }
+            if let Some(dtm) = self.probe_board(&after, color.opposite()) {
+                moves.push(DtmMove { chess_move, dtm: dtm.before_move() });
+            }
+        }
+        moves.sort_by_key(|m| m.dtm.rank());
+        Some(moves)
+    }
+
+    pub(crate) fn probe_board(&self, board: &Board, color: Color) -> Option<Dtm> {
+        let mut pieces = Vec::with_capacity(DTM_PIECES);
+        for sq in 0..64 {
+            if let Some(piece) = board.get_piece(&Position::new((sq % 8) as u8, (sq / 8) as u8)) {
+                if pieces.len() == DTM_PIECES {
+                    return None;
+                }
+                pieces.push((sq, piece_code(piece.piece_type, piece.color)));
+            }
+        }
+        if pieces.iter().filter(|(_, code)| code & 7 == KING).count() != 2 {
+            return None;
+        }
+        self.probe_pieces(&pieces, color == Color::White)
+    }
+
+    // Result of a position given as (square, code) pairs, with the tables'
+    // colours swapped when black is the stronger side
+    fn probe_pieces(&self, pieces: &[(usize, u8)], white: bool) -> Option<Dtm> {
+        if pieces.len() == 2 {
+            return Some(Dtm::Draw);
+        }
+        // Pawns can't stand on the first or last rank in a table
+        if pieces.iter().any(|&(sq, code)| code & 7 == PAWN && !(8..56).contains(&sq)) {
+            return None;
+        }
+
+        let white_material = side_material(pieces.iter().map(|&(_, c)| c).filter(|&c| is_white(c)));
+        let black_material = side_material(pieces.iter().map(|&(_, c)| c).filter(|&c| !is_white(c)));
+        let flip = stronger(&black_material, &white_material);
+        let table = self.tables.get(&table_name(&white_material, &black_material))?;
+
+        let mut squares = [0; DTM_PIECES];
+        let mut used = [false; DTM_PIECES];
+        for (i, &code) in table.pieces.iter().enumerate() {
+            let wanted = if flip { code ^ BLACK } else { code };
+            let j = (0..pieces.len()).find(|&j| !used[j] && pieces[j].1 == wanted)?;
+            used[j] = true;
+            squares[i] = if flip { pieces[j].0 ^ 56 } else { pieces[j].0 };
+        }
+        table.lookup(&squares[..pieces.len()], white != flip)
+    }
+}
+
+impl fmt::Debug for DtmTablebase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names: Vec<&String> = self.tables.keys().collect();
+        names.sort();
+        write!(f, "DtmTablebase({:?})", names)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn probe(tablebase: &DtmTablebase, fen: &str) -> Option<Dtm> {
+        tablebase.probe(&Game::from_fen(fen).unwrap())
+    }
+
+    // Generated once and shared by the tests
+    fn tablebase() -> &'static DtmTablebase {
+        static TABLEBASE: std::sync::OnceLock<DtmTablebase> = std::sync::OnceLock::new();
+        TABLEBASE.get_or_init(|| {
+            let mut tablebase = DtmTablebase::new();
+            assert_eq!(tablebase.generate("KQvK").unwrap(), vec!["KQvK"]);
+            assert_eq!(tablebase.generate("KRvK").unwrap(), vec!["KRvK"]);
+            tablebase
+        })
+    }
+
+    #[test]
+    fn longest_mates() {
+        let tablebase = tablebase();
+        for (material, plies, fen) in [
+            ("KQvK", 19, "8/8/8/5k2/8/8/1Q6/K7 w - - 0 1"),
+            ("KRvK", 31, "8/6R1/5k2/8/8/8/8/1K6 w - - 0 1"),
+        ] {
+            let table = tablebase.get(material).unwrap();
+            let stats = table.stats(Color::White);
+            assert_eq!(stats.longest_win, plies, "{}", material);
+            assert_eq!(stats.losses, 0, "{}", material);
+            // The weaker side can only lose or draw
+            assert_eq!(table.stats(Color::Black).wins, 0, "{}", material);
+            assert_eq!(table.longest_win_fen(Color::White).as_deref(), Some(fen));
+            assert_eq!(probe(tablebase, fen), Some(Dtm::Win(plies)));
+            assert_eq!(Dtm::Win(plies).mate_in(), Some((plies as i32 + 1) / 2));
+        }
+    }
+
+    #[test]
+    fn longest_four_piece_mates() {
+        // KBNvK takes a couple of minutes in a debug build
+        for (material, plies, fen) in [
+            ("KPvK", 55, "8/8/8/1k6/8/K7/6P1/8 w - - 0 1"),
+            ("KBNvK", 65, "8/8/8/6B1/8/5k2/8/2K4N w - - 0 1"),
+        ] {
+            let mut tablebase = DtmTablebase::new();
+            tablebase.generate(material).unwrap();
+            let table = tablebase.get(material).unwrap();
+            assert_eq!(table.stats(Color::White).longest_win, plies, "{}", material);
+            assert_eq!(table.stats(Color::Black).wins, 0, "{}", material);
+            assert_eq!(table.longest_win_fen(Color::White).as_deref(), Some(fen));
+            assert_eq!(probe(&tablebase, fen), Some(Dtm::Win(plies)));
+            assert_eq!(Dtm::Win(plies).mate_in(), Some(if material == "KPvK" { 28 } else { 33 }));
+
+            if material == "KPvK" {
+                // The push to the last rank can't be probed, since the board doesn't
+                // promote, but the king moves still are
+                let game = Game::from_fen("8/4P3/8/8/8/2k5/8/4K3 w - - 0 1").unwrap();
+                let moves = tablebase.probe_root(&game).unwrap();
+                assert_eq!(moves.len(), 4);
+                assert!(moves.iter().all(|m| m.chess_move.to_uci() != "e7e8"));
+            }
+        }
+    }
+
+    #[test]
+    fn probes() {
+        let tablebase = tablebase();
+        assert_eq!(probe(tablebase, "7k/8/6K1/8/8/8/8/R7 w - - 0 1"), Some(Dtm::Win(1)));
+        assert_eq!(probe(tablebase, "R6k/8/6K1/8/8/8/8/8 b - - 0 1"), Some(Dtm::Loss(0)));
+        // Stalemate, and a king that takes the undefended queen
+        assert_eq!(probe(tablebase, "7k/5Q2/6K1/8/8/8/8/8 b - - 0 1"), Some(Dtm::Draw));
+        assert_eq!(probe(tablebase, "8/8/8/8/8/8/6kQ/K7 b - - 0 1"), Some(Dtm::Draw));
+        // Colours swapped
+        assert_eq!(probe(tablebase, "1k6/8/8/8/8/5K2/6r1/8 b - - 0 1"), Some(Dtm::Win(31)));
+        // No table for the material
+        assert_eq!(probe(tablebase, "7k/8/6K1/8/8/8/8/B7 w - - 0 1"), None);
+    }
+
+    #[test]
+    fn probe_root_ranks_moves() {
+        let tablebase = tablebase();
+        // Only Qg7 mates at once (Qh7+ is met by Kxh7); the queen hanging next to the king only draws
+        let game = Game::from_fen("7k/8/5K2/8/8/8/8/6Q1 w - - 0 1").unwrap();
+        let moves = tablebase.probe_root(&game).unwrap();
+        let mates: Vec<String> = moves.iter()
+            .filter(|m| m.dtm == Dtm::Win(1))
+            .map(|m| m.chess_move.to_uci())
+            .collect();
+        assert_eq!(mates, vec!["g1g7"]);
+        assert_eq!(moves[0].chess_move.to_uci(), "g1g7");
+        assert_eq!(probe(tablebase, "7k/8/5K2/8/8/8/8/6Q1 w - - 0 1"), Some(moves[0].dtm));
+        assert!(moves.windows(2).all(|pair| pair[0].dtm.rank() <= pair[1].dtm.rank()));
+        let hanging = moves.iter().find(|m| m.chess_move.to_uci() == "g1g8").unwrap();
+        assert_eq!(hanging.dtm, Dtm::Draw);
+    }
+
+    #[test]
+    fn save_and_open_round_trip() {
+        let tablebase = tablebase();
+        let dir = std::env::temp_dir().join(format!("rustychess-dtm-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for material in ["KQvK", "KRvK"] {
+            tablebase.get(material).unwrap().write(dir.join(format!("{}.dtm", material))).unwrap();
+        }
+        let opened = DtmTablebase::open(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+        let opened = opened.unwrap();
+
+        assert_eq!(opened.len(), 2);
+        for material in ["KQvK", "KRvK"] {
+            let (table, read) = (tablebase.get(material).unwrap(), opened.get(material).unwrap());
+            assert_eq!(read.values, table.values, "{}", material);
+            assert_eq!(read.stats(Color::White), table.stats(Color::White));
+        }
+        for fen in ["8/8/8/5k2/8/8/1Q6/K7 w - - 0 1", "8/8/3k4/8/8/8/8/KR6 b - - 0 1", "1k6/8/8/8/8/5K2/6r1/8 b - - 0 1"] {
+            assert_eq!(probe(&opened, fen), probe(tablebase, fen), "{}", fen);
+        }
+    }
+}
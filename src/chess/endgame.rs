@@ -0,0 +1,387 @@
+use crate::chess::{Board, Color, PieceType, Position};
+use crate::chess::eval::{KNIGHT_VALUE, BISHOP_VALUE, ROOK_VALUE, QUEEN_VALUE};
+
+// Endgame knowledge the general evaluation lacks. Some material signatures get
+// an evaluation of their own:
+//
+//   KQK, KRK  mop-up: drive the lone king to the edge and bring ours closer
+//   KBNK      drive the lone king to a corner the bishop controls
+//
+// Other material keeps its evaluation but scales it towards a draw, out of 64:
+//
+//   no pawns and at most a minor piece ahead, e.g. KRKB or KBK, or KNNK
+//   bishops of opposite colours with only pawns besides
+//   rook pawns the defending king stops in front of, including a bishop that
+//   doesn't control the promotion square
+//
+// Signatures list the stronger side's pieces and then the weaker side's, like
+// "KBNK" or "KRKB".
+
+const SCALE_NORMAL: i32 = 64;
+// Bonus of a known win, enough to outweigh anything the general evaluation says
+// but far below mate scores
+const KNOWN_WIN: i32 = 5000;
+// Mop-up weights per step of distance
+const EDGE_WEIGHT: i32 = 20;
+const CLOSENESS_WEIGHT: i32 = 10;
+const CORNER_WEIGHT: i32 = 40;
+
+// Square colours in a side's bishop mask
+const LIGHT: u8 = 1;
+const DARK: u8 = 2;
+
+// Pieces per side, in PieceType order, with what matters about their squares.
+// Filled in once per evaluated position, so it holds no heap data.
+struct Material {
+    counts: [[u8; 6]; 2],
+    kings: [Option<Position>; 2],
+    // Files with pawns, one bit per file from a to h
+    pawn_files: [u8; 2],
+    // Lowest and highest rank with a pawn
+    pawn_ranks: [(u8, u8); 2],
+    // Square colours of the bishops, LIGHT and DARK
+    bishops: [u8; 2],
+}
+
+// Piece counts of the stronger side and then the weaker side packed into 3 bits
+// per piece type, kings left out. Counts above 7 are capped, which no rule
+// below tells apart.
+type MaterialKey = u32;
+
+// The key of a signature such as "KRKB", at compile time
+const fn signature_key(signature: &str) -> MaterialKey {
+    let bytes = signature.as_bytes();
+    let mut key = 0;
+    let mut kings = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let piece = match bytes[i] {
+            b'P' => 0,
+            b'N' => 1,
+            b'B' => 2,
+            b'R' => 3,
+            b'Q' => 4,
+            _ => 5,
+        };
+        if piece == 5 {
+            kings += 1;
+        } else {
+            key += 1 << (3 * (piece + 5 * (kings - 1)));
+        }
+        i += 1;
+    }
+    key
+}
+
+fn side(color: Color) -> usize {
+    match color {
+        Color::White => 0,
+        Color::Black => 1,
+    }
+}
+
+fn index(piece_type: PieceType) -> usize {
+    match piece_type {
+        PieceType::Pawn => 0,
+        PieceType::Knight => 1,
+        PieceType::Bishop => 2,
+        PieceType::Rook => 3,
+        PieceType::Queen => 4,
+        PieceType::King => 5,
+    }
+}
+
+impl Material {
+    fn of(board: &Board) -> Self {
+        let mut material = Material {
+            counts: [[0; 6]; 2],
+            kings: [None; 2],
+            pawn_files: [0; 2],
+            pawn_ranks: [(7, 0); 2],
+            bishops: [0; 2],
+        };
+        for rank in 0..8 {
+            for file in 0..8 {
+                let pos = Position::new(file, rank);
+                let Some(piece) = board.get_piece(&pos) else {
+                    continue;
+                };
+                let s = side(piece.color);
+                material.counts[s][index(piece.piece_type)] += 1;
+                match piece.piece_type {
+                    PieceType::King => material.kings[s] = Some(pos),
+                    PieceType::Pawn => {
+                        material.pawn_files[s] |= 1 << file;
+                        let (low, high) = material.pawn_ranks[s];
+                        material.pawn_ranks[s] = (low.min(rank), high.max(rank));
+                    },
+                    PieceType::Bishop => material.bishops[s] |= if is_dark(pos) { DARK } else { LIGHT },
+                    _ => {},
+                }
+            }
+        }
+        material
+    }
+
+    fn count(&self, color: Color, piece_type: PieceType) -> u8 {
+        self.counts[side(color)][index(piece_type)]
+    }
+
+    // The material key with `strong` as the stronger side
+    fn key(&self, strong: Color) -> MaterialKey {
+        let mut key = 0;
+        for (offset, color) in [(0, strong), (15, strong.opposite())] {
+            for (i, &count) in self.counts[side(color)][..5].iter().enumerate() {
+                key |= (count.min(7) as MaterialKey) << (offset + 3 * i);
+            }
+        }
+        key
+    }
+
+    // "KBN" for a king, bishop and knight
+    fn pieces(&self, color: Color) -> String {
+        let mut text = "K".repeat(self.count(color, PieceType::King) as usize);
+        for (piece_type, letter) in [
+            (PieceType::Queen, 'Q'), (PieceType::Rook, 'R'), (PieceType::Bishop, 'B'),
+            (PieceType::Knight, 'N'), (PieceType::Pawn, 'P'),
+        ] {
+            text.extend(std::iter::repeat_n(letter, self.count(color, piece_type) as usize));
+        }
+        text
+    }
+
+    // Material other than pawns and kings, at the default values, so that the
+    // rules don't depend on the personality
+    fn non_pawn(&self, color: Color) -> i32 {
+        self.count(color, PieceType::Knight) as i32 * KNIGHT_VALUE
+            + self.count(color, PieceType::Bishop) as i32 * BISHOP_VALUE
+            + self.count(color, PieceType::Rook) as i32 * ROOK_VALUE
+            + self.count(color, PieceType::Queen) as i32 * QUEEN_VALUE
+    }
+
+    fn is_bare(&self, color: Color) -> bool {
+        self.counts[side(color)][..5].iter().all(|&n| n == 0)
+    }
+}
+
+// Evaluation for one material signature: a bonus for the stronger side on top
+// of the general evaluation
+type EndgameFn = fn(&Material, Color) -> i32;
+
+const ENDGAMES: [(MaterialKey, EndgameFn); 3] = [
+    (signature_key("KQK"), mop_up),
+    (signature_key("KRK"), mop_up),
+    (signature_key("KBNK"), kbnk),
+];
+
+// Endgame knowledge that changed a score
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rule {
+    // An evaluation of its own for this side's material against the other's
+    Special(Color),
+    // Scaled towards a draw by a factor out of SCALE_NORMAL, and why
+    Scaled(i32, &'static str),
+}
+
+// The material on the board, White's pieces first, e.g. "KRPvKB"
+pub fn material_signature(board: &Board) -> String {
+    let material = Material::of(board);
+    format!("{}v{}", material.pieces(Color::White), material.pieces(Color::Black))
+}
+
+// Apply endgame knowledge to a score from White's point of view. Returns the
+// new score and the rule that changed it, if any.
+pub fn apply(board: &Board, score: i32) -> (i32, Option<Rule>) {
+    let material = Material::of(board);
+    if material.kings.iter().any(Option::is_none) {
+        return (score, None);
+    }
+    for strong in [Color::White, Color::Black] {
+        let weak = strong.opposite();
+        let key = material.key(strong);
+        let special = ENDGAMES.iter().find(|(k, _)| *k == key).map(|(_, f)| *f)
+            // Anything with a major piece against a bare king mates the same way
+            .or_else(|| (material.is_bare(weak) && (material.count(strong, PieceType::Queen) > 0
+                || material.count(strong, PieceType::Rook) > 0)).then_some(mop_up as EndgameFn));
+        if let Some(evaluate) = special {
+            let bonus = evaluate(&material, strong);
+            let score = if strong == Color::White { score + bonus } else { score - bonus };
+            return (score, Some(Rule::Special(strong)));
+        }
+    }
+
+    // Scale the side that is ahead
+    let strong = if score >= 0 { Color::White } else { Color::Black };
+    match scale_factor(&material, strong) {
+        Some((factor, why)) => (score * factor / SCALE_NORMAL, Some(Rule::Scaled(factor, why))),
+        None => (score, None),
+    }
+}
+
+// What a rule did, for evaluation traces: "KBNK" or "rook pawn scaled 0/64"
+pub fn describe(board: &Board, rule: Rule) -> String {
+    match rule {
+        Rule::Special(strong) => {
+            let material = Material::of(board);
+            format!("{}{}", material.pieces(strong), material.pieces(strong.opposite()))
+        },
+        Rule::Scaled(factor, why) => format!("{} scaled {}/{}", why, factor, SCALE_NORMAL),
+    }
+}
+
+// Apply endgame knowledge to a score from `color`'s point of view
+pub fn adjust(board: &Board, color: Color, score: i32) -> i32 {
+    match color {
+        Color::White => apply(board, score).0,
+        Color::Black => -apply(board, -score).0,
+    }
+}
+
+fn distance(a: Position, b: Position) -> i32 {
+    (a.file as i32 - b.file as i32).abs().max((a.rank as i32 - b.rank as i32).abs())
+}
+
+// 0 in the four central squares up to 6 in the corners
+fn distance_from_center(pos: Position) -> i32 {
+    let file = pos.file as i32;
+    let rank = pos.rank as i32;
+    (3 - file).max(file - 4) + (3 - rank).max(rank - 4)
+}
+
+fn is_dark(pos: Position) -> bool {
+    (pos.file + pos.rank).is_multiple_of(2)
+}
+
+fn kings(material: &Material, strong: Color) -> (Position, Position) {
+    let strong_king = material.kings[side(strong)].unwrap_or(Position::new(0, 0));
+    let weak_king = material.kings[side(strong.opposite())].unwrap_or(Position::new(0, 0));
+    (strong_king, weak_king)
+}
+
+// A won ending against a bare king: the lone king belongs on the edge with ours
+// next to it
+fn mop_up(material: &Material, strong: Color) -> i32 {
+    let (strong_king, weak_king) = kings(material, strong);
+    KNOWN_WIN + EDGE_WEIGHT * distance_from_center(weak_king) + CLOSENESS_WEIGHT * (7 - distance(strong_king, weak_king))
+}
+
+// Mate with bishop and knight only works in a corner of the bishop's colour,
+// so the lone king is driven to the edge and along it to one of those corners
+fn kbnk(material: &Material, strong: Color) -> i32 {
+    let (strong_king, weak_king) = kings(material, strong);
+    let dark = material.bishops[side(strong)] & DARK != 0;
+    // Distance from the long diagonal between the other two corners: 7 in
+    // either right corner, and rising one step at a time along the edge
+    let (file, rank) = (weak_king.file as i32, weak_king.rank as i32);
+    let towards_corner = if dark { (7 - file - rank).abs() } else { (file - rank).abs() };
+    KNOWN_WIN + CORNER_WEIGHT * towards_corner
+        + CLOSENESS_WEIGHT * (7 - distance(strong_king, weak_king))
+}
+
+// How much of the stronger side's advantage survives, out of SCALE_NORMAL, and
+// why, for material that is hard to win with
+fn scale_factor(material: &Material, strong: Color) -> Option<(i32, &'static str)> {
+    let weak = strong.opposite();
+    let (s, w) = (side(strong), side(weak));
+    let minors_and_majors = |color: Color| {
+        [PieceType::Knight, PieceType::Bishop, PieceType::Rook, PieceType::Queen].iter()
+            .map(|&piece_type| material.count(color, piece_type))
+            .sum::<u8>()
+    };
+
+    // Without pawns, a minor piece more is not enough to mate, and a rook
+    // against a minor piece is usually held
+    let pawns = material.count(strong, PieceType::Pawn);
+    if pawns == 0 && material.non_pawn(strong) - material.non_pawn(weak) <= BISHOP_VALUE {
+        let factor = if material.non_pawn(strong) < ROOK_VALUE {
+            0
+        } else if material.non_pawn(weak) <= BISHOP_VALUE {
+            4
+        } else {
+            14
+        };
+        return Some((factor, "insufficient advantage"));
+    }
+
+    // Two knights can't force mate
+    let knights = material.count(strong, PieceType::Knight);
+    if pawns == 0 && knights == minors_and_majors(strong) && knights <= 2 && material.is_bare(weak) {
+        return Some((0, "knights only"));
+    }
+
+    // Bishops of opposite colours and pawns: the defender blockades on the
+    // squares the attacker's bishop can't touch
+    if minors_and_majors(strong) == 1 && minors_and_majors(weak) == 1
+        && material.count(strong, PieceType::Bishop) == 1 && material.count(weak, PieceType::Bishop) == 1
+        && material.bishops[s] != material.bishops[w]
+    {
+        return Some((16, "opposite-coloured bishops"));
+    }
+
+    // Pawns only on one rook file, stopped by the defending king in the corner
+    // in front of them. A bishop changes nothing unless it controls the
+    // promotion square.
+    let bishops = material.count(strong, PieceType::Bishop);
+    let files = material.pawn_files[s];
+    if pawns > 0 && bishops == minors_and_majors(strong) && minors_and_majors(weak) == 0
+        && (files == 1 || files == 1 << 7)
+    {
+        let file = files.trailing_zeros() as u8;
+        let promotion = Position::new(file, if strong == Color::White { 7 } else { 0 });
+        let (_, weak_king) = kings(material, strong);
+        let corner = if is_dark(promotion) { DARK } else { LIGHT };
+        let controls_corner = material.bishops[s] & corner != 0;
+        // The defending king is next to the promotion square, ahead of every pawn
+        let (lowest, highest) = material.pawn_ranks[s];
+        let ahead = match strong {
+            Color::White => weak_king.rank > highest,
+            Color::Black => weak_king.rank < lowest,
+        };
+        if !controls_corner && distance(weak_king, promotion) <= 1 && ahead {
+            return Some((0, if bishops == 0 { "rook pawn" } else { "wrong-coloured bishop" }));
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::Game;
+
+    fn rule(fen: &str, score: i32) -> Option<String> {
+        let board = Game::from_fen(fen).unwrap().board;
+        let (_, rule) = apply(&board, score);
+        rule.map(|rule| describe(&board, rule))
+    }
+
+    #[test]
+    fn special_endgames() {
+        assert_eq!(rule("8/8/8/8/8/8/8/KBN4k w - - 0 1", 0).as_deref(), Some("KBNK"));
+        assert_eq!(rule("8/8/8/4k3/8/8/8/K6r w - - 0 1", 0).as_deref(), Some("KRK"));
+        // Any major piece against a bare king
+        assert_eq!(rule("8/8/8/4k3/8/8/8/KQR5 w - - 0 1", 0).as_deref(), Some("KQRK"));
+
+        let board = Game::from_fen("8/8/8/4k3/8/8/8/K6r w - - 0 1").unwrap().board;
+        assert!(apply(&board, -500).0 < -KNOWN_WIN);
+        assert_eq!(adjust(&board, Color::Black, 500), -apply(&board, -500).0);
+    }
+
+    #[test]
+    fn drawish_material() {
+        assert_eq!(rule("k7/8/P7/8/8/8/8/7K w - - 0 1", 100).as_deref(), Some("rook pawn scaled 0/64"));
+        assert_eq!(rule("k7/8/P7/8/8/8/8/2B4K w - - 0 1", 400).as_deref(), Some("wrong-coloured bishop scaled 0/64"));
+        // A light-squared bishop controls a8
+        assert_eq!(rule("k7/8/P7/8/8/8/8/3B3K w - - 0 1", 400), None);
+        assert_eq!(rule("4k3/8/3b4/8/3P4/3B4/8/4K3 w - - 0 1", 100).as_deref(), Some("opposite-coloured bishops scaled 16/64"));
+        assert_eq!(rule("4k3/8/8/8/8/8/8/3BK3 w - - 0 1", 300).as_deref(), Some("insufficient advantage scaled 0/64"));
+        assert_eq!(rule("4k3/8/8/8/8/8/8/3NNK2 w - - 0 1", 600).as_deref(), Some("knights only scaled 0/64"));
+    }
+
+    #[test]
+    fn full_board_is_left_alone() {
+        assert_eq!(rule("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1", 30), None);
+        assert_eq!(signature_key("KRKB"), Material::of(&Game::from_fen("4kb2/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap().board).key(Color::White));
+    }
+}
@@ -1,20 +1,41 @@
-use crate::chess::{Board, Position, Piece, PieceType, Color, Game};
+use crate::chess::{Board, Position, Piece, PieceType, Color, Game, SearchBackend, SearchLimits, SearchResult, PvLine, StopSignal, OpeningBook, SyzygyTablebase, TablebaseMove, Wdl, DtmTablebase, Dtm, EvalParams, Skill, WdlModel, DEFAULT_BACKEND};
+#[cfg(feature = "nnue")]
+use crate::chess::{Accumulator, Network};
+use crate::chess::eval::{PAWN_VALUE, KNIGHT_VALUE, BISHOP_VALUE, ROOK_VALUE, QUEEN_VALUE, KING_VALUE};
+use crate::chess::tt::{TranspositionTable, TtEntry, Bound, DEFAULT_HASH_MB};
+use crate::chess::zobrist;
+use crate::chess::see::see;
+use crate::chess::endgame;
 use crate::error::ChessError;
 use std::str::FromStr;
-use std::cmp;
+use std::cmp::Reverse;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
 use serde::{Serialize, Deserialize};
 
-// Point values for each piece type (traditional chess values)
-const PAWN_VALUE: i32 = 100;
-const KNIGHT_VALUE: i32 = 320;
-const BISHOP_VALUE: i32 = 330;
-const ROOK_VALUE: i32 = 500;
-const QUEEN_VALUE: i32 = 900;
-const KING_VALUE: i32 = 20000; // Very high to ensure king safety
-
-// Position evaluation bonus for controlling center, good pawn structure, etc.
-const CENTER_CONTROL_BONUS: i32 = 10;
-const DEVELOPED_PIECE_BONUS: i32 = 15;
+// Score for delivering checkmate; mate in N plies scores MATE_SCORE - N
+pub const MATE_SCORE: i32 = 30000;
+// Scores beyond this bound are mates
+const MATE_BOUND: i32 = MATE_SCORE - 1000;
+// Score of a tablebase win; below mates, which are more certain, and above any evaluation
+pub const TB_WIN_SCORE: i32 = MATE_BOUND - MAX_PLY as i32 - 1;
+// Larger than any score the search can return
+const INFINITY: i32 = 32000;
+// Deepest ply the search will ever reach
+const MAX_PLY: usize = 128;
+// Iterative deepening limit for searches bounded only by time, nodes or a stop signal
+pub const MAX_SEARCH_DEPTH: u8 = 64;
+// Nodes a search thread counts before publishing them and checking whether to
+// stop; well below the 250 node budget of the weakest skill level
+const NODE_BATCH: u64 = 32;
+// Shallowest depth at which a transposition table move is tested for being singular
+const SINGULAR_MIN_DEPTH: u8 = 4;
+// A move is singular when every other move scores this much per ply of depth
+// below its table score
+const SINGULAR_MARGIN: i32 = 8;
 
 // Directions for move generation
 const DIRECTIONS: [(i32, i32); 8] = [
@@ -28,7 +49,19 @@ const KNIGHT_MOVES: [(i32, i32); 8] = [
     (1, 2), (1, -2), (-1, 2), (-1, -2),
 ];
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+// Material value of a piece type in centipawns
+pub fn piece_value(piece_type: PieceType) -> i32 {
+    match piece_type {
+        PieceType::Pawn => PAWN_VALUE,
+        PieceType::Knight => KNIGHT_VALUE,
+        PieceType::Bishop => BISHOP_VALUE,
+        PieceType::Rook => ROOK_VALUE,
+        PieceType::Queen => QUEEN_VALUE,
+        PieceType::King => KING_VALUE,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ChessMove {
     pub from: Position,
     pub to: Position,
@@ -44,8 +77,15 @@ impl ChessMove {
         }
     }
     
-    pub fn to_string(&self) -> String {
-        format!("{}-{}", self.from, self.to)
+    // Long algebraic notation as used by UCI, e.g. "e2e4"
+    pub fn to_uci(&self) -> String {
+        format!("{}{}", self.from, self.to)
+    }
+}
+
+impl fmt::Display for ChessMove {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.from, self.to)
     }
 }
 
@@ -79,13 +119,116 @@ impl FromStr for ChessMove {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Engine {
-    // Search depth for the minimax algorithm
+    // Search depth for the alpha-beta search
     depth: u8,
-    // Number of positions evaluated
-    nodes_searched: u32,
+    // Number of positions evaluated, summed over all search threads
+    nodes_searched: u64,
     // Debug mode
     #[serde(default)]
     debug: bool,
+    // Number of Lazy SMP search threads; 1 searches on the calling thread
+    #[serde(default = "default_threads")]
+    threads: usize,
+    // Transposition table size in megabytes
+    #[serde(default = "default_hash_mb")]
+    hash_mb: usize,
+    // Number of best lines to search exactly (MultiPV)
+    #[serde(default = "default_multipv")]
+    multipv: usize,
+    // Playing strength; below full strength the search is limited and noisy
+    #[serde(default)]
+    skill: Skill,
+    // Weights of the evaluation function
+    #[serde(default)]
+    eval: EvalParams,
+    // Turns scores into win/draw/loss chances
+    #[serde(default)]
+    wdl_model: WdlModel,
+    // Check, singular and recapture extensions; only turned off to measure them
+    #[serde(default = "default_extensions")]
+    extensions: bool,
+    // Varies the evaluation noise of limited skill levels from search to search
+    #[serde(skip)]
+    noise_seed: u64,
+    // Limits for the next search besides depth
+    #[serde(skip)]
+    node_limit: Option<u64>,
+    #[serde(skip)]
+    movetime_ms: Option<u64>,
+    // Raised from another thread to cancel the running search
+    #[serde(skip)]
+    stop_signal: StopSignal,
+    // Statistics of the last search
+    #[serde(skip)]
+    seldepth: u8,
+    #[serde(skip)]
+    elapsed_ms: u64,
+    // Shared between search threads and kept between searches.
+    // Allocated on the first search so engines only used for move generation stay cheap.
+    #[serde(skip)]
+    tt: Option<Arc<TranspositionTable>>,
+    // Consulted by find_best_move before searching
+    #[serde(skip)]
+    book: Option<Arc<OpeningBook>>,
+    // Endgame tablebases for perfect play with few pieces left
+    #[serde(skip)]
+    tablebase: Option<Arc<SyzygyTablebase>>,
+    // Generated distance-to-mate tables, probed before the Syzygy ones
+    #[serde(skip)]
+    dtm_tablebase: Option<Arc<DtmTablebase>>,
+    // Neural network evaluation, used instead of the weights in `eval` when set
+    #[cfg(feature = "nnue")]
+    #[serde(skip)]
+    nnue: Option<Arc<Network>>,
+    #[serde(skip)]
+    tb_hits: u64,
+}
+
+fn default_threads() -> usize {
+    1
+}
+
+fn default_hash_mb() -> usize {
+    DEFAULT_HASH_MB
+}
+
+fn default_multipv() -> usize {
+    1
+}
+
+fn default_extensions() -> bool {
+    true
+}
+
+// State shared by all threads of one search
+struct SearchShared {
+    // Raised by the main thread when it finishes, to stop the helpers
+    done: AtomicBool,
+    stop_signal: StopSignal,
+    // Nodes searched by all threads, updated in batches
+    nodes: AtomicU64,
+    node_limit: Option<u64>,
+    deadline: Option<Instant>,
+}
+
+impl SearchShared {
+    fn should_stop(&self) -> bool {
+        self.done.load(Ordering::Relaxed)
+            || self.stop_signal.is_stopped()
+            || self.node_limit.is_some_and(|limit| self.nodes.load(Ordering::Relaxed) >= limit)
+            || self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+}
+
+// What one search thread found in its deepest completed iteration
+struct ThreadResult {
+    id: usize,
+    depth: u8,
+    seldepth: u8,
+    // Principal variations of the best root moves and their scores, best first
+    lines: Vec<(Vec<ChessMove>, i32)>,
+    nodes: u64,
+    tb_hits: u64,
 }
 
 impl Engine {
@@ -94,6 +237,26 @@ impl Engine {
             depth,
             nodes_searched: 0,
             debug: false, // Turn off debug mode by default
+            threads: default_threads(),
+            hash_mb: default_hash_mb(),
+            multipv: default_multipv(),
+            skill: Skill::full(),
+            eval: EvalParams::default(),
+            wdl_model: WdlModel::default(),
+            extensions: default_extensions(),
+            noise_seed: 0,
+            node_limit: None,
+            movetime_ms: None,
+            stop_signal: StopSignal::new(),
+            seldepth: 0,
+            elapsed_ms: 0,
+            tt: None,
+            book: None,
+            tablebase: None,
+            dtm_tablebase: None,
+            #[cfg(feature = "nnue")]
+            nnue: None,
+            tb_hits: 0,
         }
     }
     
@@ -102,6 +265,62 @@ impl Engine {
         self.debug = debug;
     }
     
+    pub fn set_depth(&mut self, depth: u8) {
+        self.depth = depth.max(1);
+    }
+    
+    // Stop after roughly this many nodes (counted in batches of NODE_BATCH)
+    pub fn set_node_limit(&mut self, nodes: Option<u64>) {
+        self.node_limit = nodes;
+    }
+    
+    // Stop after this much wall-clock time
+    pub fn set_movetime(&mut self, movetime_ms: Option<u64>) {
+        self.movetime_ms = movetime_ms;
+    }
+    
+    // Raising this signal makes the running search return its best move so far
+    pub fn set_stop_signal(&mut self, stop: StopSignal) {
+        self.stop_signal = stop;
+    }
+    
+    pub fn stop_signal(&self) -> StopSignal {
+        self.stop_signal.clone()
+    }
+    
+    // Number of search threads. With a single thread the search is deterministic.
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+    
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+    
+    // Extend forcing lines beyond the nominal depth: checks, recaptures and
+    // transposition table moves much better than the alternatives. Each line
+    // may be extended by at most the nominal depth of the iteration.
+    pub fn set_extensions(&mut self, extensions: bool) {
+        self.extensions = extensions;
+    }
+    
+    pub fn extensions(&self) -> bool {
+        self.extensions
+    }
+    
+    // Resize the transposition table; this also clears it
+    pub fn set_hash_size(&mut self, size_mb: usize) {
+        self.hash_mb = size_mb.max(1);
+        self.tt = None;
+    }
+    
+    // Forget everything learned in previous searches
+    pub fn clear_hash(&mut self) {
+        if let Some(tt) = &self.tt {
+            tt.clear();
+        }
+    }
+    
     // Helper to print debug info
     fn debug_print(&self, msg: &str) {
         if self.debug {
@@ -109,19 +328,110 @@ impl Engine {
         }
     }
     
+    pub fn set_book(&mut self, book: Option<Arc<OpeningBook>>) {
+        self.book = book;
+    }
+    
+    // A move from the opening book for this position, if the engine has a book and it's in it
+    pub fn book_move(&self, game: &Game) -> Option<ChessMove> {
+        self.book.as_ref()?.choose(game).map(|book_move| book_move.chess_move)
+    }
+    
+    // With tablebases the search stops at positions they cover, and in a covered
+    // root position only the moves that keep the best result are searched
+    pub fn set_tablebase(&mut self, tablebase: Option<Arc<SyzygyTablebase>>) {
+        self.tablebase = tablebase;
+    }
+    
+    pub fn tablebase(&self) -> Option<&Arc<SyzygyTablebase>> {
+        self.tablebase.as_ref()
+    }
+    
+    // Distance-to-mate tables give exact mate scores, so in a position they cover
+    // the engine plays the fastest mate or the longest defence
+    pub fn set_dtm_tablebase(&mut self, tablebase: Option<Arc<DtmTablebase>>) {
+        self.dtm_tablebase = tablebase;
+    }
+    
+    pub fn dtm_tablebase(&self) -> Option<&Arc<DtmTablebase>> {
+        self.dtm_tablebase.as_ref()
+    }
+    
+    // Evaluate with a neural network instead of the handcrafted evaluation, or go
+    // back to the handcrafted one with None
+    #[cfg(feature = "nnue")]
+    pub fn set_nnue(&mut self, network: Option<Arc<Network>>) {
+        self.nnue = network;
+    }
+    
+    #[cfg(feature = "nnue")]
+    pub fn nnue(&self) -> Option<&Arc<Network>> {
+        self.nnue.as_ref()
+    }
+    
+    pub fn set_skill(&mut self, skill: Skill) {
+        self.skill = skill;
+    }
+    
+    pub fn skill(&self) -> Skill {
+        self.skill
+    }
+    
+    // Change the evaluation, e.g. to one of the personalities. Entries already
+    // in the transposition table were scored with the old weights.
+    pub fn set_eval_params(&mut self, eval: EvalParams) {
+        self.eval = eval;
+    }
+    
+    pub fn eval_params(&self) -> &EvalParams {
+        &self.eval
+    }
+    
+    // Use a WDL model fitted to this engine's evaluation, e.g. by `tune --wdl`
+    pub fn set_wdl_model(&mut self, model: WdlModel) {
+        self.wdl_model = model;
+    }
+    
+    pub fn wdl_model(&self) -> &WdlModel {
+        &self.wdl_model
+    }
+    
+    // Depth the next search goes to, capped by the skill level
+    fn search_depth(&self) -> u8 {
+        let depth = self.depth.max(1);
+        self.skill.max_depth().map_or(depth, |max| depth.min(max))
+    }
+    
     // Find the best move in the current position
     pub fn find_best_move(&mut self, game: &Game) -> Result<ChessMove, ChessError> {
+        if let Some(book_move) = self.book_move(game) {
+            self.debug_print(&format!("Playing book move {}", book_move));
+            return Ok(book_move);
+        }
+        
+        let lines = self.analyze(game)?;
+        let best = &lines[0];
+        let mut best_move = best.moves[0].clone();
+        best_move.score = best.score;
+        Ok(best_move)
+    }
+    
+    // Search the position and return the best `multipv` lines, best first
+    pub fn analyze(&mut self, game: &Game) -> Result<Vec<PvLine>, ChessError> {
         let current_color = game.current_turn;
         self.debug_print(&format!("Finding best move for {:?}", current_color));
         self.debug_print(&format!("Current board state:\n{}", game.board.debug_print()));
         
-        let mut best_move = None;
-        let mut best_score = i32::MIN;
         self.nodes_searched = 0;
+        self.seldepth = 0;
+        self.elapsed_ms = 0;
+        self.tb_hits = 0;
+        self.noise_seed = rand::random();
+        let start = Instant::now();
         
         // Generate all legal moves
         self.debug_print("About to generate legal moves");
-        let moves = self.generate_moves(&game.board, current_color)?;
+        let mut moves = self.get_legal_moves(&game.board, current_color)?;
         
         if moves.is_empty() {
             self.debug_print(&format!("No legal moves found for {:?}", current_color));
@@ -133,117 +443,193 @@ impl Engine {
             self.debug_print(&format!("  Move: {} -> {}", m.from, m.to));
         }
         
-        // For each move, evaluate the resulting position
-        for mut chess_move in moves {
-            self.debug_print(&format!("Evaluating move: {} -> {}", chess_move.from, chess_move.to));
-            
-            // Create a copy of the board to simulate the move
-            let mut board_copy = game.board.clone();
-            
-            match board_copy.make_move(&chess_move.from, &chess_move.to) {
-                Ok(_) => {
-                    // Evaluate with minimax
-                    let score = -self.minimax(&board_copy, self.depth - 1, i32::MIN + 1, i32::MAX - 1, current_color.opposite());
-                    
-                    // Store the score in the move
-                    chess_move.score = score;
-                    
-                    self.debug_print(&format!("Evaluated move {} -> {} with score: {}", 
-                                             chess_move.from, chess_move.to, score));
-                    
-                    // Update best move if this is better
-                    if score > best_score {
-                        best_score = score;
-                        best_move = Some(chess_move);
-                    }
-                },
-                Err(e) => {
-                    self.debug_print(&format!("Error applying move during evaluation: {:?}", e));
-                    continue;
-                }
-            }
+        // In a DTM tablebase position keep only the moves that mate fastest or
+        // hold out longest, or that keep the draw
+        let dtm_moves = self.dtm_tablebase.as_ref().and_then(|tb| tb.probe_root(game)).unwrap_or_default();
+        if let Some(best) = dtm_moves.first() {
+            self.tb_hits += 1;
+            let dtm = best.dtm;
+            moves.retain(|m| dtm_moves.iter().any(|r| r.chess_move.from == m.from && r.chess_move.to == m.to && r.dtm == dtm));
+            self.debug_print(&format!("DTM tablebase {}, searching {} moves", dtm, moves.len()));
+        }
+        
+        // In a tablebase position keep only the moves with the best result, and of
+        // those the ones that reach a capture or pawn move soonest (DTZ-optimal)
+        let tb_moves = if dtm_moves.is_empty() {
+            self.tablebase.as_ref().and_then(|tb| tb.probe_root(game)).unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+        if let Some(best) = tb_moves.first() {
+            self.tb_hits += 1;
+            let (wdl, dtz) = (best.wdl, best.dtz);
+            moves.retain(|m| tb_moves.iter().any(|r| {
+                r.chess_move.from == m.from && r.chess_move.to == m.to
+                    && r.wdl == wdl && (wdl == Wdl::Draw || r.dtz == dtz)
+            }));
+            self.debug_print(&format!("Tablebase {} with DTZ {}, searching {} moves", wdl, dtz, moves.len()));
         }
         
-        // Return the best move found
-        match best_move {
-            Some(mv) => {
-                self.debug_print(&format!("Found best move: {} -> {} with score {}", 
-                                         mv.from, mv.to, mv.score));
-                Ok(mv)
+        let tt = self.tt.get_or_insert_with(|| Arc::new(TranspositionTable::new(self.hash_mb))).clone();
+        let shared = SearchShared {
+            done: AtomicBool::new(false),
+            stop_signal: self.stop_signal.clone(),
+            nodes: AtomicU64::new(0),
+            node_limit: match (self.node_limit, self.skill.max_nodes()) {
+                (Some(limit), Some(max)) => Some(limit.min(max)),
+                (limit, max) => limit.or(max),
             },
+            deadline: self.movetime_ms.map(|ms| start + Duration::from_millis(ms)),
+        };
+        let engine: &Engine = self;
+        
+        // Lazy SMP: every thread runs its own iterative deepening search and they only
+        // communicate through the transposition table. Helpers stop once the main thread is done.
+        let results = if engine.threads <= 1 {
+            vec![SearchThread::new(0, engine, &tt, &shared).run(&game.board, current_color, &moves)]
+        } else {
+            thread::scope(|scope| {
+                let helpers: Vec<_> = (1..engine.threads)
+                    .map(|id| {
+                        let (tt, shared, moves) = (&tt, &shared, &moves);
+                        scope.spawn(move || {
+                            SearchThread::new(id, engine, tt, shared).run(&game.board, current_color, moves)
+                        })
+                    })
+                    .collect();
+                
+                let main = SearchThread::new(0, engine, &tt, &shared).run(&game.board, current_color, &moves);
+                shared.done.store(true, Ordering::Relaxed);
+                
+                let mut results = vec![main];
+                results.extend(helpers.into_iter().filter_map(|h| h.join().ok()));
+                results
+            })
+        };
+        
+        self.nodes_searched = results.iter().map(|r| r.nodes).sum();
+        self.tb_hits += results.iter().map(|r| r.tb_hits).sum::<u64>();
+        self.seldepth = results.iter().map(|r| r.seldepth).max().unwrap_or(0);
+        self.elapsed_ms = start.elapsed().as_millis() as u64;
+        
+        // Trust the deepest completed iteration, preferring the main thread on ties
+        let best = results.into_iter()
+            .filter(|r| !r.lines.is_empty())
+            .max_by(|a, b| a.depth.cmp(&b.depth).then(b.id.cmp(&a.id)));
+        
+        let best = match best {
+            Some(best) => best,
             None => {
                 self.debug_print("No valid moves found after evaluation");
-                Err(ChessError::InvalidMove("No valid moves found".to_string()))
+                return Err(ChessError::InvalidMove("No valid moves found".to_string()));
             }
+        };
+        
+        // Lines cut short by a transposition table hit are continued from the table
+        let mut lines: Vec<PvLine> = best.lines.into_iter()
+            .map(|(mut moves, score)| {
+                if moves.len() < best.depth as usize {
+                    let mut board = game.board.clone();
+                    let mut color = current_color;
+                    for m in &moves {
+                        let _ = board.make_move(&m.from, &m.to);
+                        color = color.opposite();
+                    }
+                    let remaining = best.depth as usize - moves.len();
+                    moves.extend(self.pv_from_tt(&tt, &board, color, remaining));
+                }
+                // Tablebase results are exact, unlike anything short of a mate score
+                let tb_move = tb_moves.iter().find(|r| r.chess_move.from == moves[0].from && r.chess_move.to == moves[0].to);
+                let dtm_move = dtm_moves.iter().find(|r| r.chess_move.from == moves[0].from && r.chess_move.to == moves[0].to);
+                let score = match (dtm_move, tb_move) {
+                    (Some(dtm_move), _) => dtm_score(dtm_move.dtm, 0),
+                    (None, Some(tb_move)) if score.abs() < MATE_BOUND => tb_root_score(tb_move),
+                    _ => score,
+                };
+                let mut line = PvLine::new(game, moves, score, best.depth);
+                line.wdl = self.wdl_model.chances_on(&game.board, score);
+                line
+            })
+            .collect();
+        
+        // Limited skill levels searched extra lines to choose a weaker move from
+        if self.skill.is_limited() {
+            let pick = self.skill.pick_line(&lines, self.noise_seed);
+            let chosen = lines.remove(pick);
+            lines.insert(0, chosen);
+            lines.truncate(self.multipv.max(1));
+            self.debug_print(&format!("Skill {} picked line {}", self.skill, pick + 1));
         }
-    }
-    
-    // Minimax algorithm with alpha-beta pruning
-    fn minimax(&mut self, board: &Board, depth: u8, mut alpha: i32, mut beta: i32, color: Color) -> i32 {
-        self.nodes_searched += 1;
         
-        // Base case: if we've reached the maximum depth, evaluate the board
-        if depth == 0 {
-            return self.evaluate_board(board, color);
+        for (i, line) in lines.iter().enumerate() {
+            self.debug_print(&format!("Line {}: {} with score {} ({} nodes)", 
+                                     i + 1, line.moves[0], line.score, self.nodes_searched));
         }
         
-        // Generate legal moves for the current player
-        let moves = match self.generate_moves(board, color) {
-            Ok(m) => m,
-            Err(e) => {
-                self.debug_print(&format!("Error generating moves in minimax: {:?}", e));
-                return self.evaluate_board(board, color); // If no moves, evaluate current position
-            }
-        };
+        Ok(lines)
+    }
+    
+    // Deepest ply reached by the last search
+    pub fn seldepth(&self) -> u8 {
+        self.seldepth
+    }
+    
+    // Wall-clock duration of the last search
+    pub fn elapsed_ms(&self) -> u64 {
+        self.elapsed_ms
+    }
+    
+    // Nodes per second of the last search
+    pub fn nps(&self) -> u64 {
+        self.nodes_searched * 1000 / self.elapsed_ms.max(1)
+    }
+    
+    // Tablebase probes that gave a result during the last search
+    pub fn tb_hits(&self) -> u64 {
+        self.tb_hits
+    }
+    
+    // Number of lines `analyze` returns
+    pub fn set_multipv(&mut self, multipv: usize) {
+        self.multipv = multipv.max(1);
+    }
+    
+    pub fn multipv(&self) -> usize {
+        self.multipv
+    }
+    
+    // Walk best moves stored in the transposition table, starting from the given position
+    fn pv_from_tt(&self, tt: &TranspositionTable, board: &Board, color: Color, max_len: usize) -> Vec<ChessMove> {
+        let mut pv = Vec::new();
+        let mut board = board.clone();
+        let mut color = color;
+        let mut seen = Vec::new();
         
-        // If there are no legal moves, it's either checkmate or stalemate
-        if moves.is_empty() {
-            // This is a simplified evaluation - ideally check for checkmate vs stalemate
-            return self.evaluate_board(board, color);
-        }
-        
-        // Maximize or minimize based on the current player
-        if color == Color::White {
-            let mut max_score = i32::MIN;
-            for chess_move in moves {
-                // Create a copy of the board to simulate the move
-                let mut board_copy = board.clone();
-                if board_copy.make_move(&chess_move.from, &chess_move.to).is_err() {
-                    continue;
-                }
-                
-                // Recursively evaluate the position
-                let score = self.minimax(&board_copy, depth - 1, alpha, beta, Color::Black);
-                max_score = cmp::max(max_score, score);
-                alpha = cmp::max(alpha, score);
-                
-                // Alpha-beta pruning
-                if beta <= alpha {
-                    break;
-                }
+        while pv.len() < max_len {
+            let key = zobrist::hash(&board, color);
+            // Stop at repetitions, which would otherwise loop forever
+            if seen.contains(&key) {
+                break;
             }
-            max_score
-        } else {
-            let mut min_score = i32::MAX;
-            for chess_move in moves {
-                // Create a copy of the board to simulate the move
-                let mut board_copy = board.clone();
-                if board_copy.make_move(&chess_move.from, &chess_move.to).is_err() {
-                    continue;
-                }
-                
-                // Recursively evaluate the position
-                let score = self.minimax(&board_copy, depth - 1, alpha, beta, Color::White);
-                min_score = cmp::min(min_score, score);
-                beta = cmp::min(beta, score);
-                
-                // Alpha-beta pruning
-                if beta <= alpha {
-                    break;
-                }
+            seen.push(key);
+            
+            let tt_move = match tt.probe(key).and_then(|e| e.best_move) {
+                Some(m) => m,
+                None => break,
+            };
+            // Guard against hash collisions handing us a move from another position
+            let legal = self.get_legal_moves(&board, color)
+                .map(|moves| moves.iter().any(|m| m.from == tt_move.from && m.to == tt_move.to))
+                .unwrap_or(false);
+            if !legal || board.make_move(&tt_move.from, &tt_move.to).is_err() {
+                break;
             }
-            min_score
+            
+            pv.push(tt_move);
+            color = color.opposite();
         }
+        
+        pv
     }
     
     // Generate all legal moves for a given position and player
@@ -323,7 +709,7 @@ impl Engine {
         let new_rank = (from.rank as i32) + direction;
         
         // Check if new rank is within bounds
-        if new_rank >= 0 && new_rank < 8 {
+        if (0..8).contains(&new_rank) {
             let to_rank = new_rank as u8;
             let to = Position::new(from.file, to_rank);
             
@@ -341,7 +727,7 @@ impl Engine {
                     let double_new_rank = (from.rank as i32) + 2 * direction;
                     
                     // Make sure double move rank is valid
-                    if double_new_rank >= 0 && double_new_rank < 8 {
+                    if (0..8).contains(&double_new_rank) {
                         let double_to_rank = double_new_rank as u8;
                         let double_to = Position::new(from.file, double_to_rank);
                         
@@ -357,13 +743,13 @@ impl Engine {
         }
         
         // Captures
-        if new_rank >= 0 && new_rank < 8 {
+        if (0..8).contains(&new_rank) {
             let to_rank = new_rank as u8;
             
             for file_offset in [-1, 1].iter() {
                 let new_file = (from.file as i32) + file_offset;
                 
-                if new_file >= 0 && new_file < 8 {
+                if (0..8).contains(&new_file) {
                     let to_file = new_file as u8;
                     let to = Position::new(to_file, to_rank);
                     
@@ -389,7 +775,7 @@ impl Engine {
             let to_file = from.file as i32 + df;
             
             // Check if the move is within the board
-            if to_rank >= 0 && to_rank < 8 && to_file >= 0 && to_file < 8 {
+            if (0..8).contains(&to_rank) && (0..8).contains(&to_file) {
                 let to = Position::new(to_file as u8, to_rank as u8);
                 
                 // Check if the destination is empty or has an enemy piece
@@ -444,7 +830,7 @@ impl Engine {
             let to_file = from.file as i32 + df;
             
             // Check if the move is within the board
-            if to_rank >= 0 && to_rank < 8 && to_file >= 0 && to_file < 8 {
+            if (0..8).contains(&to_rank) && (0..8).contains(&to_file) {
                 let to = Position::new(to_file as u8, to_rank as u8);
                 
                 // Check if the destination is empty or has an enemy piece
@@ -468,7 +854,7 @@ impl Engine {
         let mut to_file = from.file as i32 + df;
         
         // Continue in the given direction until we hit the edge of the board or another piece
-        while to_rank >= 0 && to_rank < 8 && to_file >= 0 && to_file < 8 {
+        while (0..8).contains(&to_rank) && (0..8).contains(&to_file) {
             let to = Position::new(to_file as u8, to_rank as u8);
             
             // Check if the destination has a piece
@@ -492,63 +878,15 @@ impl Engine {
     
     // Evaluate the current board position
     fn evaluate_board(&self, board: &Board, color: Color) -> i32 {
-        let mut score = 0;
-        
-        // Loop through all squares on the board
-        for rank in 0..8 {
-            for file in 0..8 {
-                let pos = Position::new(file, rank);
-                
-                // Check if there's a piece at this position
-                if let Some(piece) = board.get_piece(&pos) {
-                    // Calculate the material value
-                    let piece_value = match piece.piece_type {
-                        PieceType::Pawn => PAWN_VALUE,
-                        PieceType::Knight => KNIGHT_VALUE,
-                        PieceType::Bishop => BISHOP_VALUE,
-                        PieceType::Rook => ROOK_VALUE,
-                        PieceType::Queen => QUEEN_VALUE,
-                        PieceType::King => KING_VALUE,
-                    };
-                    
-                    // Add value for the player's pieces, subtract for opponent's pieces
-                    if piece.color == color {
-                        score += piece_value;
-                        
-                        // Bonus for controlling the center (e4, d4, e5, d5)
-                        if (file == 3 || file == 4) && (rank == 3 || rank == 4) {
-                            score += CENTER_CONTROL_BONUS;
-                        }
-                        
-                        // Development bonus for minor pieces
-                        if (piece.piece_type == PieceType::Knight || piece.piece_type == PieceType::Bishop) &&
-                           ((color == Color::White && rank > 0) || (color == Color::Black && rank < 7)) {
-                            score += DEVELOPED_PIECE_BONUS;
-                        }
-                    } else {
-                        score -= piece_value;
-                        
-                        // Bonus for opponent controlling the center
-                        if (file == 3 || file == 4) && (rank == 3 || rank == 4) {
-                            score -= CENTER_CONTROL_BONUS;
-                        }
-                        
-                        // Development bonus for opponent's minor pieces
-                        if (piece.piece_type == PieceType::Knight || piece.piece_type == PieceType::Bishop) &&
-                           ((color == Color::Black && rank > 0) || (color == Color::White && rank < 7)) {
-                            score -= DEVELOPED_PIECE_BONUS;
-                        }
-                    }
-                }
-            }
+        #[cfg(feature = "nnue")]
+        if let Some(network) = &self.nnue {
+            return endgame::adjust(board, color, network.evaluate_board(board, color));
         }
-        
-        score
+        endgame::adjust(board, color, self.eval.evaluate(board, color))
     }
     
     // Convert a move to standard algebraic notation (SAN)
     pub fn to_algebraic_notation(&self, chess_move: &ChessMove, board: &Board) -> String {
-        // This is a simplified version - a full SAN implementation would be more complex
         let piece = match board.get_piece(&chess_move.from) {
             Some(p) => p,
             None => return String::from("???"),
@@ -562,17 +900,867 @@ impl Engine {
             PieceType::Queen => "Q",
             PieceType::King => "K",
         };
+        let is_capture = board.get_piece(&chess_move.to).is_some();
+        let legal_moves = self.get_legal_moves(board, piece.color).unwrap_or_default();
+        
+        let mut san = String::from(piece_letter);
+        if piece.piece_type == PieceType::Pawn {
+            // Pawn captures name the file the pawn came from
+            if is_capture {
+                san.push(chess_move.from.to_algebraic().0);
+            }
+        } else {
+            // Disambiguate when another piece of the same kind can reach the same square
+            let rivals: Vec<&ChessMove> = legal_moves.iter()
+                .filter(|m| m.to == chess_move.to && m.from != chess_move.from)
+                .filter(|m| board.get_piece(&m.from).map(|p| p.piece_type) == Some(piece.piece_type))
+                .collect();
+            if !rivals.is_empty() {
+                let (file, rank) = chess_move.from.to_algebraic();
+                if rivals.iter().all(|m| m.from.file != chess_move.from.file) {
+                    san.push(file);
+                } else if rivals.iter().all(|m| m.from.rank != chess_move.from.rank) {
+                    san.push(rank);
+                } else {
+                    san.push(file);
+                    san.push(rank);
+                }
+            }
+        }
+        if is_capture {
+            san.push('x');
+        }
+        san.push_str(&chess_move.to.to_string());
         
-        format!("{}{}", piece_letter, chess_move.to)
+        // Check and checkmate markers
+        let mut board_after = board.clone();
+        if board_after.make_move(&chess_move.from, &chess_move.to).is_ok() {
+            let opponent = piece.color.opposite();
+            if is_in_check(&board_after, opponent) {
+                let has_reply = self.get_legal_moves(&board_after, opponent)
+                    .map(|m| !m.is_empty())
+                    .unwrap_or(false);
+                san.push(if has_reply { '+' } else { '#' });
+            }
+        }
+        
+        san
     }
     
+    // Convert a sequence of moves starting from the given position to SAN
+    pub fn line_to_algebraic_notation(&self, moves: &[ChessMove], board: &Board) -> Vec<String> {
+        let mut board = board.clone();
+        let mut san = Vec::with_capacity(moves.len());
+        for chess_move in moves {
+            san.push(self.to_algebraic_notation(chess_move, &board));
+            if board.make_move(&chess_move.from, &chess_move.to).is_err() {
+                break;
+            }
+        }
+        san
+    }
+    
+    // Find the legal move a SAN string such as "Nbd7", "exd5" or "Qh5+" stands for.
+    // Castling, promotion and en passant can't be played on this board and are rejected.
+    pub fn parse_algebraic_notation(&self, san: &str, board: &Board, color: Color) -> Result<ChessMove, ChessError> {
+        let text = san.trim_end_matches(['+', '#', '!', '?']);
+        if text.starts_with("O-O") || text.starts_with("0-0") {
+            return Err(ChessError::InvalidMove(format!("Castling is not supported: {}", san)));
+        }
+        if text.contains('=') {
+            return Err(ChessError::InvalidMove(format!("Promotion is not supported: {}", san)));
+        }
+
+        let (piece_type, rest) = match text.chars().next() {
+            Some('N') => (PieceType::Knight, &text[1..]),
+            Some('B') => (PieceType::Bishop, &text[1..]),
+            Some('R') => (PieceType::Rook, &text[1..]),
+            Some('Q') => (PieceType::Queen, &text[1..]),
+            Some('K') => (PieceType::King, &text[1..]),
+            _ => (PieceType::Pawn, text),
+        };
+        let rest: Vec<char> = rest.chars().filter(|&c| c != 'x').collect();
+        if rest.len() < 2 || rest.len() > 4 {
+            return Err(ChessError::InvalidMove(format!("Invalid SAN move: {}", san)));
+        }
+        let to = Position::from_algebraic(rest[rest.len() - 2], rest[rest.len() - 1])
+            .map_err(|_| ChessError::InvalidMove(format!("Invalid SAN move: {}", san)))?;
+
+        // Whatever comes before the destination narrows down the moving piece
+        let mut from_file = None;
+        let mut from_rank = None;
+        for &c in &rest[..rest.len() - 2] {
+            match c {
+                'a'..='h' => from_file = Some(c as u8 - b'a'),
+                '1'..='8' => from_rank = Some(c as u8 - b'1'),
+                _ => return Err(ChessError::InvalidMove(format!("Invalid SAN move: {}", san))),
+            }
+        }
+
+        let candidates: Vec<ChessMove> = self.get_legal_moves(board, color)?.into_iter()
+            .filter(|m| m.to == to)
+            .filter(|m| board.get_piece(&m.from).map(|p| p.piece_type) == Some(piece_type))
+            .filter(|m| from_file.is_none_or(|file| m.from.file == file))
+            .filter(|m| from_rank.is_none_or(|rank| m.from.rank == rank))
+            .collect();
+        match candidates.len() {
+            1 => Ok(candidates.into_iter().next().unwrap()),
+            0 => Err(ChessError::InvalidMove(format!("No legal move matches {}", san))),
+            _ => Err(ChessError::InvalidMove(format!("Ambiguous move: {}", san))),
+        }
+    }
+
     // Get statistics about the search
-    pub fn get_stats(&self) -> (u32, u8) {
+    pub fn get_stats(&self) -> (u64, u8) {
         (self.nodes_searched, self.depth)
     }
     
-    // Public method for getting legal moves - to be used by Game
+    // Moves that don't leave the mover's own king in check
     pub fn get_legal_moves(&self, board: &Board, color: Color) -> Result<Vec<ChessMove>, ChessError> {
-        self.generate_moves(board, color)
+        let mut moves = self.generate_moves(board, color)?;
+        moves.retain(|m| {
+            let mut board_copy = board.clone();
+            board_copy.make_move(&m.from, &m.to).is_ok() && !is_in_check(&board_copy, color)
+        });
+        Ok(moves)
+    }
+}
+
+// Whether any piece of the given color attacks a square
+pub fn is_square_attacked(board: &Board, pos: &Position, by: Color) -> bool {
+    let attacker_at = |file: i32, rank: i32, types: &[PieceType]| -> bool {
+        if !(0..8).contains(&file) || !(0..8).contains(&rank) {
+            return false;
+        }
+        match board.get_piece(&Position::new(file as u8, rank as u8)) {
+            Some(p) => p.color == by && types.contains(&p.piece_type),
+            None => false,
+        }
+    };
+    let (file, rank) = (pos.file as i32, pos.rank as i32);
+    
+    // Pawns attack diagonally forward, so look one rank behind the square from their side
+    let pawn_rank = if by == Color::White { rank - 1 } else { rank + 1 };
+    if attacker_at(file - 1, pawn_rank, &[PieceType::Pawn]) || attacker_at(file + 1, pawn_rank, &[PieceType::Pawn]) {
+        return true;
+    }
+    
+    if KNIGHT_MOVES.iter().any(|&(dr, df)| attacker_at(file + df, rank + dr, &[PieceType::Knight])) {
+        return true;
+    }
+    
+    if DIRECTIONS.iter().any(|&(dr, df)| attacker_at(file + df, rank + dr, &[PieceType::King])) {
+        return true;
+    }
+    
+    // Sliding pieces: walk each ray to the first piece
+    for (i, &(dr, df)) in DIRECTIONS.iter().enumerate() {
+        let sliders: &[PieceType] = if i < 4 {
+            &[PieceType::Rook, PieceType::Queen]
+        } else {
+            &[PieceType::Bishop, PieceType::Queen]
+        };
+        let (mut f, mut r) = (file + df, rank + dr);
+        while (0..8).contains(&f) && (0..8).contains(&r) {
+            if board.get_piece(&Position::new(f as u8, r as u8)).is_some() {
+                if attacker_at(f, r, sliders) {
+                    return true;
+                }
+                break;
+            }
+            f += df;
+            r += dr;
+        }
+    }
+    
+    false
+}
+
+// Find the king of the given color
+pub fn find_king(board: &Board, color: Color) -> Option<Position> {
+    (0..64u8)
+        .map(|i| Position::new(i % 8, i / 8))
+        .find(|pos| board.get_piece(pos) == Some(Piece::new(PieceType::King, color)))
+}
+
+// Whether the king of the given color is attacked.
+// Positions without that king (e.g. test setups) are never in check.
+pub fn is_in_check(board: &Board, color: Color) -> bool {
+    match find_king(board, color) {
+        Some(king) => is_square_attacked(board, &king, color.opposite()),
+        None => false,
+    }
+}
+
+// Score of a tablebase result `ply` plies from the root; wins found sooner score higher.
+// Cursed wins and blessed losses are draws under the fifty-move rule, just about.
+fn tb_score(wdl: Wdl, ply: u8) -> i32 {
+    match wdl {
+        Wdl::Win => TB_WIN_SCORE - ply as i32,
+        Wdl::CursedWin => 1,
+        Wdl::Draw => 0,
+        Wdl::BlessedLoss => -1,
+        Wdl::Loss => -TB_WIN_SCORE + ply as i32,
+    }
+}
+
+// Score of a distance-to-mate result `ply` plies from the root: a mate score like the search's own
+fn dtm_score(dtm: Dtm, ply: u8) -> i32 {
+    match dtm {
+        Dtm::Win(plies) => MATE_SCORE - ply as i32 - plies as i32,
+        Dtm::Loss(plies) => -MATE_SCORE + ply as i32 + plies as i32,
+        Dtm::Draw => 0,
     }
-}
\ No newline at end of file
+}
+
+// Score of a root move from its tablebase result and distance to zeroing
+fn tb_root_score(tb_move: &TablebaseMove) -> i32 {
+    match tb_move.wdl {
+        Wdl::Win | Wdl::Loss => tb_score(tb_move.wdl, 0) - tb_move.dtz,
+        wdl => tb_score(wdl, 0),
+    }
+}
+
+// Convert a mate score to "mate in N plies from this node" for the transposition table
+fn score_to_tt(score: i32, ply: u8) -> i32 {
+    if score >= MATE_BOUND {
+        score + ply as i32
+    } else if score <= -MATE_BOUND {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+// Inverse of score_to_tt
+fn score_from_tt(score: i32, ply: u8) -> i32 {
+    if score >= MATE_BOUND {
+        score - ply as i32
+    } else if score <= -MATE_BOUND {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+// One Lazy SMP search thread. The engine is only borrowed for move generation and evaluation.
+struct SearchThread<'a> {
+    id: usize,
+    engine: &'a Engine,
+    tt: &'a TranspositionTable,
+    shared: &'a SearchShared,
+    nodes: u64,
+    // Tablebase probes that ended a branch or gave a bound
+    tb_hits: u64,
+    // Deepest ply reached
+    seldepth: u8,
+    // Triangular principal variation table: pv[ply] is the best line found from that ply
+    pv: Vec<Vec<ChessMove>>,
+    // Plies any one line may be extended by in the current iteration
+    extension_budget: u8,
+    // Move left out of the search at each ply while testing whether it is singular
+    excluded: Vec<Option<ChessMove>>,
+    // Square and value of the capture that led to each ply, for recapture extensions
+    captures: Vec<Option<(Position, i32)>>,
+    // Plies the line to each ply has been extended by
+    extended: Vec<u8>,
+    // Stop requests are only honoured once the first root move has a score
+    stoppable: bool,
+    // Set once a stop has been requested; the current iteration is then abandoned
+    stopped: bool,
+    // Network accumulators of the position at each ply, when evaluating with a network
+    #[cfg(feature = "nnue")]
+    accumulators: Vec<Accumulator>,
+}
+
+impl<'a> SearchThread<'a> {
+    fn new(id: usize, engine: &'a Engine, tt: &'a TranspositionTable, shared: &'a SearchShared) -> Self {
+        Self {
+            id,
+            engine,
+            tt,
+            shared,
+            nodes: 0,
+            tb_hits: 0,
+            seldepth: 0,
+            pv: vec![Vec::new(); MAX_PLY + 1],
+            extension_budget: 0,
+            excluded: vec![None; MAX_PLY + 1],
+            captures: vec![None; MAX_PLY + 1],
+            extended: vec![0; MAX_PLY + 1],
+            stoppable: false,
+            stopped: false,
+            #[cfg(feature = "nnue")]
+            accumulators: vec![Accumulator::default(); MAX_PLY + 1],
+        }
+    }
+    
+    // Record `chess_move` followed by the child's line as the best line at this ply
+    fn update_pv(&mut self, ply: usize, chess_move: &ChessMove) {
+        let (head, tail) = self.pv.split_at_mut(ply + 1);
+        let line = &mut head[ply];
+        line.clear();
+        line.push(chess_move.clone());
+        line.extend_from_slice(&tail[0]);
+    }
+    
+    // Bring the accumulators of the child position at `ply + 1` up to date
+    #[cfg(feature = "nnue")]
+    fn update_accumulator(&mut self, board: &Board, chess_move: &ChessMove, ply: u8) {
+        if let Some(network) = &self.engine.nnue {
+            let (head, tail) = self.accumulators.split_at_mut(ply as usize + 1);
+            network.update(&head[ply as usize], &mut tail[0], board, chess_move);
+        }
+    }
+    
+    // Static evaluation at a leaf; a network evaluates from the accumulators
+    #[cfg_attr(not(feature = "nnue"), allow(unused_variables))]
+    fn evaluate(&self, board: &Board, color: Color, ply: u8) -> i32 {
+        #[cfg(feature = "nnue")]
+        if let Some(network) = &self.engine.nnue {
+            return endgame::adjust(board, color, network.evaluate(&self.accumulators[ply as usize], color));
+        }
+        self.engine.evaluate_board(board, color)
+    }
+    
+    // Iterative deepening up to the engine's depth
+    fn run(mut self, board: &Board, color: Color, root_moves: &[ChessMove]) -> ThreadResult {
+        let mut moves = root_moves.to_vec();
+        let mut result = ThreadResult {
+            id: self.id,
+            depth: 0,
+            seldepth: 0,
+            lines: Vec::new(),
+            nodes: 0,
+            tb_hits: 0,
+        };
+        #[cfg(feature = "nnue")]
+        if let Some(network) = &self.engine.nnue {
+            self.accumulators[0] = network.refresh(board);
+        }
+        
+        for depth in 1..=self.engine.search_depth() {
+            if self.stoppable && self.shared.should_stop() {
+                break;
+            }
+            match self.search_root(board, color, &mut moves, depth) {
+                Some(lines) => {
+                    result.depth = depth;
+                    result.lines = lines;
+                },
+                None => break,
+            }
+        }
+        
+        result.nodes = self.nodes;
+        result.tb_hits = self.tb_hits;
+        result.seldepth = self.seldepth;
+        result
+    }
+    
+    // Search all root moves to the given depth and return the best `multipv` of them.
+    // Returns None if the search was stopped.
+    fn search_root(&mut self, board: &Board, color: Color, moves: &mut [ChessMove], depth: u8) -> Option<Vec<(Vec<ChessMove>, i32)>> {
+        let multipv = self.engine.multipv.max(self.engine.skill.multipv());
+        let mut best: Vec<(Vec<ChessMove>, i32)> = Vec::new();
+        // A line may be extended to at most twice the nominal depth
+        self.extension_budget = depth;
+        
+        for chess_move in moves.iter_mut() {
+            let mut board_copy = board.clone();
+            if board_copy.make_move(&chess_move.from, &chess_move.to).is_err() {
+                continue;
+            }
+            #[cfg(feature = "nnue")]
+            self.update_accumulator(board, chess_move, 0);
+            
+            // Only scores that could make it into the top lines need to be exact
+            let alpha = if best.len() >= multipv { best[multipv - 1].1 } else { -INFINITY };
+            self.captures[1] = board.get_piece(&chess_move.to).map(|p| (chess_move.to, piece_value(p.piece_type)));
+            let extension = self.extension(board, &board_copy, chess_move, 0, color, false);
+            self.extended[1] = extension;
+            let score = -self.alpha_beta(&board_copy, depth - 1 + extension, 1, -INFINITY, -alpha, color.opposite());
+            if self.stopped {
+                // Without a completed iteration the moves searched so far are all
+                // there is, which keeps small node budgets from being overrun
+                if depth == 1 && !best.is_empty() {
+                    return Some(best);
+                }
+                return None;
+            }
+            
+            chess_move.score = score;
+            if score > alpha {
+                self.update_pv(0, chess_move);
+                let index = best.iter().position(|(_, s)| score > *s).unwrap_or(best.len());
+                best.insert(index, (self.pv[0].clone(), score));
+                best.truncate(multipv);
+            }
+            // There is a move to play now, so the search may stop at any time
+            self.stoppable = true;
+        }
+        
+        // Search the best moves first in the next iteration
+        moves.sort_by_key(|m| Reverse(m.score));
+        
+        Some(best)
+    }
+    
+    // Negamax alpha-beta search; scores are from the point of view of `color`
+    fn alpha_beta(&mut self, board: &Board, depth: u8, ply: u8, mut alpha: i32, mut beta: i32, color: Color) -> i32 {
+        self.nodes += 1;
+        
+        // Publish node counts and check for a stop request every so often
+        if self.nodes.is_multiple_of(NODE_BATCH) {
+            self.shared.nodes.fetch_add(NODE_BATCH, Ordering::Relaxed);
+            if self.stoppable && self.shared.should_stop() {
+                self.stopped = true;
+            }
+        }
+        if self.stopped {
+            return 0;
+        }
+        
+        self.seldepth = self.seldepth.max(ply);
+        self.pv[ply as usize].clear();
+        
+        // Base case: at the horizon, resolve captures before evaluating
+        if depth == 0 || ply as usize >= MAX_PLY {
+            return self.quiescence(board, ply, alpha, beta, color);
+        }
+        
+        // Mate distance pruning: nothing here can beat mating on the next move or
+        // lose faster than being mated now, so a window beyond that is already settled
+        alpha = alpha.max(-MATE_SCORE + ply as i32);
+        beta = beta.min(MATE_SCORE - ply as i32 - 1);
+        if alpha >= beta {
+            return alpha;
+        }
+        
+        let key = zobrist::hash(board, color);
+        let original_alpha = alpha;
+        let excluded = self.excluded[ply as usize].clone();
+        let mut tt_move = None;
+        let mut tt_hit = None;
+        
+        if let Some(entry) = self.tt.probe(key) {
+            // The entry is about all moves, so it says nothing once one is left out
+            if entry.depth >= depth && excluded.is_none() {
+                let score = score_from_tt(entry.score, ply);
+                match entry.bound {
+                    Bound::Exact => return score,
+                    Bound::Lower if score >= beta => return score,
+                    Bound::Upper if score <= alpha => return score,
+                    _ => {}
+                }
+            }
+            tt_hit = Some((score_from_tt(entry.score, ply), entry.depth, entry.bound));
+            tt_move = entry.best_move;
+        }
+        
+        // Distance-to-mate results are exact, so they always end the search
+        if let Some(dtm) = self.engine.dtm_tablebase.as_ref().filter(|_| excluded.is_none()).and_then(|tb| tb.probe_board(board, color)) {
+            self.tb_hits += 1;
+            return dtm_score(dtm, ply);
+        }
+        
+        // A tablebase result ends the search here unless the window asks for more
+        // than it tells, e.g. a win when only a faster win would do
+        if let Some(wdl) = self.engine.tablebase.as_ref().filter(|_| excluded.is_none()).and_then(|tb| tb.probe_wdl_board(board, color)) {
+            self.tb_hits += 1;
+            let score = tb_score(wdl, ply);
+            let bound = match wdl {
+                Wdl::Win => Bound::Lower,
+                Wdl::Loss => Bound::Upper,
+                _ => Bound::Exact,
+            };
+            if bound == Bound::Exact || (bound == Bound::Lower && score >= beta) || (bound == Bound::Upper && score <= alpha) {
+                self.tt.store(key, &TtEntry {
+                    best_move: None,
+                    score: score_to_tt(score, ply),
+                    depth,
+                    bound,
+                });
+                return score;
+            }
+        }
+        
+        // Generate moves for the current player
+        let mut moves = match self.engine.generate_moves(board, color) {
+            Ok(m) => m,
+            Err(_) => return self.engine.evaluate_board(board, color),
+        };
+        self.order_moves(board, &mut moves, tt_move.as_ref());
+        
+        // Singular extension: when the table move failed high and every other move
+        // falls well short of its score in a shallower search, it is the only move
+        // and is searched a ply deeper
+        let singular = match (&tt_move, tt_hit) {
+            (Some(tt_move), Some((tt_score, tt_depth, bound)))
+                if self.engine.extensions && excluded.is_none() && self.extended[ply as usize] < self.extension_budget
+                    && depth >= SINGULAR_MIN_DEPTH && tt_depth + 3 >= depth
+                    && bound != Bound::Upper && tt_score.abs() < MATE_BOUND =>
+            {
+                let singular_beta = tt_score - SINGULAR_MARGIN * depth as i32;
+                self.excluded[ply as usize] = Some(tt_move.clone());
+                let score = self.alpha_beta(board, (depth - 1) / 2, ply, singular_beta - 1, singular_beta, color);
+                self.excluded[ply as usize] = None;
+                self.pv[ply as usize].clear();
+                if self.stopped {
+                    return 0;
+                }
+                score < singular_beta
+            },
+            _ => false,
+        };
+        
+        let mut best_score = -INFINITY;
+        let mut best_move = None;
+        let mut legal_moves = 0;
+        
+        for chess_move in moves {
+            let same_move = |m: &ChessMove| m.from == chess_move.from && m.to == chess_move.to;
+            if excluded.as_ref().is_some_and(same_move) {
+                continue;
+            }
+            // Create a copy of the board to simulate the move
+            let mut board_copy = board.clone();
+            if board_copy.make_move(&chess_move.from, &chess_move.to).is_err() {
+                continue;
+            }
+            // Skip moves that leave our own king in check
+            if is_in_check(&board_copy, color) {
+                continue;
+            }
+            legal_moves += 1;
+            #[cfg(feature = "nnue")]
+            self.update_accumulator(board, &chess_move, ply);
+            
+            let is_singular = singular && tt_move.as_ref().is_some_and(same_move);
+            self.captures[ply as usize + 1] = board.get_piece(&chess_move.to).map(|p| (chess_move.to, piece_value(p.piece_type)));
+            let extension = self.extension(board, &board_copy, &chess_move, ply, color, is_singular);
+            self.extended[ply as usize + 1] = self.extended[ply as usize] + extension;
+            let score = -self.alpha_beta(&board_copy, depth - 1 + extension, ply + 1, -beta, -alpha, color.opposite());
+            if self.stopped {
+                return 0;
+            }
+            
+            if score > best_score {
+                best_score = score;
+                if score > alpha {
+                    alpha = score;
+                    self.update_pv(ply as usize, &chess_move);
+                }
+                best_move = Some(chess_move);
+                
+                // Alpha-beta pruning
+                if alpha >= beta {
+                    break;
+                }
+            }
+        }
+        
+        // No legal moves: checkmate or stalemate. With the table move left out,
+        // having no other move makes it singular.
+        if legal_moves == 0 {
+            if excluded.is_some() {
+                return alpha;
+            }
+            return if is_in_check(board, color) { -MATE_SCORE + ply as i32 } else { 0 };
+        }
+        
+        let bound = if best_score >= beta {
+            Bound::Lower
+        } else if best_score > original_alpha {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        if excluded.is_none() {
+            self.tt.store(key, &TtEntry {
+                best_move,
+                score: score_to_tt(best_score, ply),
+                depth,
+                bound,
+            });
+        }
+        
+        best_score
+    }
+    
+    // Plies to extend `chess_move` from `board` by, given the position `after`
+    // it: one for the singular table move, a check that doesn't lose material
+    // or a recapture that completes an even trade, while the line has budget left
+    fn extension(&self, board: &Board, after: &Board, chess_move: &ChessMove, ply: u8, color: Color, singular: bool) -> u8 {
+        if !self.engine.extensions || self.extended[ply as usize] >= self.extension_budget {
+            return 0;
+        }
+        let gives_check = is_in_check(after, color.opposite()) && see(board, chess_move) >= 0;
+        let recapture = match (self.captures[ply as usize], self.captures[ply as usize + 1]) {
+            (Some((square, taken)), Some((again, retaken))) => square == again && (taken - retaken).abs() < PAWN_VALUE,
+            _ => false,
+        };
+        u8::from(singular || gives_check || recapture)
+    }
+    
+    // Search captures only until the position is quiet, so that the evaluation
+    // never sees a piece hanging halfway through an exchange. The side to move
+    // may stand pat on the static evaluation, and captures that lose material by
+    // static exchange are not tried at all.
+    fn quiescence(&mut self, board: &Board, ply: u8, mut alpha: i32, beta: i32, color: Color) -> i32 {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(NODE_BATCH) {
+            self.shared.nodes.fetch_add(NODE_BATCH, Ordering::Relaxed);
+            if self.stoppable && self.shared.should_stop() {
+                self.stopped = true;
+            }
+        }
+        if self.stopped {
+            return 0;
+        }
+        self.seldepth = self.seldepth.max(ply);
+        
+        let noise = if self.engine.skill.is_limited() {
+            self.engine.skill.noise(zobrist::hash(board, color), self.engine.noise_seed)
+        } else {
+            0
+        };
+        let stand_pat = self.evaluate(board, color, ply) + noise;
+        if stand_pat >= beta || ply as usize >= MAX_PLY {
+            return stand_pat;
+        }
+        alpha = alpha.max(stand_pat);
+        
+        let mut captures = match self.engine.generate_moves(board, color) {
+            Ok(moves) => moves,
+            Err(_) => return stand_pat,
+        };
+        captures.retain(|m| board.get_piece(&m.to).is_some() && see(board, m) >= 0);
+        self.order_moves(board, &mut captures, None);
+        
+        let mut best_score = stand_pat;
+        for chess_move in captures {
+            let mut board_copy = board.clone();
+            if board_copy.make_move(&chess_move.from, &chess_move.to).is_err() || is_in_check(&board_copy, color) {
+                continue;
+            }
+            #[cfg(feature = "nnue")]
+            self.update_accumulator(board, &chess_move, ply);
+            
+            let score = -self.quiescence(&board_copy, ply + 1, -beta, -alpha, color.opposite());
+            if self.stopped {
+                return 0;
+            }
+            if score > best_score {
+                best_score = score;
+                alpha = alpha.max(score);
+                if alpha >= beta {
+                    break;
+                }
+            }
+        }
+        best_score
+    }
+    
+    // Transposition table move first, then captures (most valuable victim, least
+    // valuable attacker), with captures that lose material by static exchange last
+    fn order_moves(&self, board: &Board, moves: &mut [ChessMove], tt_move: Option<&ChessMove>) {
+        for chess_move in moves.iter_mut() {
+            chess_move.score = if tt_move.is_some_and(|m| m.from == chess_move.from && m.to == chess_move.to) {
+                1_000_000
+            } else if let Some(victim) = board.get_piece(&chess_move.to) {
+                let attacker = board.get_piece(&chess_move.from).map(|p| piece_value(p.piece_type)).unwrap_or(0);
+                let exchange = see(board, chess_move);
+                if exchange < 0 { exchange - 100_000 } else { 10 * piece_value(victim.piece_type) - attacker }
+            } else if self.id > 0 {
+                // Helper threads vary the order of quiet moves so they explore different trees
+                let seed = (self.id as u64) << 12 | (chess_move.from.rank * 8 + chess_move.from.file) as u64 | ((chess_move.to.rank * 8 + chess_move.to.file) as u64) << 6;
+                (seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 58) as i32
+            } else {
+                0
+            };
+        }
+        moves.sort_by_key(|m| Reverse(m.score));
+    }
+}
+
+impl SearchBackend for Engine {
+    fn name(&self) -> &str {
+        DEFAULT_BACKEND
+    }
+    
+    // Without a depth, node and time limits let the search go as deep as they allow.
+    // Limits only hold for this search, so a reused engine is back to its own
+    // settings for the next one.
+    fn search(&mut self, game: &Game, limits: &SearchLimits) -> Result<SearchResult, ChessError> {
+        let settings = (self.depth, self.multipv, self.skill, self.eval);
+        if let Some(depth) = limits.depth {
+            self.depth = depth.max(1);
+        } else if limits.nodes.is_some() || limits.movetime_ms.is_some() {
+            self.depth = MAX_SEARCH_DEPTH;
+        }
+        self.node_limit = limits.nodes;
+        self.movetime_ms = limits.movetime_ms;
+        if let Some(multipv) = limits.multipv {
+            self.set_multipv(multipv);
+        }
+        if let Some(skill) = limits.skill {
+            self.skill = skill;
+        }
+        if let Some(eval) = limits.eval {
+            self.eval = eval;
+        }
+        
+        let lines = self.analyze(game);
+        (self.depth, self.multipv, self.skill, self.eval) = settings;
+        let lines = lines?;
+        let mut best_move = lines[0].moves[0].clone();
+        best_move.score = lines[0].score;
+        Ok(SearchResult {
+            score: best_move.score,
+            best_move,
+            nodes: self.nodes_searched,
+            depth: lines[0].depth,
+            seldepth: self.seldepth,
+            elapsed_ms: self.elapsed_ms,
+            nps: self.nps(),
+            lines,
+            from_book: false,
+        })
+    }
+    
+    fn set_stop_signal(&mut self, stop: StopSignal) {
+        Engine::set_stop_signal(self, stop);
+    }
+    
+    fn new_game(&mut self) -> Result<(), ChessError> {
+        self.clear_hash();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Leaf nodes of the legal move tree `depth` plies deep
+    fn perft(engine: &Engine, board: &Board, color: Color, depth: u8) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let moves = engine.get_legal_moves(board, color).unwrap();
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+        moves.iter()
+            .map(|m| {
+                let mut after = board.clone();
+                after.make_move(&m.from, &m.to).unwrap();
+                perft(engine, &after, color.opposite(), depth - 1)
+            })
+            .sum()
+    }
+
+    fn perft_fen(fen: &str, depth: u8) -> u64 {
+        let game = Game::from_fen(fen).unwrap();
+        perft(&Engine::new(1), &game.board, game.current_turn, depth)
+    }
+
+    #[test]
+    fn search_limits_only_last_one_search() {
+        let mut engine = Engine::new(3);
+        let limits = SearchLimits {
+            depth: Some(1),
+            multipv: Some(2),
+            ..SearchLimits::default()
+        };
+        let result = SearchBackend::search(&mut engine, &Game::new(), &limits).unwrap();
+        assert_eq!(result.lines.len(), 2);
+        assert_eq!((engine.depth, engine.multipv()), (3, 1));
+    }
+
+    #[test]
+    fn perft_start_position() {
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1";
+        assert_eq!(perft_fen(fen, 1), 20);
+        assert_eq!(perft_fen(fen, 2), 400);
+        assert_eq!(perft_fen(fen, 3), 8902);
+    }
+
+    #[test]
+    fn perft_pinned_pieces_and_checks() {
+        // Perft position 3; en passant only shows up from depth 3
+        let fen = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+        assert_eq!(perft_fen(fen, 1), 14);
+        assert_eq!(perft_fen(fen, 2), 191);
+    }
+
+    #[test]
+    fn finds_mate_in_one() {
+        let game = Game::from_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1").unwrap();
+        let lines = Engine::new(2).analyze(&game).unwrap();
+        assert_eq!(lines[0].moves[0].to_uci(), "a1a8");
+        assert_eq!(lines[0].score, MATE_SCORE - 1);
+    }
+
+    #[test]
+    fn finds_mate_in_two() {
+        // WAC.004: 1.Qxh7+ Kxh7 2.hxg6#
+        let game = Game::from_fen("r1bq2rk/pp3pbp/2p1p1pQ/7P/3P4/2PB1N2/PP3PPR/2KR4 w - - 0 1").unwrap();
+        let lines = Engine::new(4).analyze(&game).unwrap();
+        assert_eq!(lines[0].moves[0].to_uci(), "h6h7");
+        assert_eq!(lines[0].score, MATE_SCORE - 3);
+    }
+
+    #[test]
+    fn single_thread_search_is_deterministic() {
+        let game = Game::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w - - 2 3").unwrap();
+        let search = || {
+            let mut engine = Engine::new(4);
+            engine.set_threads(1);
+            let lines = engine.analyze(&game).unwrap();
+            (lines[0].moves.clone(), lines[0].score, engine.get_stats().0)
+        };
+        let first = search();
+        for _ in 0..3 {
+            assert_eq!(search(), first);
+        }
+    }
+
+    #[test]
+    fn lazy_smp_agrees_with_a_single_thread() {
+        // WAC.004 again: the mate is found whatever order the threads finish in
+        let game = Game::from_fen("r1bq2rk/pp3pbp/2p1p1pQ/7P/3P4/2PB1N2/PP3PPR/2KR4 w - - 0 1").unwrap();
+        let search = |threads| {
+            let mut engine = Engine::new(4);
+            engine.set_threads(threads);
+            let lines = engine.analyze(&game).unwrap();
+            (lines[0].moves[0].to_uci(), lines[0].score, engine.get_stats().0)
+        };
+        let (best, score, single_nodes) = search(1);
+        assert_eq!((best.as_str(), score), ("h6h7", MATE_SCORE - 3));
+        for threads in [2, 4] {
+            let (smp_best, smp_score, nodes) = search(threads);
+            assert_eq!((smp_best, smp_score), (best.clone(), score), "{} threads", threads);
+            // The helpers' nodes are counted along with the main thread's
+            assert!(nodes > single_nodes, "{} threads searched {} nodes, one thread {}", threads, nodes, single_nodes);
+        }
+    }
+
+    #[test]
+    fn skill_node_budget_holds_in_the_first_iteration() {
+        // Dozens of moves and captures: a full first iteration takes thousands of nodes
+        let game = Game::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w - - 0 1").unwrap();
+        let legal = Engine::new(1).get_legal_moves(&game.board, game.current_turn).unwrap();
+        for level in [0, 2] {
+            let skill = Skill::new(level);
+            let mut engine = Engine::new(10);
+            engine.set_threads(1);
+            engine.set_skill(skill);
+            let lines = engine.analyze(&game).unwrap();
+            let (nodes, _) = engine.get_stats();
+            assert!(nodes <= skill.max_nodes().unwrap() + NODE_BATCH, "level {} searched {} nodes", level, nodes);
+            assert!(legal.iter().any(|m| m.from == lines[0].moves[0].from && m.to == lines[0].moves[0].to));
+        }
+    }
+}
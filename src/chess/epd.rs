@@ -0,0 +1,270 @@
+use crate::chess::{ChessMove, Engine, Game, MATE_SCORE, TB_WIN_SCORE};
+use crate::error::ChessError;
+use std::fs;
+use std::path::Path;
+
+// Extended Position Description: a position (the first four FEN fields) followed
+// by `opcode operand...;` operations, the format of test suites such as WAC, ECM
+// and STS. The operations understood here are:
+//
+//   bm  best moves; any of them solves the position
+//   am  moves to avoid; the engine must play something else
+//   dm  direct mate: the side to move mates in this many moves
+//   id  the position's name
+//   c0  a comment, or in STS suites the points each move earns ("Nf3=10, e4=5")
+//
+// STS suites may instead list the moves in c9 (coordinate notation) with their
+// points in c8, which is also understood.
+
+#[derive(Debug, Clone)]
+pub struct EpdPosition {
+    pub game: Game,
+    // Every operation in file order, with quotes removed from its operands
+    pub operations: Vec<(String, Vec<String>)>,
+}
+
+impl EpdPosition {
+    pub fn parse(line: &str) -> Result<Self, ChessError> {
+        let mut rest = line.trim();
+        let mut fields = Vec::new();
+        while fields.len() < 4 {
+            let (field, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            if field.is_empty() {
+                return Err(ChessError::InvalidPosition(format!("Not an EPD position: {}", line)));
+            }
+            fields.push(field);
+            rest = tail.trim_start();
+        }
+        // Some files carry FEN's move counters before the operations
+        for _ in 0..2 {
+            let (field, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            if field.is_empty() || !field.bytes().all(|b| b.is_ascii_digit()) {
+                break;
+            }
+            rest = tail.trim_start();
+        }
+        let game = Game::from_fen(&fields.join(" "))?;
+        Ok(Self { game, operations: parse_operations(rest) })
+    }
+
+    // Operands of the first operation with this opcode
+    pub fn operation(&self, opcode: &str) -> Option<&[String]> {
+        self.operations.iter().find(|(op, _)| op == opcode).map(|(_, operands)| operands.as_slice())
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        self.operation("id").and_then(|operands| operands.first()).map(String::as_str)
+    }
+
+    pub fn comment(&self) -> Option<&str> {
+        self.operation("c0").and_then(|operands| operands.first()).map(String::as_str)
+    }
+
+    pub fn best_moves(&self, engine: &Engine) -> Result<Vec<ChessMove>, ChessError> {
+        self.moves(engine, "bm")
+    }
+
+    pub fn avoid_moves(&self, engine: &Engine) -> Result<Vec<ChessMove>, ChessError> {
+        self.moves(engine, "am")
+    }
+
+    // Moves to mate in, from `dm`
+    pub fn direct_mate(&self) -> Option<u32> {
+        self.operation("dm").and_then(|operands| operands.first()).and_then(|n| n.parse().ok())
+    }
+
+    fn moves(&self, engine: &Engine, opcode: &str) -> Result<Vec<ChessMove>, ChessError> {
+        self.operation(opcode).unwrap_or_default().iter()
+            .map(|text| parse_move(engine, &self.game, text))
+            .collect()
+    }
+
+    // The points each listed move earns, STS style. Empty unless the suite
+    // scores moves.
+    pub fn move_points(&self, engine: &Engine) -> Result<Vec<(ChessMove, u32)>, ChessError> {
+        // c0 "f5=10, Be5+=2, Bf2=3"
+        if let Some(comment) = self.comment() {
+            let entries: Vec<Option<(&str, u32)>> = comment.split(',')
+                .map(|entry| entry.trim().split_once('=').and_then(|(m, p)| Some((m.trim(), p.trim().parse().ok()?))))
+                .collect();
+            if !entries.is_empty() && entries.iter().all(Option::is_some) {
+                return entries.into_iter().flatten()
+                    .map(|(text, points)| Ok((parse_move(engine, &self.game, text)?, points)))
+                    .collect();
+            }
+        }
+        // c8 "10 3 2"; c9 "f7f5 e6e5 b1c3"
+        if let (Some(points), Some(moves)) = (self.operation("c8"), self.operation("c9")) {
+            let points = points.iter().flat_map(|p| p.split_whitespace());
+            let moves = moves.iter().flat_map(|m| m.split_whitespace());
+            return moves.zip(points)
+                .map(|(text, points)| {
+                    let points = points.parse()
+                        .map_err(|_| ChessError::InvalidPosition(format!("Invalid c8 points: {}", points)))?;
+                    Ok((parse_move(engine, &self.game, text)?, points))
+                })
+                .collect();
+        }
+        Ok(Vec::new())
+    }
+
+    // Whether the engine's answer solves the position: one of the best moves,
+    // none of the moves to avoid, and a mate at least as fast as the direct mate.
+    // `score` is from the side to move's point of view.
+    pub fn is_solved_by(&self, engine: &Engine, chess_move: &ChessMove, score: i32) -> Result<bool, ChessError> {
+        let same = |m: &ChessMove| m.from == chess_move.from && m.to == chess_move.to;
+        let best = self.best_moves(engine)?;
+        if !best.is_empty() && !best.iter().any(same) {
+            return Ok(false);
+        }
+        if self.avoid_moves(engine)?.iter().any(same) {
+            return Ok(false);
+        }
+        if let Some(moves) = self.direct_mate() {
+            let plies = 2 * moves as i32 - 1;
+            if score <= TB_WIN_SCORE || MATE_SCORE - score > plies {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+// Split `bm Qg6; id "WAC.001"; c0 "a; b";` into operations. Semicolons inside
+// quotes belong to the operand.
+fn parse_operations(text: &str) -> Vec<(String, Vec<String>)> {
+    let mut operations = Vec::new();
+    let mut tokens: Vec<String> = Vec::new();
+    let mut token = String::new();
+    let mut quoted = false;
+    let mut finish = |tokens: &mut Vec<String>| {
+        if !tokens.is_empty() {
+            let opcode = tokens.remove(0);
+            operations.push((opcode, std::mem::take(tokens)));
+        }
+    };
+    for c in text.chars() {
+        match c {
+            '"' if quoted => {
+                quoted = false;
+                tokens.push(std::mem::take(&mut token));
+            },
+            '"' => quoted = true,
+            _ if quoted => token.push(c),
+            ';' | ' ' | '\t' => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+                if c == ';' {
+                    finish(&mut tokens);
+                }
+            },
+            _ => token.push(c),
+        }
+    }
+    // The last operation may lack its semicolon
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+    finish(&mut tokens);
+    operations
+}
+
+// A move in SAN ("Nf3", "exd5+") or coordinate notation ("g1f3")
+fn parse_move(engine: &Engine, game: &Game, text: &str) -> Result<ChessMove, ChessError> {
+    engine.parse_algebraic_notation(text, &game.board, game.current_turn).or_else(|e| {
+        engine.get_legal_moves(&game.board, game.current_turn)?.into_iter()
+            .find(|m| m.to_uci() == text)
+            .ok_or(e)
+    })
+}
+
+// Every position of an EPD file, skipping blank lines and `#` comments
+pub fn load_epd_suite(path: impl AsRef<Path>) -> Result<Vec<EpdPosition>, ChessError> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path)
+        .map_err(|e| ChessError::InvalidPosition(format!("Failed to read {}: {}", path.display(), e)))?;
+    text.lines().enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|(number, line)| EpdPosition::parse(line)
+            .map_err(|e| ChessError::InvalidPosition(format!("{} line {}: {}", path.display(), number + 1, e))))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // "Philidor's smothered mate: depth 5, 8 without extensions" -> (5, 8)
+    fn solving_depths(comment: &str) -> (u8, u8) {
+        let numbers: Vec<u8> = comment.rsplit(':').next().unwrap()
+            .split(|c: char| !c.is_ascii_digit())
+            .filter_map(|n| n.parse().ok())
+            .collect();
+        (numbers[0], numbers[1])
+    }
+
+    // Whether the search finds the solution at `depth`, with what it played and its score
+    fn solves(position: &EpdPosition, depth: u8, extensions: bool) -> (bool, String, i32) {
+        let mut engine = Engine::new(depth);
+        engine.set_threads(1);
+        engine.set_extensions(extensions);
+        let line = &engine.analyze(&position.game).unwrap()[0];
+        let solved = position.is_solved_by(&engine, &line.moves[0], line.score).unwrap();
+        (solved, line.san[0].clone(), line.score)
+    }
+
+    // Solved at the depths the comment gives, and with extensions off not yet
+    // at the depth extensions need, where that is less
+    fn assert_depths(position: &EpdPosition) {
+        let id = position.id().unwrap_or("?");
+        let (depth, without_extensions) = solving_depths(position.comment().unwrap());
+        for (depth, extensions) in [(depth, true), (without_extensions, false)] {
+            let (solved, played, score) = solves(position, depth, extensions);
+            assert!(solved, "{} not solved at depth {} (extensions: {}): played {} with score {}", id, depth, extensions, played, score);
+        }
+        if depth < without_extensions {
+            let (solved, played, score) = solves(position, depth, false);
+            assert!(!solved, "{} solved at depth {} without extensions: played {} with score {}", id, depth, played, score);
+        }
+    }
+
+    // Positions of the suite that take well under a second each in debug builds
+    const QUICK_TACTICS: [&str; 5] = ["WAC.001", "WAC.004", "WAC.005", "tactics.05", "tactics.09"];
+
+    fn tactics() -> Vec<EpdPosition> {
+        load_epd_suite(concat!(env!("CARGO_MANIFEST_DIR"), "/suites/tactics.epd")).unwrap()
+    }
+
+    #[test]
+    fn parses_operations() {
+        let position = EpdPosition::parse(r#"7k/8/6K1/8/8/8/8/R7 w - - bm Ra8#; dm 1; id "mate; in one"; c0 "depth 1""#).unwrap();
+        assert_eq!(position.id(), Some("mate; in one"));
+        assert_eq!(position.comment(), Some("depth 1"));
+        assert_eq!(position.direct_mate(), Some(1));
+        let engine = Engine::new(1);
+        assert_eq!(position.best_moves(&engine).unwrap()[0].to_uci(), "a1a8");
+    }
+
+    // The quickest positions of the tactical regression set, so that every
+    // test run checks the extensions make a difference
+    #[test]
+    fn tactics_suite_quick() {
+        let suite = tactics();
+        for id in QUICK_TACTICS {
+            let position = suite.iter().find(|p| p.id() == Some(id)).unwrap();
+            assert_depths(position);
+        }
+    }
+
+    // Every position of the tactical regression set. Slow in debug builds, see the README
+    #[test]
+    #[ignore]
+    fn tactics_suite() {
+        let suite = tactics();
+        assert_eq!(suite.len(), 11);
+        for position in &suite {
+            assert_depths(position);
+        }
+    }
+}
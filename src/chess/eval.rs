@@ -0,0 +1,408 @@
+use crate::chess::{Board, Color, PieceType, Position};
+use crate::chess::endgame;
+use crate::error::ChessError;
+use serde::{Serialize, Deserialize};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+// Point values for each piece type (traditional chess values)
+pub(crate) const PAWN_VALUE: i32 = 100;
+pub(crate) const KNIGHT_VALUE: i32 = 320;
+pub(crate) const BISHOP_VALUE: i32 = 330;
+pub(crate) const ROOK_VALUE: i32 = 500;
+pub(crate) const QUEEN_VALUE: i32 = 900;
+pub(crate) const KING_VALUE: i32 = 20000; // Very high to ensure king safety
+
+// Position evaluation bonus for controlling center, good pawn structure, etc.
+const CENTER_CONTROL_BONUS: i32 = 10;
+const DEVELOPED_PIECE_BONUS: i32 = 15;
+
+// Largest piece value and bonus a parameter file may set, which keeps
+// evaluations well clear of mate scores
+const MAX_PIECE_VALUE: i32 = 2000;
+const MAX_BONUS: i32 = 200;
+
+// Weight of each piece in the game phase, which runs from MAX_PHASE with every
+// piece on the board down to 0 with only kings and pawns
+const KNIGHT_PHASE: i32 = 1;
+const BISHOP_PHASE: i32 = 1;
+const ROOK_PHASE: i32 = 2;
+const QUEEN_PHASE: i32 = 4;
+pub const MAX_PHASE: i32 = 24;
+
+// Built-in personalities, selectable by name
+pub const PERSONALITIES: [&str; 4] = ["default", "aggressive", "positional", "materialistic"];
+
+// Weights of the evaluation function. Files only need the fields they change;
+// the rest keep their default values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EvalParams {
+    pub pawn_value: i32,
+    pub knight_value: i32,
+    pub bishop_value: i32,
+    pub rook_value: i32,
+    pub queen_value: i32,
+    pub king_value: i32,
+    // For each of our pieces on d4, e4, d5 or e5
+    pub center_control_bonus: i32,
+    // For each of our knights and bishops off the back rank
+    pub developed_piece_bonus: i32,
+}
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        Self {
+            pawn_value: PAWN_VALUE,
+            knight_value: KNIGHT_VALUE,
+            bishop_value: BISHOP_VALUE,
+            rook_value: ROOK_VALUE,
+            queen_value: QUEEN_VALUE,
+            king_value: KING_VALUE,
+            center_control_bonus: CENTER_CONTROL_BONUS,
+            developed_piece_bonus: DEVELOPED_PIECE_BONUS,
+        }
+    }
+}
+
+impl EvalParams {
+    // A built-in personality:
+    // - aggressive gives up material for central, active pieces
+    // - positional values piece placement more than the default does
+    // - materialistic counts material and hardly anything else
+    pub fn personality(name: &str) -> Option<Self> {
+        let default = Self::default();
+        match name.to_lowercase().as_str() {
+            "default" => Some(default),
+            "aggressive" => Some(Self {
+                pawn_value: 90,
+                knight_value: 330,
+                bishop_value: 340,
+                rook_value: 480,
+                queen_value: 950,
+                center_control_bonus: 25,
+                developed_piece_bonus: 30,
+                ..default
+            }),
+            "positional" => Some(Self {
+                bishop_value: 340,
+                center_control_bonus: 20,
+                developed_piece_bonus: 25,
+                ..default
+            }),
+            "materialistic" => Some(Self {
+                pawn_value: 110,
+                knight_value: 330,
+                rook_value: 520,
+                queen_value: 950,
+                center_control_bonus: 4,
+                developed_piece_bonus: 6,
+                ..default
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, ChessError> {
+        let params: Self = serde_json::from_str(json)
+            .map_err(|e| ChessError::EvalParams(format!("Invalid JSON: {}", e)))?;
+        params.validate()
+    }
+
+    pub fn from_toml(toml: &str) -> Result<Self, ChessError> {
+        let params: Self = toml::from_str(toml)
+            .map_err(|e| ChessError::EvalParams(format!("Invalid TOML: {}", e)))?;
+        params.validate()
+    }
+
+    // Load a `.json` or `.toml` parameter file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ChessError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|e| ChessError::EvalParams(format!("Failed to read {}: {}", path.display(), e)))?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Self::from_json(&text),
+            Some("toml") => Self::from_toml(&text),
+            _ => Err(ChessError::EvalParams(format!("{} is neither a .json nor a .toml file", path.display()))),
+        }
+    }
+
+    // Write the parameters to a `.json` or `.toml` file, e.g. after tuning
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ChessError> {
+        let path = path.as_ref();
+        let text = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => serde_json::to_string_pretty(self)
+                .map_err(|e| ChessError::EvalParams(format!("Failed to encode JSON: {}", e)))? + "\n",
+            Some("toml") => toml::to_string(self)
+                .map_err(|e| ChessError::EvalParams(format!("Failed to encode TOML: {}", e)))?,
+            _ => return Err(ChessError::EvalParams(format!("{} is neither a .json nor a .toml file", path.display()))),
+        };
+        fs::write(path, text)
+            .map_err(|e| ChessError::EvalParams(format!("Failed to write {}: {}", path.display(), e)))
+    }
+
+    // A personality by name, or else a parameter file by path
+    pub fn resolve(name_or_path: &str) -> Result<Self, ChessError> {
+        match Self::personality(name_or_path) {
+            Some(params) => Ok(params),
+            None if Path::new(name_or_path).exists() => Self::load(name_or_path),
+            None => Err(ChessError::EvalParams(format!(
+                "No personality or parameter file named {} (personalities: {})", name_or_path, PERSONALITIES.join(", ")
+            ))),
+        }
+    }
+
+    // Reject values that would break the search, e.g. a piece worth nothing
+    pub fn validate(self) -> Result<Self, ChessError> {
+        let pieces = [
+            ("pawn_value", self.pawn_value),
+            ("knight_value", self.knight_value),
+            ("bishop_value", self.bishop_value),
+            ("rook_value", self.rook_value),
+            ("queen_value", self.queen_value),
+        ];
+        for (name, value) in pieces {
+            if !(1..=MAX_PIECE_VALUE).contains(&value) {
+                return Err(ChessError::EvalParams(format!("{} must be 1-{}, got {}", name, MAX_PIECE_VALUE, value)));
+            }
+        }
+        if self.king_value <= self.queen_value {
+            return Err(ChessError::EvalParams("king_value must be more than queen_value".to_string()));
+        }
+        for (name, value) in [("center_control_bonus", self.center_control_bonus), ("developed_piece_bonus", self.developed_piece_bonus)] {
+            if value.abs() > MAX_BONUS {
+                return Err(ChessError::EvalParams(format!("{} must be within ±{}, got {}", name, MAX_BONUS, value)));
+            }
+        }
+        Ok(self)
+    }
+
+    // The weights a tuner may change, by name
+    pub fn tunable(&self) -> [(&'static str, i32); 7] {
+        let mut params = *self;
+        params.tunable_mut().map(|(name, value)| (name, *value))
+    }
+
+    // The weights a tuner may change. The king is always on the board, so its
+    // value never affects the evaluation and isn't worth tuning.
+    pub fn tunable_mut(&mut self) -> [(&'static str, &mut i32); 7] {
+        [
+            ("pawn_value", &mut self.pawn_value),
+            ("knight_value", &mut self.knight_value),
+            ("bishop_value", &mut self.bishop_value),
+            ("rook_value", &mut self.rook_value),
+            ("queen_value", &mut self.queen_value),
+            ("center_control_bonus", &mut self.center_control_bonus),
+            ("developed_piece_bonus", &mut self.developed_piece_bonus),
+        ]
+    }
+
+    // Static evaluation of a position from the given color's point of view
+    pub fn evaluate(&self, board: &Board, color: Color) -> i32 {
+        let mut score = 0;
+        
+        // Loop through all squares on the board
+        for rank in 0..8 {
+            for file in 0..8 {
+                let pos = Position::new(file, rank);
+                
+                // Check if there's a piece at this position
+                if let Some(piece) = board.get_piece(&pos) {
+                    // Calculate the material value
+                    let piece_value = self.piece_value(piece.piece_type);
+                    
+                    // Add value for the player's pieces, subtract for opponent's pieces
+                    if piece.color == color {
+                        score += piece_value;
+                        
+                        // Bonus for controlling the center (e4, d4, e5, d5)
+                        if (file == 3 || file == 4) && (rank == 3 || rank == 4) {
+                            score += self.center_control_bonus;
+                        }
+                        
+                        // Development bonus for minor pieces
+                        if (piece.piece_type == PieceType::Knight || piece.piece_type == PieceType::Bishop) &&
+                           ((color == Color::White && rank > 0) || (color == Color::Black && rank < 7)) {
+                            score += self.developed_piece_bonus;
+                        }
+                    } else {
+                        score -= piece_value;
+                        
+                        // Bonus for opponent controlling the center
+                        if (file == 3 || file == 4) && (rank == 3 || rank == 4) {
+                            score -= self.center_control_bonus;
+                        }
+                        
+                        // Development bonus for opponent's minor pieces
+                        if (piece.piece_type == PieceType::Knight || piece.piece_type == PieceType::Bishop) &&
+                           ((color == Color::Black && rank > 0) || (color == Color::White && rank < 7)) {
+                            score -= self.developed_piece_bonus;
+                        }
+                    }
+                }
+            }
+        }
+        
+        score
+    }
+
+    pub fn piece_value(&self, piece_type: PieceType) -> i32 {
+        match piece_type {
+            PieceType::Pawn => self.pawn_value,
+            PieceType::Knight => self.knight_value,
+            PieceType::Bishop => self.bishop_value,
+            PieceType::Rook => self.rook_value,
+            PieceType::Queen => self.queen_value,
+            PieceType::King => self.king_value,
+        }
+    }
+
+    // The evaluation split into its terms, each for White, for Black and as
+    // White's lead. The kings are left out of the material, since there is
+    // always one a side; otherwise the total matches the engine's handcrafted
+    // evaluation for White, endgame knowledge included.
+    pub fn trace(&self, board: &Board) -> EvalTrace {
+        let mut material = [0; 2];
+        let mut center = [0; 2];
+        let mut development = [0; 2];
+        for rank in 0..8 {
+            for file in 0..8 {
+                let Some(piece) = board.get_piece(&Position::new(file, rank)) else {
+                    continue;
+                };
+                let side = if piece.color == Color::White { 0 } else { 1 };
+                if piece.piece_type != PieceType::King {
+                    material[side] += self.piece_value(piece.piece_type);
+                }
+                if (file == 3 || file == 4) && (rank == 3 || rank == 4) {
+                    center[side] += self.center_control_bonus;
+                }
+                if (piece.piece_type == PieceType::Knight || piece.piece_type == PieceType::Bishop) &&
+                   ((piece.color == Color::White && rank > 0) || (piece.color == Color::Black && rank < 7)) {
+                    development[side] += self.developed_piece_bonus;
+                }
+            }
+        }
+        let mut terms: Vec<EvalTerm> = [("Material", material), ("Center control", center), ("Development", development)]
+            .into_iter()
+            .map(|(name, [white, black])| EvalTerm { name, white, black, total: white - black, evaluated: true })
+            .collect();
+        let general: i32 = terms.iter().map(|term| term.total).sum();
+        let (total, endgame) = endgame::apply(board, general);
+        if endgame.is_some() {
+            let change = total - general;
+            terms.push(EvalTerm { name: "Endgame", white: change.max(0), black: (-change).max(0), total: change, evaluated: true });
+        }
+        terms.extend(NOT_EVALUATED.iter().map(|&name| EvalTerm { name, white: 0, black: 0, total: 0, evaluated: false }));
+        EvalTrace {
+            total,
+            terms,
+            phase: game_phase(board),
+            endgame: endgame.map(|rule| endgame::describe(board, rule)),
+        }
+    }
+}
+
+// How far the game is from the endgame, from the pieces left: MAX_PHASE with
+// all of them, 0 with only kings and pawns. Promotions can't push it past
+// MAX_PHASE.
+pub fn game_phase(board: &Board) -> i32 {
+    let mut phase = 0;
+    for rank in 0..8 {
+        for file in 0..8 {
+            phase += match board.get_piece(&Position::new(file, rank)).map(|p| p.piece_type) {
+                Some(PieceType::Knight) => KNIGHT_PHASE,
+                Some(PieceType::Bishop) => BISHOP_PHASE,
+                Some(PieceType::Rook) => ROOK_PHASE,
+                Some(PieceType::Queen) => QUEEN_PHASE,
+                _ => 0,
+            };
+        }
+    }
+    phase.min(MAX_PHASE)
+}
+
+// Common evaluation terms the handcrafted evaluation doesn't have. Traces list
+// them as not evaluated rather than leave them out.
+const NOT_EVALUATED: [&str; 4] = ["Piece-square", "Pawn structure", "King safety", "Mobility"];
+
+// One term of the evaluation in centipawns
+#[derive(Debug, Clone, Serialize)]
+pub struct EvalTerm {
+    pub name: &'static str,
+    pub white: i32,
+    pub black: i32,
+    // White's lead
+    pub total: i32,
+    // False for the terms other engines have but this evaluation doesn't;
+    // they are listed with zeros so nobody mistakes them for a balance
+    pub evaluated: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EvalTrace {
+    pub terms: Vec<EvalTerm>,
+    // See game_phase. The evaluation doesn't depend on it, but it tells how
+    // much of the game is left.
+    pub phase: i32,
+    // Sum of the terms, from White's point of view
+    pub total: i32,
+    // The endgame rule that applied, e.g. "KBNK" or "opposite-coloured bishops scaled 16/64"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endgame: Option<String>,
+}
+
+// A table like Stockfish's `eval` output, in pawns
+impl fmt::Display for EvalTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pawns = |cp: i32| format!("{:.2}", cp as f64 / 100.0);
+        let lead = |cp: i32| format!("{:+.2}", cp as f64 / 100.0);
+        writeln!(f, "{:>16} | {:>8} | {:>8} | {:>8}", "Term", "White", "Black", "Total")?;
+        writeln!(f, "{:-<17}+{:-<10}+{:-<10}+{:-<9}", "", "", "", "")?;
+        for term in &self.terms {
+            if term.evaluated {
+                writeln!(f, "{:>16} | {:>8} | {:>8} | {:>8}", term.name, pawns(term.white), pawns(term.black), lead(term.total))?;
+            } else {
+                writeln!(f, "{:>16} | {:>8} | {:>8} | {:>8}", term.name, "----", "----", "----")?;
+            }
+        }
+        writeln!(f, "{:-<17}+{:-<10}+{:-<10}+{:-<9}", "", "", "", "")?;
+        writeln!(f, "{:>16} | {:>8} | {:>8} | {:>8}", "Total", "", "", lead(self.total))?;
+        writeln!(f)?;
+        writeln!(f, "Phase: {}/{}", self.phase, MAX_PHASE)?;
+        if let Some(endgame) = &self.endgame {
+            writeln!(f, "Endgame: {}", endgame)?;
+        }
+        write!(f, "Final evaluation: {} (white side)", lead(self.total))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::Game;
+
+    #[test]
+    fn trace_matches_the_evaluation() {
+        let params = EvalParams::default();
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1",
+            "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w - - 2 3",
+            "8/8/8/8/8/8/8/KBN4k w - - 0 1",
+        ] {
+            let board = Game::from_fen(fen).unwrap().board;
+            let trace = params.trace(&board);
+            assert_eq!(trace.total, endgame::adjust(&board, Color::White, params.evaluate(&board, Color::White)));
+            assert_eq!(trace.total, trace.terms.iter().map(|term| term.total).sum::<i32>());
+        }
+    }
+
+    #[test]
+    fn trace_lists_missing_terms() {
+        let trace = EvalParams::default().trace(&Game::new().board);
+        let missing: Vec<&str> = trace.terms.iter().filter(|term| !term.evaluated).map(|term| term.name).collect();
+        assert_eq!(missing, NOT_EVALUATED);
+        assert!(trace.to_string().contains("     Mobility |     ---- |"));
+    }
+}
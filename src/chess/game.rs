@@ -1,4 +1,5 @@
-use crate::chess::{Board, Position, Color, Engine, ChessMove, PieceType};
+use crate::chess::{Board, Position, Color, Engine, ChessMove, is_in_check};
+use crate::chess::{BackendRegistry, SearchLimits, SearchResult, Skill, EvalParams, DEFAULT_BACKEND};
 use crate::error::ChessError;
 use serde::{Serialize, Deserialize};
 use std::str::FromStr;
@@ -18,7 +19,22 @@ pub struct Game {
     pub current_turn: Color,
     pub status: GameStatus,
     pub move_history: Vec<String>,
-    pub engine: Engine,
+    // Name of the search backend that plays and analyses this game
+    #[serde(default = "default_backend")]
+    pub backend: String,
+    // Whether the engine thinks on the opponent's time when it plays this game
+    #[serde(default)]
+    pub ponder: bool,
+    // How strongly the engine plays this game
+    #[serde(default)]
+    pub skill: Skill,
+    // Evaluation weights the engine plays this game with; the engine's own if unset
+    #[serde(default)]
+    pub eval_params: Option<EvalParams>,
+}
+
+fn default_backend() -> String {
+    DEFAULT_BACKEND.to_string()
 }
 
 impl Game {
@@ -28,10 +44,40 @@ impl Game {
             current_turn: Color::White,
             status: GameStatus::InProgress,
             move_history: Vec::new(),
-            engine: Engine::new(3),  // Default depth of 3
+            backend: default_backend(),
+            ponder: false,
+            skill: Skill::full(),
+            eval_params: None,
         }
     }
     
+    // Set up a game from a FEN string. Castling and en passant fields are accepted but ignored.
+    pub fn from_fen(fen: &str) -> Result<Self, ChessError> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        if fields.is_empty() {
+            return Err(ChessError::InvalidPosition("Empty FEN".to_string()));
+        }
+        
+        let mut game = Self::new();
+        game.board = Board::from_fen(fields[0])?;
+        game.current_turn = match fields.get(1) {
+            None | Some(&"w") => Color::White,
+            Some(&"b") => Color::Black,
+            Some(other) => return Err(ChessError::InvalidPosition(format!("Invalid side to move: {}", other))),
+        };
+        game.update_game_status();
+        
+        Ok(game)
+    }
+    
+    // Full FEN string for the current position.
+    // Castling and en passant aren't supported by the board, so those fields are always empty.
+    pub fn to_fen(&self) -> String {
+        let side = if self.current_turn == Color::White { "w" } else { "b" };
+        let fullmove = self.move_history.len() / 2 + 1;
+        format!("{} {} - - 0 {}", self.board.to_fen(), side, fullmove)
+    }
+    
     pub fn make_move(&mut self, from_str: &str, to_str: &str) -> Result<(), ChessError> {
         // Parse positions from strings
         let from = Position::from_str(from_str)?;
@@ -61,9 +107,18 @@ impl Game {
         Ok(())
     }
 
-    pub fn get_best_move(&self) -> Result<ChessMove, ChessError> {
-        let mut engine = Engine::new(3);
-        engine.find_best_move(self)
+    // Search the current position with this game's backend, at the game's skill level
+    // and evaluation unless the limits ask for others
+    pub fn get_best_move(&self, registry: &BackendRegistry, limits: &SearchLimits) -> Result<SearchResult, ChessError> {
+        let limits = SearchLimits {
+            skill: limits.skill.or(Some(self.skill)),
+            eval: limits.eval.or(self.eval_params),
+            ..*limits
+        };
+        let mut backend = registry.checkout(&self.backend, None)?;
+        let result = backend.search(self, &limits);
+        registry.checkin(&self.backend, None, backend);
+        result
     }
     
     pub fn get_status(&self) -> GameStatus {
@@ -72,44 +127,7 @@ impl Game {
     
     // Check if the king of the given color is in check
     fn is_king_in_check(&self, color: Color) -> bool {
-        // Find the king's position
-        let mut king_pos = None;
-        for rank in 0..8 {
-            for file in 0..8 {
-                let pos = Position::new(file, rank);
-                if let Some(piece) = self.board.get_piece(&pos) {
-                    if piece.color == color && piece.piece_type == PieceType::King {
-                        king_pos = Some(pos);
-                        break;
-                    }
-                }
-            }
-            if king_pos.is_some() {
-                break;
-            }
-        }
-        
-        // If we somehow can't find the king, consider it in check
-        let king_pos = match king_pos {
-            Some(pos) => pos,
-            None => return true,
-        };
-        
-        // Check if any opponent piece can capture the king
-        // This is a simplified approach - just see if any legal move for the opponent
-        // can land on the king's position
-        let mut engine = Engine::new(1);  // Shallow depth for finding attacks
-        let opponent_color = color.opposite();
-        
-        if let Ok(moves) = engine.generate_moves(&self.board, opponent_color) {
-            for chess_move in moves {
-                if chess_move.to == king_pos {
-                    return true;
-                }
-            }
-        }
-        
-        false
+        is_in_check(&self.board, color)
     }
     
     // Update the game status (check, checkmate, stalemate, etc.)
@@ -119,9 +137,9 @@ impl Game {
         // Check if the current player is in check
         let in_check = self.is_king_in_check(current_player);
         
-        // Create a temporary engine to check for legal moves
-        let mut engine = Engine::new(1);
-        let has_legal_moves = match engine.generate_moves(&self.board, current_player) {
+        // Moves that would leave the king in check don't count
+        let engine = Engine::new(1);
+        let has_legal_moves = match engine.get_legal_moves(&self.board, current_player) {
             Ok(moves) => !moves.is_empty(),
             Err(_) => false,
         };
@@ -140,3 +158,40 @@ impl Game {
         }
     }
 }
+
+impl Default for Game {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mated_position_is_checkmate() {
+        let game = Game::from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w - - 1 3").unwrap();
+        assert_eq!(game.get_status(), GameStatus::Checkmate);
+    }
+
+    #[test]
+    fn mating_move_ends_the_game() {
+        let mut game = Game::from_fen("rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b - - 0 2").unwrap();
+        assert_eq!(game.get_status(), GameStatus::InProgress);
+        game.make_move("d8", "h4").unwrap();
+        assert_eq!(game.get_status(), GameStatus::Checkmate);
+    }
+
+    #[test]
+    fn check_with_a_way_out_is_only_check() {
+        let game = Game::from_fen("4k3/8/8/8/8/8/8/4K2r w - - 0 1").unwrap();
+        assert_eq!(game.get_status(), GameStatus::Check);
+    }
+
+    #[test]
+    fn no_legal_move_without_check_is_stalemate() {
+        let game = Game::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(game.get_status(), GameStatus::Stalemate);
+    }
+}
@@ -0,0 +1,590 @@
+use crate::chess::{Board, ChessMove, Color, Engine, Game, StopSignal, is_in_check};
+use crate::chess::zobrist;
+use crate::error::ChessError;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+// A dedicated solver for chess problems, separate from the engine's evaluation
+// search. Three stipulations are understood:
+//
+//   #N   direct mate: the side to move mates in N moves against any defence
+//   s#N  selfmate: the side to move forces the other side to mate it in N moves
+//   h#N  helpmate: both sides cooperate so that the side not to move mates in N
+//        moves; h#N.5 starts with the mating side's move instead
+//
+// Direct mates and selfmates are a depth-limited AND/OR search: the attacker
+// needs one move after which every defence still leads to the goal in the moves
+// left. Checks are tried first everywhere. Deepening one move at a time finds the
+// shortest solution. Helpmates are enumerated exhaustively, since a problem is
+// judged by how many solutions it has.
+
+// The stop signal and node limit are checked this often
+const CHECK_INTERVAL: u64 = 4096;
+
+// A move of the side giving mate, with every defence against it
+#[derive(Debug, Clone, Serialize)]
+pub struct MateMove {
+    // UCI notation
+    #[serde(rename = "move")]
+    pub uci: String,
+    pub san: String,
+    // Other moves that mate just as fast here (duals), in SAN
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub duals: Vec<String>,
+    // Empty when the move mates
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub defences: Vec<MateDefence>,
+}
+
+// A reply of the defending side and how it is answered
+#[derive(Debug, Clone, Serialize)]
+pub struct MateDefence {
+    #[serde(rename = "move")]
+    pub uci: String,
+    pub san: String,
+    // Moves to the goal after this defence, which may be fewer than the full
+    // solution. 0 when the defence itself is the selfmate.
+    pub mate_in: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub continuation: Option<MateMove>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MateSolution {
+    pub fen: String,
+    // Length of the shortest forced mate, in moves of the side to move
+    pub mate_in: u32,
+    // A sound problem has exactly one key; any other is a cook
+    pub unique: bool,
+    // Every first move that mates in `mate_in`, with its full solution tree
+    pub keys: Vec<MateMove>,
+    pub nodes: u64,
+}
+
+// A problem's goal and length
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stipulation {
+    Direct(u32),
+    Selfmate(u32),
+    // Length in single moves (plies), so that h#2.5 is 5
+    Helpmate(u32),
+}
+
+impl FromStr for Stipulation {
+    type Err = ChessError;
+
+    // "#3", "s#2", "h#2" or "h#2.5"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ChessError::Engine(format!("Invalid stipulation: {} (e.g. #3, s#2, h#2 or h#2.5)", s));
+        let s = s.trim().to_lowercase();
+        if let Some(moves) = s.strip_prefix("h#") {
+            let (whole, half) = match moves.split_once('.') {
+                Some((whole, "5")) => (whole, 1),
+                Some(_) => return Err(invalid()),
+                None => (moves, 0),
+            };
+            let whole: u32 = whole.parse().map_err(|_| invalid())?;
+            let plies = 2 * whole + half;
+            return if plies == 0 { Err(invalid()) } else { Ok(Stipulation::Helpmate(plies)) };
+        }
+        let (selfmate, moves) = match s.strip_prefix("s#") {
+            Some(moves) => (true, moves),
+            None => (false, s.strip_prefix('#').ok_or_else(invalid)?),
+        };
+        match moves.parse::<u32>() {
+            Ok(0) | Err(_) => Err(invalid()),
+            Ok(n) if selfmate => Ok(Stipulation::Selfmate(n)),
+            Ok(n) => Ok(Stipulation::Direct(n)),
+        }
+    }
+}
+
+impl fmt::Display for Stipulation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stipulation::Direct(n) => write!(f, "#{}", n),
+            Stipulation::Selfmate(n) => write!(f, "s#{}", n),
+            Stipulation::Helpmate(plies) if plies % 2 == 1 => write!(f, "h#{}.5", plies / 2),
+            Stipulation::Helpmate(plies) => write!(f, "h#{}", plies / 2),
+        }
+    }
+}
+
+// One helpmate solution: the moves of both sides in order
+#[derive(Debug, Clone, Serialize)]
+pub struct HelpmateLine {
+    // UCI notation
+    pub moves: Vec<String>,
+    pub san: Vec<String>,
+    // Problem notation, e.g. "1.Kd5 Qe4 2.Kc6 Qc4#", or "1...Qe4 2.Kd5 ..." when
+    // the mating side moves first
+    pub notation: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HelpmateSolution {
+    pub fen: String,
+    pub stipulation: String,
+    pub solutions: Vec<HelpmateLine>,
+    // Solutions of the shortest shorter length with the same side starting; any
+    // cook the problem
+    pub short_solutions: Vec<HelpmateLine>,
+    // Groups of solutions (indices into `solutions`) that are the same mate
+    // reached by a different move order
+    pub duplicates: Vec<Vec<usize>>,
+    pub nodes: u64,
+}
+
+// What the attacker is after in the AND/OR search
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Goal {
+    // Mate the defender
+    Mate,
+    // Be mated by the defender
+    Selfmate,
+}
+
+// What is known about mate from an attacker-to-move position
+#[derive(Debug, Clone, Copy, Default)]
+struct Bounds {
+    // Mate is proven within this many moves
+    mate_within: Option<u32>,
+    // There is no mate within this many moves
+    no_mate_within: u32,
+}
+
+pub struct MateSearch {
+    engine: Engine,
+    table: HashMap<u64, Bounds>,
+    // Helpmate positions, with the plies left, that have no solution
+    help_failures: HashSet<(u64, u32)>,
+    nodes: u64,
+    node_limit: Option<u64>,
+    stop: StopSignal,
+}
+
+impl Default for MateSearch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MateSearch {
+    pub fn new() -> Self {
+        Self {
+            engine: Engine::new(1),
+            table: HashMap::new(),
+            help_failures: HashSet::new(),
+            nodes: 0,
+            node_limit: None,
+            stop: StopSignal::new(),
+        }
+    }
+
+    // Give up with a timeout error after roughly this many nodes
+    pub fn set_node_limit(&mut self, nodes: Option<u64>) {
+        self.node_limit = nodes;
+    }
+
+    // Raising this signal makes the running search give up with a timeout error
+    pub fn set_stop_signal(&mut self, stop: StopSignal) {
+        self.stop = stop;
+    }
+
+    pub fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    // The shortest forced mate for the side to move in at most `max_moves`
+    // moves, with its solution tree, or None if there is none
+    pub fn solve(&mut self, game: &Game, max_moves: u32) -> Result<Option<MateSolution>, ChessError> {
+        self.solve_goal(game, max_moves, Goal::Mate)
+    }
+
+    // The shortest forced selfmate: the side to move makes the other side mate
+    // it within `max_moves` moves whatever it tries
+    pub fn solve_selfmate(&mut self, game: &Game, max_moves: u32) -> Result<Option<MateSolution>, ChessError> {
+        self.solve_goal(game, max_moves, Goal::Selfmate)
+    }
+
+    fn solve_goal(&mut self, game: &Game, max_moves: u32, goal: Goal) -> Result<Option<MateSolution>, ChessError> {
+        self.nodes = 0;
+        self.table.clear();
+        let attacker = game.current_turn;
+        let Some(mate_in) = self.shortest_mate(&game.board, attacker, max_moves, goal)? else {
+            return Ok(None);
+        };
+        let keys = self.mating_moves(&game.board, attacker, mate_in, goal)?;
+        let keys = keys.iter()
+            .map(|key| self.solution_tree(&game.board, attacker, key, mate_in, goal, Vec::new()))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Some(MateSolution {
+            fen: game.to_fen(),
+            mate_in,
+            unique: keys.len() == 1,
+            keys,
+            nodes: self.nodes,
+        }))
+    }
+
+    fn count_node(&mut self) -> Result<(), ChessError> {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(CHECK_INTERVAL)
+            && (self.stop.is_stopped() || self.node_limit.is_some_and(|limit| self.nodes >= limit))
+        {
+            return Err(ChessError::Timeout(format!("Mate search stopped after {} nodes", self.nodes)));
+        }
+        Ok(())
+    }
+
+    // Legal moves with checks first, then captures, then the rest
+    fn ordered_moves(&self, board: &Board, color: Color) -> Result<Vec<(ChessMove, Board, bool)>, ChessError> {
+        let mut moves: Vec<(ChessMove, Board, bool)> = self.engine.get_legal_moves(board, color)?.into_iter()
+            .map(|m| {
+                let mut after = board.clone();
+                let _ = after.make_move(&m.from, &m.to);
+                let check = is_in_check(&after, color.opposite());
+                (m, after, check)
+            })
+            .collect();
+        moves.sort_by_key(|(m, _, check)| (!check, board.get_piece(&m.to).is_none()));
+        Ok(moves)
+    }
+
+    fn shortest_mate(&mut self, board: &Board, attacker: Color, max_moves: u32, goal: Goal) -> Result<Option<u32>, ChessError> {
+        for moves in 1..=max_moves {
+            if self.mates(board, attacker, moves, goal)? {
+                return Ok(Some(moves));
+            }
+        }
+        Ok(None)
+    }
+
+    // Whether the attacker, to move, reaches its goal within `moves` moves
+    fn mates(&mut self, board: &Board, attacker: Color, moves: u32, goal: Goal) -> Result<bool, ChessError> {
+        self.count_node()?;
+        let key = zobrist::hash(board, attacker);
+        let known = self.table.get(&key).copied().unwrap_or_default();
+        if known.mate_within.is_some_and(|n| n <= moves) {
+            return Ok(true);
+        }
+        if known.no_mate_within >= moves {
+            return Ok(false);
+        }
+
+        let mut found = false;
+        for (_, after, check) in self.ordered_moves(board, attacker)? {
+            // The last move of a direct mate has to give check
+            if goal == Goal::Mate && moves == 1 && !check {
+                break;
+            }
+            if self.defence_fails(&after, attacker, moves, goal)? {
+                found = true;
+                break;
+            }
+        }
+
+        let entry = self.table.entry(key).or_default();
+        if found {
+            entry.mate_within = Some(entry.mate_within.map_or(moves, |n| n.min(moves)));
+        } else {
+            entry.no_mate_within = entry.no_mate_within.max(moves);
+        }
+        Ok(found)
+    }
+
+    // Whether every defence, with the defender to move, still leads to the
+    // goal. The attacker has `moves` moves including the one just played.
+    fn defence_fails(&mut self, board: &Board, attacker: Color, moves: u32, goal: Goal) -> Result<bool, ChessError> {
+        let defender = attacker.opposite();
+        let replies = self.engine.get_legal_moves(board, defender)?;
+        if replies.is_empty() {
+            // Stalemate is no mate, and a mated defender can't selfmate anyone
+            return Ok(goal == Goal::Mate && is_in_check(board, defender));
+        }
+        if goal == Goal::Mate && moves == 1 {
+            return Ok(false);
+        }
+        for reply in replies {
+            let mut after = board.clone();
+            after.make_move(&reply.from, &reply.to)?;
+            // In a selfmate the defender is forced to mate, now or later
+            if goal == Goal::Selfmate && self.is_mated(&after, attacker)? {
+                continue;
+            }
+            if moves == 1 || !self.mates(&after, attacker, moves - 1, goal)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn is_mated(&self, board: &Board, color: Color) -> Result<bool, ChessError> {
+        Ok(is_in_check(board, color) && self.engine.get_legal_moves(board, color)?.is_empty())
+    }
+
+    // Every attacker move that reaches the goal within `moves` moves
+    fn mating_moves(&mut self, board: &Board, attacker: Color, moves: u32, goal: Goal) -> Result<Vec<ChessMove>, ChessError> {
+        let mut mating = Vec::new();
+        for (chess_move, after, check) in self.ordered_moves(board, attacker)? {
+            if (goal == Goal::Selfmate || moves > 1 || check) && self.defence_fails(&after, attacker, moves, goal)? {
+                mating.push(chess_move);
+            }
+        }
+        Ok(mating)
+    }
+
+    // The tree below a solving move: every defence, each answered by the fastest
+    // continuation. Equally fast alternatives are listed as duals rather than expanded.
+    fn solution_tree(
+        &mut self,
+        board: &Board,
+        attacker: Color,
+        chess_move: &ChessMove,
+        moves: u32,
+        goal: Goal,
+        duals: Vec<String>,
+    ) -> Result<MateMove, ChessError> {
+        let san = self.engine.to_algebraic_notation(chess_move, board);
+        let mut after = board.clone();
+        after.make_move(&chess_move.from, &chess_move.to)?;
+        let defender = attacker.opposite();
+
+        let mut defences = Vec::new();
+        for reply in self.engine.get_legal_moves(&after, defender)? {
+            let reply_san = self.engine.to_algebraic_notation(&reply, &after);
+            let mut position = after.clone();
+            position.make_move(&reply.from, &reply.to)?;
+            if goal == Goal::Selfmate && self.is_mated(&position, attacker)? {
+                defences.push(MateDefence { uci: reply.to_uci(), san: reply_san, mate_in: 0, continuation: None });
+                continue;
+            }
+            let mate_in = self.shortest_mate(&position, attacker, moves - 1, goal)?
+                .ok_or_else(|| ChessError::Internal(format!("Lost the solution after {} {}", san, reply_san)))?;
+            let mut continuations = self.mating_moves(&position, attacker, mate_in, goal)?;
+            let best = continuations.remove(0);
+            let duals = continuations.iter().map(|m| self.engine.to_algebraic_notation(m, &position)).collect();
+            defences.push(MateDefence {
+                uci: reply.to_uci(),
+                san: reply_san,
+                mate_in,
+                continuation: Some(self.solution_tree(&position, attacker, &best, mate_in, goal, duals)?),
+            });
+        }
+        // The longest-resisting defences first
+        defences.sort_by_key(|d| std::cmp::Reverse(d.mate_in));
+
+        Ok(MateMove { uci: chess_move.to_uci(), san, duals, defences })
+    }
+
+    // Every helpmate in exactly `plies` single moves: the side to move and its
+    // opponent cooperate so that the last move mates. With an even number of
+    // plies the side to move is the one mated. Also looks for shorter solutions
+    // with the same side starting, which cook the problem.
+    pub fn solve_helpmate(&mut self, game: &Game, plies: u32) -> Result<HelpmateSolution, ChessError> {
+        self.nodes = 0;
+        self.help_failures.clear();
+        let mut solutions = Vec::new();
+        self.helpmates(&game.board, game.current_turn, plies, &mut Vec::new(), &mut solutions)?;
+
+        let mut short_solutions = Vec::new();
+        let mut shorter = plies;
+        while shorter > 2 && short_solutions.is_empty() {
+            shorter -= 2;
+            self.helpmates(&game.board, game.current_turn, shorter, &mut Vec::new(), &mut short_solutions)?;
+        }
+
+        // The same final position reached in a different order
+        let mut finals: Vec<(u64, Vec<usize>)> = Vec::new();
+        for (i, line) in solutions.iter().enumerate() {
+            let mut board = game.board.clone();
+            for chess_move in line {
+                board.make_move(&chess_move.from, &chess_move.to)?;
+            }
+            let key = zobrist::hash(&board, game.current_turn);
+            match finals.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, group)) => group.push(i),
+                None => finals.push((key, vec![i])),
+            }
+        }
+        let duplicates = finals.into_iter().map(|(_, group)| group).filter(|group| group.len() > 1).collect();
+
+        Ok(HelpmateSolution {
+            fen: game.to_fen(),
+            stipulation: Stipulation::Helpmate(plies).to_string(),
+            solutions: solutions.iter().map(|line| self.helpmate_line(game, line)).collect(),
+            short_solutions: short_solutions.iter().map(|line| self.helpmate_line(game, line)).collect(),
+            duplicates,
+            nodes: self.nodes,
+        })
+    }
+
+    // Collect every sequence of `plies` moves from here that ends in mate.
+    // Returns whether there was any.
+    fn helpmates(
+        &mut self,
+        board: &Board,
+        to_move: Color,
+        plies: u32,
+        line: &mut Vec<ChessMove>,
+        solutions: &mut Vec<Vec<ChessMove>>,
+    ) -> Result<bool, ChessError> {
+        self.count_node()?;
+        let key = (zobrist::hash(board, to_move), plies);
+        if self.help_failures.contains(&key) {
+            return Ok(false);
+        }
+        let mut found = false;
+        for (chess_move, after, check) in self.ordered_moves(board, to_move)? {
+            line.push(chess_move);
+            if plies == 1 {
+                // Only checks can mate, and they come first
+                if !check {
+                    line.pop();
+                    break;
+                }
+                if self.is_mated(&after, to_move.opposite())? {
+                    solutions.push(line.clone());
+                    found = true;
+                }
+            } else if self.helpmates(&after, to_move.opposite(), plies - 1, line, solutions)? {
+                found = true;
+            }
+            line.pop();
+        }
+        if !found {
+            self.help_failures.insert(key);
+        }
+        Ok(found)
+    }
+
+    fn helpmate_line(&self, game: &Game, line: &[ChessMove]) -> HelpmateLine {
+        let san = self.engine.line_to_algebraic_notation(line, &game.board);
+        // Moves go in pairs starting with the mated side, so an odd-length
+        // helpmate opens with the mating side's half move
+        let mut notation = String::new();
+        let offset = line.len() % 2;
+        for (i, text) in san.iter().enumerate() {
+            let ply = i + offset;
+            if i == 0 && offset == 1 {
+                notation.push_str(&format!("1...{}", text));
+            } else if ply.is_multiple_of(2) {
+                notation.push_str(&format!(" {}.{}", ply / 2 + 1, text));
+            } else {
+                notation.push_str(&format!(" {}", text));
+            }
+        }
+        HelpmateLine {
+            moves: line.iter().map(|m| m.to_uci()).collect(),
+            san,
+            notation: notation.trim().to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Philidor's legacy: the queen is sacrificed for a smothered mate
+    const PHILIDOR: &str = "4r2k/6pp/8/3Q2N1/8/8/5PPP/6K1 w - - 0 1";
+
+    fn solve(fen: &str, max_moves: u32) -> Option<MateSolution> {
+        MateSearch::new().solve(&Game::from_fen(fen).unwrap(), max_moves).unwrap()
+    }
+
+    #[test]
+    fn smothered_mate_in_four() {
+        let solution = solve(PHILIDOR, 4).expect("no mate found");
+        assert_eq!(solution.mate_in, 4);
+        assert!(solution.unique);
+        let key = &solution.keys[0];
+        assert_eq!((key.uci.as_str(), key.san.as_str()), ("g5f7", "Nf7+"));
+
+        // 1.Nf7+ Kg8 2.Nh6+ Kh8 3.Qg8+ Rxg8 4.Nf7#
+        let mut line = Vec::new();
+        let mut node = Some(key);
+        while let Some(chess_move) = node {
+            line.push(chess_move.san.clone());
+            let defence = chess_move.defences.first();
+            line.extend(defence.map(|d| d.san.clone()));
+            node = defence.and_then(|d| d.continuation.as_ref());
+        }
+        assert_eq!(line, ["Nf7+", "Kg8", "Nh6+", "Kh8", "Qg8+", "Rxg8", "Nf7#"]);
+    }
+
+    #[test]
+    fn no_mate_in_two() {
+        assert!(solve(PHILIDOR, 2).is_none());
+    }
+
+    #[test]
+    fn mate_in_one() {
+        let solution = solve("7k/8/6K1/8/8/8/8/R7 w - - 0 1", 3).unwrap();
+        assert_eq!(solution.mate_in, 1);
+        assert_eq!(solution.keys.iter().map(|k| k.uci.as_str()).collect::<Vec<_>>(), ["a1a8"]);
+        assert!(solution.keys[0].defences.is_empty());
+    }
+
+    #[test]
+    fn node_limit_times_out() {
+        let mut solver = MateSearch::new();
+        solver.set_node_limit(Some(CHECK_INTERVAL));
+        let result = solver.solve(&Game::from_fen(PHILIDOR).unwrap(), 4);
+        assert!(matches!(result, Err(ChessError::Timeout(_))));
+    }
+
+    #[test]
+    fn selfmate_in_one() {
+        // 1.Qb6+ forces Kxb6, uncovering the rook's mate on the a-file
+        let game = Game::from_fen("r7/k7/6Q1/8/8/8/1P6/KR6 w - - 0 1").unwrap();
+        let solution = MateSearch::new().solve_selfmate(&game, 2).unwrap().expect("no selfmate found");
+        assert_eq!(solution.mate_in, 1);
+        assert!(solution.unique);
+        let key = &solution.keys[0];
+        assert_eq!(key.san, "Qb6+");
+        let defences: Vec<(&str, u32)> = key.defences.iter().map(|d| (d.san.as_str(), d.mate_in)).collect();
+        assert_eq!(defences, [("Kxb6#", 0)]);
+        // Not a direct mate
+        assert!(MateSearch::new().solve(&game, 1).unwrap().is_none());
+    }
+
+    #[test]
+    fn helpmates() {
+        let game = Game::from_fen("7k/8/6K1/8/8/8/8/R7 b - - 0 1").unwrap();
+        let mut solver = MateSearch::new();
+
+        let h1 = solver.solve_helpmate(&game, 2).unwrap();
+        assert_eq!(h1.stipulation, "h#1");
+        let notation: Vec<&str> = h1.solutions.iter().map(|line| line.notation.as_str()).collect();
+        assert_eq!(notation, ["1.Kg8 Ra8#"]);
+
+        // After 1.Kg8 White waits with the rook on the a-file (6) or moves it to
+        // mate on another file (5), or steps the king to h6 or f6 to cover the flight squares
+        let h2 = solver.solve_helpmate(&game, 4).unwrap();
+        assert_eq!(h2.stipulation, "h#2");
+        assert_eq!(h2.solutions.len(), 13);
+        assert!(h2.solutions.iter().all(|line| line.san[0] == "Kg8" && line.san[3].ends_with('#')));
+        for notation in ["1.Kg8 Ra5 2.Kh8 Ra8#", "1.Kg8 Rd1 2.Kh8 Rd8#", "1.Kg8 Kh6 2.Kh8 Ra8#", "1.Kg8 Kf6 2.Kf8 Ra8#"] {
+            assert!(h2.solutions.iter().any(|line| line.notation == notation), "{}", notation);
+        }
+        // The h#1 cooks it
+        assert_eq!(h2.short_solutions.len(), 1);
+        // The six rook moves along the a-file all end in the same mate
+        assert_eq!(h2.duplicates.len(), 1);
+        assert_eq!(h2.duplicates[0].len(), 6);
+    }
+
+    #[test]
+    fn stipulations() {
+        assert_eq!("#3".parse::<Stipulation>().unwrap(), Stipulation::Direct(3));
+        assert_eq!("s#2".parse::<Stipulation>().unwrap(), Stipulation::Selfmate(2));
+        assert_eq!("h#2.5".parse::<Stipulation>().unwrap(), Stipulation::Helpmate(5));
+        assert_eq!(Stipulation::Helpmate(5).to_string(), "h#2.5");
+        for invalid in ["#0", "#-1", "h#0", "h#2.4", "3", "s#x"] {
+            assert!(invalid.parse::<Stipulation>().is_err(), "{}", invalid);
+        }
+    }
+}
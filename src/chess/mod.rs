@@ -3,9 +3,53 @@ mod piece;
 mod position;
 mod game;
 mod engine;
+mod tt;
+mod zobrist;
+mod uci;
+mod search;
+mod ponder;
+mod polyglot;
+mod book;
+mod syzygy;
+mod syzygy_encode;
+mod dtm;
+mod skill;
+mod eval;
+mod tuning;
+mod stats;
+mod arena;
+mod tournament;
+mod epd;
+mod mate;
+mod see;
+mod wdl;
+mod endgame;
+#[cfg(feature = "nnue")]
+mod nnue;
 
 pub use board::Board;
 pub use piece::{Piece, PieceType, Color};
 pub use position::Position;
 pub use game::{Game, GameStatus};
-pub use engine::{Engine, ChessMove};
+pub use engine::{Engine, ChessMove, MATE_SCORE, MAX_SEARCH_DEPTH, TB_WIN_SCORE, is_in_check, is_square_attacked, find_king};
+pub use uci::{UciBackend, UciEngine, UciEngineConfig, UciGoLimits, UciInfo, UciScore, UciSearchResult};
+pub use search::{SearchBackend, SearchLimits, SearchResult, PvLine, StopSignal, BackendRegistry, RandomMover, GreedyMover, DEFAULT_BACKEND};
+pub use ponder::{Ponderer, PonderJob};
+pub use book::{OpeningBook, BookSelection, BookEntry, BookMove};
+pub use polyglot::polyglot_key;
+pub use syzygy::{SyzygyTablebase, TablebaseMove, Wdl};
+pub use syzygy_encode::encode_syzygy;
+pub use eval::{EvalParams, EvalTerm, EvalTrace, PERSONALITIES, MAX_PHASE, game_phase};
+pub use tuning::{TuningPosition, load_epd, load_pgn, is_quiet, parse_result, sigmoid, tuning_error, fit_k, tune};
+pub use skill::{Skill, MAX_SKILL_LEVEL, MIN_ELO, MAX_ELO};
+#[cfg(feature = "nnue")]
+pub use nnue::{Network, Accumulator, NNUE_INPUTS};
+pub use stats::{MatchScore, Sprt, SprtVerdict, Rating, RatedGame, bayeselo, elo_to_score, score_to_elo};
+pub use arena::{EngineSpec, TimeControl, Adjudication, GameResult, GameRecord, play_game, load_openings, random_opening, insufficient_material};
+pub use mate::{MateSearch, MateSolution, MateMove, MateDefence, Stipulation, HelpmateSolution, HelpmateLine};
+pub use endgame::material_signature;
+pub use wdl::{WdlModel, WdlChances};
+pub use see::{see, hanging_pieces};
+pub use epd::{EpdPosition, load_epd_suite};
+pub use tournament::{Tournament, TournamentFormat, Entrant, Pairing, schedule};
+pub use dtm::{DtmTablebase, DtmTable, DtmMove, DtmStats, Dtm, DTM_PIECES, dtm_material};
@@ -0,0 +1,395 @@
+use crate::chess::{Board, ChessMove, Color, Piece, PieceType, Position};
+use crate::error::ChessError;
+use std::fs;
+use std::path::Path;
+
+// An efficiently updatable neural network evaluation. The network is the simple
+// perspective design trainers such as bullet produce:
+//
+//   768 inputs (colour relative to the perspective x 6 piece types x 64 squares)
+//     -> hidden layer of N neurons, computed once per perspective (the accumulators)
+//     -> clipped ReLU on [0, QA]
+//     -> one output over both accumulators, side to move first
+//
+// The accumulators only change by a few weight columns per move, so the search
+// updates them incrementally instead of recomputing them at every node.
+//
+// The file holds little-endian int16 values, and nothing else: the feature
+// weights (768 x N, one row of N per feature), the feature biases (N), the output
+// weights (2 x N) and the output bias. Trainers pad the file to a multiple of 64
+// bytes, so N is worked out from the file size.
+
+pub const NNUE_INPUTS: usize = 768;
+// Quantisation of the hidden layer and of the output weights
+const QA: i32 = 255;
+const QB: i32 = 64;
+// Centipawns per unit of network output
+const SCALE: i32 = 400;
+// Trainers pad network files to a multiple of this
+const FILE_ALIGNMENT: usize = 64;
+
+// Index of the input for a piece on a square, seen from one side. Black sees the
+// board flipped, so both sides share the weights.
+fn feature_index(perspective: Color, piece: Piece, pos: &Position) -> usize {
+    let square = (pos.rank as usize) * 8 + pos.file as usize;
+    let (relative_color, square) = match perspective {
+        Color::White => (piece.color != Color::White, square),
+        Color::Black => (piece.color != Color::Black, square ^ 56),
+    };
+    let piece_type = match piece.piece_type {
+        PieceType::Pawn => 0,
+        PieceType::Knight => 1,
+        PieceType::Bishop => 2,
+        PieceType::Rook => 3,
+        PieceType::Queen => 4,
+        PieceType::King => 5,
+    };
+    relative_color as usize * 384 + piece_type * 64 + square
+}
+
+#[derive(Debug, Clone)]
+pub struct Network {
+    hidden: usize,
+    // NNUE_INPUTS rows of `hidden` weights
+    feature_weights: Vec<i16>,
+    feature_bias: Vec<i16>,
+    // `hidden` weights for the side to move's accumulator, then `hidden` for the other
+    output_weights: Vec<i16>,
+    output_bias: i16,
+    // Whether this CPU has the vector instructions the fast paths use
+    simd: bool,
+}
+
+// The hidden layer before activation, from each side's point of view
+#[derive(Debug, Clone, Default)]
+pub struct Accumulator {
+    white: Vec<i16>,
+    black: Vec<i16>,
+}
+
+impl Network {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ChessError> {
+        let path = path.as_ref();
+        let bytes = fs::read(path)
+            .map_err(|e| ChessError::Nnue(format!("Failed to read {}: {}", path.display(), e)))?;
+        Self::from_bytes(&bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ChessError> {
+        // 768 N + N + 2 N + 1 values, possibly followed by zeros up to the alignment.
+        // Anything else is a damaged file, even when it happens to be long enough
+        // for a smaller network.
+        let values = bytes.len() / 2;
+        let hidden = values.saturating_sub(1) / (NNUE_INPUTS + 3);
+        let used = ((NNUE_INPUTS + 3) * hidden + 1) * 2;
+        let padded = bytes.len() == used.next_multiple_of(FILE_ALIGNMENT) && bytes[used..].iter().all(|&b| b == 0);
+        if hidden == 0 || (bytes.len() != used && !padded) {
+            return Err(ChessError::Nnue(format!(
+                "{} bytes is not the size of a {}-input network", bytes.len(), NNUE_INPUTS
+            )));
+        }
+
+        let mut values = bytes.chunks_exact(2).map(|b| i16::from_le_bytes([b[0], b[1]]));
+        let mut take = |n: usize| -> Vec<i16> { values.by_ref().take(n).collect() };
+        let feature_weights = take(NNUE_INPUTS * hidden);
+        let feature_bias = take(hidden);
+        let output_weights = take(2 * hidden);
+        let output_bias = take(1)[0];
+
+        Ok(Self {
+            hidden,
+            feature_weights,
+            feature_bias,
+            output_weights,
+            output_bias,
+            simd: simd::available(),
+        })
+    }
+
+    // Number of hidden neurons per perspective
+    pub fn hidden_size(&self) -> usize {
+        self.hidden
+    }
+
+    fn weights(&self, feature: usize) -> &[i16] {
+        &self.feature_weights[feature * self.hidden..(feature + 1) * self.hidden]
+    }
+
+    // Accumulators for a position, computed from scratch
+    pub fn refresh(&self, board: &Board) -> Accumulator {
+        let mut accumulator = Accumulator {
+            white: self.feature_bias.clone(),
+            black: self.feature_bias.clone(),
+        };
+        for rank in 0..8 {
+            for file in 0..8 {
+                let pos = Position::new(file, rank);
+                if let Some(piece) = board.get_piece(&pos) {
+                    self.add_feature(&mut accumulator, piece, &pos);
+                }
+            }
+        }
+        accumulator
+    }
+
+    // Set `next` to the accumulators after `chess_move` is played from `board`,
+    // the position `previous` belongs to. Only the moved and captured pieces'
+    // weights are touched; castling, promotion and en passant never happen here.
+    pub fn update(&self, previous: &Accumulator, next: &mut Accumulator, board: &Board, chess_move: &ChessMove) {
+        next.white.clone_from(&previous.white);
+        next.black.clone_from(&previous.black);
+        let piece = match board.get_piece(&chess_move.from) {
+            Some(piece) => piece,
+            None => return,
+        };
+        if let Some(captured) = board.get_piece(&chess_move.to) {
+            self.sub_feature(next, captured, &chess_move.to);
+        }
+        self.sub_feature(next, piece, &chess_move.from);
+        self.add_feature(next, piece, &chess_move.to);
+    }
+
+    fn add_feature(&self, accumulator: &mut Accumulator, piece: Piece, pos: &Position) {
+        let white = self.weights(feature_index(Color::White, piece, pos));
+        let black = self.weights(feature_index(Color::Black, piece, pos));
+        if self.simd {
+            simd::add(&mut accumulator.white, white);
+            simd::add(&mut accumulator.black, black);
+        } else {
+            scalar::add(&mut accumulator.white, white);
+            scalar::add(&mut accumulator.black, black);
+        }
+    }
+
+    fn sub_feature(&self, accumulator: &mut Accumulator, piece: Piece, pos: &Position) {
+        let white = self.weights(feature_index(Color::White, piece, pos));
+        let black = self.weights(feature_index(Color::Black, piece, pos));
+        if self.simd {
+            simd::sub(&mut accumulator.white, white);
+            simd::sub(&mut accumulator.black, black);
+        } else {
+            scalar::sub(&mut accumulator.white, white);
+            scalar::sub(&mut accumulator.black, black);
+        }
+    }
+
+    // Evaluation in centipawns from the point of view of `color`, the side to move
+    pub fn evaluate(&self, accumulator: &Accumulator, color: Color) -> i32 {
+        let (us, them) = match color {
+            Color::White => (&accumulator.white, &accumulator.black),
+            Color::Black => (&accumulator.black, &accumulator.white),
+        };
+        let (our_weights, their_weights) = self.output_weights.split_at(self.hidden);
+        let sum = if self.simd {
+            simd::crelu_dot(us, our_weights) + simd::crelu_dot(them, their_weights)
+        } else {
+            scalar::crelu_dot(us, our_weights) + scalar::crelu_dot(them, their_weights)
+        };
+        (sum + self.output_bias as i32) * SCALE / (QA * QB)
+    }
+
+    // Evaluation of a position without an up-to-date accumulator
+    pub fn evaluate_board(&self, board: &Board, color: Color) -> i32 {
+        self.evaluate(&self.refresh(board), color)
+    }
+}
+
+// Plain loops, used wherever the vector instructions aren't available
+mod scalar {
+    use super::QA;
+
+    pub fn add(accumulator: &mut [i16], weights: &[i16]) {
+        for (a, w) in accumulator.iter_mut().zip(weights) {
+            *a = a.wrapping_add(*w);
+        }
+    }
+
+    pub fn sub(accumulator: &mut [i16], weights: &[i16]) {
+        for (a, w) in accumulator.iter_mut().zip(weights) {
+            *a = a.wrapping_sub(*w);
+        }
+    }
+
+    // Sum of clamp(x, 0, QA) * w
+    pub fn crelu_dot(values: &[i16], weights: &[i16]) -> i32 {
+        values.iter().zip(weights)
+            .map(|(&x, &w)| (x as i32).clamp(0, QA) * w as i32)
+            .sum()
+    }
+}
+
+// AVX2 versions of the scalar loops, 16 values at a time
+#[cfg(target_arch = "x86_64")]
+mod simd {
+    use super::{scalar, QA};
+    use std::arch::x86_64::*;
+
+    const LANES: usize = 16;
+
+    pub fn available() -> bool {
+        is_x86_feature_detected!("avx2")
+    }
+
+    pub fn add(accumulator: &mut [i16], weights: &[i16]) {
+        // SAFETY: only called once `available` has confirmed AVX2
+        unsafe { add_avx2(accumulator, weights) }
+    }
+
+    pub fn sub(accumulator: &mut [i16], weights: &[i16]) {
+        // SAFETY: only called once `available` has confirmed AVX2
+        unsafe { sub_avx2(accumulator, weights) }
+    }
+
+    pub fn crelu_dot(values: &[i16], weights: &[i16]) -> i32 {
+        // SAFETY: only called once `available` has confirmed AVX2
+        unsafe { crelu_dot_avx2(values, weights) }
+    }
+
+    #[target_feature(enable = "avx2")]
+    unsafe fn add_avx2(accumulator: &mut [i16], weights: &[i16]) {
+        let chunks = accumulator.len().min(weights.len()) / LANES;
+        for i in 0..chunks {
+            let a = accumulator.as_mut_ptr().add(i * LANES) as *mut __m256i;
+            let w = weights.as_ptr().add(i * LANES) as *const __m256i;
+            _mm256_storeu_si256(a, _mm256_add_epi16(_mm256_loadu_si256(a), _mm256_loadu_si256(w)));
+        }
+        scalar::add(&mut accumulator[chunks * LANES..], &weights[chunks * LANES..]);
+    }
+
+    #[target_feature(enable = "avx2")]
+    unsafe fn sub_avx2(accumulator: &mut [i16], weights: &[i16]) {
+        let chunks = accumulator.len().min(weights.len()) / LANES;
+        for i in 0..chunks {
+            let a = accumulator.as_mut_ptr().add(i * LANES) as *mut __m256i;
+            let w = weights.as_ptr().add(i * LANES) as *const __m256i;
+            _mm256_storeu_si256(a, _mm256_sub_epi16(_mm256_loadu_si256(a), _mm256_loadu_si256(w)));
+        }
+        scalar::sub(&mut accumulator[chunks * LANES..], &weights[chunks * LANES..]);
+    }
+
+    #[target_feature(enable = "avx2")]
+    unsafe fn crelu_dot_avx2(values: &[i16], weights: &[i16]) -> i32 {
+        let chunks = values.len().min(weights.len()) / LANES;
+        let zero = _mm256_setzero_si256();
+        let qa = _mm256_set1_epi16(QA as i16);
+        let mut sum = _mm256_setzero_si256();
+        for i in 0..chunks {
+            let x = _mm256_loadu_si256(values.as_ptr().add(i * LANES) as *const __m256i);
+            let w = _mm256_loadu_si256(weights.as_ptr().add(i * LANES) as *const __m256i);
+            let clipped = _mm256_min_epi16(_mm256_max_epi16(x, zero), qa);
+            // Multiplies pairs of 16-bit lanes and adds each pair into a 32-bit lane
+            sum = _mm256_add_epi32(sum, _mm256_madd_epi16(clipped, w));
+        }
+        let mut lanes = [0i32; 8];
+        _mm256_storeu_si256(lanes.as_mut_ptr() as *mut __m256i, sum);
+        lanes.iter().sum::<i32>() + scalar::crelu_dot(&values[chunks * LANES..], &weights[chunks * LANES..])
+    }
+}
+
+// Other targets always take the scalar path
+#[cfg(not(target_arch = "x86_64"))]
+mod simd {
+    pub fn available() -> bool {
+        false
+    }
+
+    pub use super::scalar::{add, sub, crelu_dot};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+
+    // Not a multiple of the 16 AVX2 lanes, so the scalar tail is used too
+    const HIDDEN: usize = 40;
+
+    // A network file with random weights, padded like a trainer's
+    fn network_bytes(seed: u64) -> Vec<u8> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        let values = (NNUE_INPUTS + 3) * HIDDEN + 1;
+        let mut bytes: Vec<u8> = (0..values).flat_map(|_| rng.gen_range(-120i16..120).to_le_bytes()).collect();
+        bytes.resize(bytes.len().next_multiple_of(FILE_ALIGNMENT), 0);
+        bytes
+    }
+
+    fn assert_same(a: &Accumulator, b: &Accumulator) {
+        assert_eq!(a.white, b.white);
+        assert_eq!(a.black, b.black);
+    }
+
+    #[test]
+    fn updates_match_a_refresh() {
+        let network = Network::from_bytes(&network_bytes(39)).unwrap();
+        let mut board = Board::new();
+        let mut accumulator = network.refresh(&board);
+        // Quiet moves and captures for both sides
+        for uci in ["e2e4", "d7d5", "e4d5", "d8d5", "b1c3", "d5a2", "a1a2", "g8f6"] {
+            let chess_move: ChessMove = uci.parse().unwrap();
+            let mut next = Accumulator::default();
+            network.update(&accumulator, &mut next, &board, &chess_move);
+            board.make_move(&chess_move.from, &chess_move.to).unwrap();
+            assert_same(&next, &network.refresh(&board));
+            accumulator = next;
+        }
+        for color in [Color::White, Color::Black] {
+            assert_eq!(network.evaluate(&accumulator, color), network.evaluate_board(&board, color));
+        }
+    }
+
+    #[test]
+    fn vector_and_scalar_paths_agree() {
+        if !simd::available() {
+            return;
+        }
+        let mut rng = rand::rngs::StdRng::seed_from_u64(39);
+        for len in [HIDDEN, 256, 7] {
+            // Values well outside [0, QA] so the clipping matters
+            let values: Vec<i16> = (0..len).map(|_| rng.gen_range(-600..600)).collect();
+            let weights: Vec<i16> = (0..len).map(|_| rng.gen_range(-600..600)).collect();
+            assert_eq!(simd::crelu_dot(&values, &weights), scalar::crelu_dot(&values, &weights));
+            let (mut vector, mut plain) = (values.clone(), values.clone());
+            simd::add(&mut vector, &weights);
+            scalar::add(&mut plain, &weights);
+            assert_eq!(vector, plain);
+            simd::sub(&mut vector, &weights);
+            scalar::sub(&mut plain, &weights);
+            assert_eq!(vector, values);
+            assert_eq!(plain, values);
+        }
+
+        // The same network either way, on the same accumulators
+        let vector = Network::from_bytes(&network_bytes(39)).unwrap();
+        let plain = Network { simd: false, ..vector.clone() };
+        let mut board = Board::new();
+        for uci in ["e2e4", "d7d5", "e4d5", "d8d5"] {
+            let chess_move: ChessMove = uci.parse().unwrap();
+            let mut from_vector = Accumulator::default();
+            let mut from_plain = Accumulator::default();
+            vector.update(&vector.refresh(&board), &mut from_vector, &board, &chess_move);
+            plain.update(&plain.refresh(&board), &mut from_plain, &board, &chess_move);
+            assert_same(&from_vector, &from_plain);
+            board.make_move(&chess_move.from, &chess_move.to).unwrap();
+            for color in [Color::White, Color::Black] {
+                assert_eq!(vector.evaluate(&from_vector, color), plain.evaluate(&from_vector, color));
+            }
+        }
+    }
+
+    #[test]
+    fn file_sizes() {
+        let bytes = network_bytes(39);
+        let network = Network::from_bytes(&bytes).unwrap();
+        assert_eq!(network.hidden_size(), HIDDEN);
+        // Without the padding
+        let unpadded = ((NNUE_INPUTS + 3) * HIDDEN + 1) * 2;
+        assert_eq!(Network::from_bytes(&bytes[..unpadded]).unwrap().hidden_size(), HIDDEN);
+        // Truncated, or not the size of any network
+        for len in [unpadded - 2, unpadded - 100, bytes.len() / 2, 1000, 2, 0] {
+            assert!(Network::from_bytes(&bytes[..len]).is_err(), "{} bytes", len);
+        }
+        let mut longer = bytes.clone();
+        longer.extend([0; FILE_ALIGNMENT]);
+        assert!(Network::from_bytes(&longer).is_err());
+    }
+}
@@ -47,6 +47,21 @@ impl Piece {
         Self { piece_type, color }
     }
     
+    // Parse a FEN piece letter: uppercase is white, lowercase is black
+    pub fn from_char(c: char) -> Option<Self> {
+        let piece_type = match c.to_ascii_lowercase() {
+            'p' => PieceType::Pawn,
+            'n' => PieceType::Knight,
+            'b' => PieceType::Bishop,
+            'r' => PieceType::Rook,
+            'q' => PieceType::Queen,
+            'k' => PieceType::King,
+            _ => return None,
+        };
+        let color = if c.is_ascii_uppercase() { Color::White } else { Color::Black };
+        Some(Self::new(piece_type, color))
+    }
+    
     pub fn to_char(&self) -> char {
         let c = match self.piece_type {
             PieceType::Pawn => 'p',
@@ -0,0 +1,126 @@
+use crate::chess::{Game, ChessMove, SearchBackend, SearchLimits, SearchResult, StopSignal};
+use crate::error::ChessError;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+type PonderOutcome = (Box<dyn SearchBackend>, Result<SearchResult, ChessError>);
+
+// The ponder search itself, for whatever runs searches in the background
+pub type PonderJob = Box<dyn FnOnce() + Send>;
+
+// A speculative search of the position after the reply the engine expects,
+// run in the background while the opponent thinks
+pub struct Ponderer {
+    ponder_move: ChessMove,
+    // Position being searched, with the ponder move already played
+    game: Game,
+    // Limits of the real search; the clock only starts on a ponder hit
+    limits: SearchLimits,
+    stop: StopSignal,
+    result: Receiver<PonderOutcome>,
+}
+
+impl Ponderer {
+    // Start pondering `ponder_move` in `game`, handing the search to `spawn` to run.
+    // Depth and node limits apply as they are. A move time only starts on a ponder
+    // hit, so until then the search runs as long as the optional safety cap allows.
+    pub fn start<S>(
+        mut backend: Box<dyn SearchBackend>,
+        game: &Game,
+        ponder_move: &ChessMove,
+        limits: SearchLimits,
+        max_ponder_ms: Option<u64>,
+        spawn: S,
+    ) -> Result<Self, ChessError>
+    where
+        S: FnOnce(PonderJob),
+    {
+        let mut ponder_game = game.clone();
+        ponder_game.make_move(&ponder_move.from.to_string(), &ponder_move.to.to_string())?;
+        
+        let stop = StopSignal::new();
+        backend.set_stop_signal(stop.clone());
+        
+        // The cap only goes on limited searches: a move time would also lift the
+        // depth of a search that otherwise runs to the backend's default depth
+        let ponder_limits = if limits.movetime_ms.is_some() || limits.depth.is_some() || limits.nodes.is_some() {
+            SearchLimits {
+                movetime_ms: max_ponder_ms,
+                ..limits
+            }
+        } else {
+            limits
+        };
+        let (sender, result) = mpsc::channel();
+        let search_game = ponder_game.clone();
+        spawn(Box::new(move || {
+            let outcome = backend.search(&search_game, &ponder_limits);
+            // Nobody is waiting if the ponderer was dropped
+            let _ = sender.send((backend, outcome));
+        }));
+        
+        Ok(Self {
+            ponder_move: ponder_move.clone(),
+            game: ponder_game,
+            limits,
+            stop,
+            result,
+        })
+    }
+    
+    pub fn ponder_move(&self) -> &ChessMove {
+        &self.ponder_move
+    }
+    
+    // Stops the ponder search, or the real search after a hit
+    pub fn stop_signal(&self) -> StopSignal {
+        self.stop.clone()
+    }
+    
+    // Whether the opponent played the expected move, leaving `game` in the pondered position
+    pub fn is_hit(&self, game: &Game) -> bool {
+        game.to_fen() == self.game.to_fen()
+    }
+    
+    // Ponder hit: the speculative search becomes the real one. Waits out the
+    // real search's movetime, if it has one, then takes the best move so far.
+    // Otherwise returns as soon as the search meets the real depth or node limits.
+    pub fn hit(self) -> Result<(Box<dyn SearchBackend>, SearchResult), ChessError> {
+        let outcome = match self.limits.movetime_ms {
+            Some(ms) => match self.result.recv_timeout(Duration::from_millis(ms)) {
+                Ok(outcome) => Ok(outcome),
+                Err(RecvTimeoutError::Timeout) => {
+                    self.stop.stop();
+                    self.result.recv()
+                },
+                Err(RecvTimeoutError::Disconnected) => self.result.recv(),
+            },
+            None => self.result.recv(),
+        };
+        
+        match outcome {
+            Ok((backend, Ok(result))) => Ok((Self::release(backend), result)),
+            Ok((_, Err(e))) => Err(e),
+            Err(_) => Err(ChessError::Internal("Ponder search stopped without a result".to_string())),
+        }
+    }
+    
+    // Ponder miss: abandon the search. Hands the backend back for reuse.
+    pub fn miss(self) -> Option<Box<dyn SearchBackend>> {
+        self.stop.stop();
+        self.result.recv().ok().map(|(backend, _)| Self::release(backend))
+    }
+    
+    // Detach the backend from this ponder search's stop signal, which may have been raised
+    fn release(mut backend: Box<dyn SearchBackend>) -> Box<dyn SearchBackend> {
+        backend.set_stop_signal(StopSignal::new());
+        backend
+    }
+}
+
+impl Drop for Ponderer {
+    // Never leave an abandoned search running
+    fn drop(&mut self) {
+        self.stop.stop();
+    }
+}
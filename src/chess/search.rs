@@ -0,0 +1,430 @@
+use crate::chess::{Game, ChessMove, Engine, EvalParams, Skill, UciEngineConfig, UciBackend, WdlChances, WdlModel};
+use crate::chess::engine::piece_value;
+use crate::error::ChessError;
+use rand::seq::SliceRandom;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Name of the built-in alpha-beta backend, used when a game doesn't pick one
+pub const DEFAULT_BACKEND: &str = "alphabeta";
+
+// Move generation and notation don't depend on any search settings, so one
+// engine serves everything here that needs only those
+fn move_generator() -> &'static Engine {
+    static ENGINE: OnceLock<Engine> = OnceLock::new();
+    ENGINE.get_or_init(|| Engine::new(1))
+}
+
+// Limits for a single search. Backends ignore limits they can't honour.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchLimits {
+    pub depth: Option<u8>,
+    pub nodes: Option<u64>,
+    pub movetime_ms: Option<u64>,
+    // Number of ranked lines to return (MultiPV); 1 if unset
+    pub multipv: Option<usize>,
+    // Playing strength; the backend's own setting if unset
+    #[serde(default)]
+    pub skill: Option<Skill>,
+    // Evaluation weights; the backend's own if unset
+    #[serde(default)]
+    pub eval: Option<EvalParams>,
+}
+
+impl SearchLimits {
+    pub fn depth(depth: u8) -> Self {
+        Self {
+            depth: Some(depth),
+            ..Self::default()
+        }
+    }
+    
+    pub fn with_skill(mut self, skill: Skill) -> Self {
+        self.skill = Some(skill);
+        self
+    }
+}
+
+// A candidate move with its score and the line the engine expects to follow
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PvLine {
+    // Starts with the candidate move itself
+    pub moves: Vec<ChessMove>,
+    // The same moves in standard algebraic notation
+    pub san: Vec<String>,
+    pub score: i32,
+    pub depth: u8,
+    // Chances of the side to move according to the score, from the default
+    // WDL model unless the backend has its own
+    #[serde(default)]
+    pub wdl: WdlChances,
+}
+
+impl PvLine {
+    // A line of moves played from the game's current position
+    pub fn new(game: &Game, moves: Vec<ChessMove>, score: i32, depth: u8) -> Self {
+        let san = move_generator().line_to_algebraic_notation(&moves, &game.board);
+        Self {
+            moves,
+            san,
+            score,
+            depth,
+            wdl: WdlModel::default().chances_on(&game.board, score),
+        }
+    }
+    
+    // The moves in UCI notation
+    pub fn uci(&self) -> Vec<String> {
+        self.moves.iter().map(|m| m.to_uci()).collect()
+    }
+}
+
+// Shared flag that asks a running search to finish as soon as possible.
+// A stopped search still returns the best move it has found so far.
+#[derive(Debug, Clone, Default)]
+pub struct StopSignal(Arc<AtomicBool>);
+
+impl StopSignal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn stop(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    // Clear the flag so the signal can be used for another search
+    pub fn reset(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+
+    // Whether both handles control the same search
+    pub fn same_as(&self, other: &StopSignal) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+// Outcome of a search, from the side to move's point of view
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchResult {
+    pub best_move: ChessMove,
+    pub score: i32,
+    pub nodes: u64,
+    pub depth: u8,
+    // Deepest ply reached, including any extensions
+    pub seldepth: u8,
+    pub elapsed_ms: u64,
+    // Nodes per second
+    pub nps: u64,
+    // Ranked candidate lines, best first; always holds at least the best move.
+    // The first line is the principal variation.
+    pub lines: Vec<PvLine>,
+    // The move was played from the opening book rather than searched
+    #[serde(default)]
+    pub from_book: bool,
+}
+
+impl SearchResult {
+    // A move taken straight from the opening book
+    pub fn book(game: &Game, book_move: ChessMove) -> Self {
+        Self {
+            lines: vec![PvLine::new(game, vec![book_move.clone()], 0, 0)],
+            best_move: book_move,
+            score: 0,
+            nodes: 0,
+            depth: 0,
+            seldepth: 0,
+            elapsed_ms: 0,
+            nps: 0,
+            from_book: true,
+        }
+    }
+    
+    // The principal variation
+    pub fn pv(&self) -> &PvLine {
+        &self.lines[0]
+    }
+    
+    // The reply the engine expects, i.e. the second move of the principal variation
+    pub fn ponder_move(&self) -> Option<&ChessMove> {
+        self.pv().moves.get(1)
+    }
+}
+
+// Anything that can pick a move for the side to move in a game
+pub trait SearchBackend: Send {
+    fn name(&self) -> &str;
+    fn search(&mut self, game: &Game, limits: &SearchLimits) -> Result<SearchResult, ChessError>;
+
+    // Signal that cancels this backend's searches from another thread.
+    // Backends that always answer instantly can ignore it.
+    fn set_stop_signal(&mut self, _stop: StopSignal) {}
+
+    // Forget what earlier games taught the backend, such as its hash table,
+    // before it searches a different game
+    fn new_game(&mut self) -> Result<(), ChessError> {
+        Ok(())
+    }
+}
+
+// Plays a uniformly random move
+#[derive(Debug, Clone, Default)]
+pub struct RandomMover;
+
+impl SearchBackend for RandomMover {
+    fn name(&self) -> &str {
+        "random"
+    }
+
+    fn search(&mut self, game: &Game, _limits: &SearchLimits) -> Result<SearchResult, ChessError> {
+        let moves = move_generator().get_legal_moves(&game.board, game.current_turn)?;
+        let best_move = moves.choose(&mut rand::thread_rng())
+            .cloned()
+            .ok_or_else(|| ChessError::InvalidMove("No legal moves available".to_string()))?;
+
+        Ok(SearchResult {
+            lines: vec![PvLine::new(game, vec![best_move.clone()], 0, 1)],
+            best_move,
+            score: 0,
+            nodes: moves.len() as u64,
+            depth: 1,
+            seldepth: 1,
+            elapsed_ms: 0,
+            nps: 0,
+            from_book: false,
+        })
+    }
+}
+
+// Grabs the most valuable piece it can, picking randomly among equal captures
+#[derive(Debug, Clone, Default)]
+pub struct GreedyMover;
+
+impl SearchBackend for GreedyMover {
+    fn name(&self) -> &str {
+        "greedy"
+    }
+
+    fn search(&mut self, game: &Game, _limits: &SearchLimits) -> Result<SearchResult, ChessError> {
+        let mut moves = move_generator().get_legal_moves(&game.board, game.current_turn)?;
+        let nodes = moves.len() as u64;
+
+        for chess_move in moves.iter_mut() {
+            chess_move.score = game.board.get_piece(&chess_move.to)
+                .map(|captured| piece_value(captured.piece_type))
+                .unwrap_or(0);
+        }
+
+        let best_score = moves.iter().map(|m| m.score).max()
+            .ok_or_else(|| ChessError::InvalidMove("No legal moves available".to_string()))?;
+        moves.retain(|m| m.score == best_score);
+        let best_move = moves.choose(&mut rand::thread_rng()).cloned()
+            .ok_or_else(|| ChessError::Internal("Greedy move selection failed".to_string()))?;
+
+        Ok(SearchResult {
+            lines: vec![PvLine::new(game, vec![best_move.clone()], best_score, 1)],
+            best_move,
+            score: best_score,
+            nodes,
+            depth: 1,
+            seldepth: 1,
+            elapsed_ms: 0,
+            nps: 0,
+            from_book: false,
+        })
+    }
+}
+
+type BackendFactory = Box<dyn Fn() -> Box<dyn SearchBackend> + Send + Sync>;
+
+// An idle instance and the game it last searched
+type IdleBackend = (Option<String>, Box<dyn SearchBackend>);
+
+// A registered backend, with its instances that aren't searching right now
+struct RegisteredBackend {
+    factory: BackendFactory,
+    idle: Mutex<Vec<IdleBackend>>,
+}
+
+// Named search backends that games, routes and tools can pick from
+pub struct BackendRegistry {
+    factories: HashMap<String, RegisteredBackend>,
+}
+
+impl BackendRegistry {
+    // An empty registry
+    pub fn new() -> Self {
+        Self {
+            factories: HashMap::new(),
+        }
+    }
+
+    // Register a backend under a name, replacing any existing one
+    pub fn register<F>(&mut self, name: &str, factory: F)
+    where
+        F: Fn() -> Box<dyn SearchBackend> + Send + Sync + 'static,
+    {
+        self.factories.insert(name.to_string(), RegisteredBackend {
+            factory: Box::new(factory),
+            idle: Mutex::new(Vec::new()),
+        });
+    }
+
+    // Register an external UCI engine under its configured name
+    pub fn register_uci(&mut self, config: UciEngineConfig) {
+        let name = config.name.clone();
+        self.register(&name, move || Box::new(UciBackend::new(config.clone())));
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.factories.contains_key(name)
+    }
+
+    // Names of all registered backends, sorted
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.factories.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    // Create a fresh instance of the named backend
+    pub fn create(&self, name: &str) -> Result<Box<dyn SearchBackend>, ChessError> {
+        self.get(name).map(|backend| (backend.factory)())
+    }
+
+    // An instance of the named backend to search `game_id` with: the one that last
+    // searched that game if it's idle, else any idle one told a new game starts,
+    // else a fresh one. Hand it back with `checkin` so the next search keeps its
+    // hash table, and external engines keep running.
+    pub fn checkout(&self, name: &str, game_id: Option<&str>) -> Result<Box<dyn SearchBackend>, ChessError> {
+        let registered = self.get(name)?;
+        let mut reused = None;
+        if let Ok(mut idle) = registered.idle.lock() {
+            let same_game = game_id.and_then(|id| idle.iter().position(|(last, _)| last.as_deref() == Some(id)));
+            match same_game {
+                Some(i) => return Ok(idle.swap_remove(i).1),
+                None => reused = idle.pop().map(|(_, backend)| backend),
+            }
+        }
+        // An instance that can't start a new game, e.g. an engine that died, is replaced
+        match reused {
+            Some(mut backend) => match backend.new_game() {
+                Ok(()) => Ok(backend),
+                Err(_) => Ok((registered.factory)()),
+            },
+            None => Ok((registered.factory)()),
+        }
+    }
+
+    // Hand back an instance taken with `checkout` once it has finished searching `game_id`
+    pub fn checkin(&self, name: &str, game_id: Option<&str>, mut backend: Box<dyn SearchBackend>) {
+        if let Some(registered) = self.factories.get(name) {
+            backend.set_stop_signal(StopSignal::new());
+            if let Ok(mut idle) = registered.idle.lock() {
+                idle.push((game_id.map(str::to_string), backend));
+            }
+        }
+    }
+
+    fn get(&self, name: &str) -> Result<&RegisteredBackend, ChessError> {
+        self.factories.get(name)
+            .ok_or_else(|| ChessError::Engine(format!("Unknown search backend: {}", name)))
+    }
+}
+
+impl Default for BackendRegistry {
+    // The built-in backends: alpha-beta, random and material-greedy
+    fn default() -> Self {
+        let mut registry = Self::new();
+        registry.register(DEFAULT_BACKEND, || Box::new(Engine::new(3)));
+        registry.register("random", || Box::new(RandomMover));
+        registry.register("greedy", || Box::new(GreedyMover));
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::PieceType;
+    use std::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn random_mover_plays_legal_moves() {
+        // In check from the rook; d1 and f1 stay on its rank
+        let game = Game::from_fen("4k3/8/8/8/8/8/8/r3K3 w - - 0 1").unwrap();
+        let mut backend = RandomMover;
+        for _ in 0..50 {
+            let result = backend.search(&game, &SearchLimits::default()).unwrap();
+            assert!(["e1d2", "e1e2", "e1f2"].contains(&result.best_move.to_uci().as_str()));
+        }
+    }
+
+    // Counts the new games it is told about
+    struct NewGameCounter(Arc<AtomicUsize>);
+
+    impl SearchBackend for NewGameCounter {
+        fn name(&self) -> &str {
+            "counter"
+        }
+
+        fn search(&mut self, game: &Game, limits: &SearchLimits) -> Result<SearchResult, ChessError> {
+            RandomMover.search(game, limits)
+        }
+
+        fn new_game(&mut self) -> Result<(), ChessError> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn registry_reuses_idle_backends() {
+        let created = Arc::new(AtomicUsize::new(0));
+        let new_games = Arc::new(AtomicUsize::new(0));
+        let mut registry = BackendRegistry::new();
+        {
+            let (created, new_games) = (created.clone(), new_games.clone());
+            registry.register("counter", move || {
+                created.fetch_add(1, Ordering::SeqCst);
+                Box::new(NewGameCounter(new_games.clone()))
+            });
+        }
+
+        let backend = registry.checkout("counter", Some("a")).unwrap();
+        registry.checkin("counter", Some("a"), backend);
+        // Same game: the same instance carries on
+        let backend = registry.checkout("counter", Some("a")).unwrap();
+        assert_eq!((created.load(Ordering::SeqCst), new_games.load(Ordering::SeqCst)), (1, 0));
+        registry.checkin("counter", Some("a"), backend);
+
+        // Another game: the idle instance starts a new game
+        let first = registry.checkout("counter", Some("b")).unwrap();
+        assert_eq!((created.load(Ordering::SeqCst), new_games.load(Ordering::SeqCst)), (1, 1));
+        // None idle: a fresh instance
+        let second = registry.checkout("counter", Some("b")).unwrap();
+        assert_eq!(created.load(Ordering::SeqCst), 2);
+        registry.checkin("counter", Some("b"), first);
+        registry.checkin("counter", Some("c"), second);
+
+        // The instance that searched "c" is preferred over the other idle one
+        registry.checkout("counter", Some("c")).unwrap();
+        assert_eq!((created.load(Ordering::SeqCst), new_games.load(Ordering::SeqCst)), (2, 1));
+        assert!(registry.checkout("missing", None).is_err());
+    }
+
+    #[test]
+    fn greedy_mover_ignores_pinned_captures() {
+        // Rxa2 would win the queen but the rook is pinned to the king
+        let game = Game::from_fen("4r2k/8/8/8/8/8/q3R3/4K3 w - - 0 1").unwrap();
+        let result = GreedyMover.search(&game, &SearchLimits::default()).unwrap();
+        assert_eq!(result.best_move.to_uci(), "e2e8");
+        assert_eq!(result.score, piece_value(PieceType::Rook));
+    }
+}
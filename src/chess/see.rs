@@ -0,0 +1,175 @@
+use crate::chess::{Board, ChessMove, Color, PieceType, Position, is_square_attacked};
+use crate::chess::engine::piece_value;
+
+// Static exchange evaluation: the material balance of a capture after both
+// sides keep recapturing on the target square with their least valuable
+// attacker, each stopping as soon as going on would lose more. Pieces behind an
+// attacker on the same line (x-rays) join in once it has moved. Pins are
+// ignored, as is usual.
+
+const ROOK_DIRECTIONS: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+const BISHOP_DIRECTIONS: [(i32, i32); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+const KNIGHT_MOVES: [(i32, i32); 8] = [
+    (2, 1), (2, -1), (-2, 1), (-2, -1),
+    (1, 2), (1, -2), (-1, 2), (-1, -2),
+];
+
+// Expected material gain of `chess_move` in centipawns for the side making it.
+// Quiet moves score what is lost if the moved piece is then taken.
+pub fn see(board: &Board, chess_move: &ChessMove) -> i32 {
+    let Some(mover) = board.get_piece(&chess_move.from) else {
+        return 0;
+    };
+    let target = chess_move.to;
+    let mut board = board.clone();
+    // gains[i]: material won by the side making capture i, if the sequence ended there
+    let mut gains = vec![board.get_piece(&target).map_or(0, |p| piece_value(p.piece_type))];
+    let mut on_square = piece_value(mover.piece_type);
+    let mut side = mover.color.opposite();
+    let _ = board.set_piece(&chess_move.from, None);
+    let _ = board.set_piece(&target, Some(mover));
+
+    while let Some((from, attacker)) = least_valuable_attacker(&board, &target, side) {
+        // The king can only capture onto an undefended square
+        if attacker == PieceType::King && is_square_attacked(&board, &target, side.opposite()) {
+            break;
+        }
+        gains.push(on_square - gains[gains.len() - 1]);
+        on_square = piece_value(attacker);
+        let piece = board.get_piece(&from);
+        let _ = board.set_piece(&from, None);
+        let _ = board.set_piece(&target, piece);
+        side = side.opposite();
+    }
+
+    // Either side may decline to recapture
+    while gains.len() > 1 {
+        let last = gains.pop().unwrap_or(0);
+        let previous = gains.len() - 1;
+        gains[previous] = -(-gains[previous]).max(last);
+    }
+    gains[0]
+}
+
+// Pieces of `color` the opponent wins material by capturing
+pub fn hanging_pieces(board: &Board, color: Color) -> Vec<Position> {
+    let mut hanging = Vec::new();
+    for index in 0..64u8 {
+        let square = Position::new(index % 8, index / 8);
+        if board.get_piece(&square).is_none_or(|p| p.color != color) {
+            continue;
+        }
+        if let Some((from, _)) = least_valuable_attacker(board, &square, color.opposite()) {
+            if see(board, &ChessMove::new(from, square)) > 0 {
+                hanging.push(square);
+            }
+        }
+    }
+    hanging
+}
+
+// The cheapest piece of `color` attacking `square`, looking through the current
+// occupancy so that x-ray attackers show up once the pieces in front are gone
+fn least_valuable_attacker(board: &Board, square: &Position, color: Color) -> Option<(Position, PieceType)> {
+    let (file, rank) = (square.file as i32, square.rank as i32);
+    let piece_at = |f: i32, r: i32, piece_type: PieceType| -> Option<Position> {
+        if !(0..8).contains(&f) || !(0..8).contains(&r) {
+            return None;
+        }
+        let pos = Position::new(f as u8, r as u8);
+        board.get_piece(&pos).filter(|p| p.color == color && p.piece_type == piece_type).map(|_| pos)
+    };
+    // The first piece along a ray, if it is one of `types`
+    let slider = |directions: &[(i32, i32)], types: &[PieceType]| -> Vec<(Position, PieceType)> {
+        let mut found = Vec::new();
+        for &(df, dr) in directions {
+            let (mut f, mut r) = (file + df, rank + dr);
+            while (0..8).contains(&f) && (0..8).contains(&r) {
+                let pos = Position::new(f as u8, r as u8);
+                if let Some(piece) = board.get_piece(&pos) {
+                    if piece.color == color && types.contains(&piece.piece_type) {
+                        found.push((pos, piece.piece_type));
+                    }
+                    break;
+                }
+                f += df;
+                r += dr;
+            }
+        }
+        found
+    };
+
+    let pawn_rank = if color == Color::White { rank - 1 } else { rank + 1 };
+    if let Some(pos) = piece_at(file - 1, pawn_rank, PieceType::Pawn).or_else(|| piece_at(file + 1, pawn_rank, PieceType::Pawn)) {
+        return Some((pos, PieceType::Pawn));
+    }
+    if let Some(pos) = KNIGHT_MOVES.iter().find_map(|&(df, dr)| piece_at(file + df, rank + dr, PieceType::Knight)) {
+        return Some((pos, PieceType::Knight));
+    }
+    let mut sliders = slider(&BISHOP_DIRECTIONS, &[PieceType::Bishop, PieceType::Queen]);
+    sliders.extend(slider(&ROOK_DIRECTIONS, &[PieceType::Rook, PieceType::Queen]));
+    if let Some(&attacker) = sliders.iter().min_by_key(|(_, piece_type)| piece_value(*piece_type)) {
+        return Some(attacker);
+    }
+    ROOK_DIRECTIONS.iter().chain(BISHOP_DIRECTIONS.iter())
+        .find_map(|&(df, dr)| piece_at(file + df, rank + dr, PieceType::King))
+        .map(|pos| (pos, PieceType::King))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::Game;
+
+    fn board(fen: &str) -> Board {
+        Game::from_fen(fen).unwrap().board
+    }
+
+    fn see_of(fen: &str, uci: &str) -> i32 {
+        see(&board(fen), &uci.parse().unwrap())
+    }
+
+    #[test]
+    fn captures() {
+        // Rxd5 wins the undefended knight
+        assert_eq!(see_of("4k3/8/8/3n4/8/8/8/3RK3 w - - 0 1", "d1d5"), 320);
+        // Rxd5 exd5 gives the rook for a pawn
+        assert_eq!(see_of("4k3/8/4p3/3p4/8/8/8/3RK3 w - - 0 1", "d1d5"), -400);
+        // Pawn takes knight, whatever comes next
+        assert_eq!(see_of("4k3/8/4p3/3n4/4P3/8/8/4K3 w - - 0 1", "e4d5"), 220);
+    }
+
+    #[test]
+    fn x_ray_recaptures() {
+        // Rxd5 Rxd5 Rxd5: the rook behind recaptures through the first
+        assert_eq!(see_of("3r3k/8/8/3n4/8/8/3R4/3R3K w - - 0 1", "d2d5"), 320);
+        // Without it the first rook is lost for the knight
+        assert_eq!(see_of("3r3k/8/8/3n4/8/8/3R4/7K w - - 0 1", "d2d5"), -180);
+        // Bxe4 Rxe4 Qxe4 with the queen behind the bishop, so the rook won't recapture
+        assert_eq!(see_of("4r1k1/8/8/8/4p3/8/2B5/1Q5K w - - 0 1", "c2e4"), 100);
+        assert_eq!(see_of("4r1k1/8/8/8/4p3/8/2B5/7K w - - 0 1", "c2e4"), -230);
+    }
+
+    #[test]
+    fn kings_only_recapture_undefended() {
+        // Kxd5 wins the rook for the pawn
+        assert_eq!(see_of("8/8/4k3/3p4/8/8/8/3R2K1 w - - 0 1", "d1d5"), -400);
+        // With the bishop guarding d5 the king can't recapture
+        assert_eq!(see_of("8/8/4k3/3p4/8/5B2/8/3R2K1 w - - 0 1", "d1d5"), 100);
+    }
+
+    #[test]
+    fn quiet_moves() {
+        // Nf3 walks into exf3
+        assert_eq!(see_of("4k3/8/8/8/4p3/8/8/4K1N1 w - - 0 1", "g1f3"), -320);
+        assert_eq!(see_of("4k3/8/8/8/4p3/8/8/4K1N1 w - - 0 1", "g1h3"), 0);
+    }
+
+    #[test]
+    fn hanging() {
+        // The knight on d4 is loose; the bishop on f4 is attacked by a rook but defended by a pawn
+        let board = board("3r1r1k/8/8/8/3N1B2/6P1/8/6K1 w - - 0 1");
+        assert_eq!(hanging_pieces(&board, Color::White), ["d4".parse::<Position>().unwrap()]);
+        assert!(hanging_pieces(&board, Color::Black).is_empty());
+    }
+}
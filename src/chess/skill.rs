@@ -0,0 +1,237 @@
+use crate::chess::PvLine;
+use crate::chess::eval::PAWN_VALUE;
+use crate::error::ChessError;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use serde::{Serialize, Deserialize};
+use std::fmt;
+use std::str::FromStr;
+
+// Playing strength below the engine's best. Weaker levels search shallower and
+// fewer nodes, see a noisy evaluation, and pick among the best few moves with
+// errors that grow with the level's weakness, the way Stockfish's skill levels do.
+
+pub const MAX_SKILL_LEVEL: u8 = 20;
+
+// Range of UCI_Elo. The ratings are a rough guide rather than a calibration:
+// each level is worth about 60 points, with full strength at the top.
+pub const MIN_ELO: u32 = 800;
+pub const MAX_ELO: u32 = 2000;
+const ELO_PER_LEVEL: u32 = (MAX_ELO - MIN_ELO) / MAX_SKILL_LEVEL as u32;
+
+// Lines a limited level searches so it has alternatives to the best move
+const SKILL_MULTIPV: usize = 4;
+
+// A skill level from 0 (weakest) to 20 (full strength)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(from = "u8", into = "u8")]
+pub struct Skill(u8);
+
+impl Skill {
+    pub fn new(level: u8) -> Self {
+        Self(level.min(MAX_SKILL_LEVEL))
+    }
+
+    // The level closest to a target rating
+    pub fn from_elo(elo: u32) -> Self {
+        let elo = elo.clamp(MIN_ELO, MAX_ELO);
+        Self::new(((elo - MIN_ELO + ELO_PER_LEVEL / 2) / ELO_PER_LEVEL) as u8)
+    }
+
+    pub fn full() -> Self {
+        Self(MAX_SKILL_LEVEL)
+    }
+
+    pub fn level(&self) -> u8 {
+        self.0
+    }
+
+    // Approximate rating of the level
+    pub fn elo(&self) -> u32 {
+        MIN_ELO + self.0 as u32 * ELO_PER_LEVEL
+    }
+
+    // Whether the level plays below full strength
+    pub fn is_limited(&self) -> bool {
+        self.0 < MAX_SKILL_LEVEL
+    }
+
+    // Deepest search the level may do, from 1 ply at level 0 to 5 at level 19
+    pub fn max_depth(&self) -> Option<u8> {
+        self.is_limited().then_some(1 + self.0 / 4)
+    }
+
+    // Node budget per search, doubling every two levels
+    pub fn max_nodes(&self) -> Option<u64> {
+        self.is_limited().then_some(250u64 << (self.0 / 2))
+    }
+
+    // Largest random change to a leaf evaluation, in centipawns
+    pub fn eval_noise(&self) -> i32 {
+        (MAX_SKILL_LEVEL - self.0) as i32 * 8
+    }
+
+    // Lines the search needs for `pick_line` to have a choice
+    pub fn multipv(&self) -> usize {
+        if self.is_limited() { SKILL_MULTIPV } else { 1 }
+    }
+
+    // Evaluation noise for one position, fixed for the whole search so
+    // transpositions agree. `key` is the position's hash, `seed` the search's.
+    pub(crate) fn noise(&self, key: u64, seed: u64) -> i32 {
+        let amplitude = self.eval_noise();
+        if amplitude == 0 {
+            return 0;
+        }
+        // SplitMix64 finaliser
+        let mut x = key ^ seed;
+        x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        x ^= x >> 31;
+        (x % (2 * amplitude as u64 + 1)) as i32 - amplitude
+    }
+
+    // Index of the line to play. Each candidate gets a bonus that grows with how
+    // much worse it is than the best line and with a random share of the spread
+    // between the candidates, so weak levels often play slightly worse moves and
+    // only rarely blunder badly. The same `seed` always picks the same line.
+    pub fn pick_line(&self, lines: &[PvLine], seed: u64) -> usize {
+        if !self.is_limited() || lines.len() < 2 {
+            return 0;
+        }
+
+        let mut rng = StdRng::seed_from_u64(seed);
+        let top = lines[0].score as i64;
+        // Gaps beyond a pawn between the best and worst candidate don't widen the choice further
+        let delta = (top - lines[lines.len() - 1].score as i64).min(PAWN_VALUE as i64);
+        let weakness = 120 - 2 * self.0 as i64;
+
+        let mut best = (i64::MIN, 0);
+        for (i, line) in lines.iter().enumerate() {
+            let score = line.score as i64;
+            let push = (weakness * (top - score) + delta * rng.gen_range(0..weakness)) / 128;
+            if score + push > best.0 {
+                best = (score + push, i);
+            }
+        }
+        best.1
+    }
+}
+
+impl Default for Skill {
+    fn default() -> Self {
+        Self::full()
+    }
+}
+
+impl From<u8> for Skill {
+    fn from(level: u8) -> Self {
+        Self::new(level)
+    }
+}
+
+impl From<Skill> for u8 {
+    fn from(skill: Skill) -> Self {
+        skill.0
+    }
+}
+
+impl FromStr for Skill {
+    type Err = ChessError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().parse::<u8>() {
+            Ok(level) if level <= MAX_SKILL_LEVEL => Ok(Self(level)),
+            _ => Err(ChessError::Engine(format!("Skill level must be 0-{}, got {}", MAX_SKILL_LEVEL, s))),
+        }
+    }
+}
+
+impl fmt::Display for Skill {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "level {} (~{} Elo)", self.0, self.elo())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::{Engine, Game};
+
+    // The best few moves of the starting position, with made-up scores
+    fn lines(scores: &[i32]) -> Vec<PvLine> {
+        let game = Game::new();
+        let moves = Engine::new(1).get_legal_moves(&game.board, game.current_turn).unwrap();
+        scores.iter().zip(moves)
+            .map(|(&score, chess_move)| PvLine::new(&game, vec![chess_move], score, 4))
+            .collect()
+    }
+
+    #[test]
+    fn levels_from_ratings() {
+        assert_eq!(Skill::from_elo(MIN_ELO), Skill::new(0));
+        assert_eq!(Skill::from_elo(MAX_ELO), Skill::full());
+        assert_eq!(Skill::from_elo(0), Skill::new(0));
+        assert_eq!(Skill::from_elo(3000), Skill::full());
+        let mut previous = Skill::new(0);
+        for elo in MIN_ELO..=MAX_ELO {
+            let skill = Skill::from_elo(elo);
+            assert!(skill >= previous, "{} Elo gives {}", elo, skill);
+            previous = skill;
+        }
+        for level in 0..=MAX_SKILL_LEVEL {
+            assert_eq!(Skill::from_elo(Skill::new(level).elo()), Skill::new(level));
+        }
+        assert_eq!(Skill::new(50), Skill::full());
+    }
+
+    #[test]
+    fn limits_per_level() {
+        assert_eq!(Skill::new(0).max_depth(), Some(1));
+        assert_eq!(Skill::new(19).max_depth(), Some(5));
+        assert_eq!(Skill::new(0).max_nodes(), Some(250));
+        assert_eq!(Skill::new(19).max_nodes(), Some(250 << 9));
+        assert_eq!(Skill::new(0).multipv(), SKILL_MULTIPV);
+        for level in 1..MAX_SKILL_LEVEL {
+            let (weaker, skill) = (Skill::new(level - 1), Skill::new(level));
+            assert!(skill.max_depth() >= weaker.max_depth());
+            assert!(skill.max_nodes() >= weaker.max_nodes());
+            assert!(skill.eval_noise() < weaker.eval_noise());
+        }
+
+        let full = Skill::full();
+        assert!(!full.is_limited());
+        assert_eq!(full.max_depth(), None);
+        assert_eq!(full.max_nodes(), None);
+        assert_eq!(full.eval_noise(), 0);
+        assert_eq!(full.multipv(), 1);
+        assert_eq!(full.noise(12345, 678), 0);
+    }
+
+    #[test]
+    fn picks_are_repeatable() {
+        let lines = lines(&[50, 30, 10, -40]);
+        for level in [0, 5, 12, 19] {
+            let skill = Skill::new(level);
+            for seed in 0..100 {
+                assert_eq!(skill.pick_line(&lines, seed), skill.pick_line(&lines, seed));
+            }
+        }
+        // Different seeds do differ at a weak level
+        let picks: Vec<usize> = (0..100).map(|seed| Skill::new(0).pick_line(&lines, seed)).collect();
+        assert!(picks.iter().any(|&pick| pick != picks[0]));
+    }
+
+    #[test]
+    fn full_strength_plays_the_best_line() {
+        let close = lines(&[20, 19, 18, 17]);
+        let spread = lines(&[300, -100, -500, -900]);
+        for seed in 0..200 {
+            assert_eq!(Skill::full().pick_line(&close, seed), 0);
+            assert_eq!(Skill::full().pick_line(&spread, seed), 0);
+            // A blunder of several pawns is never worth it, even at the weakest level
+            assert_ne!(Skill::new(0).pick_line(&spread, seed), 3);
+        }
+        assert_eq!(Skill::new(0).pick_line(&close[..1], 7), 0);
+    }
+}
@@ -0,0 +1,380 @@
+use serde::{Serialize, Deserialize};
+use std::fmt;
+
+// Statistics for engine matches: the Elo difference a score implies, its error
+// bars, a sequential probability ratio test that decides between two Elo
+// hypotheses as the games come in, and rating lists for tournaments.
+
+// z for a two-sided 95% confidence interval
+const Z_95: f64 = 1.959964;
+
+// Expected score of a player `elo` points stronger than its opponent
+pub fn elo_to_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+// Elo difference for an expected score strictly between 0 and 1
+pub fn score_to_elo(score: f64) -> f64 {
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+// Wins, draws and losses of one player against another
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MatchScore {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl MatchScore {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    // Points scored, a draw counting half
+    pub fn points(&self) -> f64 {
+        self.wins as f64 + self.draws as f64 / 2.0
+    }
+
+    // Points per game
+    pub fn score(&self) -> f64 {
+        if self.games() == 0 { 0.5 } else { self.points() / self.games() as f64 }
+    }
+
+    // Add the result of one game, 1.0 for a win down to 0.0 for a loss
+    pub fn add(&mut self, points: f64) {
+        if points > 0.75 {
+            self.wins += 1;
+        } else if points < 0.25 {
+            self.losses += 1;
+        } else {
+            self.draws += 1;
+        }
+    }
+
+    // The same games from the opponent's side
+    pub fn reversed(&self) -> Self {
+        Self { wins: self.losses, draws: self.draws, losses: self.wins }
+    }
+
+    // Variance of the result of a single game
+    fn variance(&self) -> f64 {
+        let n = self.games() as f64;
+        let s = self.score();
+        (self.wins as f64 * (1.0 - s).powi(2) + self.draws as f64 * (0.5 - s).powi(2) + self.losses as f64 * s.powi(2)) / n
+    }
+
+    // Elo difference and the half-width of its 95% confidence interval. None
+    // until the score is strictly between 0% and 100%.
+    pub fn elo(&self) -> Option<(f64, f64)> {
+        let s = self.score();
+        if self.games() == 0 || s <= 0.0 || s >= 1.0 {
+            return None;
+        }
+        let margin = Z_95 * (self.variance() / self.games() as f64).sqrt();
+        let low = score_to_elo((s - margin).max(1e-6));
+        let high = score_to_elo((s + margin).min(1.0 - 1e-6));
+        Some((score_to_elo(s), (high - low) / 2.0))
+    }
+
+    // Likelihood of superiority: the probability that the player is the
+    // stronger one, from wins and losses alone
+    pub fn los(&self) -> f64 {
+        let decisive = (self.wins + self.losses) as f64;
+        if decisive == 0.0 {
+            return 0.5;
+        }
+        0.5 * (1.0 + erf((self.wins as f64 - self.losses as f64) / (2.0 * decisive).sqrt()))
+    }
+}
+
+impl fmt::Display for MatchScore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "+{} ={} -{}", self.wins, self.draws, self.losses)
+    }
+}
+
+// Error function, by the Abramowitz and Stegun approximation 7.1.26
+fn erf(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.3275911 * x.abs());
+    let poly = t * (0.254829592 + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    let y = 1.0 - poly * (-x * x).exp();
+    if x < 0.0 { -y } else { y }
+}
+
+// What a sequential probability ratio test has concluded so far
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SprtVerdict {
+    // The difference is at least elo1: accept H1
+    Accepted,
+    // The difference is at most elo0: accept H0
+    Rejected,
+    // Keep playing
+    Continue,
+}
+
+impl fmt::Display for SprtVerdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SprtVerdict::Accepted => write!(f, "H1 accepted"),
+            SprtVerdict::Rejected => write!(f, "H0 accepted"),
+            SprtVerdict::Continue => write!(f, "continue"),
+        }
+    }
+}
+
+// A test of H0: the Elo difference is elo0, against H1: it is elo1, with false
+// positive rate alpha and false negative rate beta
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+impl Sprt {
+    pub fn new(elo0: f64, elo1: f64) -> Self {
+        Self { elo0, elo1, alpha: 0.05, beta: 0.05 }
+    }
+
+    // Log-likelihood ratio bounds: H0 is accepted below the first, H1 above the second
+    pub fn bounds(&self) -> (f64, f64) {
+        ((self.beta / (1.0 - self.alpha)).ln(), ((1.0 - self.beta) / self.alpha).ln())
+    }
+
+    // Log-likelihood ratio of H1 against H0 for a score, by the normal
+    // approximation of the generalised SPRT used by fishtest
+    pub fn llr(&self, score: &MatchScore) -> f64 {
+        let n = score.games() as f64;
+        if n == 0.0 {
+            return 0.0;
+        }
+        // Identical results say nothing about the spread yet
+        let variance = score.variance();
+        if variance <= 0.0 {
+            return 0.0;
+        }
+        let (s0, s1) = (elo_to_score(self.elo0), elo_to_score(self.elo1));
+        (s1 - s0) * (2.0 * score.score() - s0 - s1) / (2.0 * variance / n)
+    }
+
+    pub fn verdict(&self, score: &MatchScore) -> SprtVerdict {
+        let llr = self.llr(score);
+        let (lower, upper) = self.bounds();
+        if llr >= upper {
+            SprtVerdict::Accepted
+        } else if llr <= lower {
+            SprtVerdict::Rejected
+        } else {
+            SprtVerdict::Continue
+        }
+    }
+}
+
+// Defaults of BayesElo: the advantage of moving first and how far apart two
+// players must be before a draw gets less likely than a decisive result, in Elo
+const ELO_ADVANTAGE: f64 = 32.8;
+const ELO_DRAW: f64 = 97.3;
+// Virtual draws added between every two players who met, so that perfect
+// scores still get a finite rating
+const PRIOR_DRAWS: f64 = 2.0;
+// Ratings are fitted to within this many Elo
+const RATING_PRECISION: f64 = 0.01;
+
+// A player's place in a rating list
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rating {
+    pub player: usize,
+    pub elo: f64,
+    // Half-width of the 95% confidence interval
+    pub error: f64,
+    pub games: u32,
+    // Points per game and share of draws
+    pub score: f64,
+    pub draw_ratio: f64,
+}
+
+// One game between two players, by index, for rating
+#[derive(Debug, Clone, Copy)]
+pub struct RatedGame {
+    pub white: usize,
+    pub black: usize,
+    // Points White scored: 1.0, 0.5 or 0.0
+    pub white_points: f64,
+}
+
+// Log-likelihood of one game under BayesElo's model, given White's rating advantage
+fn game_likelihood(delta: f64, white_points: f64, weight: f64) -> f64 {
+    let win = elo_to_score(delta + ELO_ADVANTAGE - ELO_DRAW);
+    let loss = elo_to_score(-delta - ELO_ADVANTAGE - ELO_DRAW);
+    let p = if white_points > 0.75 {
+        win
+    } else if white_points < 0.25 {
+        loss
+    } else {
+        1.0 - win - loss
+    };
+    weight * p.max(1e-300).ln()
+}
+
+// Log-likelihood of all games involving `player` if it were rated `elo`
+fn player_likelihood(player: usize, elo: f64, ratings: &[f64], games: &[(RatedGame, f64)]) -> f64 {
+    games.iter()
+        .filter(|(game, _)| game.white == player || game.black == player)
+        .map(|(game, weight)| {
+            let (white, black) = (
+                if game.white == player { elo } else { ratings[game.white] },
+                if game.black == player { elo } else { ratings[game.black] },
+            );
+            game_likelihood(white - black, game.white_points, *weight)
+        })
+        .sum()
+}
+
+// Ratings the way BayesElo computes them: the maximum likelihood ratings under
+// a model with a first-move advantage and a draw margin, after adding a prior of
+// virtual draws between every two players who met. The average rating is 0.
+// Returns the players best first.
+pub fn bayeselo(players: usize, games: &[RatedGame]) -> Vec<Rating> {
+    // The real games plus the prior, split evenly between both colours
+    let mut weighted: Vec<(RatedGame, f64)> = games.iter().map(|game| (*game, 1.0)).collect();
+    let mut met = std::collections::HashSet::new();
+    for game in games {
+        let pair = (game.white.min(game.black), game.white.max(game.black));
+        if met.insert(pair) {
+            weighted.push((RatedGame { white: pair.0, black: pair.1, white_points: 0.5 }, PRIOR_DRAWS / 2.0));
+            weighted.push((RatedGame { white: pair.1, black: pair.0, white_points: 0.5 }, PRIOR_DRAWS / 2.0));
+        }
+    }
+
+    // Coordinate ascent: fit each rating with the others fixed, by golden
+    // section search, until nothing moves
+    let mut ratings = vec![0.0; players];
+    let ratio = (5f64.sqrt() - 1.0) / 2.0;
+    for _ in 0..1000 {
+        let mut largest_change: f64 = 0.0;
+        for player in 0..players {
+            let (mut low, mut high) = (ratings[player] - 1000.0, ratings[player] + 1000.0);
+            while high - low > RATING_PRECISION {
+                let a = high - ratio * (high - low);
+                let b = low + ratio * (high - low);
+                if player_likelihood(player, a, &ratings, &weighted) > player_likelihood(player, b, &ratings, &weighted) {
+                    high = b;
+                } else {
+                    low = a;
+                }
+            }
+            let elo = (low + high) / 2.0;
+            largest_change = largest_change.max((elo - ratings[player]).abs());
+            ratings[player] = elo;
+        }
+        if largest_change < RATING_PRECISION {
+            break;
+        }
+    }
+    let mean = ratings.iter().sum::<f64>() / players.max(1) as f64;
+
+    let mut list: Vec<Rating> = (0..players).map(|player| {
+        // The curvature of the likelihood gives the standard error
+        let h = 1.0;
+        let elo = ratings[player];
+        let curvature = (player_likelihood(player, elo + h, &ratings, &weighted)
+            - 2.0 * player_likelihood(player, elo, &ratings, &weighted)
+            + player_likelihood(player, elo - h, &ratings, &weighted)) / (h * h);
+        let error = if curvature < 0.0 { Z_95 / (-curvature).sqrt() } else { f64::INFINITY };
+
+        let mut score = MatchScore::default();
+        for game in games {
+            if game.white == player {
+                score.add(game.white_points);
+            } else if game.black == player {
+                score.add(1.0 - game.white_points);
+            }
+        }
+        Rating {
+            player,
+            elo: elo - mean,
+            error,
+            games: score.games(),
+            score: score.score(),
+            draw_ratio: if score.games() == 0 { 0.0 } else { score.draws as f64 / score.games() as f64 },
+        }
+    }).collect();
+    list.sort_by(|a, b| b.elo.total_cmp(&a.elo));
+    list
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!((actual - expected).abs() <= tolerance, "{} is not {}", actual, expected);
+    }
+
+    #[test]
+    fn elo_conversions() {
+        assert_close(elo_to_score(0.0), 0.5, 1e-12);
+        // 3:1 odds is 400 * log10(3) Elo
+        assert_close(score_to_elo(0.75), 190.848, 1e-3);
+        assert_close(elo_to_score(score_to_elo(0.3)), 0.3, 1e-12);
+    }
+
+    #[test]
+    fn elo_with_error_bars() {
+        // 70%, with a per-game variance of 0.16
+        let score = MatchScore { wins: 60, draws: 20, losses: 20 };
+        let (elo, error) = score.elo().unwrap();
+        assert_close(elo, 147.191, 1e-3);
+        assert_close(error, 66.013, 1e-3);
+        assert_eq!(score.reversed().elo().map(|(elo, _)| elo.round()), Some(-147.0));
+        assert!(MatchScore { wins: 5, draws: 0, losses: 0 }.elo().is_none());
+    }
+
+    #[test]
+    fn likelihood_of_superiority() {
+        // (W - L) / sqrt(W + L) = 1, one standard deviation
+        assert_close(MatchScore { wins: 10, draws: 7, losses: 6 }.los(), 0.841345, 1e-6);
+        assert_close(MatchScore { wins: 3, draws: 4, losses: 3 }.los(), 0.5, 1e-9);
+    }
+
+    #[test]
+    fn sprt() {
+        let sprt = Sprt::new(0.0, 5.0);
+        let (lower, upper) = sprt.bounds();
+        assert_close(lower, -2.944439, 1e-6);
+        assert_close(upper, 2.944439, 1e-6);
+
+        let score = MatchScore { wins: 1000, draws: 2000, losses: 900 };
+        assert_close(sprt.llr(&score), 2.12779, 1e-5);
+        assert_eq!(sprt.verdict(&score), SprtVerdict::Continue);
+        let score = MatchScore { wins: 2000, draws: 5000, losses: 1800 };
+        assert_close(sprt.llr(&score), 4.56037, 1e-5);
+        assert_eq!(sprt.verdict(&score), SprtVerdict::Accepted);
+        let score = MatchScore { wins: 900, draws: 2000, losses: 1000 };
+        assert_close(sprt.llr(&score), -3.78773, 1e-5);
+        assert_eq!(sprt.verdict(&score), SprtVerdict::Rejected);
+        assert_eq!(sprt.llr(&MatchScore { wins: 4, draws: 0, losses: 0 }), 0.0);
+    }
+
+    #[test]
+    fn bayeselo_two_players() {
+        // Player 0 scores 12.5/20: +6 =2 -2 with White, +4 =3 -3 with Black
+        let mut games = Vec::new();
+        for (white, black, white_points, count) in [(0, 1, 1.0, 6), (0, 1, 0.5, 2), (0, 1, 0.0, 2), (1, 0, 1.0, 3), (1, 0, 0.5, 3), (1, 0, 0.0, 4)] {
+            games.extend(std::iter::repeat_n(RatedGame { white, black, white_points }, count));
+        }
+        let ratings = bayeselo(2, &games);
+
+        // The likelihood, prior draws included, peaks 83.8 Elo apart
+        assert_eq!((ratings[0].player, ratings[1].player), (0, 1));
+        assert_close(ratings[0].elo, 41.908, 0.02);
+        assert_close(ratings[1].elo, -41.908, 0.02);
+        assert_close(ratings[0].error, 133.05, 0.1);
+        assert_eq!(ratings[0].games, 20);
+        assert_close(ratings[0].score, 0.625, 1e-12);
+        assert_close(ratings[0].draw_ratio, 0.25, 1e-12);
+    }
+}
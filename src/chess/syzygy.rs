@@ -0,0 +1,1408 @@
+use crate::chess::{Board, ChessMove, Color, Engine, Game, PieceType, Position, is_in_check};
+use crate::error::ChessError;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs;
+use std::ops::Neg;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+// Syzygy tablebases, probed the same way as the reference prober by Ronald de Man.
+// WDL files (.rtbw) hold the game-theoretic result of every position, DTZ files
+// (.rtbz) the distance to the next capture or pawn move (zeroing move) on the
+// way to it. Positions where a capture is the best move aren't stored reliably,
+// so probes always try the captures (and for DTZ the pawn moves) themselves.
+
+// Most pieces, kings included, a table can have
+const TB_PIECES: usize = 7;
+
+pub(super) const WDL_MAGIC: [u8; 4] = [0x71, 0xE8, 0x23, 0x5D];
+pub(super) const DTZ_MAGIC: [u8; 4] = [0xD7, 0x66, 0x0C, 0xA5];
+
+// Flags of one compressed table; all but SINGLE_VALUE only occur in DTZ files
+const FLAG_STM: u8 = 1;
+pub(super) const FLAG_MAPPED: u8 = 2;
+const FLAG_WIN_PLIES: u8 = 4;
+const FLAG_LOSS_PLIES: u8 = 8;
+const FLAG_WIDE: u8 = 16;
+const FLAG_SINGLE_VALUE: u8 = 128;
+
+// File header flags
+pub(super) const HEADER_SPLIT: u8 = 1;
+const HEADER_HAS_PAWNS: u8 = 2;
+
+// Result of a position with perfect play, from the side to move's point of view.
+// Cursed wins and blessed losses are wins and losses that the fifty-move rule turns into draws.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Wdl {
+    Loss,
+    BlessedLoss,
+    Draw,
+    CursedWin,
+    Win,
+}
+
+impl Wdl {
+    fn from_value(value: i32) -> Self {
+        match value {
+            i32::MIN..=-2 => Self::Loss,
+            -1 => Self::BlessedLoss,
+            0 => Self::Draw,
+            1 => Self::CursedWin,
+            _ => Self::Win,
+        }
+    }
+
+    // -2 for a loss up to 2 for a win
+    pub fn value(self) -> i32 {
+        self as i32 - 2
+    }
+}
+
+impl Neg for Wdl {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::from_value(-self.value())
+    }
+}
+
+impl fmt::Display for Wdl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Loss => "loss",
+            Self::BlessedLoss => "blessed loss",
+            Self::Draw => "draw",
+            Self::CursedWin => "cursed win",
+            Self::Win => "win",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// A legal move in a tablebase position and where it leads, from the mover's point of view.
+// `dtz` counts plies to the next zeroing move, positive when winning and negative when losing.
+#[derive(Debug, Clone, Serialize)]
+pub struct TablebaseMove {
+    pub chess_move: ChessMove,
+    pub wdl: Wdl,
+    pub dtz: i32,
+}
+
+// Squares are numbered a1 = 0, b1 = 1, ..., h8 = 63.
+// Ranks above the a1-h8 diagonal are positive, below negative
+pub(super) fn off_a1h8(sq: usize) -> i32 {
+    (sq / 8) as i32 - (sq % 8) as i32
+}
+
+// Piece codes used in table headers: white pawn to king 1-6, black 9-14
+pub(super) fn piece_code(piece_type: PieceType, color: Color) -> u8 {
+    let kind = match piece_type {
+        PieceType::Pawn => 1,
+        PieceType::Knight => 2,
+        PieceType::Bishop => 3,
+        PieceType::Rook => 4,
+        PieceType::Queen => 5,
+        PieceType::King => 6,
+    };
+    match color {
+        Color::White => kind,
+        Color::Black => kind | 8,
+    }
+}
+
+// Mapping tables shared by all tablebase files
+pub(super) struct Indexing {
+    // Squares a2-h7 to 0..47; the leading pawn is the one with the highest value
+    map_pawns: [usize; 64],
+    // Squares below the a1-h8 diagonal to 0..27
+    pub(super) map_b1h1h7: [usize; 64],
+    // Squares of the a1-d1-d4 triangle to 0..9, diagonal squares last
+    pub(super) map_a1d1d4: [usize; 64],
+    // Both kings, the first in the a1-d1-d4 triangle, to 0..461
+    map_kk: [[usize; 64]; 10],
+    // binomial[k][n] ways to choose k of n squares
+    binomial: [[u64; 64]; 6],
+    // Index of the leading pawn group by its number of pawns and leading square
+    lead_pawn_idx: [[u64; 64]; 6],
+    // Number of leading pawn placements by number of pawns and file a-d
+    lead_pawns_size: [[u64; 4]; 6],
+}
+
+impl Indexing {
+    fn new() -> Self {
+        let mut ix = Self {
+            map_pawns: [0; 64],
+            map_b1h1h7: [0; 64],
+            map_a1d1d4: [0; 64],
+            map_kk: [[0; 64]; 10],
+            binomial: [[0; 64]; 6],
+            lead_pawn_idx: [[0; 64]; 6],
+            lead_pawns_size: [[0; 4]; 6],
+        };
+
+        let mut code = 0;
+        for sq in 0..64 {
+            if off_a1h8(sq) < 0 {
+                ix.map_b1h1h7[sq] = code;
+                code += 1;
+            }
+        }
+
+        let mut diagonal = Vec::new();
+        code = 0;
+        for sq in 0..=27 {
+            if off_a1h8(sq) < 0 && sq % 8 <= 3 {
+                ix.map_a1d1d4[sq] = code;
+                code += 1;
+            } else if off_a1h8(sq) == 0 && sq % 8 <= 3 {
+                diagonal.push(sq);
+            }
+        }
+        for sq in diagonal {
+            ix.map_a1d1d4[sq] = code;
+            code += 1;
+        }
+
+        // If the first king is on the diagonal the second may not be above it
+        let mut both_on_diagonal = Vec::new();
+        code = 0;
+        for idx in 0..10 {
+            for s1 in 0..=27 {
+                // b1 is the only triangle square mapped to 0
+                if ix.map_a1d1d4[s1] != idx || (idx == 0 && s1 != 1) {
+                    continue;
+                }
+                for s2 in 0..64 {
+                    let touching = (s1 % 8).abs_diff(s2 % 8) <= 1 && (s1 / 8).abs_diff(s2 / 8) <= 1;
+                    if touching || (off_a1h8(s1) == 0 && off_a1h8(s2) > 0) {
+                        continue;
+                    }
+                    if off_a1h8(s1) == 0 && off_a1h8(s2) == 0 {
+                        both_on_diagonal.push((idx, s2));
+                    } else {
+                        ix.map_kk[idx][s2] = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+        for (idx, s2) in both_on_diagonal {
+            ix.map_kk[idx][s2] = code;
+            code += 1;
+        }
+
+        ix.binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..6.min(n + 1) {
+                ix.binomial[k][n] = if k > 0 { ix.binomial[k - 1][n - 1] } else { 0 }
+                    + if k < n { ix.binomial[k][n - 1] } else { 0 };
+            }
+        }
+
+        // Pawns nearer the edge and, on the same file, further back lead
+        let mut available = 48;
+        for lead_count in 1..=5 {
+            for file in 0..4 {
+                let mut idx = 0;
+                for rank in 1..=6 {
+                    let sq = rank * 8 + file;
+                    if lead_count == 1 {
+                        available -= 1;
+                        ix.map_pawns[sq] = available;
+                        available -= 1;
+                        ix.map_pawns[sq ^ 7] = available;
+                    }
+                    ix.lead_pawn_idx[lead_count][sq] = idx;
+                    idx += ix.binomial[lead_count - 1][ix.map_pawns[sq]];
+                }
+                ix.lead_pawns_size[lead_count][file] = idx;
+            }
+        }
+
+        ix
+    }
+}
+
+pub(super) fn indexing() -> &'static Indexing {
+    static INDEXING: OnceLock<Indexing> = OnceLock::new();
+    INDEXING.get_or_init(Indexing::new)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TableKind {
+    Wdl,
+    Dtz,
+}
+
+// What a table's material, e.g. "KRPvKR", says about its encoding
+#[derive(Debug, Clone)]
+struct TableInfo {
+    piece_count: usize,
+    has_pawns: bool,
+    // Some side has a piece or pawn it has only one of
+    has_unique_pieces: bool,
+    // Both sides have the same material
+    symmetric: bool,
+    // Pawns of the leading side (the one with fewer pawns), then of the other side
+    pawn_count: [usize; 2],
+}
+
+impl TableInfo {
+    fn parse(name: &str) -> Option<Self> {
+        let (white, black) = name.split_once('v')?;
+        let valid = |side: &str| {
+            side.starts_with('K')
+                && side.matches('K').count() == 1
+                && side.chars().all(|c| "KQRBNP".contains(c))
+        };
+        if !valid(white) || !valid(black) || white.len() + black.len() > TB_PIECES {
+            return None;
+        }
+
+        let unique = |side: &str| "QRBNP".chars().any(|c| side.matches(c).count() == 1);
+        let white_pawns = white.matches('P').count();
+        let black_pawns = black.matches('P').count();
+        let white_leads = black_pawns == 0 || (white_pawns > 0 && black_pawns >= white_pawns);
+
+        Some(Self {
+            piece_count: white.len() + black.len(),
+            has_pawns: white_pawns + black_pawns > 0,
+            has_unique_pieces: unique(white) || unique(black),
+            symmetric: white == black,
+            pawn_count: if white_leads { [white_pawns, black_pawns] } else { [black_pawns, white_pawns] },
+        })
+    }
+}
+
+// Decoding information for one compressed table of a file; a file has one per
+// side to move it stores and, with pawns, per file a-d of the leading pawn
+#[derive(Debug, Clone, Default)]
+struct PairsData {
+    flags: u8,
+    // Shortest Huffman code in bits, or the value of a single-value table
+    min_sym_len: u8,
+    block_size: usize,
+    // Every `span` values there is a sparse index entry
+    span: u64,
+    num_blocks: usize,
+    // File offsets of the table's parts
+    lowest_sym: usize,
+    btree: usize,
+    block_length: usize,
+    block_length_size: usize,
+    sparse_index: usize,
+    sparse_index_size: usize,
+    data: usize,
+    // base64[l] is the lowest code of length l + min_sym_len, left-aligned in 64 bits
+    base64: Vec<u64>,
+    // Number of values (minus one) each symbol expands to
+    symlen: Vec<u32>,
+    // Piece codes in the order they are encoded
+    pieces: [u8; TB_PIECES],
+    // Multiplier of each group's index and, after the last group, the table size
+    group_idx: [u64; TB_PIECES + 1],
+    // Number of pieces in each group, zero-terminated
+    group_len: [usize; TB_PIECES + 1],
+    // Start of the DTZ value map for each WDL result
+    map_idx: [usize; 4],
+}
+
+// A tablebase file read into memory and its decoding information
+struct LoadedTable {
+    bytes: Vec<u8>,
+    // File offset of the DTZ value maps
+    map: usize,
+    // Indexed by side to move, then by file of the leading pawn
+    items: Vec<Vec<PairsData>>,
+}
+
+impl LoadedTable {
+    fn get(&self, stm: usize, file: usize) -> &PairsData {
+        let side = &self.items[stm % self.items.len()];
+        &side[file.min(side.len() - 1)]
+    }
+
+    // Reads past the end of the file give zeros. Together with the checks when a
+    // file is loaded, a corrupt file gives wrong results or none, not a panic.
+    fn byte(&self, at: usize) -> u8 {
+        self.bytes.get(at).copied().unwrap_or(0)
+    }
+
+    fn read<const N: usize>(&self, at: usize) -> [u8; N] {
+        let mut buf = [0; N];
+        if let Some(slice) = self.bytes.get(at..at.saturating_add(N)) {
+            buf.copy_from_slice(slice);
+        }
+        buf
+    }
+
+    fn u16_le(&self, at: usize) -> u16 {
+        u16::from_le_bytes(self.read(at))
+    }
+
+    fn u32_le(&self, at: usize) -> u32 {
+        u32::from_le_bytes(self.read(at))
+    }
+
+    fn u32_be(&self, at: usize) -> u32 {
+        u32::from_be_bytes(self.read(at))
+    }
+
+    fn u64_be(&self, at: usize) -> u64 {
+        u64::from_be_bytes(self.read(at))
+    }
+
+    // Left and right child symbols packed into 3 bytes per symbol
+    fn left(&self, d: &PairsData, sym: usize) -> usize {
+        let at = d.btree + 3 * sym;
+        ((self.byte(at + 1) as usize & 0xF) << 8) | self.byte(at) as usize
+    }
+
+    fn right(&self, d: &PairsData, sym: usize) -> usize {
+        let at = d.btree + 3 * sym;
+        ((self.byte(at + 2) as usize) << 4) | (self.byte(at + 1) as usize >> 4)
+    }
+
+    fn parse(bytes: Vec<u8>, info: &TableInfo, kind: TableKind) -> Result<Self, String> {
+        let magic = match kind {
+            TableKind::Wdl => WDL_MAGIC,
+            TableKind::Dtz => DTZ_MAGIC,
+        };
+        if bytes.get(0..4) != Some(&magic[..]) {
+            return Err("bad magic number".to_string());
+        }
+
+        let mut table = Self {
+            bytes,
+            map: 0,
+            items: Vec::new(),
+        };
+        let header = table.byte(4);
+        if (header & HEADER_HAS_PAWNS != 0) != info.has_pawns || (header & HEADER_SPLIT != 0) == info.symmetric {
+            return Err("header doesn't match the file name".to_string());
+        }
+
+        // WDL files store both sides to move unless the material is symmetric
+        let sides = if kind == TableKind::Wdl && !info.symmetric { 2 } else { 1 };
+        let files = if info.has_pawns { 4 } else { 1 };
+        let both_pawns = info.has_pawns && info.pawn_count[1] > 0;
+        let mut items = vec![vec![PairsData::default(); files]; sides];
+        let mut p = 5;
+
+        for file in 0..files {
+            let order_byte = table.byte(p);
+            let pawn_order_byte = if both_pawns { table.byte(p + 1) } else { 0xFF };
+            let order = [
+                [order_byte & 0xF, pawn_order_byte & 0xF],
+                [order_byte >> 4, pawn_order_byte >> 4],
+            ];
+            p += 1 + both_pawns as usize;
+
+            for k in 0..info.piece_count {
+                let byte = table.byte(p);
+                for (side, item) in items.iter_mut().enumerate() {
+                    item[file].pieces[k] = if side == 0 { byte & 0xF } else { byte >> 4 };
+                }
+                p += 1;
+            }
+            for (side, item) in items.iter_mut().enumerate() {
+                set_groups(info, &mut item[file], order[side], file)?;
+            }
+        }
+        p += p & 1;
+
+        for file in 0..files {
+            for item in items.iter_mut() {
+                p = table.set_sizes(&mut item[file], p)?;
+            }
+        }
+
+        if kind == TableKind::Dtz {
+            table.map = p;
+            for item in items[0].iter_mut() {
+                if item.flags & FLAG_MAPPED == 0 {
+                    continue;
+                }
+                if item.flags & FLAG_WIDE != 0 {
+                    p += p & 1;
+                    for i in 0..4 {
+                        item.map_idx[i] = (p - table.map) / 2 + 1;
+                        p += 2 * table.u16_le(p) as usize + 2;
+                    }
+                } else {
+                    for i in 0..4 {
+                        item.map_idx[i] = p - table.map + 1;
+                        p += table.byte(p) as usize + 1;
+                    }
+                }
+            }
+            p += p & 1;
+        }
+
+        // Sizes come from the file, so a corrupt one could overflow the offsets
+        for file in 0..files {
+            for item in items.iter_mut() {
+                item[file].sparse_index = p;
+                p = p.saturating_add(item[file].sparse_index_size.saturating_mul(6));
+            }
+        }
+        for file in 0..files {
+            for item in items.iter_mut() {
+                item[file].block_length = p;
+                p = p.saturating_add(item[file].block_length_size.saturating_mul(2));
+            }
+        }
+        for file in 0..files {
+            for item in items.iter_mut() {
+                // Compressed data starts on a 64-byte boundary
+                p = p.saturating_add(0x3F) & !0x3F;
+                item[file].data = p;
+                p = p.saturating_add(item[file].num_blocks.saturating_mul(item[file].block_size));
+            }
+        }
+
+        if p > table.bytes.len() {
+            return Err("file is truncated".to_string());
+        }
+        table.items = items;
+        Ok(table)
+    }
+
+    // Read the Huffman code description of one table, returning the offset after it
+    fn set_sizes(&self, d: &mut PairsData, mut p: usize) -> Result<usize, String> {
+        d.flags = self.byte(p);
+        p += 1;
+
+        if d.flags & FLAG_SINGLE_VALUE != 0 {
+            d.min_sym_len = self.byte(p);
+            return Ok(p + 1);
+        }
+
+        let groups = d.group_len.iter().position(|&len| len == 0).unwrap_or(TB_PIECES);
+        let tb_size = d.group_idx[groups];
+
+        d.block_size = 1usize.checked_shl(self.byte(p) as u32).ok_or("bad block size")?;
+        d.span = 1u64.checked_shl(self.byte(p + 1) as u32).ok_or("bad sparse index span")?;
+        d.sparse_index_size = tb_size.div_ceil(d.span) as usize;
+        let padding = self.byte(p + 2) as usize;
+        d.num_blocks = self.u32_le(p + 3) as usize;
+        d.block_length_size = d.num_blocks + padding;
+        let max_sym_len = self.byte(p + 7);
+        d.min_sym_len = self.byte(p + 8);
+        p += 9;
+        if d.min_sym_len == 0 || max_sym_len < d.min_sym_len || max_sym_len > 32 {
+            return Err("bad symbol lengths".to_string());
+        }
+
+        // Canonical Huffman code: longer codes have lower values, so the lowest
+        // code of each length can be derived from the shortest one upwards
+        d.lowest_sym = p;
+        let lengths = (max_sym_len - d.min_sym_len) as usize + 1;
+        d.base64 = vec![0; lengths];
+        for i in (0..lengths - 1).rev() {
+            let lowest = self.u16_le(d.lowest_sym + 2 * i) as u64;
+            let next_lowest = self.u16_le(d.lowest_sym + 2 * (i + 1)) as u64;
+            d.base64[i] = (d.base64[i + 1] + lowest).wrapping_sub(next_lowest) / 2;
+        }
+        for (i, base) in d.base64.iter_mut().enumerate() {
+            *base = base.checked_shl(64 - i as u32 - d.min_sym_len as u32).unwrap_or(0);
+        }
+        p += 2 * lengths;
+
+        let symbols = self.u16_le(p) as usize;
+        p += 2;
+        d.btree = p;
+
+        // Recursive pairing: each symbol stands for a pair of symbols, down to single values
+        d.symlen = vec![0; symbols];
+        let mut visited = vec![false; symbols];
+        for sym in 0..symbols {
+            if !visited[sym] {
+                d.symlen[sym] = self.set_symlen(d, sym, &mut visited);
+            }
+        }
+        // Both halves of a pair must be real symbols that stand for fewer values,
+        // or expanding a symbol could run off the table or never end
+        for sym in 0..symbols {
+            let pair = self.right(d, sym) != 0xFFF;
+            let smaller = |child: usize| d.symlen.get(child).is_some_and(|&len| len < d.symlen[sym]);
+            if pair && !(smaller(self.left(d, sym)) && smaller(self.right(d, sym))) {
+                return Err("bad symbol tree".to_string());
+            }
+        }
+
+        Ok(p + 3 * symbols + (symbols & 1))
+    }
+
+    fn set_symlen(&self, d: &mut PairsData, sym: usize, visited: &mut [bool]) -> u32 {
+        visited[sym] = true;
+        let right = self.right(d, sym);
+        if right == 0xFFF {
+            return 0;
+        }
+        let left = self.left(d, sym);
+        for child in [left, right] {
+            if child < visited.len() && !visited[child] {
+                d.symlen[child] = self.set_symlen(d, child, visited);
+            }
+        }
+        let len = |s: usize| d.symlen.get(s).copied().unwrap_or(0);
+        len(left).saturating_add(len(right)).saturating_add(1)
+    }
+
+    // The value stored at `idx`
+    fn decompress(&self, d: &PairsData, idx: u64) -> Option<u32> {
+        if d.flags & FLAG_SINGLE_VALUE != 0 {
+            return Some(d.min_sym_len as u32);
+        }
+
+        // Sparse index entry k points at the value with index k * span + span / 2;
+        // walk the block lengths from there to the block holding `idx`
+        let k = (idx / d.span) as usize;
+        if k >= d.sparse_index_size {
+            return None;
+        }
+        let entry = d.sparse_index + 6 * k;
+        let mut block = self.u32_le(entry) as usize;
+        let mut offset = self.u16_le(entry + 4) as i64 + (idx % d.span) as i64 - (d.span / 2) as i64;
+
+        let block_length = |block: usize| self.u16_le(d.block_length + 2 * block) as i64;
+        while offset < 0 {
+            block = block.checked_sub(1)?;
+            offset += block_length(block) + 1;
+        }
+        while offset > block_length(block) {
+            offset -= block_length(block) + 1;
+            block += 1;
+            if block >= d.block_length_size {
+                return None;
+            }
+        }
+
+        // Skip over the block's symbols until the one covering our value
+        let mut ptr = d.data + block * d.block_size;
+        let mut buf = self.u64_be(ptr);
+        ptr += 8;
+        let mut buf_size = 64u32;
+        let min_len = d.min_sym_len as u32;
+        let mut sym;
+
+        loop {
+            let mut len = 0;
+            while buf < d.base64[len] {
+                len += 1;
+                if len >= d.base64.len() {
+                    return None;
+                }
+            }
+            let bits = len as u32 + min_len;
+            sym = (buf - d.base64[len]).checked_shr(64u32.checked_sub(bits)?)? as usize;
+            sym += self.u16_le(d.lowest_sym + 2 * len) as usize;
+            let values = *d.symlen.get(sym)? as i64 + 1;
+            if offset < values {
+                break;
+            }
+            offset -= values;
+
+            buf = buf.checked_shl(bits)?;
+            buf_size = buf_size.checked_sub(bits)?;
+            if buf_size <= 32 {
+                buf_size += 32;
+                buf |= (self.u32_be(ptr) as u64).checked_shl(64 - buf_size)?;
+                ptr += 4;
+            }
+        }
+
+        // Expand the symbol down to the single value at our offset
+        while d.symlen[sym] != 0 {
+            let left = self.left(d, sym);
+            let left_values = *d.symlen.get(left)? as i64 + 1;
+            if offset < left_values {
+                sym = left;
+            } else {
+                offset -= left_values;
+                sym = self.right(d, sym);
+            }
+            if sym >= d.symlen.len() {
+                return None;
+            }
+        }
+
+        Some(self.left(d, sym) as u32)
+    }
+}
+
+// Split the encoded pieces into groups and work out each group's index multiplier.
+// The first group is the leading pawns or, without pawns, three unique pieces
+// (or just the kings); after that each group is identical pieces of one color.
+fn set_groups(info: &TableInfo, d: &mut PairsData, order: [u8; 2], file: usize) -> Result<(), String> {
+    let ix = indexing();
+    let mut n = 0;
+    let mut first_len: i32 = if info.has_pawns { 0 } else if info.has_unique_pieces { 3 } else { 2 };
+    d.group_len[0] = 1;
+
+    for i in 1..info.piece_count {
+        first_len -= 1;
+        if first_len > 0 || d.pieces[i] == d.pieces[i - 1] {
+            d.group_len[n] += 1;
+        } else {
+            n += 1;
+            d.group_len[n] = 1;
+        }
+    }
+    n += 1;
+    d.group_len[n] = 0;
+    // The index tables only go up to five pieces in a group
+    if d.group_len[..n].iter().any(|&len| len > 5) {
+        return Err("bad piece list".to_string());
+    }
+
+    // The groups are combined in a per-table order rather than the order they're listed in
+    let both_pawns = info.has_pawns && info.pawn_count[1] > 0;
+    let mut next = if both_pawns { 2 } else { 1 };
+    let mut free_squares = 64 - d.group_len[0] - if both_pawns { d.group_len[1] } else { 0 };
+    let mut idx: u64 = 1;
+    let (order0, order1) = (order[0] as usize, order[1] as usize);
+    if order0 >= n || (both_pawns && (order1 >= n || order1 == order0)) {
+        return Err("bad group order".to_string());
+    }
+
+    let mut k = 0;
+    while next < n || k == order0 || k == order1 {
+        if k == order0 {
+            d.group_idx[0] = idx;
+            idx *= if info.has_pawns {
+                ix.lead_pawns_size[d.group_len[0]][file]
+            } else if info.has_unique_pieces {
+                31332
+            } else {
+                462
+            };
+        } else if k == order1 {
+            d.group_idx[1] = idx;
+            idx *= ix.binomial[d.group_len[1]][48 - d.group_len[0]];
+        } else {
+            d.group_idx[next] = idx;
+            idx *= ix.binomial[d.group_len[next]][free_squares];
+            free_squares -= d.group_len[next];
+            next += 1;
+        }
+        k += 1;
+    }
+    d.group_idx[n] = idx;
+    Ok(())
+}
+
+// A tablebase file found on disk, read on first use
+struct TableFile {
+    info: TableInfo,
+    path: PathBuf,
+    kind: TableKind,
+    loaded: OnceLock<Option<LoadedTable>>,
+}
+
+impl TableFile {
+    fn table(&self) -> Option<&LoadedTable> {
+        self.loaded.get_or_init(|| {
+            let parsed = fs::read(&self.path)
+                .map_err(|e| e.to_string())
+                .and_then(|bytes| LoadedTable::parse(bytes, &self.info, self.kind));
+            match parsed {
+                Ok(table) => Some(table),
+                Err(e) => {
+                    log::warn!("Unusable tablebase file {}: {}", self.path.display(), e);
+                    None
+                },
+            }
+        }).as_ref()
+    }
+}
+
+// Result of looking a position up in one table
+enum TableProbe {
+    Value(i32),
+    // A DTZ file only stores the other side to move
+    ChangeStm,
+}
+
+// What the capture search of a probe found
+struct SearchProbe {
+    wdl: Wdl,
+    // The best result comes from a capture or pawn move, so no DTZ is stored for it
+    zeroing_best: bool,
+}
+
+// The pieces on the board by square, a1 first
+fn occupied(board: &Board) -> Vec<(usize, u8)> {
+    let mut pieces = Vec::new();
+    for sq in 0..64 {
+        if let Some(piece) = board.get_piece(&Position::new((sq % 8) as u8, (sq / 8) as u8)) {
+            pieces.push((sq, piece_code(piece.piece_type, piece.color)));
+        }
+    }
+    pieces
+}
+
+// One side's material as in table file names, e.g. "KRP"
+fn material(pieces: &[(usize, u8)], color: Color) -> String {
+    let mut material = String::new();
+    let kinds = [
+        (PieceType::King, 'K'), (PieceType::Queen, 'Q'), (PieceType::Rook, 'R'),
+        (PieceType::Bishop, 'B'), (PieceType::Knight, 'N'), (PieceType::Pawn, 'P'),
+    ];
+    for (piece_type, letter) in kinds {
+        let code = piece_code(piece_type, color);
+        material.extend(pieces.iter().filter(|(_, c)| *c == code).map(|_| letter));
+    }
+    material
+}
+
+fn dtz_before_zeroing(wdl: Wdl) -> i32 {
+    match wdl {
+        Wdl::Win => 1,
+        Wdl::CursedWin => 101,
+        Wdl::Draw => 0,
+        Wdl::BlessedLoss => -101,
+        Wdl::Loss => -1,
+    }
+}
+
+// Syzygy WDL and DTZ tables found in one or more directories
+pub struct SyzygyTablebase {
+    // By material, e.g. "KRvK"
+    wdl: HashMap<String, TableFile>,
+    dtz: HashMap<String, TableFile>,
+    max_pieces: usize,
+}
+
+impl fmt::Debug for SyzygyTablebase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SyzygyTablebase")
+            .field("wdl_tables", &self.wdl.len())
+            .field("dtz_tables", &self.dtz.len())
+            .field("max_pieces", &self.max_pieces)
+            .finish()
+    }
+}
+
+impl SyzygyTablebase {
+    // Find the `.rtbw` and `.rtbz` files in a directory, or several separated like
+    // PATH entries. Files are only read once a position needs them.
+    pub fn open(paths: impl AsRef<OsStr>) -> Result<Self, ChessError> {
+        let mut tablebase = Self {
+            wdl: HashMap::new(),
+            dtz: HashMap::new(),
+            max_pieces: 0,
+        };
+
+        for dir in std::env::split_paths(&paths) {
+            let entries = fs::read_dir(&dir)
+                .map_err(|e| ChessError::Tablebase(format!("Failed to read {}: {}", dir.display(), e)))?;
+            for entry in entries.flatten() {
+                tablebase.add(&entry.path());
+            }
+        }
+
+        if tablebase.wdl.is_empty() {
+            return Err(ChessError::Tablebase(format!(
+                "No Syzygy WDL files in {}", paths.as_ref().to_string_lossy()
+            )));
+        }
+        Ok(tablebase)
+    }
+
+    fn add(&mut self, path: &Path) {
+        let kind = match path.extension().and_then(|e| e.to_str()) {
+            Some("rtbw") => TableKind::Wdl,
+            Some("rtbz") => TableKind::Dtz,
+            _ => return,
+        };
+        let Some(name) = path.file_stem().and_then(|s| s.to_str()) else {
+            return;
+        };
+        let Some(info) = TableInfo::parse(name) else {
+            return;
+        };
+
+        if kind == TableKind::Wdl {
+            self.max_pieces = self.max_pieces.max(info.piece_count);
+        }
+        let tables = match kind {
+            TableKind::Wdl => &mut self.wdl,
+            TableKind::Dtz => &mut self.dtz,
+        };
+        // With the same table in several directories the first one wins
+        tables.entry(name.to_string()).or_insert(TableFile {
+            info,
+            path: path.to_path_buf(),
+            kind,
+            loaded: OnceLock::new(),
+        });
+    }
+
+    // Number of WDL tables
+    pub fn len(&self) -> usize {
+        self.wdl.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.wdl.is_empty()
+    }
+
+    // Most pieces, kings included, of any table
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    // Win, draw or loss for the side to move with perfect play
+    pub fn probe_wdl(&self, game: &Game) -> Option<Wdl> {
+        self.probe_wdl_board(&game.board, game.current_turn)
+    }
+
+    // Plies to the next capture or pawn move with perfect play: positive when the side
+    // to move wins, negative when it loses and 0 for draws. Cursed wins and blessed
+    // losses are off by more than 100. Like the reference prober, the value may be
+    // one ply too high, which can only matter close to the fifty-move limit.
+    pub fn probe_dtz(&self, game: &Game) -> Option<i32> {
+        self.probe_dtz_board(&game.board, game.current_turn)
+    }
+
+    // Every legal move ranked by where it leads: the fastest wins first, then draws,
+    // then the longest losses
+    pub fn probe_root(&self, game: &Game) -> Option<Vec<TablebaseMove>> {
+        let board = &game.board;
+        let color = game.current_turn;
+        if !self.can_probe(board) {
+            return None;
+        }
+
+        let mut moves = Vec::new();
+        for chess_move in Engine::new(1).get_legal_moves(board, color).ok()? {
+            let zeroing = is_zeroing(board, &chess_move);
+            let mut after = board.clone();
+            after.make_move(&chess_move.from, &chess_move.to).ok()?;
+
+            let wdl = -self.probe_wdl_board(&after, color.opposite())?;
+            let mut dtz = if zeroing {
+                dtz_before_zeroing(wdl)
+            } else {
+                let dtz = -self.probe_dtz_board(&after, color.opposite())?;
+                dtz + dtz.signum()
+            };
+            // Mate is the end of the road, not a step towards a zeroing move
+            if dtz == 2 && is_mate(&after, color.opposite()) {
+                dtz = 1;
+            }
+            moves.push(TablebaseMove {
+                chess_move,
+                wdl,
+                dtz,
+            });
+        }
+
+        moves.sort_by_key(|m| (std::cmp::Reverse(m.wdl), if m.wdl == Wdl::Draw { 0 } else { m.dtz }));
+        Some(moves)
+    }
+
+    // Whether the board has few enough pieces and nothing the tables can't encode
+    pub(crate) fn can_probe(&self, board: &Board) -> bool {
+        let pieces = occupied(board);
+        let kings = pieces.iter().filter(|(_, code)| code & 7 == 6).count();
+        // Pawns on the first or last rank can't occur in the tables
+        let stray_pawns = pieces.iter().any(|&(sq, code)| code & 7 == 1 && !(8..56).contains(&sq));
+        pieces.len() <= self.max_pieces && kings == 2 && !stray_pawns
+    }
+
+    pub(crate) fn probe_wdl_board(&self, board: &Board, color: Color) -> Option<Wdl> {
+        if !self.can_probe(board) {
+            return None;
+        }
+        self.search(board, color, false).map(|probe| probe.wdl)
+    }
+
+    fn probe_dtz_board(&self, board: &Board, color: Color) -> Option<i32> {
+        if !self.can_probe(board) {
+            return None;
+        }
+        let probe = self.search(board, color, true)?;
+        if probe.wdl == Wdl::Draw {
+            return Some(0);
+        }
+        if probe.zeroing_best {
+            return Some(dtz_before_zeroing(probe.wdl));
+        }
+
+        match self.probe_table(board, color, TableKind::Dtz, probe.wdl)? {
+            TableProbe::Value(dtz) => {
+                let cursed = matches!(probe.wdl, Wdl::CursedWin | Wdl::BlessedLoss);
+                Some((dtz + if cursed { 100 } else { 0 }) * probe.wdl.value().signum())
+            },
+            // Only the other side to move is stored: take the best reply's DTZ
+            TableProbe::ChangeStm => {
+                let mut best = i32::MAX;
+                for chess_move in Engine::new(1).get_legal_moves(board, color).ok()? {
+                    let zeroing = is_zeroing(board, &chess_move);
+                    let mut after = board.clone();
+                    after.make_move(&chess_move.from, &chess_move.to).ok()?;
+
+                    let mut dtz = if zeroing {
+                        -dtz_before_zeroing(self.search(&after, color.opposite(), false)?.wdl)
+                    } else {
+                        -self.probe_dtz_board(&after, color.opposite())?
+                    };
+                    if dtz == 1 && is_mate(&after, color.opposite()) {
+                        best = 1;
+                    }
+                    if !zeroing {
+                        dtz += dtz.signum();
+                    }
+                    if dtz < best && dtz.signum() == probe.wdl.value().signum() {
+                        best = dtz;
+                    }
+                }
+                // No legal moves means mate
+                Some(if best == i32::MAX { -1 } else { best })
+            },
+        }
+    }
+
+    // Try the captures (and, for DTZ, pawn moves) and combine their results with
+    // the table's, which may hold any value where such a move is best
+    fn search(&self, board: &Board, color: Color, zeroing_moves: bool) -> Option<SearchProbe> {
+        let moves = Engine::new(1).get_legal_moves(board, color).ok()?;
+        let mut best = Wdl::Loss;
+        let mut tried = 0;
+
+        for chess_move in &moves {
+            let capture = board.get_piece(&chess_move.to).is_some();
+            if !(capture || zeroing_moves && is_zeroing(board, chess_move)) {
+                continue;
+            }
+            tried += 1;
+
+            let mut after = board.clone();
+            after.make_move(&chess_move.from, &chess_move.to).ok()?;
+            if !self.can_probe(&after) {
+                return None;
+            }
+            let wdl = -self.search(&after, color.opposite(), false)?.wdl;
+            if wdl > best {
+                best = wdl;
+                if wdl == Wdl::Win {
+                    return Some(SearchProbe { wdl, zeroing_best: true });
+                }
+            }
+        }
+
+        // With every move already tried the table's value isn't needed
+        let all_tried = tried > 0 && tried == moves.len();
+        let wdl = if all_tried {
+            best
+        } else {
+            match self.probe_table(board, color, TableKind::Wdl, Wdl::Draw)? {
+                TableProbe::Value(value) => Wdl::from_value(value),
+                TableProbe::ChangeStm => return None,
+            }
+        };
+
+        if best >= wdl {
+            Some(SearchProbe { wdl: best, zeroing_best: best > Wdl::Draw || all_tried })
+        } else {
+            Some(SearchProbe { wdl, zeroing_best: false })
+        }
+    }
+
+    // Look the position up in its WDL or DTZ table. DTZ values need the position's WDL result.
+    fn probe_table(&self, board: &Board, color: Color, kind: TableKind, wdl: Wdl) -> Option<TableProbe> {
+        let pieces = occupied(board);
+        // Bare kings aren't worth a file
+        if pieces.len() == 2 {
+            return Some(TableProbe::Value(0));
+        }
+
+        // Tables are named with the stronger side as white; otherwise swap colors and flip the board
+        let tables = match kind {
+            TableKind::Wdl => &self.wdl,
+            TableKind::Dtz => &self.dtz,
+        };
+        let white = material(&pieces, Color::White);
+        let black = material(&pieces, Color::Black);
+        let (file, black_stronger) = match tables.get(&format!("{}v{}", white, black)) {
+            Some(file) => (file, false),
+            None => (tables.get(&format!("{}v{}", black, white))?, true),
+        };
+        let info = &file.info;
+        let table = file.table()?;
+
+        // Symmetric tables only store white to move
+        let flip = black_stronger || (info.symmetric && color == Color::Black);
+        let flip_color = if flip { 8 } else { 0 };
+        let flip_squares = if flip { 56 } else { 0 };
+        let stm = flip as usize ^ (color == Color::Black) as usize;
+
+        let ix = indexing();
+        let mut squares = [0usize; TB_PIECES];
+        let mut codes = [0u8; TB_PIECES];
+        let mut size = 0;
+        let mut lead_count = 0;
+        let mut lead_code = 0;
+        let mut tb_file = 0;
+
+        // Tables with pawns are split by the file of the leading pawn, mirrored to a-d
+        if info.has_pawns {
+            lead_code = table.get(0, 0).pieces[0] ^ flip_color;
+            for &(sq, code) in &pieces {
+                if code == lead_code {
+                    squares[size] = sq ^ flip_squares;
+                    size += 1;
+                }
+            }
+            lead_count = size;
+            let lead = (0..lead_count).max_by_key(|&i| ix.map_pawns[squares[i]])?;
+            squares.swap(0, lead);
+            tb_file = (squares[0] % 8).min(7 - squares[0] % 8);
+        }
+
+        if kind == TableKind::Dtz {
+            // Symmetric pawnless tables can be probed from either side
+            let stored = (table.get(stm, tb_file).flags & FLAG_STM) as usize;
+            if stored != stm && (info.has_pawns || !info.symmetric) {
+                return Some(TableProbe::ChangeStm);
+            }
+        }
+
+        for &(sq, code) in &pieces {
+            if !(info.has_pawns && code == lead_code) {
+                squares[size] = sq ^ flip_squares;
+                codes[size] = code ^ flip_color;
+                size += 1;
+            }
+        }
+
+        // Put the pieces in the order the table encodes them
+        let d = table.get(stm, tb_file);
+        for i in lead_count..size.saturating_sub(1) {
+            if let Some(j) = (i + 1..size).find(|&j| codes[j] == d.pieces[i]) {
+                codes.swap(i, j);
+                squares.swap(i, j);
+            }
+        }
+
+        // Mirror so the leading piece is on files a-d
+        if squares[0] % 8 > 3 {
+            for sq in squares.iter_mut().take(size) {
+                *sq ^= 7;
+            }
+        }
+
+        let mut idx: u64;
+        if info.has_pawns {
+            idx = ix.lead_pawn_idx[lead_count][squares[0]];
+            squares[1..lead_count].sort_by_key(|&sq| ix.map_pawns[sq]);
+            for (i, &sq) in squares.iter().enumerate().take(lead_count).skip(1) {
+                idx += ix.binomial[i][ix.map_pawns[sq]];
+            }
+        } else {
+            // Without pawns also mirror to ranks 1-4 and below the a1-h8 diagonal
+            if squares[0] / 8 > 3 {
+                for sq in squares.iter_mut().take(size) {
+                    *sq ^= 56;
+                }
+            }
+            for i in 0..d.group_len[0] {
+                let off = off_a1h8(squares[i]);
+                if off == 0 {
+                    continue;
+                }
+                if off > 0 {
+                    for sq in squares.iter_mut().take(size).skip(i) {
+                        *sq = ((*sq >> 3) | (*sq << 3)) & 63;
+                    }
+                }
+                break;
+            }
+
+            idx = if info.has_unique_pieces {
+                let (s0, s1, s2) = (squares[0], squares[1], squares[2]);
+                let adjust1 = (s1 > s0) as u64;
+                let adjust2 = (s2 > s0) as u64 + (s2 > s1) as u64;
+                let (r0, r1, r2) = ((s0 / 8) as u64, (s1 / 8) as u64, (s2 / 8) as u64);
+
+                if off_a1h8(s0) != 0 {
+                    (ix.map_a1d1d4[s0] as u64 * 63 + (s1 as u64 - adjust1)) * 62 + s2 as u64 - adjust2
+                } else if off_a1h8(s1) != 0 {
+                    (6 * 63 + r0 * 28 + ix.map_b1h1h7[s1] as u64) * 62 + s2 as u64 - adjust2
+                } else if off_a1h8(s2) != 0 {
+                    6 * 63 * 62 + 4 * 28 * 62 + r0 * 7 * 28 + (r1 - adjust1) * 28 + ix.map_b1h1h7[s2] as u64
+                } else {
+                    6 * 63 * 62 + 4 * 28 * 62 + 4 * 7 * 28 + r0 * 7 * 6 + (r1 - adjust1) * 6 + (r2 - adjust2)
+                }
+            } else {
+                ix.map_kk[ix.map_a1d1d4[squares[0]]][squares[1]] as u64
+            };
+        }
+
+        // The other groups: each set of identical pieces as a combination of the free squares
+        idx *= d.group_idx[0];
+        let mut start = d.group_len[0];
+        let mut remaining_pawns = info.has_pawns && info.pawn_count[1] > 0;
+        let mut next = 1;
+        while d.group_len[next] != 0 {
+            let len = d.group_len[next];
+            squares[start..start + len].sort_unstable();
+            let mut n = 0;
+            for i in 0..len {
+                let sq = squares[start + i];
+                let adjust = squares[..start].iter().filter(|&&s| sq > s).count();
+                let free = sq - adjust - if remaining_pawns { 8 } else { 0 };
+                n += ix.binomial[i + 1][free];
+            }
+            remaining_pawns = false;
+            idx += n * d.group_idx[next];
+            start += len;
+            next += 1;
+        }
+
+        let value = table.decompress(d, idx)? as i32;
+        Some(TableProbe::Value(match kind {
+            TableKind::Wdl => value - 2,
+            TableKind::Dtz => map_dtz(table, tb_file, value, wdl),
+        }))
+    }
+}
+
+// Turn a stored DTZ value back into plies
+fn map_dtz(table: &LoadedTable, file: usize, value: i32, wdl: Wdl) -> i32 {
+    let d = table.get(0, file);
+    let mut value = value;
+
+    // Values are stored by frequency; the map per WDL result restores them
+    if d.flags & FLAG_MAPPED != 0 {
+        let map = match wdl {
+            Wdl::Win | Wdl::Draw => 0,
+            Wdl::Loss => 1,
+            Wdl::CursedWin => 2,
+            Wdl::BlessedLoss => 3,
+        };
+        let at = d.map_idx[map] + value as usize;
+        value = if d.flags & FLAG_WIDE != 0 {
+            table.u16_le(table.map + 2 * at) as i32
+        } else {
+            table.byte(table.map + at) as i32
+        };
+    }
+
+    // Some tables count moves rather than plies
+    let in_moves = match wdl {
+        Wdl::Win => d.flags & FLAG_WIN_PLIES == 0,
+        Wdl::Loss => d.flags & FLAG_LOSS_PLIES == 0,
+        _ => true,
+    };
+    if in_moves {
+        value *= 2;
+    }
+    value + 1
+}
+
+// Captures and pawn moves reset the fifty-move counter
+fn is_zeroing(board: &Board, chess_move: &ChessMove) -> bool {
+    board.get_piece(&chess_move.to).is_some()
+        || board.get_piece(&chess_move.from).is_some_and(|p| p.piece_type == PieceType::Pawn)
+}
+
+fn is_mate(board: &Board, color: Color) -> bool {
+    is_in_check(board, color)
+        && Engine::new(1).get_legal_moves(board, color).map(|m| m.is_empty()).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // KQvK and KRvK tables small enough to keep in the repository
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/syzygy");
+
+    fn tablebase() -> SyzygyTablebase {
+        SyzygyTablebase::open(FIXTURES).unwrap()
+    }
+
+    fn probe(tablebase: &SyzygyTablebase, fen: &str) -> (Wdl, i32) {
+        let game = Game::from_fen(fen).unwrap();
+        (tablebase.probe_wdl(&game).unwrap(), tablebase.probe_dtz(&game).unwrap())
+    }
+
+    // The DTM tables the fixtures were encoded from, generated once
+    fn dtm_tablebase() -> &'static crate::chess::DtmTablebase {
+        static TABLEBASE: OnceLock<crate::chess::DtmTablebase> = OnceLock::new();
+        TABLEBASE.get_or_init(|| {
+            let mut tablebase = crate::chess::DtmTablebase::new();
+            tablebase.generate("KQvK").unwrap();
+            tablebase.generate("KRvK").unwrap();
+            tablebase
+        })
+    }
+
+    // Compare every `step`th placement of KQvK and KRvK, with either side to
+    // move, against the DTM tables. Without captures or pawn moves on the way, a
+    // win's DTZ is its distance to mate; `slack` allows for tables that store
+    // DTZ in moves and so may be a ply out.
+    fn assert_matches_dtm(tablebase: &SyzygyTablebase, step: usize, slack: i32) {
+        use crate::chess::{Dtm, Piece};
+        let dtm = dtm_tablebase();
+        let mut checked = 0;
+        for piece_type in [PieceType::Queen, PieceType::Rook] {
+            let pieces = [(piece_type, Color::White), (PieceType::King, Color::White), (PieceType::King, Color::Black)];
+            for squares in (0..64 * 64 * 64).step_by(step).map(|i| [i / 4096, i / 64 % 64, i % 64]) {
+                if squares[0] == squares[1] || squares[0] == squares[2] || squares[1] == squares[2] {
+                    continue;
+                }
+                let mut board = Board::empty();
+                for (&sq, &(piece_type, color)) in squares.iter().zip(&pieces) {
+                    board.set_piece(&Position::new((sq % 8) as u8, (sq / 8) as u8), Some(Piece::new(piece_type, color))).unwrap();
+                }
+                for color in [Color::White, Color::Black] {
+                    if is_in_check(&board, color.opposite()) {
+                        continue;
+                    }
+                    let (wdl, dtz) = match dtm.probe_board(&board, color).unwrap() {
+                        Dtm::Win(plies) => (Wdl::Win, plies as i32),
+                        Dtm::Draw => (Wdl::Draw, 0),
+                        // Checkmated is -1 by convention
+                        Dtm::Loss(plies) => (Wdl::Loss, -(plies as i32).max(1)),
+                    };
+                    let position = format!("{} {:?} to move", board.to_fen(), color);
+                    assert_eq!(tablebase.probe_wdl_board(&board, color), Some(wdl), "{}", position);
+                    let probed = tablebase.probe_dtz_board(&board, color).unwrap_or_else(|| panic!("no DTZ for {}", position));
+                    assert!(probed.signum() == dtz.signum() && (probed - dtz).abs() <= slack, "{}: DTZ {}, mate in {} plies", position, probed, dtz);
+                    checked += 1;
+                }
+            }
+        }
+        assert!(checked > 600_000 / step);
+    }
+
+    #[test]
+    fn fixtures_are_the_encoders_output() {
+        for material in ["KQvK", "KRvK"] {
+            let (wdl, dtz) = crate::chess::encode_syzygy(dtm_tablebase(), material).unwrap();
+            assert!(wdl == fs::read(format!("{}/{}.rtbw", FIXTURES, material)).unwrap(), "{}.rtbw", material);
+            assert!(dtz == fs::read(format!("{}/{}.rtbz", FIXTURES, material)).unwrap(), "{}.rtbz", material);
+        }
+        assert!(crate::chess::encode_syzygy(dtm_tablebase(), "KPvK").is_err());
+    }
+
+    #[test]
+    fn fixtures_match_the_dtm_tables() {
+        assert_matches_dtm(&tablebase(), 37, 0);
+    }
+
+    #[test]
+    fn corrupt_files_give_no_result_rather_than_panicking() {
+        use rand::{Rng, SeedableRng};
+        let dir = std::env::temp_dir().join(format!("rustychess-syzygy-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let wdl = fs::read(format!("{}/KQvK.rtbw", FIXTURES)).unwrap();
+        let dtz = fs::read(format!("{}/KQvK.rtbz", FIXTURES)).unwrap();
+        let games: Vec<Game> = ["8/8/8/5k2/8/8/1Q6/K7 w - - 0 1", "8/8/8/5k2/8/8/1Q6/K7 b - - 0 1", "7k/8/6K1/8/8/8/Q7/8 w - - 0 1", "8/8/3k4/8/8/8/8/KQ6 w - - 0 1"]
+            .iter().map(|fen| Game::from_fen(fen).unwrap()).collect();
+        let probe = |wdl: &[u8], dtz: &[u8]| {
+            fs::write(dir.join("KQvK.rtbw"), wdl).unwrap();
+            fs::write(dir.join("KQvK.rtbz"), dtz).unwrap();
+            let tablebase = SyzygyTablebase::open(&dir).unwrap();
+            games.iter().map(|game| (tablebase.probe_wdl(game), tablebase.probe_dtz(game))).collect::<Vec<_>>()
+        };
+        let intact = probe(&wdl, &dtz);
+        assert!(intact.iter().all(|&(wdl, dtz)| wdl.is_some() && dtz.is_some()));
+
+        // Sizes and symbol lengths that can't be right are rejected when the file is
+        // loaded. After the 10-byte header come the flags, the block size and span
+        // as powers of two, padding, the number of blocks and the longest and
+        // shortest code lengths.
+        let corrupt = |at: usize, value: u8| {
+            let mut bytes = wdl.clone();
+            bytes[at] = value;
+            bytes
+        };
+        for bytes in [corrupt(11, 64), corrupt(12, 64), corrupt(18, 33), corrupt(19, 0), corrupt(19, 40), wdl[..wdl.len() / 2].to_vec(), wdl[..12].to_vec()] {
+            assert!(probe(&bytes, &dtz).iter().all(|&(wdl, _)| wdl.is_none()));
+        }
+
+        // Other damage may give wrong results, but never a panic or a hang
+        let mut rng = rand::rngs::StdRng::seed_from_u64(34);
+        for round in 0..300 {
+            let (mut wdl, mut dtz) = (wdl.clone(), dtz.clone());
+            let bytes = if round % 2 == 0 { &mut wdl } else { &mut dtz };
+            // Mostly in the headers and symbol trees, where damage does the most harm
+            let end = if round % 3 == 0 { bytes.len() } else { 200 };
+            for _ in 0..rng.gen_range(1..8) {
+                let at = rng.gen_range(4..end);
+                bytes[at] = rng.gen();
+            }
+            probe(&wdl, &dtz);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    // The published KQvK and KRvK tables, from a directory given in
+    // RUSTYCHESS_SYZYGY_PATH. Every position is checked, so run it with
+    // cargo test --release published_tables -- --ignored
+    #[test]
+    #[ignore]
+    fn published_tables() {
+        let path = std::env::var("RUSTYCHESS_SYZYGY_PATH").expect("RUSTYCHESS_SYZYGY_PATH should point at the published tables");
+        let tablebase = SyzygyTablebase::open(path).unwrap();
+        assert_matches_dtm(&tablebase, 1, 1);
+    }
+
+    #[test]
+    fn opens_the_fixtures() {
+        let tablebase = tablebase();
+        assert_eq!(tablebase.len(), 2);
+        assert_eq!(tablebase.max_pieces(), 3);
+        // Nothing to probe with four pieces
+        assert_eq!(tablebase.probe_wdl(&Game::from_fen("7k/8/6K1/8/8/8/8/QQ6 w - - 0 1").unwrap()), None);
+    }
+
+    #[test]
+    fn kqvk() {
+        let tablebase = tablebase();
+        // Qa8 mates
+        assert_eq!(probe(&tablebase, "7k/8/6K1/8/8/8/Q7/8 w - - 0 1"), (Wdl::Win, 1));
+        // Kh7 is forced and Qh1 mates
+        assert_eq!(probe(&tablebase, "7k/5K2/8/8/8/8/8/6Q1 b - - 0 1"), (Wdl::Loss, -2));
+        // Checkmated
+        assert_eq!(probe(&tablebase, "6Qk/5K2/8/8/8/8/8/8 b - - 0 1"), (Wdl::Loss, -1));
+        // The king takes the undefended queen
+        assert_eq!(probe(&tablebase, "8/8/8/8/8/8/6kQ/K7 b - - 0 1"), (Wdl::Draw, 0));
+        // Stalemate
+        assert_eq!(probe(&tablebase, "7k/5Q2/6K1/8/8/8/8/8 b - - 0 1"), (Wdl::Draw, 0));
+        // The longest win, mate in 10
+        assert_eq!(probe(&tablebase, "8/8/8/5k2/8/8/1Q6/K7 w - - 0 1"), (Wdl::Win, 19));
+        // Black to move is only stored in the WDL table, so DTZ comes from the replies
+        assert_eq!(probe(&tablebase, "8/8/8/5k2/8/8/1Q6/K7 b - - 0 1"), (Wdl::Loss, -20));
+
+        let moves = tablebase.probe_root(&Game::from_fen("8/8/3k4/8/8/8/8/KQ6 w - - 0 1").unwrap()).unwrap();
+        assert!(moves.iter().all(|m| m.wdl == Wdl::Win && m.dtz > 0));
+        assert_eq!((moves[0].wdl, moves[0].dtz), probe(&tablebase, "8/8/3k4/8/8/8/8/KQ6 w - - 0 1"));
+    }
+
+    #[test]
+    fn krvk() {
+        let tablebase = tablebase();
+        // Ra8 mates
+        assert_eq!(probe(&tablebase, "7k/8/6K1/8/8/8/8/R7 w - - 0 1"), (Wdl::Win, 1));
+        // The king takes the undefended rook
+        assert_eq!(probe(&tablebase, "8/8/8/8/8/8/6k1/K4R2 b - - 0 1"), (Wdl::Draw, 0));
+        // The longest win, mate in 16
+        assert_eq!(probe(&tablebase, "8/6R1/5k2/8/8/8/8/1K6 w - - 0 1"), (Wdl::Win, 31));
+        // Colours swapped: the tables are probed with the board flipped
+        assert_eq!(probe(&tablebase, "1k6/8/8/8/8/5K2/6r1/8 b - - 0 1"), (Wdl::Win, 31));
+        let (wdl, dtz) = probe(&tablebase, "8/8/3k4/8/8/8/8/KR6 b - - 0 1");
+        assert_eq!(wdl, Wdl::Loss);
+        assert!(dtz < -1);
+    }
+}
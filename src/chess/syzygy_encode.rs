@@ -0,0 +1,386 @@
+use crate::chess::{Board, Color, Dtm, DtmTablebase, Piece, PieceType, Position, dtm_material, is_in_check};
+use crate::chess::syzygy::{DTZ_MAGIC, FLAG_MAPPED, HEADER_SPLIT, WDL_MAGIC, indexing, off_a1h8, piece_code};
+use crate::error::ChessError;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+// Syzygy WDL and DTZ files written from generated DTM tables, for the
+// three-piece endings without pawns. These are small enough to keep as test
+// fixtures for the prober. The published tables hold the same results but were
+// compressed with different choices, so the files aren't byte for byte the same.
+
+// Positions per side to move of a table with three unique pieces
+const TABLE_SIZE: usize = 31332;
+// 32-byte blocks and a sparse index entry every 256 values
+const BLOCK_SIZE_LOG: u8 = 5;
+const SPAN_LOG: u8 = 8;
+// Rounds of replacing the most common pair of symbols with a new one, and the
+// fewest occurrences that still make a pair worth its own symbol
+const PAIR_ROUNDS: usize = 40;
+const MIN_PAIR_COUNT: usize = 8;
+// Most values one symbol may stand for, and one block
+const MAX_SYMBOL_VALUES: usize = 4096;
+const MAX_BLOCK_VALUES: usize = 30000;
+// Marks a symbol without children in the symbol tree
+const LEAF: usize = 0xFFF;
+
+// The WDL and DTZ files of a material such as "KQvK", from its DTM table in
+// `tablebase`. The DTZ file only stores the stronger side to move, in moves.
+pub fn encode_syzygy(tablebase: &DtmTablebase, material: &str) -> Result<(Vec<u8>, Vec<u8>), ChessError> {
+    let name = dtm_material(material)?;
+    let piece_type = match name.as_str() {
+        "KQvK" => PieceType::Queen,
+        "KRvK" => PieceType::Rook,
+        "KBvK" => PieceType::Bishop,
+        "KNvK" => PieceType::Knight,
+        _ => return Err(ChessError::Tablebase(format!("Only three-piece tables without pawns can be encoded, not {}", name))),
+    };
+    if tablebase.get(&name).is_none() {
+        return Err(ChessError::Tablebase(format!("No DTM table for {}", name)));
+    }
+    // Encoding order: the piece, the white king, the black king
+    let pieces = [(piece_type, Color::White), (PieceType::King, Color::White), (PieceType::King, Color::Black)];
+    let codes = pieces.map(|(piece_type, color)| piece_code(piece_type, color));
+
+    let wdl = |dtm: Dtm| Some(match dtm {
+        Dtm::Win(_) => 4,
+        Dtm::Draw => 2,
+        Dtm::Loss(_) => 0,
+    });
+    let white = compress(&fill(&values(tablebase, pieces, Color::White, wdl)));
+    let black = compress(&fill(&values(tablebase, pieces, Color::Black, wdl)));
+    let mut wdl_file = header(WDL_MAGIC, codes);
+    write_pairs(&mut wdl_file, 0, &white);
+    write_pairs(&mut wdl_file, 0, &black);
+    write_data(&mut wdl_file, &[&white, &black]);
+
+    // Only wins need a DTZ value; they are stored in moves through a map from
+    // the most common distance down
+    let moves = |dtm: Dtm| match dtm {
+        Dtm::Win(plies) => Some(((plies - 1) / 2) as u32),
+        _ => None,
+    };
+    let raw = values(tablebase, pieces, Color::White, moves);
+    let mut counts: HashMap<u32, usize> = HashMap::new();
+    for &value in raw.iter().flatten() {
+        *counts.entry(value).or_default() += 1;
+    }
+    let mut map: Vec<u32> = counts.keys().copied().collect();
+    map.sort_by_key(|value| (Reverse(counts[value]), *value));
+    let slots: HashMap<u32, u32> = map.iter().enumerate().map(|(slot, &value)| (value, slot as u32)).collect();
+    let mapped: Vec<Option<u32>> = raw.iter().map(|value| value.map(|value| slots[&value])).collect();
+    let dtz = compress(&fill(&mapped));
+    let mut dtz_file = header(DTZ_MAGIC, codes);
+    write_pairs(&mut dtz_file, FLAG_MAPPED, &dtz);
+    dtz_file.push(map.len() as u8);
+    dtz_file.extend(map.iter().map(|&value| value as u8));
+    // No maps for the other three kinds of result
+    dtz_file.extend([0; 3]);
+    align(&mut dtz_file, 2);
+    write_data(&mut dtz_file, &[&dtz]);
+
+    Ok((wdl_file, dtz_file))
+}
+
+// Index of three unique pieces as the prober computes it: mirrored so the first
+// is in the a1-d1-d4 triangle and, while they are on the diagonal, so the next
+// piece off it is below
+fn index(mut squares: [usize; 3]) -> usize {
+    let ix = indexing();
+    if squares[0] % 8 > 3 {
+        squares.iter_mut().for_each(|sq| *sq ^= 7);
+    }
+    if squares[0] / 8 > 3 {
+        squares.iter_mut().for_each(|sq| *sq ^= 56);
+    }
+    if let Some(i) = (0..3).find(|&i| off_a1h8(squares[i]) != 0) {
+        if off_a1h8(squares[i]) > 0 {
+            squares[i..].iter_mut().for_each(|sq| *sq = ((*sq >> 3) | (*sq << 3)) & 63);
+        }
+    }
+    let [s0, s1, s2] = squares;
+    let adjust1 = usize::from(s1 > s0);
+    let adjust2 = usize::from(s2 > s0) + usize::from(s2 > s1);
+    if off_a1h8(s0) != 0 {
+        (ix.map_a1d1d4[s0] * 63 + s1 - adjust1) * 62 + s2 - adjust2
+    } else if off_a1h8(s1) != 0 {
+        (6 * 63 + (s0 / 8) * 28 + ix.map_b1h1h7[s1]) * 62 + s2 - adjust2
+    } else if off_a1h8(s2) != 0 {
+        6 * 63 * 62 + 4 * 28 * 62 + (s0 / 8) * 7 * 28 + (s1 / 8 - adjust1) * 28 + ix.map_b1h1h7[s2]
+    } else {
+        6 * 63 * 62 + 4 * 28 * 62 + 4 * 7 * 28 + (s0 / 8) * 7 * 6 + (s1 / 8 - adjust1) * 6 + (s2 / 8 - adjust2)
+    }
+}
+
+// What `value` makes of each position with `color` to move, by index. Illegal
+// positions are None, as the prober never looks them up.
+fn values(
+    tablebase: &DtmTablebase,
+    pieces: [(PieceType, Color); 3],
+    color: Color,
+    value: impl Fn(Dtm) -> Option<u32>,
+) -> Vec<Option<u32>> {
+    let mut values = vec![None; TABLE_SIZE];
+    for a in 0..64 {
+        for b in 0..64 {
+            for c in 0..64 {
+                let squares = [a, b, c];
+                if a == b || a == c || b == c {
+                    continue;
+                }
+                let mut board = Board::empty();
+                for (&sq, &(piece_type, piece_color)) in squares.iter().zip(&pieces) {
+                    let position = Position::new((sq % 8) as u8, (sq / 8) as u8);
+                    board.set_piece(&position, Some(Piece::new(piece_type, piece_color))).unwrap();
+                }
+                if is_in_check(&board, color.opposite()) {
+                    continue;
+                }
+                let Some(value) = tablebase.probe_board(&board, color).and_then(&value) else {
+                    continue;
+                };
+                let index = index(squares);
+                debug_assert!(values[index].is_none_or(|old| old == value), "symmetric positions differ");
+                values[index] = Some(value);
+            }
+        }
+    }
+    values
+}
+
+// Give the positions that don't matter the value before them, or the most
+// common value at the start, so that they compress into the runs around them
+fn fill(values: &[Option<u32>]) -> Vec<u32> {
+    let mut counts: HashMap<u32, usize> = HashMap::new();
+    for &value in values.iter().flatten() {
+        *counts.entry(value).or_default() += 1;
+    }
+    let mut previous = counts.iter()
+        .max_by_key(|&(&value, &count)| (count, Reverse(value)))
+        .map_or(0, |(&value, _)| value);
+    values.iter().map(|value| {
+        previous = value.unwrap_or(previous);
+        previous
+    }).collect()
+}
+
+// A symbol stands for a value or for the values of two other symbols in turn
+#[derive(Clone, Copy)]
+enum Symbol {
+    Value(u32),
+    Pair(usize, usize),
+}
+
+// One compressed table, laid out as the prober expects
+struct Compressed {
+    blocks: u32,
+    max_len: u8,
+    min_len: u8,
+    // Lowest symbol number of each code length, shortest first
+    lowest: Vec<u16>,
+    // Children of each symbol, or its value and LEAF
+    tree: Vec<(usize, usize)>,
+    // Block and offset of every 2^SPAN_LOG-th value
+    sparse: Vec<(u32, u16)>,
+    // Values in each block, less one
+    block_lengths: Vec<u16>,
+    data: Vec<u8>,
+}
+
+// Code lengths of a Huffman code for the given frequencies
+fn code_lengths(frequencies: &[usize]) -> Vec<u32> {
+    let n = frequencies.len();
+    if n == 1 {
+        return vec![1];
+    }
+    let mut parent = vec![None; 2 * n];
+    let mut heap: BinaryHeap<Reverse<(usize, usize)>> = frequencies.iter().enumerate()
+        .map(|(symbol, &frequency)| Reverse((frequency, symbol)))
+        .collect();
+    let mut next = n;
+    while let (Some(Reverse((w1, a))), Some(Reverse((w2, b)))) = (heap.pop(), heap.pop()) {
+        parent[a] = Some(next);
+        parent[b] = Some(next);
+        heap.push(Reverse((w1 + w2, next)));
+        next += 1;
+    }
+    (0..n).map(|symbol| {
+        let (mut depth, mut node) = (0, symbol);
+        while let Some(up) = parent[node] {
+            node = up;
+            depth += 1;
+        }
+        depth
+    }).collect()
+}
+
+// Compress values as the Syzygy tables do: repeatedly pair up the most common
+// neighbouring symbols, then Huffman-code what is left into fixed-size blocks
+fn compress(values: &[u32]) -> Compressed {
+    let mut symbols: Vec<Symbol> = Vec::new();
+    let mut value_symbols: HashMap<u32, usize> = HashMap::new();
+    let mut sequence: Vec<usize> = values.iter().map(|&value| {
+        *value_symbols.entry(value).or_insert_with(|| {
+            symbols.push(Symbol::Value(value));
+            symbols.len() - 1
+        })
+    }).collect();
+    let mut lengths: Vec<usize> = vec![1; symbols.len()];
+
+    for _ in 0..PAIR_ROUNDS {
+        let mut counts: HashMap<(usize, usize), usize> = HashMap::new();
+        for pair in sequence.windows(2) {
+            *counts.entry((pair[0], pair[1])).or_default() += 1;
+        }
+        let best = counts.iter()
+            .filter(|&(&(a, b), _)| lengths[a] + lengths[b] <= MAX_SYMBOL_VALUES)
+            .max_by_key(|&(&pair, &count)| (count, Reverse(pair)));
+        let Some((&(a, b), &count)) = best else { break };
+        if count < MIN_PAIR_COUNT {
+            break;
+        }
+        symbols.push(Symbol::Pair(a, b));
+        lengths.push(lengths[a] + lengths[b]);
+        let pair = symbols.len() - 1;
+        let mut paired = Vec::with_capacity(sequence.len());
+        let mut i = 0;
+        while i < sequence.len() {
+            if i + 1 < sequence.len() && sequence[i] == a && sequence[i + 1] == b {
+                paired.push(pair);
+                i += 2;
+            } else {
+                paired.push(sequence[i]);
+                i += 1;
+            }
+        }
+        sequence = paired;
+    }
+
+    // Symbols the sequence no longer uses still need a code, as pairs refer to them
+    let mut frequencies = vec![1; symbols.len()];
+    for &symbol in &sequence {
+        frequencies[symbol] += 1;
+    }
+    let code_lengths = code_lengths(&frequencies);
+    let max_len = *code_lengths.iter().max().unwrap();
+    let min_len = *code_lengths.iter().min().unwrap();
+
+    // Canonical code: symbols are renumbered longest code first
+    let mut order: Vec<usize> = (0..symbols.len()).collect();
+    order.sort_by_key(|&symbol| (Reverse(code_lengths[symbol]), symbol));
+    let mut number = vec![0; symbols.len()];
+    for (i, &symbol) in order.iter().enumerate() {
+        number[symbol] = i;
+    }
+    let len_count = (max_len - min_len + 1) as usize;
+    let mut count = vec![0u64; len_count];
+    for &len in &code_lengths {
+        count[(len - min_len) as usize] += 1;
+    }
+    let (mut lowest, mut base) = (vec![0u64; len_count], vec![0u64; len_count]);
+    for i in (0..len_count - 1).rev() {
+        lowest[i] = lowest[i + 1] + count[i + 1];
+        base[i] = (base[i + 1] + count[i + 1]) / 2;
+    }
+    let code = |symbol: usize| {
+        let len = code_lengths[symbol];
+        let i = (len - min_len) as usize;
+        (base[i] + number[symbol] as u64 - lowest[i], len)
+    };
+    let tree = order.iter().map(|&symbol| match symbols[symbol] {
+        Symbol::Value(value) => (value as usize, LEAF),
+        Symbol::Pair(a, b) => (number[a], number[b]),
+    }).collect();
+
+    // Fill the blocks with codes, most significant bit first
+    let block_bytes = 1 << BLOCK_SIZE_LOG;
+    let (mut data, mut block_lengths, mut starts) = (Vec::new(), Vec::new(), Vec::new());
+    let (mut block, mut bits, mut block_values, mut total) = (vec![0u8; block_bytes], 0, 0, 0);
+    for (i, &symbol) in sequence.iter().enumerate() {
+        let (bits_of_code, len) = code(symbol);
+        block_values += lengths[symbol];
+        for k in (0..len).rev() {
+            if (bits_of_code >> k) & 1 == 1 {
+                block[bits / 8] |= 0x80 >> (bits % 8);
+            }
+            bits += 1;
+        }
+        let full = sequence.get(i + 1).is_none_or(|&next| {
+            bits + code(next).1 as usize > 8 * block_bytes || block_values + lengths[next] > MAX_BLOCK_VALUES
+        });
+        if full {
+            starts.push(total);
+            total += block_values;
+            block_lengths.push((block_values - 1) as u16);
+            data.append(&mut block);
+            (block, bits, block_values) = (vec![0u8; block_bytes], 0, 0);
+        }
+    }
+
+    // Where the value in the middle of each span is
+    let span = 1 << SPAN_LOG;
+    let sparse = (0..values.len().div_ceil(span)).map(|k| {
+        let middle = k * span + span / 2;
+        let at = middle.min(values.len() - 1);
+        let block = starts.partition_point(|&start| start <= at) - 1;
+        (block as u32, (middle - starts[block]) as u16)
+    }).collect();
+
+    Compressed {
+        blocks: block_lengths.len() as u32,
+        max_len: max_len as u8,
+        min_len: min_len as u8,
+        lowest: lowest.iter().map(|&symbol| symbol as u16).collect(),
+        tree,
+        sparse,
+        block_lengths,
+        data,
+    }
+}
+
+// Magic, flags and the pieces in encoding order for both sides to move
+fn header(magic: [u8; 4], codes: [u8; 3]) -> Vec<u8> {
+    let mut file = magic.to_vec();
+    file.extend([HEADER_SPLIT, 0]);
+    file.extend(codes.map(|code| code | (code << 4)));
+    align(&mut file, 2);
+    file
+}
+
+// A table's decoding information: sizes, code lengths and the symbol tree
+fn write_pairs(file: &mut Vec<u8>, flags: u8, table: &Compressed) {
+    file.extend([flags, BLOCK_SIZE_LOG, SPAN_LOG, 0]);
+    file.extend(table.blocks.to_le_bytes());
+    file.extend([table.max_len, table.min_len]);
+    for lowest in &table.lowest {
+        file.extend(lowest.to_le_bytes());
+    }
+    file.extend((table.tree.len() as u16).to_le_bytes());
+    for &(left, right) in &table.tree {
+        file.extend([(left & 0xFF) as u8, ((left >> 8) & 0xF) as u8 | ((right & 0xF) << 4) as u8, (right >> 4) as u8]);
+    }
+    align(file, 2);
+}
+
+// The sparse indices, block lengths and blocks of every table, in that order
+fn write_data(file: &mut Vec<u8>, tables: &[&Compressed]) {
+    for table in tables {
+        for &(block, offset) in &table.sparse {
+            file.extend(block.to_le_bytes());
+            file.extend(offset.to_le_bytes());
+        }
+    }
+    for table in tables {
+        for length in &table.block_lengths {
+            file.extend(length.to_le_bytes());
+        }
+    }
+    for table in tables {
+        align(file, 64);
+        file.extend(&table.data);
+    }
+}
+
+fn align(file: &mut Vec<u8>, to: usize) {
+    file.resize(file.len().next_multiple_of(to), 0);
+}
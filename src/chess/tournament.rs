@@ -0,0 +1,472 @@
+use crate::chess::{bayeselo, Adjudication, Color, Game, GameRecord, MatchScore, RatedGame, Rating, TimeControl};
+use crate::error::ChessError;
+use serde::{Serialize, Deserialize};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+// Tournaments between several engines: who meets whom with which colours and
+// openings, the results so far, and the tables made from them. The whole
+// tournament is plain data so that it can be saved after every game and picked
+// up again after an interruption.
+
+// How the players are paired
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TournamentFormat {
+    // Everyone plays everyone once per cycle
+    RoundRobin,
+    // Everyone plays everyone twice per cycle, once with each colour and the same opening
+    DoubleRoundRobin,
+    // The first player plays everyone else twice per cycle, once with each colour
+    Gauntlet,
+}
+
+impl FromStr for TournamentFormat {
+    type Err = ChessError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "roundrobin" | "round-robin" | "rr" => Ok(TournamentFormat::RoundRobin),
+            "double" | "double-round-robin" | "drr" => Ok(TournamentFormat::DoubleRoundRobin),
+            "gauntlet" => Ok(TournamentFormat::Gauntlet),
+            _ => Err(ChessError::Engine(format!("Unknown tournament format: {} (round-robin, double or gauntlet)", s))),
+        }
+    }
+}
+
+impl fmt::Display for TournamentFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TournamentFormat::RoundRobin => write!(f, "round-robin"),
+            TournamentFormat::DoubleRoundRobin => write!(f, "double round-robin"),
+            TournamentFormat::Gauntlet => write!(f, "gauntlet"),
+        }
+    }
+}
+
+// One scheduled game. Players and openings are indices into the tournament's lists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Pairing {
+    pub round: usize,
+    pub white: usize,
+    pub black: usize,
+    pub opening: usize,
+}
+
+// The games of `cycles` cycles of a format between `players` players, round by
+// round. Returns the pairings and the number of distinct openings they use: the
+// two games of a pair with colours reversed share one.
+pub fn schedule(format: TournamentFormat, players: usize, cycles: usize) -> (Vec<Pairing>, usize) {
+    let mut pairings = Vec::new();
+    let mut openings = 0;
+    let mut round = 0;
+    for cycle in 0..cycles {
+        match format {
+            TournamentFormat::RoundRobin | TournamentFormat::DoubleRoundRobin => {
+                let mut first_leg = circle_rounds(players);
+                // Every other cycle swaps colours, so repeated cycles even out
+                if cycle % 2 == 1 {
+                    for games in &mut first_leg {
+                        for game in games.iter_mut() {
+                            *game = (game.1, game.0);
+                        }
+                    }
+                }
+                let first_opening = openings;
+                for games in &first_leg {
+                    round += 1;
+                    for &(white, black) in games {
+                        pairings.push(Pairing { round, white, black, opening: openings });
+                        openings += 1;
+                    }
+                }
+                if format == TournamentFormat::DoubleRoundRobin {
+                    // The second leg repeats the first with colours reversed
+                    let mut opening = first_opening;
+                    for games in &first_leg {
+                        round += 1;
+                        for &(white, black) in games {
+                            pairings.push(Pairing { round, white: black, black: white, opening });
+                            opening += 1;
+                        }
+                    }
+                }
+            },
+            TournamentFormat::Gauntlet => {
+                round += 1;
+                for opponent in 1..players {
+                    pairings.push(Pairing { round, white: 0, black: opponent, opening: openings });
+                    pairings.push(Pairing { round, white: opponent, black: 0, opening: openings });
+                    openings += 1;
+                }
+            },
+        }
+    }
+    (pairings, openings)
+}
+
+// A single round-robin by the circle method: one slot stays put while the
+// others rotate around it. The game at the fixed slot alternates colours from
+// round to round and the others by board, so nobody's whites and blacks differ
+// by more than one. With an odd number of players the fixed slot is the extra
+// one, and whoever meets it sits out.
+fn circle_rounds(players: usize) -> Vec<Vec<(usize, usize)>> {
+    let slots = players + players % 2;
+    let mut circle: Vec<usize> = (0..slots).collect();
+    circle.rotate_right(1);
+    let mut rounds = Vec::new();
+    for round in 0..slots.saturating_sub(1) {
+        let mut games = Vec::new();
+        for i in 0..slots / 2 {
+            let (a, b) = (circle[i], circle[slots - 1 - i]);
+            if a >= players || b >= players {
+                continue;
+            }
+            let a_white = if i == 0 { round.is_multiple_of(2) } else { i % 2 == 1 };
+            games.push(if a_white { (a, b) } else { (b, a) });
+        }
+        rounds.push(games);
+        circle[1..].rotate_right(1);
+    }
+    rounds
+}
+
+// A player: the engine spec it was entered with, and its unique display name
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entrant {
+    pub spec: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tournament {
+    pub format: TournamentFormat,
+    pub cycles: usize,
+    pub time_control: TimeControl,
+    pub adjudication: Adjudication,
+    pub players: Vec<Entrant>,
+    // Starting positions as FEN
+    pub openings: Vec<String>,
+    pub pairings: Vec<Pairing>,
+    // The finished game of each pairing
+    pub games: Vec<Option<GameRecord>>,
+}
+
+impl Tournament {
+    // A tournament that hasn't started. Players with the same name are told
+    // apart by a number; `opening` supplies the starting position for each
+    // opening index.
+    pub fn new(
+        players: Vec<Entrant>,
+        format: TournamentFormat,
+        cycles: usize,
+        time_control: TimeControl,
+        adjudication: Adjudication,
+        mut opening: impl FnMut(usize) -> Game,
+    ) -> Result<Self, ChessError> {
+        if players.len() < 2 {
+            return Err(ChessError::Engine("A tournament needs at least two engines".to_string()));
+        }
+        let names: Vec<String> = players.iter().map(|p| p.name.clone()).collect();
+        let mut players = players;
+        for (i, player) in players.iter_mut().enumerate() {
+            if names.iter().filter(|&name| *name == names[i]).count() > 1 {
+                let number = names[..i].iter().filter(|&name| *name == names[i]).count() + 1;
+                player.name = format!("{} ({})", names[i], number);
+            }
+        }
+        let (pairings, opening_count) = schedule(format, players.len(), cycles.max(1));
+        let openings = (0..opening_count).map(|i| opening(i).to_fen()).collect();
+        Ok(Self {
+            format,
+            cycles: cycles.max(1),
+            time_control,
+            adjudication,
+            players,
+            openings,
+            games: vec![None; pairings.len()],
+            pairings,
+        })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ChessError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|e| ChessError::Internal(format!("Failed to read {}: {}", path.display(), e)))?;
+        let tournament: Self = serde_json::from_str(&text)
+            .map_err(|e| ChessError::Internal(format!("Invalid tournament state in {}: {}", path.display(), e)))?;
+        if tournament.games.len() != tournament.pairings.len()
+            || tournament.pairings.iter().any(|p| p.white >= tournament.players.len() || p.black >= tournament.players.len() || p.opening >= tournament.openings.len())
+        {
+            return Err(ChessError::Internal(format!("Inconsistent tournament state in {}", path.display())));
+        }
+        Ok(tournament)
+    }
+
+    // Written to a temporary file first so an interruption never leaves a
+    // half-written state behind
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ChessError> {
+        let path = path.as_ref();
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| ChessError::Internal(format!("Failed to serialize tournament: {}", e)))?;
+        let temporary = path.with_extension("tmp");
+        fs::write(&temporary, json + "\n")
+            .and_then(|_| fs::rename(&temporary, path))
+            .map_err(|e| ChessError::Internal(format!("Failed to write {}: {}", path.display(), e)))
+    }
+
+    // Indices of the pairings still to be played, in schedule order
+    pub fn pending(&self) -> Vec<usize> {
+        (0..self.pairings.len()).filter(|&i| self.games[i].is_none()).collect()
+    }
+
+    pub fn finished_games(&self) -> usize {
+        self.games.iter().filter(|g| g.is_some()).count()
+    }
+
+    pub fn opening(&self, pairing: usize) -> Result<Game, ChessError> {
+        Game::from_fen(&self.openings[self.pairings[pairing].opening])
+    }
+
+    pub fn record(&mut self, pairing: usize, record: GameRecord) {
+        self.games[pairing] = Some(record);
+    }
+
+    // The round tag of a game, e.g. "3.2" for the second game of round 3
+    pub fn round_label(&self, pairing: usize) -> String {
+        let round = self.pairings[pairing].round;
+        let number = self.pairings[..pairing].iter().filter(|p| p.round == round).count() + 1;
+        format!("{}.{}", round, number)
+    }
+
+    // Each player's score against each other player
+    pub fn scores(&self) -> Vec<Vec<MatchScore>> {
+        let count = self.players.len();
+        let mut scores = vec![vec![MatchScore::default(); count]; count];
+        for (pairing, game) in self.pairings.iter().zip(&self.games) {
+            if let Some(game) = game {
+                scores[pairing.white][pairing.black].add(game.result.points(Color::White));
+                scores[pairing.black][pairing.white].add(game.result.points(Color::Black));
+            }
+        }
+        scores
+    }
+
+    // Rating list of the games played so far, best first
+    pub fn ratings(&self) -> Vec<Rating> {
+        let games: Vec<RatedGame> = self.pairings.iter().zip(&self.games)
+            .filter_map(|(pairing, game)| game.as_ref().map(|game| RatedGame {
+                white: pairing.white,
+                black: pairing.black,
+                white_points: game.result.points(Color::White),
+            }))
+            .collect();
+        bayeselo(self.players.len(), &games)
+    }
+
+    // Every finished game as PGN, in schedule order
+    pub fn to_pgn(&self, event: &str) -> String {
+        self.games.iter().enumerate()
+            .filter_map(|(i, game)| game.as_ref().map(|game| game.to_pgn(event, &self.round_label(i))))
+            .collect()
+    }
+
+    // The crosstable: players by points, each row holding the points scored
+    // against every opponent
+    pub fn crosstable(&self) -> String {
+        let scores = self.scores();
+        let totals: Vec<MatchScore> = scores.iter().map(|row| {
+            row.iter().fold(MatchScore::default(), |total, s| MatchScore {
+                wins: total.wins + s.wins,
+                draws: total.draws + s.draws,
+                losses: total.losses + s.losses,
+            })
+        }).collect();
+        let mut order: Vec<usize> = (0..self.players.len()).collect();
+        order.sort_by(|&a, &b| totals[b].points().total_cmp(&totals[a].points()));
+
+        let name_width = self.players.iter().map(|p| p.name.len()).max().unwrap_or(0).max(6);
+        let cell = |score: &MatchScore| format!("{:.1}/{}", score.points(), score.games());
+        let cell_width = scores.iter().flatten().map(|s| cell(s).len()).max().unwrap_or(0).max(3);
+        let total_width = totals.iter().map(|s| cell(s).len()).max().unwrap_or(0).max(5);
+
+        let mut table = format!("{:>3}  {:<name_width$}  {:>total_width$}", "#", "Engine", "Score");
+        for rank in 1..=order.len() {
+            table.push_str(&format!("  {:>cell_width$}", rank));
+        }
+        table.push('\n');
+        for (rank, &player) in order.iter().enumerate() {
+            table.push_str(&format!("{:>3}  {:<name_width$}  {:>total_width$}", rank + 1, self.players[player].name, cell(&totals[player])));
+            for &opponent in &order {
+                let text = if opponent == player {
+                    "-".to_string()
+                } else if scores[player][opponent].games() == 0 {
+                    String::new()
+                } else {
+                    cell(&scores[player][opponent])
+                };
+                table.push_str(&format!("  {:>cell_width$}", text));
+            }
+            table.push('\n');
+        }
+        table
+    }
+
+    // The rating list as a table
+    pub fn rating_table(&self) -> String {
+        let name_width = self.players.iter().map(|p| p.name.len()).max().unwrap_or(0).max(6);
+        let mut table = format!("{:>4}  {:<name_width$}  {:>6}  {:>5}  {:>5}  {:>6}  {:>6}\n", "Rank", "Engine", "Elo", "+/-", "Games", "Score", "Draws");
+        for (rank, rating) in self.ratings().iter().enumerate() {
+            table.push_str(&format!(
+                "{:>4}  {:<name_width$}  {:>6.0}  {:>5.0}  {:>5}  {:>5.1}%  {:>5.1}%\n",
+                rank + 1, self.players[rating.player].name, rating.elo, rating.error, rating.games,
+                rating.score * 100.0, rating.draw_ratio * 100.0,
+            ));
+        }
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::GameResult;
+
+    // How often each ordered pair (white, black) occurs
+    fn meetings(pairings: &[Pairing], players: usize) -> Vec<Vec<usize>> {
+        let mut count = vec![vec![0; players]; players];
+        for p in pairings {
+            count[p.white][p.black] += 1;
+        }
+        count
+    }
+
+    #[test]
+    fn round_robin_meets_everyone_once_per_cycle_with_balanced_colours() {
+        for players in 2..=9 {
+            let (pairings, openings) = schedule(TournamentFormat::RoundRobin, players, 2);
+            let count = meetings(&pairings, players);
+            assert_eq!(pairings.len(), players * (players - 1));
+            assert_eq!(openings, pairings.len());
+            // Two cycles play each pair once with each colour
+            for (a, row) in count.iter().enumerate() {
+                for (b, &games) in row.iter().enumerate() {
+                    assert_eq!(games, usize::from(a != b), "{} players: {} v {}", players, a, b);
+                }
+            }
+            // One cycle is a complete round-robin on its own, with colours as even as they can be
+            let (cycle, _) = schedule(TournamentFormat::RoundRobin, players, 1);
+            let count = meetings(&cycle, players);
+            for (a, row) in count.iter().enumerate() {
+                for (b, &games) in row.iter().enumerate().skip(a + 1) {
+                    assert_eq!(games + count[b][a], 1);
+                }
+                let whites = cycle.iter().filter(|p| p.white == a).count();
+                let blacks = cycle.iter().filter(|p| p.black == a).count();
+                assert!(whites.abs_diff(blacks) <= 1, "{} players: player {} has {} whites, {} blacks", players, a, whites, blacks);
+            }
+        }
+    }
+
+    #[test]
+    fn double_round_robin_reverses_colours_with_the_same_opening() {
+        let (pairings, openings) = schedule(TournamentFormat::DoubleRoundRobin, 4, 1);
+        assert_eq!(pairings.len(), 12);
+        assert_eq!(openings, 6);
+        let count = meetings(&pairings, 4);
+        for (a, row) in count.iter().enumerate() {
+            for (b, &games) in row.iter().enumerate() {
+                assert_eq!(games, usize::from(a != b));
+            }
+        }
+        for p in &pairings {
+            let reverse = pairings.iter().find(|q| q.white == p.black && q.black == p.white).unwrap();
+            assert_eq!(reverse.opening, p.opening);
+        }
+    }
+
+    #[test]
+    fn odd_player_counts_get_a_bye() {
+        let (pairings, _) = schedule(TournamentFormat::RoundRobin, 5, 1);
+        let rounds = pairings.iter().map(|p| p.round).max().unwrap();
+        assert_eq!(rounds, 5);
+        let mut byes = vec![0; 5];
+        for round in 1..=rounds {
+            let games: Vec<&Pairing> = pairings.iter().filter(|p| p.round == round).collect();
+            assert_eq!(games.len(), 2);
+            let playing: Vec<usize> = games.iter().flat_map(|p| [p.white, p.black]).collect();
+            for (player, byes) in byes.iter_mut().enumerate() {
+                match playing.iter().filter(|&&p| p == player).count() {
+                    0 => *byes += 1,
+                    1 => {},
+                    n => panic!("player {} plays {} games in round {}", player, n, round),
+                }
+            }
+        }
+        assert_eq!(byes, [1; 5]);
+    }
+
+    #[test]
+    fn gauntlet_plays_the_first_player_against_everyone() {
+        let (pairings, openings) = schedule(TournamentFormat::Gauntlet, 4, 2);
+        assert_eq!(pairings.len(), 12);
+        assert_eq!(openings, 6);
+        assert!(pairings.iter().all(|p| (p.white == 0) != (p.black == 0)));
+        assert_eq!(pairings.iter().filter(|p| p.white == 0).count(), 6);
+    }
+
+    fn tournament(players: usize) -> Tournament {
+        let players = (0..players).map(|i| Entrant { spec: "builtin".to_string(), name: format!("engine{}", i) }).collect();
+        Tournament::new(players, TournamentFormat::RoundRobin, 1, TimeControl::Depth(1), Adjudication::default(), |_| Game::new()).unwrap()
+    }
+
+    fn record(tournament: &Tournament, pairing: usize, result: GameResult) -> GameRecord {
+        let p = tournament.pairings[pairing];
+        GameRecord {
+            white: tournament.players[p.white].name.clone(),
+            black: tournament.players[p.black].name.clone(),
+            start_fen: tournament.openings[p.opening].clone(),
+            moves: Vec::new(),
+            result,
+            termination: "adjudication".to_string(),
+        }
+    }
+
+    #[test]
+    fn resumes_without_replaying_finished_games() {
+        let mut tournament = tournament(4);
+        assert_eq!(tournament.pending(), (0..6).collect::<Vec<_>>());
+        let first = record(&tournament, 0, GameResult::WhiteWins);
+        let fourth = record(&tournament, 3, GameResult::Draw);
+        tournament.record(0, first);
+        tournament.record(3, fourth);
+
+        let path = std::env::temp_dir().join(format!("rustychess-tournament-{}.json", std::process::id()));
+        tournament.save(&path).unwrap();
+        let resumed = Tournament::load(&path);
+        fs::remove_file(&path).unwrap();
+        let resumed = resumed.unwrap();
+
+        assert_eq!(resumed.pairings, tournament.pairings);
+        assert_eq!(resumed.finished_games(), 2);
+        assert_eq!(resumed.pending(), [1, 2, 4, 5]);
+        let white = resumed.pairings[0].white;
+        let black = resumed.pairings[0].black;
+        assert_eq!(resumed.scores()[white][black].wins, 1);
+        assert_eq!(resumed.scores()[black][white].losses, 1);
+    }
+
+    #[test]
+    fn duplicate_names_are_numbered() {
+        let players = vec![
+            Entrant { spec: "a".to_string(), name: "engine".to_string() },
+            Entrant { spec: "b".to_string(), name: "engine".to_string() },
+            Entrant { spec: "c".to_string(), name: "other".to_string() },
+        ];
+        let tournament = Tournament::new(players, TournamentFormat::Gauntlet, 1, TimeControl::Depth(1), Adjudication::default(), |_| Game::new()).unwrap();
+        let names: Vec<&str> = tournament.players.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["engine (1)", "engine (2)", "other"]);
+    }
+}
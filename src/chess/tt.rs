@@ -0,0 +1,146 @@
+use crate::chess::{ChessMove, Position};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Default transposition table size in megabytes
+pub const DEFAULT_HASH_MB: usize = 16;
+
+// What a stored score tells us about the true value of the position
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    // The score is at least this value (fail high)
+    Lower,
+    // The score is at most this value (fail low)
+    Upper,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TtEntry {
+    pub best_move: Option<ChessMove>,
+    pub score: i32,
+    pub depth: u8,
+    pub bound: Bound,
+}
+
+impl TtEntry {
+    // Layout: from (6 bits) | to (6) | has move (1) | depth (8) | bound (2) | unused | score (32)
+    fn pack(&self) -> u64 {
+        let mut data = 0u64;
+        if let Some(m) = &self.best_move {
+            data |= square_index(&m.from) as u64;
+            data |= (square_index(&m.to) as u64) << 6;
+            data |= 1 << 12;
+        }
+        data |= (self.depth as u64) << 13;
+        data |= (match self.bound {
+            Bound::Exact => 0u64,
+            Bound::Lower => 1,
+            Bound::Upper => 2,
+        }) << 21;
+        data |= (self.score as u32 as u64) << 32;
+        data
+    }
+
+    fn unpack(data: u64) -> Self {
+        let best_move = if data & (1 << 12) != 0 {
+            Some(ChessMove::new(
+                square_from_index((data & 0x3F) as u8),
+                square_from_index(((data >> 6) & 0x3F) as u8),
+            ))
+        } else {
+            None
+        };
+        let bound = match (data >> 21) & 0x3 {
+            0 => Bound::Exact,
+            1 => Bound::Lower,
+            _ => Bound::Upper,
+        };
+
+        Self {
+            best_move,
+            score: (data >> 32) as u32 as i32,
+            depth: ((data >> 13) & 0xFF) as u8,
+            bound,
+        }
+    }
+}
+
+fn square_index(pos: &Position) -> u8 {
+    pos.rank * 8 + pos.file
+}
+
+fn square_from_index(index: u8) -> Position {
+    Position::new(index % 8, index / 8)
+}
+
+struct Slot {
+    // Stored as key ^ data so a torn write from another thread fails verification
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+// Lock-free transposition table shared between search threads
+pub struct TranspositionTable {
+    slots: Vec<Slot>,
+}
+
+impl TranspositionTable {
+    pub fn new(size_mb: usize) -> Self {
+        let count = (size_mb.max(1) * 1024 * 1024 / std::mem::size_of::<Slot>()).max(1);
+        let slots = (0..count)
+            .map(|_| Slot { key: AtomicU64::new(0), data: AtomicU64::new(0) })
+            .collect();
+        Self { slots }
+    }
+
+    fn slot(&self, key: u64) -> &Slot {
+        &self.slots[(key % self.slots.len() as u64) as usize]
+    }
+
+    pub fn probe(&self, key: u64) -> Option<TtEntry> {
+        let slot = self.slot(key);
+        let data = slot.data.load(Ordering::Relaxed);
+        let stored = slot.key.load(Ordering::Relaxed);
+
+        if data != 0 && stored ^ data == key {
+            Some(TtEntry::unpack(data))
+        } else {
+            None
+        }
+    }
+
+    pub fn store(&self, key: u64, entry: &TtEntry) {
+        let slot = self.slot(key);
+
+        // Keep deeper results for the same position
+        if let Some(existing) = self.probe(key) {
+            if existing.depth > entry.depth && entry.bound != Bound::Exact {
+                return;
+            }
+        }
+
+        let data = entry.pack();
+        slot.key.store(key ^ data, Ordering::Relaxed);
+        slot.data.store(data, Ordering::Relaxed);
+    }
+
+    pub fn clear(&self) {
+        for slot in &self.slots {
+            slot.key.store(0, Ordering::Relaxed);
+            slot.data.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        Self::new(DEFAULT_HASH_MB)
+    }
+}
+
+impl fmt::Debug for TranspositionTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "TranspositionTable({} slots)", self.slots.len())
+    }
+}
@@ -0,0 +1,405 @@
+use crate::chess::{Board, Color, Engine, EvalParams, Game, is_in_check, is_square_attacked};
+use crate::chess::endgame;
+use crate::chess::engine::piece_value;
+use crate::error::ChessError;
+use std::thread;
+
+// Texel tuning: fit the evaluation weights to game results. A position's
+// evaluation q predicts White's score as sigmoid(q) = 1 / (1 + 10^(-K q / 400)),
+// and the tuner minimises the mean squared difference between that prediction
+// and the result of the game the position came from.
+
+// Largest change the local search tries first; it halves whenever no single
+// change of that size improves the error, down to one centipawn
+const INITIAL_STEP: i32 = 16;
+
+// A position labelled with the result of its game, from White's point of view:
+// 1 for a White win, 0.5 for a draw and 0 for a Black win
+#[derive(Debug, Clone)]
+pub struct TuningPosition {
+    pub board: Board,
+    pub side_to_move: Color,
+    pub result: f64,
+}
+
+// Parse a game result as written in PGN ("1-0", "1/2-1/2") or as a score ("1.0", "0.5")
+pub fn parse_result(token: &str) -> Option<f64> {
+    match token.trim_matches(|c| c == '"' || c == ';' || c == '[' || c == ']') {
+        "1-0" | "1.0" => Some(1.0),
+        "0-1" | "0.0" => Some(0.0),
+        "1/2-1/2" | "½-½" | "0.5" => Some(0.5),
+        _ => None,
+    }
+}
+
+// Read labelled positions from EPD (or FEN) lines. The result may be given as a
+// `c9 "1-0";` operation or a trailing `[0.5]`, the two usual formats of tuning sets.
+pub fn load_epd(text: &str) -> Result<Vec<TuningPosition>, ChessError> {
+    let mut positions = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 2 {
+            return Err(ChessError::InvalidPosition(format!("Line {}: not an EPD position", number + 1)));
+        }
+        let game = Game::from_fen(&format!("{} {}", fields[0], fields[1]))
+            .map_err(|e| ChessError::InvalidPosition(format!("Line {}: {}", number + 1, e)))?;
+        let result = fields[2..].iter().find_map(|token| parse_result(token))
+            .ok_or_else(|| ChessError::InvalidPosition(format!("Line {}: no game result", number + 1)))?;
+        positions.push(TuningPosition { board: game.board, side_to_move: game.current_turn, result });
+    }
+    Ok(positions)
+}
+
+// Read the positions of every finished game in a PGN file, skipping the first
+// `skip_plies` of each game as opening theory. A game stops at the first move
+// the board can't play (castling, promotion, en passant) since every position
+// after it would be wrong.
+pub fn load_pgn(text: &str, engine: &Engine, skip_plies: usize) -> Vec<TuningPosition> {
+    let mut positions = Vec::new();
+    let mut result = None;
+    let mut movetext = String::new();
+    for line in text.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            // A tag after the moves starts the next game
+            if !movetext.is_empty() {
+                read_pgn_game(&movetext, result, engine, skip_plies, &mut positions);
+                movetext.clear();
+                result = None;
+            }
+            if let Some(value) = line.strip_prefix("[Result ") {
+                result = parse_result(value);
+            }
+        } else if !line.starts_with('%') {
+            movetext.push_str(line);
+            movetext.push('\n');
+        }
+    }
+    if !movetext.trim().is_empty() {
+        read_pgn_game(&movetext, result, engine, skip_plies, &mut positions);
+    }
+    positions
+}
+
+fn read_pgn_game(movetext: &str, result: Option<f64>, engine: &Engine, skip_plies: usize, positions: &mut Vec<TuningPosition>) {
+    // Unfinished games ("*") say nothing about who was better
+    let result = match result {
+        Some(result) => result,
+        None => return,
+    };
+
+    // Drop comments, variations and rest-of-line comments
+    let mut clean = String::with_capacity(movetext.len());
+    let mut braces = false;
+    let mut parens = 0;
+    let mut line_comment = false;
+    for c in movetext.chars() {
+        match c {
+            '{' if !line_comment => braces = true,
+            '}' if braces => braces = false,
+            '(' if !braces && !line_comment => parens += 1,
+            ')' if !braces && !line_comment && parens > 0 => parens -= 1,
+            ';' if !braces => line_comment = true,
+            '\n' => line_comment = false,
+            _ if braces || parens > 0 || line_comment => {},
+            _ => clean.push(c),
+        }
+    }
+
+    let mut game = Game::new();
+    for (ply, token) in movetext_moves(&clean).enumerate() {
+        let chess_move = match engine.parse_algebraic_notation(token, &game.board, game.current_turn) {
+            Ok(chess_move) => chess_move,
+            Err(_) => return,
+        };
+        if game.board.make_move(&chess_move.from, &chess_move.to).is_err() {
+            return;
+        }
+        game.current_turn = game.current_turn.opposite();
+        if ply + 1 > skip_plies {
+            positions.push(TuningPosition { board: game.board.clone(), side_to_move: game.current_turn, result });
+        }
+    }
+}
+
+// Movetext tokens that are moves: move numbers, NAGs and the result marker are left out
+fn movetext_moves(movetext: &str) -> impl Iterator<Item = &str> {
+    movetext.split_whitespace()
+        .map(|token| token.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.'))
+        .filter(|token| !token.is_empty() && !token.starts_with('$') && parse_result(token).is_none() && *token != "*")
+}
+
+// Whether the static evaluation can stand for a position: the side to move isn't
+// in check and can't win material at once by capturing a more valuable piece or
+// an undefended one
+pub fn is_quiet(engine: &Engine, board: &Board, color: Color) -> bool {
+    if is_in_check(board, color) {
+        return false;
+    }
+    let moves = match engine.get_legal_moves(board, color) {
+        Ok(moves) => moves,
+        Err(_) => return false,
+    };
+    !moves.iter().any(|m| {
+        let victim = match board.get_piece(&m.to) {
+            Some(victim) => victim,
+            None => return false,
+        };
+        let attacker = board.get_piece(&m.from).map(|p| piece_value(p.piece_type)).unwrap_or(0);
+        piece_value(victim.piece_type) > attacker || !is_square_attacked(board, &m.to, color.opposite())
+    })
+}
+
+// White's expected score for an evaluation of `score` centipawns
+pub fn sigmoid(score: f64, k: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * score / 400.0))
+}
+
+// Mean squared difference between the predicted and actual results, computed
+// across all cores
+pub fn tuning_error(params: &EvalParams, positions: &[TuningPosition], k: f64) -> f64 {
+    if positions.is_empty() {
+        return 0.0;
+    }
+    let threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    let chunk_size = positions.len().div_ceil(threads);
+    let total: f64 = thread::scope(|scope| {
+        let workers: Vec<_> = positions.chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || {
+                chunk.iter()
+                    .map(|position| {
+                        // The engine scales drawish material the same way, so the
+                        // weights are fitted to the score it actually uses
+                        let score = endgame::adjust(&position.board, Color::White, params.evaluate(&position.board, Color::White)) as f64;
+                        (position.result - sigmoid(score, k)).powi(2)
+                    })
+                    .sum::<f64>()
+            }))
+            .collect();
+        workers.into_iter().map(|worker| worker.join().unwrap_or(0.0)).sum()
+    });
+    total / positions.len() as f64
+}
+
+// The scaling constant K that best fits the current weights to the results,
+// found by a scan that narrows tenfold each round
+pub fn fit_k(params: &EvalParams, positions: &[TuningPosition]) -> f64 {
+    let (mut start, mut end, mut step) = (0.0, 3.0, 0.1);
+    let mut best = (1.0, f64::MAX);
+    for _ in 0..4 {
+        let mut k = start;
+        while k <= end + 1e-9 {
+            let error = tuning_error(params, positions, k);
+            if error < best.1 {
+                best = (k, error);
+            }
+            k += step;
+        }
+        start = (best.0 - step).max(0.0);
+        end = best.0 + step;
+        step /= 10.0;
+    }
+    best.0
+}
+
+// Texel's local search: nudge each weight up or down, keeping any change that
+// lowers the error, until no change helps. `report` is called after each pass
+// with the pass number, the weights and their error. Returns the tuned weights
+// and their error.
+pub fn tune(
+    params: EvalParams,
+    positions: &[TuningPosition],
+    k: f64,
+    max_passes: usize,
+    mut report: impl FnMut(usize, &EvalParams, f64),
+) -> (EvalParams, f64) {
+    let mut best = params;
+    let mut best_error = tuning_error(&best, positions, k);
+    let mut step = INITIAL_STEP;
+    let tunable = best.tunable_mut().len();
+
+    for pass in 1..=max_passes {
+        let mut improved = false;
+        for i in 0..tunable {
+            for delta in [step, -step] {
+                let mut candidate = best;
+                *candidate.tunable_mut()[i].1 += delta;
+                if candidate.validate().is_err() {
+                    continue;
+                }
+                let error = tuning_error(&candidate, positions, k);
+                if error < best_error {
+                    best = candidate;
+                    best_error = error;
+                    improved = true;
+                    break;
+                }
+            }
+        }
+        report(pass, &best, best_error);
+        if !improved {
+            if step == 1 {
+                break;
+            }
+            step /= 2;
+        }
+    }
+    (best, best_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::Position;
+
+    fn position(fen: &str, result: f64) -> TuningPosition {
+        let game = Game::from_fen(fen).unwrap();
+        TuningPosition { board: game.board, side_to_move: game.current_turn, result }
+    }
+
+    // Positions of varied material, labelled with the results `params` predicts at `k`
+    fn labelled(params: &EvalParams, k: f64) -> Vec<TuningPosition> {
+        [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1",
+            "rnbqkbnr/ppp2ppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPP3PP/RNBQKBNR b - - 0 1",
+            "4k3/pppp4/8/8/8/8/PPPPPPP1/4K3 w - - 0 1",
+            "4k3/pp6/8/8/8/8/PPPPP3/4K3 b - - 0 1",
+            "r3k3/8/8/8/8/8/PPP5/4K3 w - - 0 1",
+            "1n2k3/p7/8/8/8/8/PPPP4/4K3 w - - 0 1",
+            "4k3/8/8/8/3P4/8/8/2B1K3 w - - 0 1",
+        ].iter()
+            .map(|fen| {
+                let mut position = position(fen, 0.0);
+                let score = endgame::adjust(&position.board, Color::White, params.evaluate(&position.board, Color::White));
+                position.result = sigmoid(score as f64, k);
+                position
+            })
+            .collect()
+    }
+
+    #[test]
+    fn results() {
+        assert_eq!(parse_result("1-0"), Some(1.0));
+        assert_eq!(parse_result("\"0-1\";"), Some(0.0));
+        assert_eq!(parse_result("\"1/2-1/2\"]"), Some(0.5));
+        assert_eq!(parse_result("[0.5]"), Some(0.5));
+        assert_eq!(parse_result("1.0"), Some(1.0));
+        assert_eq!(parse_result("*"), None);
+        assert_eq!(parse_result("e4"), None);
+    }
+
+    #[test]
+    fn epd_positions() {
+        let text = "# a comment\n\
+            rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - c9 \"1-0\";\n\
+            \n\
+            4k3/8/8/8/8/8/4P3/4K3 w - - [0.5]\n\
+            4k3/8/8/8/8/8/8/4K2q w - - 0 1 c9 \"0-1\";\n";
+        let positions = load_epd(text).unwrap();
+        assert_eq!(positions.iter().map(|p| p.result).collect::<Vec<_>>(), vec![1.0, 0.5, 0.0]);
+        assert_eq!(positions[0].side_to_move, Color::Black);
+        assert_eq!(positions[1].side_to_move, Color::White);
+        assert!(positions[0].board.get_piece(&Position::new(4, 3)).is_some());
+
+        assert!(load_epd("4k3/8/8/8/8/8/4P3/4K3 w - -\n").is_err());
+        assert!(load_epd("4k3/8/8/8/8/8/4P3/4K3\n").is_err());
+        assert!(load_epd("not/a/fen w [1.0]\n").is_err());
+    }
+
+    #[test]
+    fn pgn_positions() {
+        let text = "[Event \"First\"]\n\
+            [Result \"1-0\"]\n\
+            \n\
+            1. e4 e5 2. Nf3 {a comment} Nc6 (2... d6 3. d4) 3. Bb5 $1 a6 ; the Morphy defence\n\
+            1-0\n\
+            \n\
+            [Event \"Unfinished\"]\n\
+            [Result \"*\"]\n\
+            \n\
+            1. d4 d5 *\n\
+            \n\
+            [Event \"Castled\"]\n\
+            [Result \"1/2-1/2\"]\n\
+            \n\
+            1. e4 e5 2. Nf3 Nc6 3. Bc4 Bc5 4. O-O Nf6 1/2-1/2\n";
+        let engine = Engine::new(1);
+        let positions = load_pgn(text, &engine, 2);
+
+        // Plies 3 to 6 of the first game and 3 to 6 of the last, which stops at castling
+        assert_eq!(positions.len(), 8);
+        assert!(positions[..4].iter().all(|p| p.result == 1.0));
+        assert!(positions[4..].iter().all(|p| p.result == 0.5));
+        assert_eq!(positions[0].side_to_move, Color::Black);
+        assert_eq!(positions[3].side_to_move, Color::White);
+        let after_a6 = Game::from_fen("r1bqkbnr/1ppp1ppp/p1n5/1B2p3/4P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 0 4").unwrap();
+        assert_eq!(positions[3].board.to_fen(), after_a6.board.to_fen());
+
+        assert_eq!(load_pgn(text, &engine, 0).len(), 12);
+    }
+
+    #[test]
+    fn quiet_positions() {
+        let engine = Engine::new(1);
+        let quiet = |fen: &str| {
+            let game = Game::from_fen(fen).unwrap();
+            is_quiet(&engine, &game.board, game.current_turn)
+        };
+        assert!(quiet("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"));
+        // An even trade of defended pawns wins nothing
+        assert!(quiet("4k3/8/2p5/3p4/4P3/5P2/8/4K3 w - - 0 1"));
+        // In check
+        assert!(!quiet("4k3/8/8/8/8/8/8/r3K3 w - - 0 1"));
+        // A knight can take the queen, defended or not
+        assert!(!quiet("4k3/8/3p4/4q3/8/5N2/8/4K3 w - - 0 1"));
+        // An undefended pawn hangs
+        assert!(!quiet("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1"));
+    }
+
+    #[test]
+    fn error_uses_the_engines_score() {
+        let params = EvalParams::default();
+        // Rook against bishop is scaled towards a draw
+        let board = position("4k3/8/8/2b5/8/8/8/R3K3 w - - 0 1", 0.5).board;
+        let raw = params.evaluate(&board, Color::White);
+        let adjusted = endgame::adjust(&board, Color::White, raw);
+        assert!(adjusted < raw);
+
+        let positions = vec![TuningPosition { board, side_to_move: Color::White, result: 0.5 }];
+        let expected = (0.5 - sigmoid(adjusted as f64, 1.0)).powi(2);
+        assert!((tuning_error(&params, &positions, 1.0) - expected).abs() < 1e-12);
+        assert_eq!(tuning_error(&params, &[], 1.0), 0.0);
+    }
+
+    #[test]
+    fn fit_k_recovers_the_labelling_constant() {
+        let params = EvalParams::default();
+        for k in [0.6, 1.13, 1.9] {
+            let fitted = fit_k(&params, &labelled(&params, k));
+            assert!((fitted - k).abs() < 0.001, "fitted {} for {}", fitted, k);
+        }
+    }
+
+    #[test]
+    fn tuning_lowers_the_error() {
+        let target = EvalParams { pawn_value: 160, ..EvalParams::default() };
+        let positions = labelled(&target, 1.0);
+        let start = EvalParams::default();
+        let start_error = tuning_error(&start, &positions, 1.0);
+
+        let mut passes = 0;
+        let (tuned, error) = tune(start, &positions, 1.0, 20, |pass, params, pass_error| {
+            passes = pass;
+            assert!((tuning_error(params, &positions, 1.0) - pass_error).abs() < 1e-12);
+        });
+        assert!(passes > 0);
+        assert!(error < start_error / 2.0, "{} after tuning, {} before", error, start_error);
+        assert_eq!(error, tuning_error(&tuned, &positions, 1.0));
+        assert!(tuned.pawn_value > start.pawn_value);
+    }
+}
@@ -0,0 +1,676 @@
+use crate::chess::{Game, ChessMove, SearchBackend, SearchLimits, SearchResult, PvLine, StopSignal, WdlChances};
+use crate::error::ChessError;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::str::FromStr;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Score used to report "mate in N" results as centipawns
+const UCI_MATE_SCORE: i32 = 30000;
+
+// How long to wait for the engine to acknowledge `uci` and `isready`
+const DEFAULT_HANDSHAKE_TIMEOUT_MS: u64 = 5000;
+
+// Extra time granted on top of the move time before we send `stop`
+const SEARCH_GRACE_MS: u64 = 2000;
+
+// How often a running search checks its stop signal
+const STOP_POLL_MS: u64 = 50;
+
+// Configuration for an external UCI engine binary on the local machine
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UciEngineConfig {
+    pub name: String,
+    pub path: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    // UCI options sent with `setoption` after the handshake
+    #[serde(default)]
+    pub options: HashMap<String, String>,
+    // Search limits used when the caller doesn't give any
+    #[serde(default)]
+    pub depth: Option<u8>,
+    #[serde(default)]
+    pub movetime_ms: Option<u64>,
+    #[serde(default)]
+    pub handshake_timeout_ms: Option<u64>,
+}
+
+impl UciEngineConfig {
+    pub fn new(name: &str, path: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            path: path.to_string(),
+            args: Vec::new(),
+            options: HashMap::new(),
+            depth: None,
+            movetime_ms: None,
+            handshake_timeout_ms: None,
+        }
+    }
+
+    // Parse a list of engines in the form "name=path,name2=path2"
+    // This is the format of the RUSTYCHESS_UCI_ENGINES environment variable
+    pub fn parse_list(list: &str) -> Result<Vec<Self>, ChessError> {
+        let mut configs = Vec::new();
+
+        for entry in list.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (name, path) = entry.split_once('=').ok_or_else(|| {
+                ChessError::Engine(format!("Expected name=path, got {}", entry))
+            })?;
+            configs.push(Self::new(name.trim(), path.trim()));
+        }
+
+        Ok(configs)
+    }
+
+    fn handshake_timeout(&self) -> Duration {
+        Duration::from_millis(self.handshake_timeout_ms.unwrap_or(DEFAULT_HANDSHAKE_TIMEOUT_MS))
+    }
+}
+
+// Limits passed to the `go` command
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UciGoLimits {
+    pub depth: Option<u8>,
+    pub movetime_ms: Option<u64>,
+    pub nodes: Option<u64>,
+}
+
+impl UciGoLimits {
+    fn to_command(self) -> String {
+        let mut cmd = String::from("go");
+        if let Some(depth) = self.depth {
+            cmd.push_str(&format!(" depth {}", depth));
+        }
+        if let Some(nodes) = self.nodes {
+            cmd.push_str(&format!(" nodes {}", nodes));
+        }
+        if let Some(movetime) = self.movetime_ms {
+            cmd.push_str(&format!(" movetime {}", movetime));
+        }
+        // An unlimited search would never return on its own
+        if self.depth.is_none() && self.nodes.is_none() && self.movetime_ms.is_none() {
+            cmd.push_str(" movetime 1000");
+        }
+        cmd
+    }
+
+    // Upper bound on how long a search with these limits should take
+    fn deadline(self) -> Duration {
+        let movetime = self.movetime_ms.unwrap_or(if self.depth.is_some() || self.nodes.is_some() {
+            // Depth and node limits have no known duration, so allow a generous budget
+            30000
+        } else {
+            1000
+        });
+        Duration::from_millis(movetime + SEARCH_GRACE_MS)
+    }
+}
+
+// Score reported in an `info` line, from the side to move's point of view
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UciScore {
+    Centipawns(i32),
+    Mate(i32),
+}
+
+impl UciScore {
+    // Convert to a single centipawn value, with mates mapped close to UCI_MATE_SCORE
+    pub fn to_centipawns(&self) -> i32 {
+        match *self {
+            UciScore::Centipawns(cp) => cp,
+            UciScore::Mate(n) if n > 0 => UCI_MATE_SCORE - n,
+            UciScore::Mate(n) => -UCI_MATE_SCORE - n,
+        }
+    }
+}
+
+// The interesting parts of an `info` line
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UciInfo {
+    pub depth: Option<u32>,
+    pub seldepth: Option<u32>,
+    pub multipv: Option<u32>,
+    pub score: Option<UciScore>,
+    // Win, draw and loss chances in permille, from engines that report them
+    #[serde(default)]
+    pub wdl: Option<WdlChances>,
+    pub nodes: Option<u64>,
+    pub nps: Option<u64>,
+    pub time_ms: Option<u64>,
+    pub pv: Vec<String>,
+}
+
+impl UciInfo {
+    // Parse an `info ...` line; returns None for other lines
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut tokens = line.split_whitespace();
+        if tokens.next() != Some("info") {
+            return None;
+        }
+
+        let mut info = UciInfo::default();
+        while let Some(token) = tokens.next() {
+            match token {
+                "depth" => info.depth = tokens.next().and_then(|t| t.parse().ok()),
+                "seldepth" => info.seldepth = tokens.next().and_then(|t| t.parse().ok()),
+                "multipv" => info.multipv = tokens.next().and_then(|t| t.parse().ok()),
+                "nodes" => info.nodes = tokens.next().and_then(|t| t.parse().ok()),
+                "nps" => info.nps = tokens.next().and_then(|t| t.parse().ok()),
+                "time" => info.time_ms = tokens.next().and_then(|t| t.parse().ok()),
+                "score" => {
+                    let kind = tokens.next();
+                    let value = tokens.next().and_then(|t| t.parse().ok());
+                    info.score = match (kind, value) {
+                        (Some("cp"), Some(v)) => Some(UciScore::Centipawns(v)),
+                        (Some("mate"), Some(v)) => Some(UciScore::Mate(v)),
+                        _ => None,
+                    };
+                },
+                "wdl" => {
+                    let values: Vec<u32> = tokens.by_ref().take(3).filter_map(|t| t.parse().ok()).collect();
+                    info.wdl = match values[..] {
+                        [win, draw, loss] => Some(WdlChances { win, draw, loss }),
+                        _ => None,
+                    };
+                },
+                // The pv runs to the end of the line
+                "pv" => info.pv = tokens.by_ref().map(str::to_string).collect(),
+                // Free text, nothing after it is structured
+                "string" => break,
+                _ => {}
+            }
+        }
+
+        Some(info)
+    }
+}
+
+// Result of a search by an external engine
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UciSearchResult {
+    pub best_move: String,
+    pub ponder: Option<String>,
+    // The last info line that carried a score, if any
+    pub info: Option<UciInfo>,
+    // Latest scored info for each MultiPV line, best first
+    pub lines: Vec<UciInfo>,
+}
+
+impl UciSearchResult {
+    // The best move converted to our own move type
+    pub fn chess_move(&self) -> Result<ChessMove, ChessError> {
+        // Promotion suffixes aren't supported by the board, so only from/to are used
+        if self.best_move.len() < 4 || !self.best_move.is_char_boundary(4) {
+            return Err(ChessError::Engine(format!("Engine returned no usable move: {}", self.best_move)));
+        }
+        let mut chess_move = ChessMove::from_str(&self.best_move[0..4])?;
+        if let Some(score) = self.info.as_ref().and_then(|i| i.score) {
+            chess_move.score = score.to_centipawns();
+        }
+        Ok(chess_move)
+    }
+
+    pub fn nodes(&self) -> u64 {
+        self.info.as_ref().and_then(|i| i.nodes).unwrap_or(0)
+    }
+
+    // The reported lines converted to our own types; unparseable moves end a line early
+    pub fn pv_lines(&self, game: &Game) -> Vec<PvLine> {
+        self.lines.iter()
+            .filter_map(|info| {
+                let moves: Vec<ChessMove> = info.pv.iter()
+                    .map_while(|m| m.get(0..4).and_then(|m| ChessMove::from_str(m).ok()))
+                    .collect();
+                if moves.is_empty() {
+                    return None;
+                }
+                let depth = info.depth.unwrap_or(0).min(u8::MAX as u32) as u8;
+                let score = info.score.map(|s| s.to_centipawns()).unwrap_or(0);
+                let mut line = PvLine::new(game, moves, score, depth);
+                if let Some(wdl) = info.wdl {
+                    line.wdl = wdl;
+                }
+                Some(line)
+            })
+            .collect()
+    }
+}
+
+// A running external engine process speaking UCI over stdin/stdout
+pub struct UciEngine {
+    config: UciEngineConfig,
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    // Name reported by the engine in `id name`
+    id_name: Option<String>,
+    // Lines asked for with the MultiPV option; info about any other line is ignored
+    multipv: usize,
+}
+
+impl UciEngine {
+    // Spawn the engine and run the `uci` / `isready` handshake
+    pub fn spawn(config: &UciEngineConfig) -> Result<Self, ChessError> {
+        let mut child = Command::new(&config.path)
+            .args(&config.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| ChessError::Engine(format!("Failed to start {}: {}", config.path, e)))?;
+
+        let stdin = child.stdin.take()
+            .ok_or_else(|| ChessError::Engine("Engine stdin unavailable".to_string()))?;
+        let stdout = child.stdout.take()
+            .ok_or_else(|| ChessError::Engine("Engine stdout unavailable".to_string()))?;
+
+        // Read stdout on a separate thread so we can wait on it with a timeout.
+        // The channel disconnects when the process exits or closes stdout.
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                match line {
+                    Ok(line) => {
+                        if sender.send(line).is_err() {
+                            break;
+                        }
+                    },
+                    Err(_) => break,
+                }
+            }
+        });
+
+        let mut engine = Self {
+            config: config.clone(),
+            child,
+            stdin,
+            lines,
+            id_name: None,
+            multipv: 1,
+        };
+
+        engine.handshake()?;
+        Ok(engine)
+    }
+
+    pub fn config(&self) -> &UciEngineConfig {
+        &self.config
+    }
+
+    // Name reported by the engine, falling back to the configured name
+    pub fn name(&self) -> &str {
+        self.id_name.as_deref().unwrap_or(&self.config.name)
+    }
+
+    fn handshake(&mut self) -> Result<(), ChessError> {
+        let timeout = self.config.handshake_timeout();
+
+        self.send("uci")?;
+        let deadline = Instant::now() + timeout;
+        loop {
+            let line = self.read_line(deadline)?;
+            if let Some(name) = line.strip_prefix("id name ") {
+                self.id_name = Some(name.trim().to_string());
+            } else if line.trim() == "uciok" {
+                break;
+            }
+        }
+
+        let options: Vec<(String, String)> = self.config.options.iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        for (name, value) in options {
+            self.set_option(&name, &value)?;
+        }
+
+        self.wait_ready()
+    }
+
+    pub fn set_option(&mut self, name: &str, value: &str) -> Result<(), ChessError> {
+        if name.eq_ignore_ascii_case("MultiPV") {
+            self.multipv = value.trim().parse::<usize>().unwrap_or(1).max(1);
+        }
+        self.send(&format!("setoption name {} value {}", name, value))
+    }
+
+    // Tell the engine a new game is starting so it can clear its state
+    pub fn new_game(&mut self) -> Result<(), ChessError> {
+        self.send("ucinewgame")?;
+        self.wait_ready()
+    }
+
+    fn wait_ready(&mut self) -> Result<(), ChessError> {
+        self.send("isready")?;
+        let deadline = Instant::now() + self.config.handshake_timeout();
+        loop {
+            if self.read_line(deadline)?.trim() == "readyok" {
+                return Ok(());
+            }
+        }
+    }
+
+    // Search the game's current position and wait for `bestmove`
+    pub fn search(&mut self, game: &Game, limits: UciGoLimits) -> Result<UciSearchResult, ChessError> {
+        self.search_until(game, limits, &StopSignal::new())
+    }
+
+    // Like `search`, but sends `stop` to the engine as soon as the signal is raised
+    pub fn search_until(&mut self, game: &Game, limits: UciGoLimits, stop: &StopSignal) -> Result<UciSearchResult, ChessError> {
+        let limits = UciGoLimits {
+            depth: limits.depth.or(self.config.depth),
+            movetime_ms: limits.movetime_ms.or(self.config.movetime_ms),
+            nodes: limits.nodes,
+        };
+
+        self.send(&format!("position fen {}", game.to_fen()))?;
+        self.send(&limits.to_command())?;
+
+        let mut deadline = Instant::now() + limits.deadline();
+        let mut stop_sent = false;
+        let mut last_info: Option<UciInfo> = None;
+        let mut lines: Vec<UciInfo> = Vec::new();
+
+        loop {
+            if stop.is_stopped() && !stop_sent {
+                self.send("stop")?;
+                stop_sent = true;
+                deadline = Instant::now() + Duration::from_millis(SEARCH_GRACE_MS);
+            }
+
+            // Wake up regularly to notice stop requests
+            let poll_until = deadline.min(Instant::now() + Duration::from_millis(STOP_POLL_MS));
+            let line = match self.read_line(poll_until) {
+                Ok(line) => line,
+                Err(ChessError::Timeout(_)) if Instant::now() < deadline => continue,
+                Err(ChessError::Timeout(_)) if !stop_sent => {
+                    // Ask the engine to return what it has; give it a little longer to comply
+                    log::warn!("UCI engine {} exceeded its time, sending stop", self.name());
+                    self.send("stop")?;
+                    stop_sent = true;
+                    deadline = Instant::now() + Duration::from_millis(SEARCH_GRACE_MS);
+                    continue;
+                },
+                Err(e) => {
+                    // The engine is unresponsive or gone; make sure it doesn't linger
+                    let _ = self.child.kill();
+                    return Err(e);
+                },
+            };
+
+            if let Some(info) = UciInfo::parse(&line) {
+                if info.score.is_some() {
+                    // Engines without MultiPV support don't send the multipv field
+                    let index = info.multipv.unwrap_or(1).max(1) as usize - 1;
+                    if index >= self.multipv {
+                        continue;
+                    }
+                    if index >= lines.len() {
+                        lines.resize(index + 1, UciInfo::default());
+                    }
+                    lines[index] = info.clone();
+                    if index == 0 {
+                        last_info = Some(info);
+                    }
+                }
+            } else if let Some(rest) = line.strip_prefix("bestmove") {
+                let mut tokens = rest.split_whitespace();
+                let best_move = tokens.next()
+                    .ok_or_else(|| ChessError::Engine("Empty bestmove line".to_string()))?
+                    .to_string();
+                let ponder = match (tokens.next(), tokens.next()) {
+                    (Some("ponder"), Some(mv)) => Some(mv.to_string()),
+                    _ => None,
+                };
+
+                if best_move == "(none)" || best_move == "0000" {
+                    return Err(ChessError::InvalidMove("No legal moves available".to_string()));
+                }
+
+                lines.retain(|l| l.score.is_some());
+                return Ok(UciSearchResult { best_move, ponder, info: last_info, lines });
+            }
+        }
+    }
+
+    fn send(&mut self, command: &str) -> Result<(), ChessError> {
+        log::debug!("-> {}: {}", self.config.name, command);
+        writeln!(self.stdin, "{}", command)
+            .and_then(|_| self.stdin.flush())
+            .map_err(|e| ChessError::Engine(format!("Engine {} is not accepting input: {}", self.config.name, e)))
+    }
+
+    fn read_line(&mut self, deadline: Instant) -> Result<String, ChessError> {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        match self.lines.recv_timeout(timeout) {
+            Ok(line) => {
+                log::debug!("<- {}: {}", self.config.name, line);
+                Ok(line)
+            },
+            Err(RecvTimeoutError::Timeout) => Err(ChessError::Timeout(
+                format!("Engine {} did not respond in time", self.config.name)
+            )),
+            Err(RecvTimeoutError::Disconnected) => {
+                let status = self.child.try_wait().ok().flatten();
+                Err(ChessError::Engine(match status {
+                    Some(status) => format!("Engine {} exited unexpectedly ({})", self.config.name, status),
+                    None => format!("Engine {} closed its output", self.config.name),
+                }))
+            },
+        }
+    }
+}
+
+impl Drop for UciEngine {
+    fn drop(&mut self) {
+        // Ask politely first, then make sure the process is gone
+        let _ = writeln!(self.stdin, "quit").and_then(|_| self.stdin.flush());
+        let deadline = Instant::now() + Duration::from_millis(200);
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+// Search backend that starts an external engine on its first search and keeps
+// it running for later searches until the backend is dropped
+pub struct UciBackend {
+    config: UciEngineConfig,
+    engine: Option<UciEngine>,
+    stop: StopSignal,
+}
+
+impl UciBackend {
+    pub fn new(config: UciEngineConfig) -> Self {
+        Self {
+            config,
+            engine: None,
+            stop: StopSignal::new(),
+        }
+    }
+}
+
+impl SearchBackend for UciBackend {
+    fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    fn search(&mut self, game: &Game, limits: &SearchLimits) -> Result<SearchResult, ChessError> {
+        if self.engine.is_none() {
+            let mut engine = UciEngine::spawn(&self.config)?;
+            engine.new_game()?;
+            self.engine = Some(engine);
+        }
+
+        let engine = self.engine.as_mut()
+            .ok_or_else(|| ChessError::Internal("UCI engine not started".to_string()))?;
+        let multipv = limits.multipv.unwrap_or(1).max(1);
+        let go_limits = UciGoLimits {
+            depth: limits.depth,
+            movetime_ms: limits.movetime_ms,
+            nodes: limits.nodes,
+        };
+        let result = engine.set_option("MultiPV", &multipv.to_string())
+            .and_then(|_| engine.search_until(game, go_limits, &self.stop));
+
+        let result = match result {
+            Ok(result) => result,
+            Err(e) => {
+                // Start from a fresh process next time rather than reusing a broken one
+                self.engine = None;
+                return Err(e);
+            },
+        };
+
+        let best_move = result.chess_move()?;
+        let depth = result.info.as_ref().and_then(|i| i.depth).unwrap_or(0).min(u8::MAX as u32) as u8;
+        let mut lines = result.pv_lines(game);
+        if lines.is_empty() {
+            lines.push(PvLine::new(game, vec![best_move.clone()], best_move.score, depth));
+        }
+        let info = result.info.clone().unwrap_or_default();
+        Ok(SearchResult {
+            score: best_move.score,
+            best_move,
+            nodes: result.nodes(),
+            depth,
+            seldepth: info.seldepth.unwrap_or(depth as u32).min(u8::MAX as u32) as u8,
+            elapsed_ms: info.time_ms.unwrap_or(0),
+            nps: info.nps.unwrap_or(0),
+            lines,
+            from_book: false,
+        })
+    }
+
+    fn set_stop_signal(&mut self, stop: StopSignal) {
+        self.stop = stop;
+    }
+
+    // A running engine gets `ucinewgame`; one not started yet gets it on its first search
+    fn new_game(&mut self) -> Result<(), ChessError> {
+        if let Some(engine) = self.engine.as_mut() {
+            if let Err(e) = engine.new_game() {
+                self.engine = None;
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn fake_engine() -> UciEngineConfig {
+        let mut config = UciEngineConfig::new("fake", "sh");
+        config.args.push(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fake_uci.sh").to_string());
+        config
+    }
+
+    #[test]
+    fn handshake() {
+        let engine = UciEngine::spawn(&fake_engine()).unwrap();
+        assert_eq!(engine.name(), "Fake Engine");
+    }
+
+    #[test]
+    fn search_returns_the_best_move() {
+        let mut backend = UciBackend::new(fake_engine());
+        let result = backend.search(&Game::new(), &SearchLimits::depth(3)).unwrap();
+        assert_eq!(result.best_move.to_uci(), "e2e4");
+        assert_eq!(result.score, 30);
+        assert_eq!((result.depth, result.seldepth), (3, 5));
+        assert_eq!(result.nodes, 1200);
+        assert_eq!(result.lines.len(), 1);
+        assert_eq!(result.lines[0].moves.len(), 2);
+    }
+
+    #[test]
+    fn multipv_lines() {
+        let mut backend = UciBackend::new(fake_engine());
+        let limits = SearchLimits {
+            multipv: Some(3),
+            ..SearchLimits::depth(3)
+        };
+        let result = backend.search(&Game::new(), &limits).unwrap();
+        // The engine also reports a 400th line, which wasn't asked for
+        let lines: Vec<(String, i32)> = result.lines.iter()
+            .map(|l| (l.moves[0].to_uci(), l.score))
+            .collect();
+        assert_eq!(lines, vec![
+            ("e2e4".to_string(), 30),
+            ("d2d4".to_string(), 20),
+            ("g1f3".to_string(), 10),
+        ]);
+
+        // The same process serves the next search with fewer lines
+        let result = backend.search(&Game::new(), &SearchLimits::depth(3)).unwrap();
+        assert_eq!(result.lines.len(), 1);
+    }
+
+    #[test]
+    fn new_game_keeps_the_engine_running() {
+        let mut backend = UciBackend::new(fake_engine());
+        // Nothing to tell an engine that hasn't started
+        backend.new_game().unwrap();
+        assert!(backend.engine.is_none());
+
+        let limits = SearchLimits {
+            depth: Some(3),
+            ..SearchLimits::default()
+        };
+        backend.search(&Game::new(), &limits).unwrap();
+        let pid = backend.engine.as_ref().unwrap().child.id();
+        backend.new_game().unwrap();
+        backend.search(&Game::new(), &limits).unwrap();
+        assert_eq!(backend.engine.as_ref().unwrap().child.id(), pid);
+    }
+
+    #[test]
+    fn stop_ends_the_search() {
+        let mut backend = UciBackend::new(fake_engine());
+        let stop = StopSignal::new();
+        backend.set_stop_signal(stop.clone());
+
+        let stopper = {
+            let stop = stop.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(200));
+                stop.stop();
+            })
+        };
+        let start = Instant::now();
+        let limits = SearchLimits {
+            movetime_ms: Some(60_000),
+            ..SearchLimits::default()
+        };
+        let result = backend.search(&Game::new(), &limits).unwrap();
+        stopper.join().unwrap();
+
+        assert_eq!(result.best_move.to_uci(), "c2c4");
+        assert_eq!(result.score, 15);
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn info_parsing() {
+        let info = UciInfo::parse("info depth 12 seldepth 18 multipv 2 score mate -3 wdl 10 200 790 nodes 5000 pv e2e4 e7e5").unwrap();
+        assert_eq!(info.depth, Some(12));
+        assert_eq!(info.multipv, Some(2));
+        assert_eq!(info.score, Some(UciScore::Mate(-3)));
+        assert_eq!(info.wdl, Some(WdlChances { win: 10, draw: 200, loss: 790 }));
+        assert_eq!(info.pv, vec!["e2e4", "e7e5"]);
+        assert_eq!(UciInfo::parse("bestmove e2e4"), None);
+    }
+}
@@ -0,0 +1,288 @@
+use crate::chess::{Board, Color, EvalParams, TuningPosition, MAX_PHASE, TB_WIN_SCORE, game_phase};
+use crate::chess::endgame;
+use crate::error::ChessError;
+use serde::{Serialize, Deserialize};
+use std::fs;
+use std::path::Path;
+
+// Win/draw/loss model: turns a score into the chances of each result. The
+// chance of winning is a logistic curve of the score, 1 / (1 + e^((a - x) / b)),
+// and losing is the same curve mirrored, so that a is the score at which a win
+// becomes as likely as not and b how quickly the chances change around it. Both
+// move linearly with the game phase, since how often an advantage is converted
+// depends on the material left. Drawing takes whatever is left.
+//
+// The defaults are not fitted to anything. They are round, deliberately cautious
+// numbers: the same curve at every phase, a win only even money at four pawns
+// up, and a level position a draw 87% of the time, so that an unfitted model
+// never promises more than the evaluation can back up. `tune --wdl` fits real
+// parameters to any dataset tune can read, such as the engine's own datagen games.
+
+// Scores beyond this are mates or tablebase results, whose outcome is certain
+const DECISIVE_SCORE: i32 = TB_WIN_SCORE - 2000;
+
+// Smallest step the fit refines a parameter to
+const MIN_FIT_STEP: f64 = 0.25;
+
+// Keeps the log-likelihood finite for outcomes the model thinks impossible
+const MIN_PROBABILITY: f64 = 1e-6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WdlModel {
+    // Centipawns at which a win is even money, with only kings and pawns left
+    // and with every piece on the board
+    pub a_endgame: f64,
+    pub a_opening: f64,
+    // Spread of the curve in centipawns, likewise
+    pub b_endgame: f64,
+    pub b_opening: f64,
+}
+
+impl Default for WdlModel {
+    fn default() -> Self {
+        Self {
+            a_endgame: 400.0,
+            a_opening: 400.0,
+            b_endgame: 150.0,
+            b_opening: 150.0,
+        }
+    }
+}
+
+// Chances of each result for one side, in permille; they always sum to 1000
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WdlChances {
+    pub win: u32,
+    pub draw: u32,
+    pub loss: u32,
+}
+
+impl WdlChances {
+    // The same chances seen from the other side
+    pub fn flipped(self) -> Self {
+        Self { win: self.loss, draw: self.draw, loss: self.win }
+    }
+
+    // Expected score from 0 to 1, e.g. for an eval bar
+    pub fn expected_score(&self) -> f64 {
+        (self.win as f64 + self.draw as f64 / 2.0) / 1000.0
+    }
+}
+
+impl WdlModel {
+    // The parameters at a game phase between 0 and MAX_PHASE
+    fn curve(&self, phase: i32) -> (f64, f64) {
+        let t = phase.clamp(0, MAX_PHASE) as f64 / MAX_PHASE as f64;
+        (
+            self.a_endgame + (self.a_opening - self.a_endgame) * t,
+            self.b_endgame + (self.b_opening - self.b_endgame) * t,
+        )
+    }
+
+    // Probabilities of winning, drawing and losing with a score of `score`
+    // centipawns at game phase `phase`
+    pub fn probabilities(&self, score: f64, phase: i32) -> (f64, f64, f64) {
+        let (a, b) = self.curve(phase);
+        let win = 1.0 / (1.0 + ((a - score) / b).exp());
+        let loss = 1.0 / (1.0 + ((a + score) / b).exp());
+        (win, (1.0 - win - loss).max(0.0), loss)
+    }
+
+    // Chances of the side whose score this is. Mates and tablebase results are
+    // certain.
+    pub fn chances(&self, score: i32, phase: i32) -> WdlChances {
+        if score > DECISIVE_SCORE {
+            return WdlChances { win: 1000, draw: 0, loss: 0 };
+        }
+        if score < -DECISIVE_SCORE {
+            return WdlChances { win: 0, draw: 0, loss: 1000 };
+        }
+        let (win, _, loss) = self.probabilities(score as f64, phase);
+        let win = (win * 1000.0).round() as u32;
+        let loss = ((loss * 1000.0).round() as u32).min(1000 - win);
+        WdlChances { win, draw: 1000 - win - loss, loss }
+    }
+
+    // Chances for the side to move in a position scored `score` for that side
+    pub fn chances_on(&self, board: &Board, score: i32) -> WdlChances {
+        self.chances(score, game_phase(board))
+    }
+
+    // Fit the model to positions labelled with their game results, scoring each
+    // with the evaluation `params`. Maximises the likelihood of the results by a
+    // local search that halves its steps once no single change helps. Returns
+    // the model and its mean negative log-likelihood per position.
+    pub fn fit(positions: &[TuningPosition], params: &EvalParams) -> (Self, f64) {
+        let samples: Vec<(f64, i32, f64)> = positions.iter()
+            // The score the engine reports, endgame knowledge included
+            .map(|p| (endgame::adjust(&p.board, Color::White, params.evaluate(&p.board, Color::White)) as f64, game_phase(&p.board), p.result))
+            .collect();
+        fit_samples(&samples)
+    }
+
+    fn parameters_mut(&mut self) -> [&mut f64; 4] {
+        [&mut self.a_endgame, &mut self.a_opening, &mut self.b_endgame, &mut self.b_opening]
+    }
+
+    // A negative `a` would make draws less than impossible
+    pub fn validate(self) -> Result<Self, ChessError> {
+        if self.a_endgame < 0.0 || self.a_opening < 0.0 {
+            return Err(ChessError::EvalParams("WDL model: a must not be negative".to_string()));
+        }
+        if self.b_endgame < 1.0 || self.b_opening < 1.0 {
+            return Err(ChessError::EvalParams("WDL model: b must be at least 1".to_string()));
+        }
+        Ok(self)
+    }
+
+    // Load a `.json` or `.toml` model file, as written by `save`
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ChessError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|e| ChessError::EvalParams(format!("Failed to read {}: {}", path.display(), e)))?;
+        let model: Self = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => serde_json::from_str(&text)
+                .map_err(|e| ChessError::EvalParams(format!("Invalid JSON: {}", e)))?,
+            Some("toml") => toml::from_str(&text)
+                .map_err(|e| ChessError::EvalParams(format!("Invalid TOML: {}", e)))?,
+            _ => return Err(ChessError::EvalParams(format!("{} is neither a .json nor a .toml file", path.display()))),
+        };
+        model.validate()
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ChessError> {
+        let path = path.as_ref();
+        let text = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => serde_json::to_string_pretty(self)
+                .map_err(|e| ChessError::EvalParams(format!("Failed to encode JSON: {}", e)))? + "\n",
+            Some("toml") => toml::to_string(self)
+                .map_err(|e| ChessError::EvalParams(format!("Failed to encode TOML: {}", e)))?,
+            _ => return Err(ChessError::EvalParams(format!("{} is neither a .json nor a .toml file", path.display()))),
+        };
+        fs::write(path, text)
+            .map_err(|e| ChessError::EvalParams(format!("Failed to write {}: {}", path.display(), e)))
+    }
+}
+
+// The model that best explains (White's score, phase, result) samples, searched
+// from the defaults, and its mean negative log-likelihood
+fn fit_samples(samples: &[(f64, i32, f64)]) -> (WdlModel, f64) {
+    let mut best = WdlModel::default();
+    let mut best_loss = log_loss(&best, samples);
+    let mut step = 32.0;
+    while step >= MIN_FIT_STEP {
+        let mut improved = false;
+        for i in 0..4 {
+            for delta in [step, -step] {
+                let mut candidate = best;
+                *candidate.parameters_mut()[i] += delta;
+                if candidate.validate().is_err() {
+                    continue;
+                }
+                let loss = log_loss(&candidate, samples);
+                if loss < best_loss {
+                    best = candidate;
+                    best_loss = loss;
+                    improved = true;
+                    break;
+                }
+            }
+        }
+        if !improved {
+            step /= 2.0;
+        }
+    }
+    (best, best_loss)
+}
+
+// Mean negative log-likelihood of the results (White's score, phase, result)
+fn log_loss(model: &WdlModel, samples: &[(f64, i32, f64)]) -> f64 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    let total: f64 = samples.iter()
+        .map(|&(score, phase, result)| {
+            let (win, draw, loss) = model.probabilities(score, phase);
+            let p = if result > 0.75 { win } else if result < 0.25 { loss } else { draw };
+            -p.max(MIN_PROBABILITY).ln()
+        })
+        .sum();
+    total / samples.len() as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chances_sum_to_a_thousand() {
+        let model = WdlModel::default();
+        for phase in [0, MAX_PHASE / 2, MAX_PHASE] {
+            for score in (-3000..=3000).step_by(7) {
+                let chances = model.chances(score, phase);
+                assert_eq!(chances.win + chances.draw + chances.loss, 1000, "{} at phase {}", score, phase);
+                // Seen from the other side
+                assert_eq!(model.chances(-score, phase), chances.flipped(), "{} at phase {}", score, phase);
+            }
+        }
+        let level = model.chances(0, MAX_PHASE);
+        assert_eq!(level.win, level.loss);
+        assert_eq!(level.expected_score(), 0.5);
+    }
+
+    #[test]
+    fn decisive_scores_are_certain() {
+        let model = WdlModel::default();
+        let win = WdlChances { win: 1000, draw: 0, loss: 0 };
+        assert_eq!(model.chances(TB_WIN_SCORE, 0), win);
+        assert_eq!(model.chances(DECISIVE_SCORE + 1, MAX_PHASE), win);
+        assert_eq!(model.chances(-TB_WIN_SCORE, MAX_PHASE), win.flipped());
+        assert_eq!(win.flipped().expected_score(), 0.0);
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let model = WdlModel { a_endgame: 310.5, a_opening: 512.25, b_endgame: 95.0, b_opening: 180.75 };
+        let dir = std::env::temp_dir().join(format!("rustychess-wdl-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let loaded: Vec<Result<WdlModel, ChessError>> = ["wdl.json", "wdl.toml"].iter()
+            .map(|name| model.save(dir.join(name)).and_then(|_| WdlModel::load(dir.join(name))))
+            .collect();
+        let unsupported = model.save(dir.join("wdl.txt"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        for loaded in loaded {
+            assert_eq!(loaded.unwrap(), model);
+        }
+        assert!(unsupported.is_err());
+    }
+
+    #[test]
+    fn fit_recovers_the_model() {
+        let truth = WdlModel { a_endgame: 300.0, a_opening: 500.0, b_endgame: 110.0, b_opening: 200.0 };
+        // Results in exactly the proportions the model predicts
+        let mut samples = Vec::new();
+        for phase in [0, MAX_PHASE / 2, MAX_PHASE] {
+            for score in (-800..=800).step_by(40) {
+                let (win, _, loss) = truth.probabilities(score as f64, phase);
+                let (wins, losses) = ((win * 100.0).round() as usize, (loss * 100.0).round() as usize);
+                for i in 0..100 {
+                    let result = if i < wins { 1.0 } else if i < wins + losses { 0.0 } else { 0.5 };
+                    samples.push((score as f64, phase, result));
+                }
+            }
+        }
+
+        let (fitted, loss) = fit_samples(&samples);
+        assert!(loss <= log_loss(&truth, &samples) + 1e-4);
+        for (fitted, truth) in [
+            (fitted.a_endgame, truth.a_endgame),
+            (fitted.a_opening, truth.a_opening),
+            (fitted.b_endgame, truth.b_endgame),
+            (fitted.b_opening, truth.b_opening),
+        ] {
+            assert!((fitted - truth).abs() <= 0.03 * truth, "fitted {:?}", fitted);
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use crate::chess::{Board, Color, PieceType, Position};
+
+// Random keys for hashing positions into the transposition table.
+// Generated at compile time from a fixed seed so hashes are stable between runs.
+const PIECE_KEYS: [[u64; 64]; 12] = generate_piece_keys();
+const SIDE_KEY: u64 = splitmix64(0x05EE_D0FC_4E55).0;
+
+// SplitMix64 step, returns the output and the next state
+const fn splitmix64(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    (z ^ (z >> 31), state)
+}
+
+const fn generate_piece_keys() -> [[u64; 64]; 12] {
+    let mut keys = [[0u64; 64]; 12];
+    let mut state = 0x2545_F491_4F6C_DD1D;
+    let mut piece = 0;
+    while piece < 12 {
+        let mut square = 0;
+        while square < 64 {
+            let (key, next) = splitmix64(state);
+            keys[piece][square] = key;
+            state = next;
+            square += 1;
+        }
+        piece += 1;
+    }
+    keys
+}
+
+// Index of a piece in the key table: white pieces 0-5, black pieces 6-11
+fn piece_index(piece_type: PieceType, color: Color) -> usize {
+    let base = match piece_type {
+        PieceType::Pawn => 0,
+        PieceType::Knight => 1,
+        PieceType::Bishop => 2,
+        PieceType::Rook => 3,
+        PieceType::Queen => 4,
+        PieceType::King => 5,
+    };
+    if color == Color::White { base } else { base + 6 }
+}
+
+// Hash of a board with the given side to move
+pub fn hash(board: &Board, side_to_move: Color) -> u64 {
+    let mut key = 0;
+
+    for rank in 0..8 {
+        for file in 0..8 {
+            if let Some(piece) = board.get_piece(&Position::new(file, rank)) {
+                let square = (rank * 8 + file) as usize;
+                key ^= PIECE_KEYS[piece_index(piece.piece_type, piece.color)][square];
+            }
+        }
+    }
+
+    if side_to_move == Color::Black {
+        key ^= SIDE_KEY;
+    }
+
+    key
+}
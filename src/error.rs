@@ -11,6 +11,24 @@ pub enum ChessError {
     #[error("Game over: {0}")]
     GameOver(String),
     
+    #[error("Engine error: {0}")]
+    Engine(String),
+    
+    #[error("Timeout: {0}")]
+    Timeout(String),
+    
+    #[error("Opening book error: {0}")]
+    Book(String),
+
+    #[error("Tablebase error: {0}")]
+    Tablebase(String),
+
+    #[error("Evaluation parameters error: {0}")]
+    EvalParams(String),
+
+    #[error("Network error: {0}")]
+    Nnue(String),
+
     #[error("Internal error: {0}")]
     Internal(String),
 }
@@ -2,9 +2,12 @@ use actix_web::{App, HttpServer, web};
 use actix_files as fs;
 use actix_cors::Cors;
 use rustychess::api;
-use log::info;
+use rustychess::chess::{BackendRegistry, BookSelection, Engine, EvalParams, DtmTablebase, OpeningBook, SyzygyTablebase, UciEngineConfig, WdlModel, DEFAULT_BACKEND};
+#[cfg(feature = "nnue")]
+use rustychess::chess::Network;
+use log::{info, warn};
 use std::io;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 use std::collections::HashMap;
 
 #[actix_web::main]
@@ -13,9 +16,161 @@ async fn main() -> io::Result<()> {
     
     info!("Starting RustyChess engine API on http://0.0.0.0:8080");
     
+    // Built-in backends plus any external UCI engines, e.g. "stockfish=/usr/bin/stockfish"
+    let mut backends = BackendRegistry::default();
+    
+    // Lazy SMP threads for the built-in engine
+    let threads = std::env::var("RUSTYCHESS_SEARCH_THREADS").ok().and_then(|t| t.parse::<usize>().ok());
+    if let Some(threads) = threads {
+        info!("Built-in engine will search with {} threads", threads);
+    }
+    
+    // Syzygy tablebases, e.g. RUSTYCHESS_SYZYGY_PATH=/data/syzygy
+    let tablebase = std::env::var_os("RUSTYCHESS_SYZYGY_PATH").and_then(|path| {
+        match SyzygyTablebase::open(&path) {
+            Ok(tablebase) => {
+                info!("Found {} Syzygy tables with up to {} pieces", tablebase.len(), tablebase.max_pieces());
+                Some(Arc::new(tablebase))
+            },
+            Err(e) => {
+                warn!("Not using tablebases: {}", e);
+                None
+            },
+        }
+    });
+    
+    // Distance-to-mate tables written by the tbgen binary, e.g. RUSTYCHESS_DTM_PATH=tablebases
+    let dtm_tablebase = std::env::var_os("RUSTYCHESS_DTM_PATH").and_then(|path| {
+        match DtmTablebase::open(&path) {
+            Ok(tablebase) => {
+                info!("Found {} DTM tables with up to {} pieces", tablebase.len(), tablebase.max_pieces());
+                Some(Arc::new(tablebase))
+            },
+            Err(e) => {
+                warn!("Not using DTM tables: {}", e);
+                None
+            },
+        }
+    });
+    
+    // Evaluation weights, as a personality name or a .toml/.json file
+    let eval_params = std::env::var("RUSTYCHESS_EVAL_PARAMS").ok().and_then(|value| {
+        match EvalParams::resolve(&value) {
+            Ok(params) => {
+                info!("Built-in engine evaluates with {}", value);
+                Some(params)
+            },
+            Err(e) => {
+                warn!("Using the default evaluation: {}", e);
+                None
+            },
+        }
+    });
+    
+    // Win/draw/loss model written by `tune --wdl`, e.g. RUSTYCHESS_WDL_MODEL=wdl.toml
+    let wdl_model = std::env::var("RUSTYCHESS_WDL_MODEL").ok().and_then(|path| {
+        match WdlModel::load(&path) {
+            Ok(model) => {
+                info!("Built-in engine reports WDL chances with {}", path);
+                Some(model)
+            },
+            Err(e) => {
+                warn!("Using the default WDL model: {}", e);
+                None
+            },
+        }
+    });
+    
+    // Neural network evaluation, e.g. RUSTYCHESS_NNUE=networks/net.bin
+    #[cfg(feature = "nnue")]
+    let network = std::env::var_os("RUSTYCHESS_NNUE").and_then(|path| {
+        match Network::load(&path) {
+            Ok(network) => {
+                info!("Built-in engine evaluates with a network of {} hidden neurons", network.hidden_size());
+                Some(Arc::new(network))
+            },
+            Err(e) => {
+                warn!("Using the handcrafted evaluation: {}", e);
+                None
+            },
+        }
+    });
+    // Builds without the feature never have a network
+    #[cfg(not(feature = "nnue"))]
+    let network: Option<()> = None;
+    
+    if threads.is_some() || tablebase.is_some() || dtm_tablebase.is_some() || eval_params.is_some() || wdl_model.is_some() || network.is_some() {
+        let tablebase = tablebase.clone();
+        #[cfg(feature = "nnue")]
+        let network = network.clone();
+        backends.register(DEFAULT_BACKEND, move || {
+            let mut engine = Engine::new(3);
+            engine.set_threads(threads.unwrap_or(1));
+            engine.set_tablebase(tablebase.clone());
+            engine.set_dtm_tablebase(dtm_tablebase.clone());
+            engine.set_eval_params(eval_params.unwrap_or_default());
+            engine.set_wdl_model(wdl_model.unwrap_or_default());
+            #[cfg(feature = "nnue")]
+            engine.set_nnue(network.clone());
+            Box::new(engine)
+        });
+    }
+    if let Ok(list) = std::env::var("RUSTYCHESS_UCI_ENGINES") {
+        match UciEngineConfig::parse_list(&list) {
+            Ok(configs) => {
+                for config in configs {
+                    info!("Registered external engine {} at {}", config.name, config.path);
+                    backends.register_uci(config);
+                }
+            },
+            Err(e) => warn!("Ignoring RUSTYCHESS_UCI_ENGINES: {}", e),
+        }
+    }
+    
+    // Searches run on their own threads, one per core unless configured
+    let search_pool = match std::env::var("RUSTYCHESS_SEARCH_WORKERS").ok().and_then(|n| n.parse::<usize>().ok()) {
+        Some(workers) => api::SearchPool::new(workers),
+        None => api::SearchPool::with_default_size(),
+    };
+    info!("Running searches on {} worker threads", search_pool.size());
+    let max_search_ms = std::env::var("RUSTYCHESS_MAX_SEARCH_MS")
+        .ok()
+        .and_then(|ms| ms.parse::<u64>().ok())
+        .unwrap_or(api::DEFAULT_MAX_SEARCH_MS);
+    
+    // Polyglot opening book, e.g. RUSTYCHESS_BOOK=books/performance.bin
+    let book = std::env::var("RUSTYCHESS_BOOK").ok().and_then(|path| {
+        let selection = std::env::var("RUSTYCHESS_BOOK_SELECTION")
+            .ok()
+            .and_then(|s| s.parse::<BookSelection>().map_err(|e| warn!("{}", e)).ok())
+            .unwrap_or_default();
+        let max_depth = std::env::var("RUSTYCHESS_BOOK_DEPTH").ok().and_then(|d| d.parse::<usize>().ok());
+        match OpeningBook::open(&path) {
+            Ok(book) => {
+                info!("Loaded opening book {} with {} entries", path, book.len());
+                Some(Arc::new(book.with_selection(selection).with_max_depth(max_depth)))
+            },
+            Err(e) => {
+                warn!("Not using an opening book: {}", e);
+                None
+            },
+        }
+    });
+    
     // Create app state for storing games
     let app_state = web::Data::new(api::AppState {
         games: Mutex::new(HashMap::new()),
+        backends,
+        search_pool,
+        active_searches: Mutex::new(HashMap::new()),
+        max_search_ms,
+        ponders: Mutex::new(HashMap::new()),
+        book,
+        tablebase,
+        wdl_model: wdl_model.unwrap_or_default(),
+        eval_params: eval_params.unwrap_or_default(),
+        #[cfg(feature = "nnue")]
+        network,
     });
     
     HttpServer::new(move || {
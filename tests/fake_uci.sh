@@ -0,0 +1,45 @@
+#!/bin/sh
+# Scripted UCI engine for the UciBackend tests in src/chess/uci.rs.
+# `go depth` answers at once with one info line per MultiPV line, plus one for a
+# line that was never asked for. Any other `go` searches until `stop`.
+multipv=1
+while read -r line; do
+    case "$line" in
+        uci)
+            echo "id name Fake Engine"
+            echo "id author RustyChess tests"
+            echo "option name MultiPV type spin default 1 min 1 max 500"
+            echo "uciok"
+            ;;
+        isready)
+            echo "readyok"
+            ;;
+        "setoption name MultiPV value "*)
+            multipv=${line##* }
+            ;;
+        "go depth"*)
+            i=1
+            while [ "$i" -le "$multipv" ] && [ "$i" -le 3 ]; do
+                case $i in
+                    1) pv="e2e4 e7e5" ;;
+                    2) pv="d2d4 d7d5" ;;
+                    3) pv="g1f3 g8f6" ;;
+                esac
+                echo "info depth 3 seldepth 5 multipv $i score cp $((40 - 10 * i)) nodes 1200 nps 60000 time 20 pv $pv"
+                i=$((i + 1))
+            done
+            echo "info depth 3 multipv 400 score cp -500 pv a2a3"
+            echo "info string done"
+            echo "bestmove e2e4 ponder e7e5"
+            ;;
+        go*)
+            echo "info depth 1 score cp 15 nodes 20 pv c2c4"
+            ;;
+        stop)
+            echo "bestmove c2c4"
+            ;;
+        quit)
+            exit 0
+            ;;
+    esac
+done